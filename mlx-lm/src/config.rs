@@ -0,0 +1,504 @@
+//! Configuration of the models, read from the `config.json` of a Hugging Face checkpoint.
+
+use std::{collections::HashMap, path::Path};
+
+use mlx_rs::{builder::Builder, error::Exception, nn};
+use serde::{Deserialize, Deserializer};
+
+use crate::error::ModelError;
+
+/// Name of the configuration file of a checkpoint.
+pub const CONFIG_FILE: &str = "config.json";
+
+/// Bits per quantized weight supported by [`mlx_rs::ops::quantize`].
+const SUPPORTED_BITS: [i32; 5] = [2, 3, 4, 6, 8];
+
+/// Quantization of a checkpoint converted by `mlx-lm`, ie. the `quantization` entry of
+/// `config.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct QuantizationConfig {
+    /// Group size of the quantized weights
+    pub group_size: i32,
+
+    /// Bits per quantized weight
+    pub bits: i32,
+
+    /// Layers quantized differently from the others, by path, eg. `"model.layers.0.mlp.down_proj"`
+    #[serde(flatten)]
+    pub layers: HashMap<String, LayerQuantization>,
+}
+
+/// Quantization of a single layer in [`QuantizationConfig::layers`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum LayerQuantization {
+    /// `false` if the layer is not quantized, `true` if it uses the default settings
+    Enabled(bool),
+
+    /// Group size and bits of the layer
+    Custom {
+        /// Group size of the quantized weights
+        group_size: i32,
+
+        /// Bits per quantized weight
+        bits: i32,
+    },
+}
+
+impl QuantizationConfig {
+    /// Group size and bits of the layer at `path`, whose weight has `input_dims` columns, or `None`
+    /// if the layer is not quantized.
+    ///
+    /// Like the reference converter, the layers whose input dimensions are not divisible by the
+    /// group size are not quantized unless they are listed in [`QuantizationConfig::layers`].
+    pub fn layer(&self, path: &str, input_dims: i32) -> Option<(i32, i32)> {
+        match self.layers.get(path) {
+            Some(LayerQuantization::Enabled(false)) => None,
+            Some(LayerQuantization::Enabled(true)) => Some((self.group_size, self.bits)),
+            Some(LayerQuantization::Custom { group_size, bits }) => Some((*group_size, *bits)),
+            None => (input_dims % self.group_size == 0).then_some((self.group_size, self.bits)),
+        }
+    }
+
+    /// Checks that the default settings and those of every layer in
+    /// [`QuantizationConfig::layers`] have a positive group size and supported bits.
+    pub fn validate(&self) -> Result<(), ModelError> {
+        check_quantization("quantization", self.group_size, self.bits)?;
+        for (path, layer) in &self.layers {
+            if let LayerQuantization::Custom { group_size, bits } = layer {
+                check_quantization(path, *group_size, *bits)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_quantization(name: &str, group_size: i32, bits: i32) -> Result<(), ModelError> {
+    if group_size <= 0 {
+        return Err(ModelError::InvalidConfig(format!(
+            "the group size of {} must be positive, got {}",
+            name, group_size
+        )));
+    }
+    if !SUPPORTED_BITS.contains(&bits) {
+        return Err(ModelError::InvalidConfig(format!(
+            "the bits of {} must be one of {:?}, got {}",
+            name, SUPPORTED_BITS, bits
+        )));
+    }
+    Ok(())
+}
+
+/// The `rope_scaling` entry of `config.json`.
+///
+/// A `"default"` scaling is read as no scaling at all, ie. `rope_scaling: None`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RopeScalingConfig {
+    /// A scaling method implemented by [`nn::Rope`]
+    Supported(nn::RopeScaling),
+
+    /// A scaling method that is not implemented, eg. the `"longrope"` of Phi-3, with its type
+    Unsupported(String),
+}
+
+/// Hyperparameters of a decoder-only language model.
+///
+/// The fields follow the Hugging Face `config.json` of the supported architectures, so it can be
+/// deserialized from it directly. The entries that are not used by any of the models are ignored.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ModelConfig {
+    /// Architecture of the model, eg. `"llama"`
+    pub model_type: String,
+
+    /// Number of features of the hidden states
+    pub hidden_size: i32,
+
+    /// Number of decoder layers
+    pub num_hidden_layers: i32,
+
+    /// Number of hidden features of the feed forward layers
+    pub intermediate_size: i32,
+
+    /// Number of query heads
+    pub num_attention_heads: i32,
+
+    /// Number of key and value heads. Defaults to `num_attention_heads`
+    #[serde(default)]
+    pub num_key_value_heads: Option<i32>,
+
+    /// Number of features of each head. Defaults to `hidden_size / num_attention_heads`
+    #[serde(default)]
+    pub head_dim: Option<i32>,
+
+    /// Size of the vocabulary
+    pub vocab_size: i32,
+
+    /// Value added to the denominator of the RMS normalization layers. Default to
+    /// [`ModelConfig::DEFAULT_RMS_NORM_EPS`]
+    #[serde(default = "default_rms_norm_eps")]
+    pub rms_norm_eps: f32,
+
+    /// Base of the rotary positional encoding. Default to [`ModelConfig::DEFAULT_ROPE_THETA`]
+    #[serde(default = "default_rope_theta")]
+    pub rope_theta: f32,
+
+    /// Whether the rotary positional encoding rotates consecutive pairs of features
+    #[serde(default)]
+    pub rope_traditional: bool,
+
+    /// Scaling of the rotary positional encoding for long contexts
+    #[serde(default, deserialize_with = "deserialize_rope_scaling")]
+    pub rope_scaling: Option<RopeScalingConfig>,
+
+    /// Fraction of the features of each head that are rotated. Default to `1.0`
+    #[serde(default = "default_partial_rotary_factor")]
+    pub partial_rotary_factor: f32,
+
+    /// Context length the model was trained with
+    #[serde(default)]
+    pub max_position_embeddings: Option<i32>,
+
+    /// Whether the output projection shares its weight with the token embedding
+    #[serde(default)]
+    pub tie_word_embeddings: bool,
+
+    /// Whether the projections of the attention layers have a bias
+    #[serde(default)]
+    pub attention_bias: bool,
+
+    /// Whether the projections of the feed forward layers have a bias
+    #[serde(default)]
+    pub mlp_bias: bool,
+
+    /// Quantization of the weights, if the checkpoint is quantized
+    #[serde(default)]
+    pub quantization: Option<QuantizationConfig>,
+}
+
+fn default_rms_norm_eps() -> f32 {
+    ModelConfig::DEFAULT_RMS_NORM_EPS
+}
+
+fn default_rope_theta() -> f32 {
+    ModelConfig::DEFAULT_ROPE_THETA
+}
+
+fn default_partial_rotary_factor() -> f32 {
+    1.0
+}
+
+fn check_positive(name: &str, value: i32) -> Result<(), ModelError> {
+    if value <= 0 {
+        return Err(ModelError::InvalidConfig(format!(
+            "{} must be positive, got {}",
+            name, value
+        )));
+    }
+    Ok(())
+}
+
+fn deserialize_rope_scaling<'de, D>(deserializer: D) -> Result<Option<RopeScalingConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    // Older checkpoints name the entry `type` instead of `rope_type`
+    let rope_type = value
+        .get("rope_type")
+        .or_else(|| value.get("type"))
+        .and_then(|rope_type| rope_type.as_str())
+        .unwrap_or_default()
+        .to_string();
+    if rope_type == "default" {
+        return Ok(None);
+    }
+
+    let supported =
+        serde_json::from_value::<nn::RopeScalingType>(serde_json::Value::from(rope_type.as_str()))
+            .is_ok();
+    if !supported {
+        return Ok(Some(RopeScalingConfig::Unsupported(rope_type)));
+    }
+
+    serde_json::from_value(value)
+        .map(|scaling| Some(RopeScalingConfig::Supported(scaling)))
+        .map_err(serde::de::Error::custom)
+}
+
+impl ModelConfig {
+    /// Default value for `rms_norm_eps`.
+    pub const DEFAULT_RMS_NORM_EPS: f32 = 1e-6;
+
+    /// Default value for `rope_theta`.
+    pub const DEFAULT_ROPE_THETA: f32 = 10_000.0;
+
+    /// Reads the configuration from a `config.json` file, or from the [`CONFIG_FILE`] of a
+    /// checkpoint directory.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ModelError> {
+        let path = path.as_ref();
+        let path = if path.is_dir() {
+            path.join(CONFIG_FILE)
+        } else {
+            path.to_path_buf()
+        };
+
+        let content = std::fs::read_to_string(path)?;
+        let config: Self = serde_json::from_str(&content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Number of key and value heads.
+    pub fn num_key_value_heads(&self) -> i32 {
+        self.num_key_value_heads.unwrap_or(self.num_attention_heads)
+    }
+
+    /// Number of features of each head.
+    pub fn head_dim(&self) -> i32 {
+        self.head_dim
+            .unwrap_or(self.hidden_size / self.num_attention_heads)
+    }
+
+    /// Checks that the hyperparameters describe a valid model.
+    pub fn validate(&self) -> Result<(), ModelError> {
+        let positive = [
+            ("hidden_size", self.hidden_size),
+            ("num_hidden_layers", self.num_hidden_layers),
+            ("intermediate_size", self.intermediate_size),
+            ("num_attention_heads", self.num_attention_heads),
+            ("num_key_value_heads", self.num_key_value_heads()),
+            ("vocab_size", self.vocab_size),
+        ];
+        for (name, value) in positive {
+            check_positive(name, value)?;
+        }
+
+        // The default `head_dim` divides by `num_attention_heads`, which is checked above
+        check_positive("head_dim", self.head_dim())?;
+
+        if self.num_attention_heads % self.num_key_value_heads() != 0 {
+            return Err(ModelError::InvalidConfig(format!(
+                "num_attention_heads ({}) must be divisible by num_key_value_heads ({})",
+                self.num_attention_heads,
+                self.num_key_value_heads()
+            )));
+        }
+
+        if let Some(RopeScalingConfig::Unsupported(rope_type)) = &self.rope_scaling {
+            return Err(ModelError::InvalidConfig(format!(
+                "rope_scaling type {:?} is not supported, expected linear, dynamic, yarn or llama3",
+                rope_type
+            )));
+        }
+
+        if !(self.partial_rotary_factor > 0.0 && self.partial_rotary_factor <= 1.0) {
+            return Err(ModelError::InvalidConfig(format!(
+                "partial_rotary_factor must be in (0, 1], got {}",
+                self.partial_rotary_factor
+            )));
+        }
+
+        if let Some(quantization) = &self.quantization {
+            quantization.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Builds the rotary positional encoding of the attention layers.
+    ///
+    /// Dynamic scaling without `original_max_position_embeddings` uses `max_position_embeddings`
+    /// instead, as the reference implementation does.
+    pub(crate) fn rope(&self) -> Result<nn::Rope, Exception> {
+        let mut scaling = match &self.rope_scaling {
+            Some(RopeScalingConfig::Supported(scaling)) => Some(scaling.clone()),
+            Some(RopeScalingConfig::Unsupported(rope_type)) => {
+                return Err(Exception::custom(format!(
+                    "Unsupported rope_scaling type {:?}",
+                    rope_type
+                )))
+            }
+            None => None,
+        };
+        if let Some(scaling) = &mut scaling {
+            if scaling.original_max_position_embeddings.is_none() {
+                scaling.original_max_position_embeddings = self.max_position_embeddings;
+            }
+        }
+
+        let dimensions = (self.head_dim() as f32 * self.partial_rotary_factor) as i32;
+        nn::RopeBuilder::new(dimensions)
+            .traditional(self.rope_traditional)
+            .base(self.rope_theta)
+            .scaling(scaling)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LLAMA_3_CONFIG: &str = r#"{
+        "architectures": ["LlamaForCausalLM"],
+        "model_type": "llama",
+        "hidden_size": 2048,
+        "intermediate_size": 8192,
+        "num_attention_heads": 32,
+        "num_hidden_layers": 16,
+        "num_key_value_heads": 8,
+        "head_dim": 64,
+        "max_position_embeddings": 131072,
+        "rms_norm_eps": 1e-05,
+        "rope_scaling": {
+            "factor": 32.0,
+            "high_freq_factor": 4.0,
+            "low_freq_factor": 1.0,
+            "original_max_position_embeddings": 8192,
+            "rope_type": "llama3"
+        },
+        "rope_theta": 500000.0,
+        "tie_word_embeddings": true,
+        "torch_dtype": "bfloat16",
+        "vocab_size": 128256,
+        "quantization": {"group_size": 64, "bits": 4}
+    }"#;
+
+    #[test]
+    fn test_deserialize_config() {
+        let config: ModelConfig = serde_json::from_str(LLAMA_3_CONFIG).unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.model_type, "llama");
+        assert_eq!(config.num_key_value_heads(), 8);
+        assert_eq!(config.head_dim(), 64);
+        assert_eq!(config.rms_norm_eps, 1e-5);
+        assert!(config.tie_word_embeddings);
+        assert!(!config.attention_bias);
+        assert!(matches!(
+            &config.rope_scaling,
+            Some(RopeScalingConfig::Supported(scaling))
+                if scaling.rope_type == nn::RopeScalingType::Llama3
+        ));
+        assert_eq!(
+            config.quantization,
+            Some(QuantizationConfig {
+                group_size: 64,
+                bits: 4,
+                layers: HashMap::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_quantization_layers() {
+        let quantization: QuantizationConfig = serde_json::from_str(
+            r#"{
+                "group_size": 64,
+                "bits": 4,
+                "model.embed_tokens": false,
+                "model.layers.0.mlp.down_proj": {"group_size": 32, "bits": 8}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(quantization.layer("model.embed_tokens", 128), None);
+        assert_eq!(
+            quantization.layer("model.layers.0.mlp.down_proj", 96),
+            Some((32, 8))
+        );
+        assert_eq!(
+            quantization.layer("model.layers.0.mlp.up_proj", 128),
+            Some((64, 4))
+        );
+        assert_eq!(quantization.layer("model.layers.0.mlp.up_proj", 96), None);
+    }
+
+    #[test]
+    fn test_invalid_quantization() {
+        let mut config: ModelConfig = serde_json::from_str(LLAMA_3_CONFIG).unwrap();
+        let quantization = config.quantization.as_mut().unwrap();
+        quantization.group_size = 0;
+        assert!(matches!(
+            config.validate(),
+            Err(ModelError::InvalidConfig(_))
+        ));
+
+        let mut config: ModelConfig = serde_json::from_str(LLAMA_3_CONFIG).unwrap();
+        let quantization = config.quantization.as_mut().unwrap();
+        quantization.layers.insert(
+            "model.layers.0.mlp.down_proj".to_string(),
+            LayerQuantization::Custom {
+                group_size: 64,
+                bits: 5,
+            },
+        );
+        match config.validate() {
+            Err(ModelError::InvalidConfig(message)) => {
+                assert!(message.contains("model.layers.0.mlp.down_proj"))
+            }
+            other => panic!("expected an invalid config, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let mut config: ModelConfig = serde_json::from_str(LLAMA_3_CONFIG).unwrap();
+        config.num_key_value_heads = Some(5);
+        assert!(matches!(
+            config.validate(),
+            Err(ModelError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_zero_heads() {
+        let json = LLAMA_3_CONFIG.replace("\"head_dim\": 64,", "");
+        let mut config: ModelConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.head_dim, None);
+        config.num_attention_heads = 0;
+        assert!(matches!(
+            config.validate(),
+            Err(ModelError::InvalidConfig(_))
+        ));
+
+        let mut config: ModelConfig = serde_json::from_str(&json).unwrap();
+        config.num_key_value_heads = Some(0);
+        assert!(matches!(
+            config.validate(),
+            Err(ModelError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_default_rope_scaling() {
+        let json = LLAMA_3_CONFIG.replace("\"llama3\"", "\"default\"");
+        let config: ModelConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.rope_scaling, None);
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_unsupported_rope_scaling() {
+        for rope_type in ["longrope", "su"] {
+            let json = LLAMA_3_CONFIG.replace(
+                "\"rope_type\": \"llama3\"",
+                &format!("\"type\": \"{}\"", rope_type),
+            );
+            let config: ModelConfig = serde_json::from_str(&json).unwrap();
+            assert_eq!(
+                config.rope_scaling,
+                Some(RopeScalingConfig::Unsupported(rope_type.to_string()))
+            );
+
+            match config.validate() {
+                Err(ModelError::InvalidConfig(message)) => assert!(message.contains(rope_type)),
+                other => panic!("expected an invalid config, got {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Error types of the crate.
+
+use mlx_rs::error::{Exception, IoError};
+use thiserror::Error;
+
+/// Error while building or loading a model.
+#[derive(Debug, Error)]
+pub enum ModelError {
+    /// The configuration does not describe a valid model
+    #[error("Invalid model config: {0}")]
+    InvalidConfig(String),
+
+    /// The `model_type` of the configuration is not supported
+    #[error("Unsupported model type: {0}")]
+    UnsupportedModelType(String),
+
+    /// The checkpoint directory does not contain any weights
+    #[error("No safetensors files found in the checkpoint")]
+    MissingWeights,
+
+    /// Unable to read a file of the checkpoint
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// Unable to parse the configuration
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// Unable to load the weights
+    #[error(transparent)]
+    Load(#[from] IoError),
+
+    /// Exception
+    #[error(transparent)]
+    Exception(#[from] Exception),
+}
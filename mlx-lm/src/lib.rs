@@ -0,0 +1,39 @@
+//! Decoder-only language models built on [`mlx_rs`].
+//!
+//! The models are configured from the `config.json` of a Hugging Face checkpoint and their
+//! parameters follow the keys of the checkpoint, so the `safetensors` weights are loaded as is.
+//! Checkpoints quantized by `mlx-lm` are supported through
+//! [`MaybeQuantized`](mlx_rs::quantization::MaybeQuantized) layers.
+//!
+//! Supported architectures:
+//!
+//! - [`Llama`], which also loads the Mistral and Qwen2 checkpoints
+//! - [`Phi3`]
+//! - [`Gemma`]
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use mlx_lm::load_model;
+//! use mlx_rs::{array, ops::indexing::argmax};
+//!
+//! let mut model = load_model("Mistral-7B-Instruct-v0.3-4bit").unwrap();
+//! let mut cache = model.make_cache();
+//!
+//! // Process the prompt, then feed the generated tokens back one at a time
+//! let prompt = array!([[1, 851, 349, 264]]);
+//! let logits = model.forward_with_cache(&prompt, &mut cache).unwrap();
+//! let next_token = argmax(&logits, -1, None).unwrap();
+//! ```
+
+#![deny(missing_docs, missing_debug_implementations)]
+
+pub mod config;
+pub mod error;
+mod load;
+pub mod models;
+
+pub use config::{LayerQuantization, ModelConfig, QuantizationConfig, RopeScalingConfig};
+pub use error::ModelError;
+pub use load::*;
+pub use models::{Gemma, LanguageModel, Llama, Phi3};
@@ -0,0 +1,308 @@
+//! Loading of Hugging Face checkpoints.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use mlx_rs::{
+    error::Exception,
+    module::{load_parameters, remap_quantized_keys, ModuleParameters, ModuleParametersExt},
+    ops::SAFETENSORS_INDEX_FILE,
+    quantization::Quantizable,
+    Array,
+};
+
+use crate::{
+    config::ModelConfig,
+    error::ModelError,
+    models::{Gemma, LanguageModel, Llama, Phi3},
+};
+
+/// Loads the model of a checkpoint directory with a `config.json` and `safetensors` weights.
+///
+/// The architecture is selected by the `model_type` of the configuration:
+///
+/// - `"llama"`, `"mistral"` and `"qwen2"`: [`Llama`]
+/// - `"phi3"`: [`Phi3`]
+/// - `"gemma"`: [`Gemma`]
+///
+/// If the configuration has a `quantization` entry, the layers are quantized before the weights
+/// are loaded, with the settings given by [`QuantizationConfig::layer`](crate::QuantizationConfig::layer).
+/// See [`load_weights`] for how the weights are found.
+pub fn load_model(path: impl AsRef<Path>) -> Result<Box<dyn LanguageModel>, ModelError> {
+    let path = path.as_ref();
+    let config = ModelConfig::from_file(path)?;
+
+    match config.model_type.as_str() {
+        "llama" | "mistral" | "qwen2" => build_and_load(Llama::new(config)?, path),
+        "phi3" => build_and_load(Phi3::new(config)?, path),
+        "gemma" => build_and_load(Gemma::new(config)?, path),
+        other => Err(ModelError::UnsupportedModelType(other.to_string())),
+    }
+}
+
+fn build_and_load<M>(model: M, path: &Path) -> Result<Box<dyn LanguageModel>, ModelError>
+where
+    M: LanguageModel + Quantizable<Quantized = M, QuantizationError = Exception> + 'static,
+{
+    let mut model = match model.config().quantization.clone() {
+        Some(quantization) => {
+            // The input dimensions of a layer are the last dimension of its weight
+            let input_dims: HashMap<String, i32> = model
+                .parameters()
+                .flatten()
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    let path = key.strip_suffix(".weight")?;
+                    Some((path.to_string(), value.dim(-1)))
+                })
+                .collect();
+            model.try_into_quantized_with("", &mut |path| {
+                quantization.layer(path, *input_dims.get(path)?)
+            })?
+        }
+        None => model,
+    };
+    load_weights(&mut model, path)?;
+    Ok(Box::new(model))
+}
+
+/// Loads the `safetensors` weights of a checkpoint directory into `model`.
+///
+/// The shards listed in [`SAFETENSORS_INDEX_FILE`] are loaded if the directory has one, otherwise
+/// every `.safetensors` file of the directory. The parameters are cast to the data type of the
+/// checkpoint before loading.
+///
+/// The weights of the quantized layers are stored as `weight` in the checkpoints but are nested in
+/// `inner` in the quantized modules, so both keys are accepted, see
+/// [`remap_quantized_keys`](mlx_rs::module::remap_quantized_keys). The rotary embedding buffers and
+/// the output projection of a model whose output projection is tied to the token embedding are
+/// ignored. Any other difference between the checkpoint and the parameters is reported as an
+/// [`IoError::ParameterMismatch`](mlx_rs::error::IoError::ParameterMismatch).
+pub fn load_weights<M>(model: &mut M, path: impl AsRef<Path>) -> Result<(), ModelError>
+where
+    M: ModuleParameters,
+{
+    let weights = load_safetensors_dir(path.as_ref())?;
+
+    // The normalization layers are never quantized, so their weight has the floating point type
+    // of the checkpoint
+    let dtype = weights
+        .iter()
+        .find(|(key, _)| key.ends_with("norm.weight"))
+        .map(|(_, weight)| weight.dtype());
+    if let Some(dtype) = dtype {
+        model.to_dtype(dtype)?;
+    }
+
+    let tied = !model
+        .parameters()
+        .flatten()
+        .keys()
+        .any(|key| key.starts_with("lm_head."));
+    let remap_quantized = remap_quantized_keys(model);
+    let remap = |key: &str| {
+        if key.contains("rotary_emb.inv_freq") || (tied && key.starts_with("lm_head.")) {
+            return None;
+        }
+        remap_quantized(key)
+    };
+
+    load_parameters(model, weights, remap, true)?;
+    Ok(())
+}
+
+/// Loads every array of the `safetensors` files of a checkpoint directory.
+fn load_safetensors_dir(dir: &Path) -> Result<HashMap<String, Array>, ModelError> {
+    if dir.join(SAFETENSORS_INDEX_FILE).is_file() {
+        return Ok(Array::load_safetensors_sharded(dir)?);
+    }
+
+    let mut files = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    files.retain(|file| file.extension().is_some_and(|ext| ext == "safetensors"));
+    files.sort();
+
+    if files.is_empty() {
+        return Err(ModelError::MissingWeights);
+    }
+
+    let mut weights = HashMap::new();
+    for file in files {
+        weights.extend(Array::load_safetensors(file)?);
+    }
+    Ok(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use mlx_rs::{array, module::Module, nn, Dtype};
+
+    use super::*;
+
+    fn write_checkpoint(dir: &Path, config: serde_json::Value, model: &impl ModuleParameters) {
+        std::fs::write(dir.join("config.json"), config.to_string()).unwrap();
+
+        let mut weights: HashMap<String, Array> = model
+            .parameters()
+            .flatten()
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value.dtype().is_float() {
+                    true => value.as_dtype(Dtype::Float16).unwrap(),
+                    false => value.clone(),
+                };
+                (key.to_string(), value)
+            })
+            .collect();
+        weights.insert(
+            "model.layers.0.self_attn.rotary_emb.inv_freq".to_string(),
+            array!([1.0f32]),
+        );
+        Array::save_safetensors(&weights, None, dir.join("model.safetensors")).unwrap();
+    }
+
+    fn config_json(model_type: &str) -> serde_json::Value {
+        serde_json::json!({
+            "model_type": model_type,
+            "hidden_size": 64,
+            "num_hidden_layers": 1,
+            "intermediate_size": 128,
+            "num_attention_heads": 4,
+            "vocab_size": 64,
+            "tie_word_embeddings": true,
+        })
+    }
+
+    #[test]
+    fn test_load_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config_json("qwen2");
+        let mut reference = Llama::new(serde_json::from_value(config.clone()).unwrap()).unwrap();
+        write_checkpoint(dir.path(), config, &reference);
+
+        let mut model = load_model(dir.path()).unwrap();
+        assert_eq!(model.config().model_type, "qwen2");
+
+        let inputs = array!([[1, 2, 3]]);
+        let mut cache = model.make_cache();
+        let logits = model.forward_with_cache(&inputs, &mut cache).unwrap();
+        assert_eq!(logits.dtype(), Dtype::Float16);
+
+        reference.to_dtype(Dtype::Float16).unwrap();
+        let expected = reference.forward(&inputs).unwrap();
+        assert!(logits
+            .all_close(&expected, 1e-2, 1e-2, None)
+            .unwrap()
+            .item::<bool>());
+    }
+
+    #[test]
+    fn test_load_quantized_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config_json("gemma");
+        config["quantization"] = serde_json::json!({"group_size": 64, "bits": 4});
+
+        // The quantized checkpoints store the weights of the quantized layers as `weight`
+        let reference = Gemma::new(serde_json::from_value(config.clone()).unwrap()).unwrap();
+        let reference = nn::quantize(reference, 64, 4).unwrap();
+        write_checkpoint(dir.path(), config, &reference);
+        let checkpoint = dir.path().join("model.safetensors");
+        let weights: HashMap<String, Array> = Array::load_safetensors(&checkpoint)
+            .unwrap()
+            .into_iter()
+            .map(|(key, value)| (key.replace(".inner.", "."), value))
+            .collect();
+        Array::save_safetensors(&weights, None, &checkpoint).unwrap();
+
+        let mut model = load_model(dir.path()).unwrap();
+        let mut cache = model.make_cache();
+        let logits = model
+            .forward_with_cache(&array!([[4, 5]]), &mut cache)
+            .unwrap();
+        assert_eq!(logits.shape(), &[1, 2, 64]);
+    }
+
+    #[test]
+    fn test_load_partially_quantized_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config_json("llama");
+        config["quantization"] = serde_json::json!({
+            "group_size": 128,
+            "bits": 4,
+            "model.layers.0.self_attn.o_proj": {"group_size": 32, "bits": 8},
+        });
+
+        // Only the input dimensions of `down_proj` are divisible by 128
+        let reference = Llama::new(serde_json::from_value(config.clone()).unwrap()).unwrap();
+        let reference = reference
+            .try_into_quantized_with("", &mut |path| {
+                if path.ends_with("down_proj") {
+                    Some((128, 4))
+                } else if path.ends_with("o_proj") {
+                    Some((32, 8))
+                } else {
+                    None
+                }
+            })
+            .unwrap();
+        write_checkpoint(dir.path(), config, &reference);
+        let checkpoint = dir.path().join("model.safetensors");
+        let weights: HashMap<String, Array> = Array::load_safetensors(&checkpoint)
+            .unwrap()
+            .into_iter()
+            .map(|(key, value)| (key.replace(".inner.", "."), value))
+            .collect();
+        Array::save_safetensors(&weights, None, &checkpoint).unwrap();
+
+        let mut model = load_model(dir.path()).unwrap();
+        let params = model.parameters().flatten();
+        assert!(params.contains_key("model.layers.0.mlp.down_proj.scales"));
+        assert!(params.contains_key("model.layers.0.self_attn.o_proj.scales"));
+        assert!(!params.contains_key("model.layers.0.mlp.up_proj.scales"));
+        assert!(!params.contains_key("model.embed_tokens.scales"));
+
+        let mut cache = model.make_cache();
+        let logits = model
+            .forward_with_cache(&array!([[4, 5]]), &mut cache)
+            .unwrap();
+        assert_eq!(logits.shape(), &[1, 2, 64]);
+    }
+
+    #[test]
+    fn test_load_model_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("config.json"),
+            config_json("mamba").to_string(),
+        )
+        .unwrap();
+        assert!(matches!(
+            load_model(dir.path()),
+            Err(ModelError::UnsupportedModelType(_))
+        ));
+
+        std::fs::write(
+            dir.path().join("config.json"),
+            config_json("llama").to_string(),
+        )
+        .unwrap();
+        assert!(matches!(
+            load_model(dir.path()),
+            Err(ModelError::MissingWeights)
+        ));
+
+        let mut config = config_json("llama");
+        config["tie_word_embeddings"] = serde_json::json!(false);
+        let model = Llama::new(serde_json::from_value(config).unwrap()).unwrap();
+        write_checkpoint(dir.path(), config_json("llama"), &model);
+        std::fs::write(
+            dir.path().join("config.json"),
+            config_json("phi3").to_string(),
+        )
+        .unwrap();
+        assert!(matches!(load_model(dir.path()), Err(ModelError::Load(_))));
+    }
+}
@@ -0,0 +1,258 @@
+use mlx_rs::{
+    array,
+    error::Exception,
+    fast,
+    macros::{ModuleParameters, Quantizable},
+    module::{Module, Param},
+    nn::{self, KeyValueCache},
+    ops::zeros,
+    quantization::MaybeQuantized,
+    Array,
+};
+
+use crate::{config::ModelConfig, error::ModelError};
+
+use super::{
+    check_cache_len, create_attention_mask, embedding_as_linear, Activation, Attention,
+    AttentionInput, LanguageModel, Mlp,
+};
+
+/// RMS normalization that scales by `1 + weight`, so the weight is initialized with zeros.
+#[derive(Debug, Clone, ModuleParameters)]
+struct RmsNorm {
+    #[param]
+    weight: Param<Array>,
+
+    eps: f32,
+}
+
+impl RmsNorm {
+    fn new(dimensions: i32, eps: f32) -> Result<Self, Exception> {
+        Ok(Self {
+            weight: Param::new(zeros::<f32>(&[dimensions])?),
+            eps,
+        })
+    }
+}
+
+impl Module<&Array> for RmsNorm {
+    type Output = Array;
+
+    type Error = Exception;
+
+    fn forward(&mut self, x: &Array) -> Result<Self::Output, Self::Error> {
+        let weight = self.weight.as_ref();
+        let weight = weight.add(array!(1.0f32).as_dtype(weight.dtype())?)?;
+        fast::rms_norm(x, weight, self.eps)
+    }
+
+    fn training_mode(&mut self, _mode: bool) {}
+}
+
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+struct DecoderLayer {
+    #[quantizable]
+    #[param]
+    self_attn: Attention,
+
+    #[quantizable]
+    #[param]
+    mlp: Mlp,
+
+    #[param]
+    input_layernorm: RmsNorm,
+
+    #[param]
+    post_attention_layernorm: RmsNorm,
+}
+
+impl DecoderLayer {
+    fn new(config: &ModelConfig) -> Result<Self, Exception> {
+        Ok(Self {
+            self_attn: Attention::new(config, config.attention_bias, config.attention_bias)?,
+            mlp: Mlp::new(config, Activation::GeluApproximate)?,
+            input_layernorm: RmsNorm::new(config.hidden_size, config.rms_norm_eps)?,
+            post_attention_layernorm: RmsNorm::new(config.hidden_size, config.rms_norm_eps)?,
+        })
+    }
+}
+
+impl Module<AttentionInput<'_>> for DecoderLayer {
+    type Output = Array;
+
+    type Error = Exception;
+
+    fn forward(&mut self, input: AttentionInput<'_>) -> Result<Self::Output, Self::Error> {
+        let AttentionInput { x, mask, cache } = input;
+
+        let normed = self.input_layernorm.forward(x)?;
+        let attention_input = AttentionInput {
+            x: &normed,
+            mask,
+            cache,
+        };
+        let h = x.add(self.self_attn.forward(attention_input)?)?;
+        let r = self
+            .mlp
+            .forward(&self.post_attention_layernorm.forward(&h)?)?;
+        h.add(r)
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.self_attn.training_mode(mode);
+        self.mlp.training_mode(mode);
+    }
+}
+
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+struct GemmaModel {
+    #[quantizable]
+    #[param]
+    embed_tokens: MaybeQuantized<nn::Embedding>,
+
+    #[quantizable]
+    #[param]
+    layers: Vec<DecoderLayer>,
+
+    #[param]
+    norm: RmsNorm,
+}
+
+/// Gemma model.
+///
+/// Gemma differs from Llama by the scaling of the token embedding by `sqrt(hidden_size)`, the
+/// `1 + weight` scaling of the RMS normalization, the GELU activation of the feed forward layers
+/// and the output projection, which is always tied to the token embedding.
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+pub struct Gemma {
+    config: ModelConfig,
+
+    #[quantizable]
+    #[param]
+    model: GemmaModel,
+}
+
+impl Gemma {
+    /// Creates a model with randomly initialized weights.
+    pub fn new(config: ModelConfig) -> Result<Self, ModelError> {
+        config.validate()?;
+
+        let embed_tokens = nn::Embedding::new(config.vocab_size, config.hidden_size)?;
+        let layers = (0..config.num_hidden_layers)
+            .map(|_| DecoderLayer::new(&config))
+            .collect::<Result<Vec<_>, _>>()?;
+        let norm = RmsNorm::new(config.hidden_size, config.rms_norm_eps)?;
+
+        Ok(Self {
+            config,
+            model: GemmaModel {
+                embed_tokens: MaybeQuantized::new(embed_tokens),
+                layers,
+                norm,
+            },
+        })
+    }
+
+    /// Runs the layers, each with the cache yielded by `caches`.
+    fn forward_layers<'c>(
+        &mut self,
+        inputs: &Array,
+        offset: i32,
+        caches: impl Iterator<Item = Option<&'c mut dyn KeyValueCache>>,
+    ) -> Result<Array, Exception> {
+        let h = self.model.embed_tokens.forward(inputs)?;
+        let scale = array!((self.config.hidden_size as f32).sqrt()).as_dtype(h.dtype())?;
+        let mut h = h.multiply(scale)?;
+        let mask = create_attention_mask(&h, offset)?;
+
+        for (layer, cache) in self.model.layers.iter_mut().zip(caches) {
+            let layer_input = AttentionInput {
+                x: &h,
+                mask: mask.as_ref(),
+                cache,
+            };
+            h = layer.forward(layer_input)?;
+        }
+
+        let h = self.model.norm.forward(&h)?;
+        embedding_as_linear(&self.model.embed_tokens, &h)
+    }
+}
+
+impl Module<&Array> for Gemma {
+    type Output = Array;
+
+    type Error = Exception;
+
+    fn forward(&mut self, inputs: &Array) -> Result<Self::Output, Self::Error> {
+        self.forward_layers(inputs, 0, std::iter::repeat_with(|| None))
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.model.embed_tokens.training_mode(mode);
+        self.model
+            .layers
+            .iter_mut()
+            .for_each(|layer| layer.training_mode(mode));
+    }
+}
+
+impl LanguageModel for Gemma {
+    fn config(&self) -> &ModelConfig {
+        &self.config
+    }
+
+    fn forward_with_cache(
+        &mut self,
+        inputs: &Array,
+        cache: &mut [Box<dyn KeyValueCache>],
+    ) -> Result<Array, Exception> {
+        check_cache_len(cache, self.model.layers.len())?;
+
+        let offset = cache.first().map(|c| c.offset()).unwrap_or(0);
+        let caches = cache.iter_mut().map(|c| Some(c as &mut dyn KeyValueCache));
+        self.forward_layers(inputs, offset, caches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mlx_rs::{module::ModuleParameters, ops::indexing::IndexOp};
+
+    use super::*;
+    use crate::models::tiny_config;
+
+    #[test]
+    fn test_gemma() {
+        let mut config = tiny_config("gemma");
+        config.head_dim = Some(32);
+        let mut model = Gemma::new(config).unwrap();
+
+        let params = model.parameters().flatten();
+        assert!(!params.keys().any(|key| key.starts_with("lm_head")));
+        assert_eq!(
+            params["model.layers.0.self_attn.q_proj.weight"].shape(),
+            &[128, 64]
+        );
+        assert_eq!(
+            params["model.layers.0.self_attn.o_proj.weight"].shape(),
+            &[64, 128]
+        );
+
+        let inputs = mlx_rs::array!([[3, 1, 4, 1]]);
+        let logits = model.forward(&inputs).unwrap();
+        assert_eq!(logits.shape(), &[1, 4, 128]);
+
+        let mut cache = model.make_cache();
+        model
+            .forward_with_cache(&inputs.index((.., ..3)), &mut cache)
+            .unwrap();
+        let last = model
+            .forward_with_cache(&inputs.index((.., 3..)), &mut cache)
+            .unwrap();
+        assert!(last
+            .all_close(logits.index((.., 3..)), 1e-4, 1e-4, None)
+            .unwrap()
+            .item::<bool>());
+    }
+}
@@ -0,0 +1,296 @@
+use mlx_rs::{
+    builder::Builder,
+    error::Exception,
+    macros::{ModuleParameters, Quantizable},
+    module::Module,
+    nn::{self, KeyValueCache},
+    quantization::MaybeQuantized,
+    Array,
+};
+
+use crate::{config::ModelConfig, error::ModelError};
+
+use super::{
+    check_cache_len, create_attention_mask, embedding_as_linear, Activation, Attention,
+    AttentionInput, LanguageModel, Mlp,
+};
+
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+struct DecoderLayer {
+    #[quantizable]
+    #[param]
+    self_attn: Attention,
+
+    #[quantizable]
+    #[param]
+    mlp: Mlp,
+
+    #[param]
+    input_layernorm: nn::RmsNorm,
+
+    #[param]
+    post_attention_layernorm: nn::RmsNorm,
+}
+
+impl DecoderLayer {
+    fn new(config: &ModelConfig) -> Result<Self, Exception> {
+        // Qwen2 has biases on the query, key and value projections but no `attention_bias` entry
+        let qkv_bias = config.attention_bias || config.model_type == "qwen2";
+
+        Ok(Self {
+            self_attn: Attention::new(config, qkv_bias, config.attention_bias)?,
+            mlp: Mlp::new(config, Activation::Silu)?,
+            input_layernorm: nn::RmsNormBuilder::new(config.hidden_size)
+                .eps(config.rms_norm_eps)
+                .build()?,
+            post_attention_layernorm: nn::RmsNormBuilder::new(config.hidden_size)
+                .eps(config.rms_norm_eps)
+                .build()?,
+        })
+    }
+}
+
+impl Module<AttentionInput<'_>> for DecoderLayer {
+    type Output = Array;
+
+    type Error = Exception;
+
+    fn forward(&mut self, input: AttentionInput<'_>) -> Result<Self::Output, Self::Error> {
+        let AttentionInput { x, mask, cache } = input;
+
+        let normed = self.input_layernorm.forward(x)?;
+        let attention_input = AttentionInput {
+            x: &normed,
+            mask,
+            cache,
+        };
+        let h = x.add(self.self_attn.forward(attention_input)?)?;
+        let r = self
+            .mlp
+            .forward(&self.post_attention_layernorm.forward(&h)?)?;
+        h.add(r)
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.self_attn.training_mode(mode);
+        self.mlp.training_mode(mode);
+        self.input_layernorm.training_mode(mode);
+        self.post_attention_layernorm.training_mode(mode);
+    }
+}
+
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+struct LlamaModel {
+    #[quantizable]
+    #[param]
+    embed_tokens: MaybeQuantized<nn::Embedding>,
+
+    #[quantizable]
+    #[param]
+    layers: Vec<DecoderLayer>,
+
+    #[param]
+    norm: nn::RmsNorm,
+}
+
+/// Llama model, also used for the Mistral and Qwen2 checkpoints.
+///
+/// Mistral only differs from Llama by its hyperparameters and Qwen2 by the biases of the query,
+/// key and value projections. The sliding window attention of the Mistral configuration is not
+/// applied, which only matters for sequences longer than the window.
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+pub struct Llama {
+    config: ModelConfig,
+
+    #[quantizable]
+    #[param]
+    model: LlamaModel,
+
+    /// `None` if the output projection is tied to the token embedding
+    #[quantizable]
+    #[param]
+    lm_head: Option<MaybeQuantized<nn::Linear>>,
+}
+
+impl Llama {
+    /// Creates a model with randomly initialized weights.
+    pub fn new(config: ModelConfig) -> Result<Self, ModelError> {
+        config.validate()?;
+
+        let embed_tokens = nn::Embedding::new(config.vocab_size, config.hidden_size)?;
+        let layers = (0..config.num_hidden_layers)
+            .map(|_| DecoderLayer::new(&config))
+            .collect::<Result<Vec<_>, _>>()?;
+        let norm = nn::RmsNormBuilder::new(config.hidden_size)
+            .eps(config.rms_norm_eps)
+            .build()?;
+        let lm_head = match config.tie_word_embeddings {
+            true => None,
+            false => {
+                let lm_head = nn::LinearBuilder::new(config.hidden_size, config.vocab_size)
+                    .bias(false)
+                    .build()?;
+                Some(MaybeQuantized::new(lm_head))
+            }
+        };
+
+        Ok(Self {
+            config,
+            model: LlamaModel {
+                embed_tokens: MaybeQuantized::new(embed_tokens),
+                layers,
+                norm,
+            },
+            lm_head,
+        })
+    }
+
+    /// Runs the layers, each with the cache yielded by `caches`.
+    fn forward_layers<'c>(
+        &mut self,
+        inputs: &Array,
+        offset: i32,
+        caches: impl Iterator<Item = Option<&'c mut dyn KeyValueCache>>,
+    ) -> Result<Array, Exception> {
+        let mut h = self.model.embed_tokens.forward(inputs)?;
+        let mask = create_attention_mask(&h, offset)?;
+
+        for (layer, cache) in self.model.layers.iter_mut().zip(caches) {
+            let layer_input = AttentionInput {
+                x: &h,
+                mask: mask.as_ref(),
+                cache,
+            };
+            h = layer.forward(layer_input)?;
+        }
+
+        let h = self.model.norm.forward(&h)?;
+        match &mut self.lm_head {
+            Some(lm_head) => lm_head.forward(&h),
+            None => embedding_as_linear(&self.model.embed_tokens, &h),
+        }
+    }
+}
+
+impl Module<&Array> for Llama {
+    type Output = Array;
+
+    type Error = Exception;
+
+    fn forward(&mut self, inputs: &Array) -> Result<Self::Output, Self::Error> {
+        self.forward_layers(inputs, 0, std::iter::repeat_with(|| None))
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.model.embed_tokens.training_mode(mode);
+        self.model
+            .layers
+            .iter_mut()
+            .for_each(|layer| layer.training_mode(mode));
+        self.model.norm.training_mode(mode);
+        if let Some(lm_head) = &mut self.lm_head {
+            lm_head.training_mode(mode);
+        }
+    }
+}
+
+impl LanguageModel for Llama {
+    fn config(&self) -> &ModelConfig {
+        &self.config
+    }
+
+    fn forward_with_cache(
+        &mut self,
+        inputs: &Array,
+        cache: &mut [Box<dyn KeyValueCache>],
+    ) -> Result<Array, Exception> {
+        check_cache_len(cache, self.model.layers.len())?;
+
+        let offset = cache.first().map(|c| c.offset()).unwrap_or(0);
+        let caches = cache.iter_mut().map(|c| Some(c as &mut dyn KeyValueCache));
+        self.forward_layers(inputs, offset, caches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mlx_rs::{
+        array,
+        module::ModuleParameters,
+        ops::{concatenate, indexing::IndexOp},
+    };
+
+    use super::*;
+    use crate::models::tiny_config;
+
+    #[test]
+    fn test_llama_parameter_keys() {
+        let model = Llama::new(tiny_config("llama")).unwrap();
+        let params = model.parameters().flatten();
+
+        assert!(params.contains_key("model.embed_tokens.weight"));
+        assert!(params.contains_key("model.layers.1.self_attn.q_proj.weight"));
+        assert!(params.contains_key("model.layers.1.mlp.gate_proj.weight"));
+        assert!(params.contains_key("model.layers.1.post_attention_layernorm.weight"));
+        assert!(params.contains_key("model.norm.weight"));
+        assert!(params.contains_key("lm_head.weight"));
+        assert!(!params.contains_key("model.layers.0.self_attn.q_proj.bias"));
+        assert_eq!(
+            params["model.layers.0.self_attn.k_proj.weight"].shape(),
+            &[32, 64]
+        );
+
+        let qwen2 = Llama::new(tiny_config("qwen2")).unwrap();
+        let params = qwen2.parameters().flatten();
+        assert!(params.contains_key("model.layers.0.self_attn.q_proj.bias"));
+        assert!(!params.contains_key("model.layers.0.self_attn.o_proj.bias"));
+    }
+
+    #[test]
+    fn test_llama_cache_matches_full_forward() {
+        let mut config = tiny_config("mistral");
+        config.tie_word_embeddings = true;
+        let mut model = Llama::new(config).unwrap();
+
+        let inputs = array!([[1, 5, 7, 3, 9]]);
+        let logits = model.forward(&inputs).unwrap();
+        assert_eq!(logits.shape(), &[1, 5, 128]);
+
+        // A prompt of three tokens followed by two tokens decoded one at a time
+        let mut cache = model.make_cache();
+        let prompt = model
+            .forward_with_cache(&inputs.index((.., ..3)), &mut cache)
+            .unwrap();
+        let fourth = model
+            .forward_with_cache(&inputs.index((.., 3..4)), &mut cache)
+            .unwrap();
+        let fifth = model
+            .forward_with_cache(&inputs.index((.., 4..)), &mut cache)
+            .unwrap();
+        assert_eq!(cache[0].offset(), 5);
+
+        let cached = concatenate(&[prompt, fourth, fifth], 1).unwrap();
+        assert!(cached
+            .all_close(&logits, 1e-4, 1e-4, None)
+            .unwrap()
+            .item::<bool>());
+
+        let mut too_short = cache.split_off(1);
+        assert!(model.forward_with_cache(&inputs, &mut too_short).is_err());
+    }
+
+    #[test]
+    fn test_quantized_llama() {
+        let model = Llama::new(tiny_config("llama")).unwrap();
+        let mut model = nn::quantize(model, None, None).unwrap();
+        assert!(model.model.layers[0].self_attn.q_proj.is_quantized());
+        assert!(model.lm_head.as_ref().unwrap().is_quantized());
+
+        let params = model.parameters().flatten();
+        assert!(params.contains_key("model.embed_tokens.scales"));
+        assert!(params.contains_key("model.layers.0.mlp.down_proj.inner.weight"));
+
+        let logits = model.forward(&array!([[1, 2, 3]])).unwrap();
+        assert_eq!(logits.shape(), &[1, 3, 128]);
+    }
+}
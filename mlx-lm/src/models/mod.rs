@@ -0,0 +1,425 @@
+//! Architectures of the supported models.
+//!
+//! The parameters of every model are named after the keys of the Hugging Face checkpoints, so the
+//! weights are loaded without renaming.
+
+use mlx_rs::{
+    array,
+    builder::Builder,
+    error::Exception,
+    fast::scaled_dot_product_attention,
+    macros::{ModuleParameters, Quantizable},
+    module::{Module, ModuleParameters},
+    nn::{self, KeyValueCache},
+    ops::{arange, expand_dims},
+    quantization::MaybeQuantized,
+    Array,
+};
+
+use crate::config::ModelConfig;
+
+mod gemma;
+mod llama;
+mod phi3;
+
+pub use gemma::*;
+pub use llama::*;
+pub use phi3::*;
+
+/// A decoder-only language model.
+///
+/// This trait is object safe so that a model loaded with [`load_model`](crate::load_model) can be
+/// used without knowing its architecture.
+pub trait LanguageModel: ModuleParameters + std::fmt::Debug {
+    /// Configuration the model was built with.
+    fn config(&self) -> &ModelConfig;
+
+    /// Computes the logits of the next token at every position of `inputs`.
+    ///
+    /// `inputs` are token ids of shape `[batch, sequence]` and the output has the shape
+    /// `[batch, sequence, vocab_size]`. The keys and values of the new positions are appended to
+    /// `cache`, which must hold one cache per layer, eg. from [`LanguageModel::make_cache`].
+    fn forward_with_cache(
+        &mut self,
+        inputs: &Array,
+        cache: &mut [Box<dyn KeyValueCache>],
+    ) -> Result<Array, Exception>;
+
+    /// Creates an empty [`KvCache`](nn::KvCache) for each layer.
+    fn make_cache(&self) -> Vec<Box<dyn KeyValueCache>> {
+        (0..self.config().num_hidden_layers)
+            .map(|_| Box::new(nn::KvCache::new()) as Box<dyn KeyValueCache>)
+            .collect()
+    }
+}
+
+/// Returns an error unless there is one cache per layer.
+fn check_cache_len<C>(cache: &[C], num_layers: usize) -> Result<(), Exception> {
+    if cache.len() != num_layers {
+        return Err(Exception::custom(format!(
+            "Expected {} caches, got {}",
+            num_layers,
+            cache.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Creates the additive causal mask of the hidden states `h` of shape `[batch, sequence, dims]`
+/// that follow `offset` cached positions, or `None` if there is a single position.
+fn create_attention_mask(h: &Array, offset: i32) -> Result<Option<Array>, Exception> {
+    let n = h.dim(1);
+    if n <= 1 {
+        return Ok(None);
+    }
+
+    let rows = arange::<_, i32>(offset, offset + n, None)?;
+    let columns = arange::<_, i32>(None, offset + n, None)?;
+    let mask = expand_dims(&rows, &[1])?.lt(expand_dims(&columns, &[0])?)?;
+    let mask = mask
+        .as_type::<f32>()?
+        .multiply(array!(f32::MIN))?
+        .as_dtype(h.dtype())?;
+    Ok(Some(mask))
+}
+
+/// Projects `x` to the vocabulary with the weight of `embedding`, for models whose output
+/// projection is tied to the token embedding.
+fn embedding_as_linear(
+    embedding: &MaybeQuantized<nn::Embedding>,
+    x: &Array,
+) -> Result<Array, Exception> {
+    match embedding {
+        MaybeQuantized::Original(embedding) => embedding.as_linear(x),
+        MaybeQuantized::Quantized(embedding) => embedding.as_linear(x),
+    }
+}
+
+/// Input to the [`Attention`] and the decoder layers.
+struct AttentionInput<'a> {
+    x: &'a Array,
+    mask: Option<&'a Array>,
+    cache: Option<&'a mut dyn KeyValueCache>,
+}
+
+/// Self attention with separate query, key, value and output projections and grouped-query
+/// attention.
+///
+/// This computes the same as [`nn::MultiHeadAttention::forward_with_cache`] with a rotary
+/// positional encoding, which is checked by the tests, but the library module does not fit the
+/// checkpoints:
+///
+/// - its projections are named `query_proj`, `key_proj`, `value_proj` and `output_proj`, while
+///   the checkpoints, and the layer paths in the `quantization` entry of `config.json`, use
+///   `q_proj`, `k_proj`, `v_proj` and `o_proj`. Renaming the keys when loading would not rename
+///   the paths given to the quantization predicate, nor the keys of the saved weights.
+/// - its projections share a single `bias`, while Qwen2 has a bias on the query, key and value
+///   projections but not on the output projection.
+///
+/// Phi-3 fuses the query, key and value projections, so it only shares [`attend`] with this.
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+struct Attention {
+    num_heads: i32,
+    num_kv_heads: i32,
+    scale: f32,
+
+    #[quantizable]
+    #[param]
+    q_proj: MaybeQuantized<nn::Linear>,
+
+    #[quantizable]
+    #[param]
+    k_proj: MaybeQuantized<nn::Linear>,
+
+    #[quantizable]
+    #[param]
+    v_proj: MaybeQuantized<nn::Linear>,
+
+    #[quantizable]
+    #[param]
+    o_proj: MaybeQuantized<nn::Linear>,
+
+    rope: nn::Rope,
+}
+
+impl Attention {
+    fn new(config: &ModelConfig, qkv_bias: bool, o_bias: bool) -> Result<Self, Exception> {
+        let dims = config.hidden_size;
+        let num_heads = config.num_attention_heads;
+        let num_kv_heads = config.num_key_value_heads();
+        let head_dim = config.head_dim();
+
+        let q_proj = nn::LinearBuilder::new(dims, num_heads * head_dim)
+            .bias(qkv_bias)
+            .build()?;
+        let k_proj = nn::LinearBuilder::new(dims, num_kv_heads * head_dim)
+            .bias(qkv_bias)
+            .build()?;
+        let v_proj = nn::LinearBuilder::new(dims, num_kv_heads * head_dim)
+            .bias(qkv_bias)
+            .build()?;
+        let o_proj = nn::LinearBuilder::new(num_heads * head_dim, dims)
+            .bias(o_bias)
+            .build()?;
+
+        Ok(Self {
+            num_heads,
+            num_kv_heads,
+            scale: (head_dim as f32).powf(-0.5),
+            q_proj: MaybeQuantized::new(q_proj),
+            k_proj: MaybeQuantized::new(k_proj),
+            v_proj: MaybeQuantized::new(v_proj),
+            o_proj: MaybeQuantized::new(o_proj),
+            rope: config.rope()?,
+        })
+    }
+}
+
+/// Applies the rotary positional encoding to the queries and keys of shape
+/// `[batch, heads, sequence, head_dim]`, updates the cache and computes the attention.
+///
+/// Returns the output of shape `[batch, sequence, heads * head_dim]`.
+fn attend(
+    rope: &mut nn::Rope,
+    scale: f32,
+    queries: Array,
+    keys: Array,
+    values: Array,
+    mask: Option<&Array>,
+    cache: Option<&mut dyn KeyValueCache>,
+) -> Result<Array, Exception> {
+    let (queries, keys, values) = match cache {
+        Some(cache) => {
+            let offset = cache.offset();
+            let queries = rope.forward((&queries, offset))?;
+            let keys = rope.forward((&keys, offset))?;
+            let (keys, values) = cache.update_and_fetch(keys, values)?;
+            (queries, keys, values)
+        }
+        None => (rope.forward(&queries)?, rope.forward(&keys)?, values),
+    };
+
+    let (batch, length) = (queries.dim(0), queries.dim(2));
+    scaled_dot_product_attention(queries, keys, values, scale, mask, None)?
+        .transpose(&[0, 2, 1, 3])?
+        .reshape(&[batch, length, -1])
+}
+
+impl Module<AttentionInput<'_>> for Attention {
+    type Output = Array;
+
+    type Error = Exception;
+
+    fn forward(&mut self, input: AttentionInput<'_>) -> Result<Self::Output, Self::Error> {
+        let AttentionInput { x, mask, cache } = input;
+        let (batch, length) = (x.dim(0), x.dim(1));
+
+        let queries = self
+            .q_proj
+            .forward(x)?
+            .reshape(&[batch, length, self.num_heads, -1])?
+            .transpose(&[0, 2, 1, 3])?;
+        let keys = self
+            .k_proj
+            .forward(x)?
+            .reshape(&[batch, length, self.num_kv_heads, -1])?
+            .transpose(&[0, 2, 1, 3])?;
+        let values = self
+            .v_proj
+            .forward(x)?
+            .reshape(&[batch, length, self.num_kv_heads, -1])?
+            .transpose(&[0, 2, 1, 3])?;
+
+        let output = attend(
+            &mut self.rope,
+            self.scale,
+            queries,
+            keys,
+            values,
+            mask,
+            cache,
+        )?;
+        self.o_proj.forward(&output)
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.q_proj.training_mode(mode);
+        self.k_proj.training_mode(mode);
+        self.v_proj.training_mode(mode);
+        self.o_proj.training_mode(mode);
+    }
+}
+
+/// Activation of the gated feed forward layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Activation {
+    Silu,
+    GeluApproximate,
+}
+
+impl Activation {
+    fn apply(self, x: &Array) -> Result<Array, Exception> {
+        match self {
+            Activation::Silu => nn::silu(x),
+            Activation::GeluApproximate => nn::gelu_approximate(x),
+        }
+    }
+}
+
+/// Gated feed forward layer, ie. `down_proj(activation(gate_proj(x)) * up_proj(x))`.
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+struct Mlp {
+    activation: Activation,
+
+    #[quantizable]
+    #[param]
+    gate_proj: MaybeQuantized<nn::Linear>,
+
+    #[quantizable]
+    #[param]
+    up_proj: MaybeQuantized<nn::Linear>,
+
+    #[quantizable]
+    #[param]
+    down_proj: MaybeQuantized<nn::Linear>,
+}
+
+impl Mlp {
+    fn new(config: &ModelConfig, activation: Activation) -> Result<Self, Exception> {
+        let dims = config.hidden_size;
+        let hidden_dims = config.intermediate_size;
+
+        let gate_proj = nn::LinearBuilder::new(dims, hidden_dims)
+            .bias(config.mlp_bias)
+            .build()?;
+        let up_proj = nn::LinearBuilder::new(dims, hidden_dims)
+            .bias(config.mlp_bias)
+            .build()?;
+        let down_proj = nn::LinearBuilder::new(hidden_dims, dims)
+            .bias(config.mlp_bias)
+            .build()?;
+
+        Ok(Self {
+            activation,
+            gate_proj: MaybeQuantized::new(gate_proj),
+            up_proj: MaybeQuantized::new(up_proj),
+            down_proj: MaybeQuantized::new(down_proj),
+        })
+    }
+}
+
+impl Module<&Array> for Mlp {
+    type Output = Array;
+
+    type Error = Exception;
+
+    fn forward(&mut self, x: &Array) -> Result<Self::Output, Self::Error> {
+        let gate = self.activation.apply(&self.gate_proj.forward(x)?)?;
+        let hidden = gate.multiply(self.up_proj.forward(x)?)?;
+        self.down_proj.forward(&hidden)
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.gate_proj.training_mode(mode);
+        self.up_proj.training_mode(mode);
+        self.down_proj.training_mode(mode);
+    }
+}
+
+/// A small configuration for the tests of the models.
+#[cfg(test)]
+fn tiny_config(model_type: &str) -> ModelConfig {
+    serde_json::from_value(serde_json::json!({
+        "model_type": model_type,
+        "hidden_size": 64,
+        "num_hidden_layers": 2,
+        "intermediate_size": 128,
+        "num_attention_heads": 4,
+        "num_key_value_heads": 2,
+        "vocab_size": 128,
+    }))
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use mlx_rs::{
+        ops::{indexing::IndexOp, zeros},
+        Dtype,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_attention_matches_multi_head_attention() {
+        mlx_rs::random::seed(42).unwrap();
+
+        // A head dimension that is not `hidden_size / num_attention_heads`, as in Gemma
+        let mut config = tiny_config("llama");
+        config.head_dim = Some(32);
+        let (num_heads, num_kv_heads) = (config.num_attention_heads, config.num_key_value_heads());
+        let dims = num_heads * config.head_dim();
+
+        let mut attention = Attention::new(&config, false, false).unwrap();
+        let mut mha = nn::MultiHeadAttentionBuilder::new(dims, num_heads)
+            .num_kv_heads(num_kv_heads)
+            .query_input_dims(config.hidden_size)
+            .key_input_dims(config.hidden_size)
+            .value_input_dims(config.hidden_size)
+            .value_output_dims(config.hidden_size)
+            .rope(config.rope().unwrap())
+            .build()
+            .unwrap();
+        mha.query_proj = attention.q_proj.clone();
+        mha.key_proj = attention.k_proj.clone();
+        mha.value_proj = attention.v_proj.clone();
+        mha.output_proj = attention.o_proj.clone();
+
+        // A prompt of three positions followed by a single position
+        let mut cache = nn::KvCache::new();
+        let mut mha_cache = nn::KvCache::new();
+        for length in [3, 1] {
+            let x =
+                mlx_rs::random::normal::<f32>(&[1, length, config.hidden_size], None, None, None)
+                    .unwrap();
+            let mask = create_attention_mask(&x, cache.offset()).unwrap();
+
+            let output = attention
+                .forward(AttentionInput {
+                    x: &x,
+                    mask: mask.as_ref(),
+                    cache: Some(&mut cache),
+                })
+                .unwrap();
+            let expected = mha
+                .forward_with_cache((&x, &x, &x, mask.as_ref()), Some(&mut mha_cache))
+                .unwrap();
+
+            assert_eq!(output.shape(), &[1, length, config.hidden_size]);
+            assert!(output
+                .all_close(&expected, 1e-5, 1e-5, None)
+                .unwrap()
+                .item::<bool>());
+        }
+    }
+
+    #[test]
+    fn test_create_attention_mask() {
+        let h = zeros::<f32>(&[1, 1, 4]).unwrap();
+        assert!(create_attention_mask(&h, 3).unwrap().is_none());
+
+        let h = zeros::<f32>(&[1, 2, 4])
+            .unwrap()
+            .as_dtype(Dtype::Float16)
+            .unwrap();
+        let mask = create_attention_mask(&h, 2).unwrap().unwrap();
+        assert_eq!(mask.shape(), &[2, 4]);
+        assert_eq!(mask.dtype(), Dtype::Float16);
+
+        // The last new position attends to every position, the one before to all but the last
+        let mask = mask.as_dtype(Dtype::Float32).unwrap();
+        assert_eq!(mask.index((1, 3)).item::<f32>(), 0.0);
+        assert_eq!(mask.index((0, 2)).item::<f32>(), 0.0);
+        assert!(mask.index((0, 3)).item::<f32>() < -1e4);
+    }
+}
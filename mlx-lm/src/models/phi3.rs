@@ -0,0 +1,384 @@
+use mlx_rs::{
+    builder::Builder,
+    error::Exception,
+    macros::{ModuleParameters, Quantizable},
+    module::Module,
+    nn::{self, KeyValueCache},
+    quantization::MaybeQuantized,
+    Array,
+};
+
+use crate::{config::ModelConfig, error::ModelError};
+
+use super::{
+    attend, check_cache_len, create_attention_mask, embedding_as_linear, AttentionInput,
+    LanguageModel,
+};
+
+/// Self attention with a single projection for the queries, keys and values.
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+struct Attention {
+    num_heads: i32,
+    num_kv_heads: i32,
+    head_dim: i32,
+    scale: f32,
+
+    #[quantizable]
+    #[param]
+    qkv_proj: MaybeQuantized<nn::Linear>,
+
+    #[quantizable]
+    #[param]
+    o_proj: MaybeQuantized<nn::Linear>,
+
+    rope: nn::Rope,
+}
+
+impl Attention {
+    fn new(config: &ModelConfig) -> Result<Self, Exception> {
+        let dims = config.hidden_size;
+        let num_heads = config.num_attention_heads;
+        let num_kv_heads = config.num_key_value_heads();
+        let head_dim = config.head_dim();
+
+        let qkv_proj = nn::LinearBuilder::new(dims, (num_heads + 2 * num_kv_heads) * head_dim)
+            .bias(false)
+            .build()?;
+        let o_proj = nn::LinearBuilder::new(num_heads * head_dim, dims)
+            .bias(false)
+            .build()?;
+
+        Ok(Self {
+            num_heads,
+            num_kv_heads,
+            head_dim,
+            scale: (head_dim as f32).powf(-0.5),
+            qkv_proj: MaybeQuantized::new(qkv_proj),
+            o_proj: MaybeQuantized::new(o_proj),
+            rope: config.rope()?,
+        })
+    }
+}
+
+impl Module<AttentionInput<'_>> for Attention {
+    type Output = Array;
+
+    type Error = Exception;
+
+    fn forward(&mut self, input: AttentionInput<'_>) -> Result<Self::Output, Self::Error> {
+        let AttentionInput { x, mask, cache } = input;
+        let (batch, length) = (x.dim(0), x.dim(1));
+
+        let query_dims = self.num_heads * self.head_dim;
+        let kv_dims = self.num_kv_heads * self.head_dim;
+        let qkv = self
+            .qkv_proj
+            .forward(x)?
+            .split(&[query_dims, query_dims + kv_dims], -1)?;
+
+        let queries = qkv[0]
+            .reshape(&[batch, length, self.num_heads, -1])?
+            .transpose(&[0, 2, 1, 3])?;
+        let keys = qkv[1]
+            .reshape(&[batch, length, self.num_kv_heads, -1])?
+            .transpose(&[0, 2, 1, 3])?;
+        let values = qkv[2]
+            .reshape(&[batch, length, self.num_kv_heads, -1])?
+            .transpose(&[0, 2, 1, 3])?;
+
+        let output = attend(
+            &mut self.rope,
+            self.scale,
+            queries,
+            keys,
+            values,
+            mask,
+            cache,
+        )?;
+        self.o_proj.forward(&output)
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.qkv_proj.training_mode(mode);
+        self.o_proj.training_mode(mode);
+    }
+}
+
+/// Gated feed forward layer with a single projection for the gate and the hidden features.
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+struct Mlp {
+    #[quantizable]
+    #[param]
+    gate_up_proj: MaybeQuantized<nn::Linear>,
+
+    #[quantizable]
+    #[param]
+    down_proj: MaybeQuantized<nn::Linear>,
+}
+
+impl Mlp {
+    fn new(config: &ModelConfig) -> Result<Self, Exception> {
+        let gate_up_proj = nn::LinearBuilder::new(config.hidden_size, 2 * config.intermediate_size)
+            .bias(false)
+            .build()?;
+        let down_proj = nn::LinearBuilder::new(config.intermediate_size, config.hidden_size)
+            .bias(false)
+            .build()?;
+
+        Ok(Self {
+            gate_up_proj: MaybeQuantized::new(gate_up_proj),
+            down_proj: MaybeQuantized::new(down_proj),
+        })
+    }
+}
+
+impl Module<&Array> for Mlp {
+    type Output = Array;
+
+    type Error = Exception;
+
+    fn forward(&mut self, x: &Array) -> Result<Self::Output, Self::Error> {
+        let gate_up = self.gate_up_proj.forward(x)?.split_equal(2, -1)?;
+        let hidden = nn::silu(&gate_up[0])?.multiply(&gate_up[1])?;
+        self.down_proj.forward(&hidden)
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.gate_up_proj.training_mode(mode);
+        self.down_proj.training_mode(mode);
+    }
+}
+
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+struct DecoderLayer {
+    #[quantizable]
+    #[param]
+    self_attn: Attention,
+
+    #[quantizable]
+    #[param]
+    mlp: Mlp,
+
+    #[param]
+    input_layernorm: nn::RmsNorm,
+
+    #[param]
+    post_attention_layernorm: nn::RmsNorm,
+}
+
+impl DecoderLayer {
+    fn new(config: &ModelConfig) -> Result<Self, Exception> {
+        Ok(Self {
+            self_attn: Attention::new(config)?,
+            mlp: Mlp::new(config)?,
+            input_layernorm: nn::RmsNormBuilder::new(config.hidden_size)
+                .eps(config.rms_norm_eps)
+                .build()?,
+            post_attention_layernorm: nn::RmsNormBuilder::new(config.hidden_size)
+                .eps(config.rms_norm_eps)
+                .build()?,
+        })
+    }
+}
+
+impl Module<AttentionInput<'_>> for DecoderLayer {
+    type Output = Array;
+
+    type Error = Exception;
+
+    fn forward(&mut self, input: AttentionInput<'_>) -> Result<Self::Output, Self::Error> {
+        let AttentionInput { x, mask, cache } = input;
+
+        let normed = self.input_layernorm.forward(x)?;
+        let attention_input = AttentionInput {
+            x: &normed,
+            mask,
+            cache,
+        };
+        let h = x.add(self.self_attn.forward(attention_input)?)?;
+        let r = self
+            .mlp
+            .forward(&self.post_attention_layernorm.forward(&h)?)?;
+        h.add(r)
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.self_attn.training_mode(mode);
+        self.mlp.training_mode(mode);
+        self.input_layernorm.training_mode(mode);
+        self.post_attention_layernorm.training_mode(mode);
+    }
+}
+
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+struct Phi3Model {
+    #[quantizable]
+    #[param]
+    embed_tokens: MaybeQuantized<nn::Embedding>,
+
+    #[quantizable]
+    #[param]
+    layers: Vec<DecoderLayer>,
+
+    #[param]
+    norm: nn::RmsNorm,
+}
+
+/// Phi-3 model.
+///
+/// Phi-3 has the same layers as Llama, except that the query, key and value projections and the
+/// gate and up projections of the feed forward layers are fused into a single projection each.
+/// The `longrope` scaling of the long context variants is not supported.
+#[derive(Debug, Clone, ModuleParameters, Quantizable)]
+pub struct Phi3 {
+    config: ModelConfig,
+
+    #[quantizable]
+    #[param]
+    model: Phi3Model,
+
+    /// `None` if the output projection is tied to the token embedding
+    #[quantizable]
+    #[param]
+    lm_head: Option<MaybeQuantized<nn::Linear>>,
+}
+
+impl Phi3 {
+    /// Creates a model with randomly initialized weights.
+    pub fn new(config: ModelConfig) -> Result<Self, ModelError> {
+        config.validate()?;
+
+        let embed_tokens = nn::Embedding::new(config.vocab_size, config.hidden_size)?;
+        let layers = (0..config.num_hidden_layers)
+            .map(|_| DecoderLayer::new(&config))
+            .collect::<Result<Vec<_>, _>>()?;
+        let norm = nn::RmsNormBuilder::new(config.hidden_size)
+            .eps(config.rms_norm_eps)
+            .build()?;
+        let lm_head = match config.tie_word_embeddings {
+            true => None,
+            false => {
+                let lm_head = nn::LinearBuilder::new(config.hidden_size, config.vocab_size)
+                    .bias(false)
+                    .build()?;
+                Some(MaybeQuantized::new(lm_head))
+            }
+        };
+
+        Ok(Self {
+            config,
+            model: Phi3Model {
+                embed_tokens: MaybeQuantized::new(embed_tokens),
+                layers,
+                norm,
+            },
+            lm_head,
+        })
+    }
+
+    /// Runs the layers, each with the cache yielded by `caches`.
+    fn forward_layers<'c>(
+        &mut self,
+        inputs: &Array,
+        offset: i32,
+        caches: impl Iterator<Item = Option<&'c mut dyn KeyValueCache>>,
+    ) -> Result<Array, Exception> {
+        let mut h = self.model.embed_tokens.forward(inputs)?;
+        let mask = create_attention_mask(&h, offset)?;
+
+        for (layer, cache) in self.model.layers.iter_mut().zip(caches) {
+            let layer_input = AttentionInput {
+                x: &h,
+                mask: mask.as_ref(),
+                cache,
+            };
+            h = layer.forward(layer_input)?;
+        }
+
+        let h = self.model.norm.forward(&h)?;
+        match &mut self.lm_head {
+            Some(lm_head) => lm_head.forward(&h),
+            None => embedding_as_linear(&self.model.embed_tokens, &h),
+        }
+    }
+}
+
+impl Module<&Array> for Phi3 {
+    type Output = Array;
+
+    type Error = Exception;
+
+    fn forward(&mut self, inputs: &Array) -> Result<Self::Output, Self::Error> {
+        self.forward_layers(inputs, 0, std::iter::repeat_with(|| None))
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.model.embed_tokens.training_mode(mode);
+        self.model
+            .layers
+            .iter_mut()
+            .for_each(|layer| layer.training_mode(mode));
+        self.model.norm.training_mode(mode);
+        if let Some(lm_head) = &mut self.lm_head {
+            lm_head.training_mode(mode);
+        }
+    }
+}
+
+impl LanguageModel for Phi3 {
+    fn config(&self) -> &ModelConfig {
+        &self.config
+    }
+
+    fn forward_with_cache(
+        &mut self,
+        inputs: &Array,
+        cache: &mut [Box<dyn KeyValueCache>],
+    ) -> Result<Array, Exception> {
+        check_cache_len(cache, self.model.layers.len())?;
+
+        let offset = cache.first().map(|c| c.offset()).unwrap_or(0);
+        let caches = cache.iter_mut().map(|c| Some(c as &mut dyn KeyValueCache));
+        self.forward_layers(inputs, offset, caches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mlx_rs::{array, module::ModuleParameters, ops::indexing::IndexOp};
+
+    use super::*;
+    use crate::models::tiny_config;
+
+    #[test]
+    fn test_phi3() {
+        let mut model = Phi3::new(tiny_config("phi3")).unwrap();
+
+        let params = model.parameters().flatten();
+        assert_eq!(
+            params["model.layers.0.self_attn.qkv_proj.weight"].shape(),
+            &[128, 64]
+        );
+        assert_eq!(
+            params["model.layers.0.mlp.gate_up_proj.weight"].shape(),
+            &[256, 64]
+        );
+        assert!(params.contains_key("lm_head.weight"));
+
+        let inputs = array!([[2, 7, 1, 8]]);
+        let logits = model.forward(&inputs).unwrap();
+        assert_eq!(logits.shape(), &[1, 4, 128]);
+
+        let mut cache = model.make_cache();
+        model
+            .forward_with_cache(&inputs.index((.., ..2)), &mut cache)
+            .unwrap();
+        let rest = model
+            .forward_with_cache(&inputs.index((.., 2..)), &mut cache)
+            .unwrap();
+        assert!(rest
+            .all_close(logits.index((.., 2..)), 1e-4, 1e-4, None)
+            .unwrap()
+            .item::<bool>());
+    }
+}
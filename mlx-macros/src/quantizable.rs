@@ -48,8 +48,12 @@ fn impl_quantizable_module_for_struct(
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     // let field_names: Vec<_> = fields.iter().map(|field| &field.ident).collect();
 
-    let filtered_field_names = fields.filtered.iter().map(|field| &field.ident);
-    let other_field_names = fields.other_fields.iter().map(|field| &field.ident);
+    let filtered_field_names: Vec<_> = fields.filtered.iter().map(|field| &field.ident).collect();
+    let other_field_names: Vec<_> = fields
+        .other_fields
+        .iter()
+        .map(|field| &field.ident)
+        .collect();
 
     if fields.filtered.is_empty() {
         return Err(syn::Error::new_spanned(
@@ -89,6 +93,30 @@ fn impl_quantizable_module_for_struct(
                         )*
                     })
                 }
+
+                fn try_into_quantized_with(
+                    self,
+                    path: &str,
+                    predicate: &mut #root::quantization::QuantizationPredicate<'_>,
+                ) -> Result<Self::Quantized, Self::QuantizationError> {
+                    Ok(Self {
+                        #(
+                            #filtered_field_names: #root::quantization::Quantizable
+                                ::try_into_quantized_with(
+                                    self.#filtered_field_names,
+                                    &if path.is_empty() {
+                                        stringify!(#filtered_field_names).to_string()
+                                    } else {
+                                        format!("{}.{}", path, stringify!(#filtered_field_names))
+                                    },
+                                    predicate,
+                                )?,
+                        )*
+                        #(
+                            #other_field_names: self.#other_field_names,
+                        )*
+                    })
+                }
             }
         };
     };
@@ -13,7 +13,7 @@ use thiserror::Error;
 pub type Result<T> = std::result::Result<T, Exception>;
 
 /// Error with io operations
-#[derive(Error, PartialEq, Debug)]
+#[derive(Error, Debug)]
 pub enum IoError {
     /// Path must point to a local file
     #[error("Path must point to a local file")]
@@ -39,6 +39,10 @@ pub enum IoError {
     #[error("Unable to open file")]
     UnableToOpenFile,
 
+    /// Error with reading or writing a file
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
     /// Unable to allocate memory
     #[error("Unable to allocate memory")]
     AllocationError,
@@ -51,6 +55,53 @@ pub enum IoError {
     #[error(transparent)]
     Unflatten(#[from] UnflattenError),
 
+    /// Invalid or unsupported `gguf` file
+    #[error("Invalid gguf file: {0}")]
+    InvalidGguf(String),
+
+    /// The `gguf` file contains a tensor of an unsupported type
+    #[error("Tensor {name:?} of the gguf file has the unsupported type {ggml_type}")]
+    UnsupportedGgufType {
+        /// Name of the tensor
+        name: String,
+
+        /// Value of the `ggml_type` of the tensor
+        ggml_type: u32,
+    },
+
+    /// Invalid or unsupported `npy` or `npz` file
+    #[error("Invalid NumPy file: {0}")]
+    InvalidNumpy(String),
+
+    /// Invalid `safetensors` data
+    #[error("Invalid safetensors data: {0}")]
+    InvalidSafeTensors(String),
+
+    /// The data type is not supported by the file format
+    #[error("Unsupported data type {0:?}")]
+    UnsupportedDtype(Dtype),
+
+    /// Invalid index of a sharded checkpoint
+    #[error("Invalid index of a sharded checkpoint: {0}")]
+    InvalidIndex(String),
+
+    /// Two loaded arrays are remapped to the same parameter
+    #[error("The arrays {first:?} and {second:?} are both remapped to the parameter {key:?}")]
+    DuplicateParameter {
+        /// Flattened key of the parameter
+        key: String,
+
+        /// Key of the first array
+        first: String,
+
+        /// Key of the second array
+        second: String,
+    },
+
+    /// The loaded arrays do not match the parameters of the module
+    #[error("The loaded arrays do not match the parameters of the module: {0}")]
+    ParameterMismatch(crate::module::LoadReport),
+
     /// Exception
     #[error(transparent)]
     Exception(#[from] Exception),
@@ -110,7 +161,7 @@ pub enum UnflattenError {
 }
 
 /// Error with loading an optimizer state
-#[derive(Debug, PartialEq, Error)]
+#[derive(Debug, Error)]
 pub enum OptimizerStateLoadError {
     /// Error with io operations
     #[error(transparent)]
@@ -294,7 +345,7 @@ impl From<CrossEntropyBuildError> for Exception {
 }
 
 /// Error with building a RmsProp optimizer
-#[derive(Debug, Clone, PartialEq, Error)]
+#[derive(Debug, PartialEq, Error)]
 pub enum RmsPropBuildError {
     /// Alpha must be non-negative
     #[error("alpha must be non-negative")]
@@ -303,10 +354,14 @@ pub enum RmsPropBuildError {
     /// Epsilon must be non-negative
     #[error("epsilon must be non-negative")]
     NegativeEpsilon,
+
+    /// Exceptions
+    #[error(transparent)]
+    Exception(#[from] Exception),
 }
 
 /// Error with building an AdaDelta optimizer
-#[derive(Debug, Clone, PartialEq, Error)]
+#[derive(Debug, PartialEq, Error)]
 pub enum AdaDeltaBuildError {
     /// Rho must be non-negative
     #[error("rho must be non-negative")]
@@ -315,14 +370,132 @@ pub enum AdaDeltaBuildError {
     /// Epsilon must be non-negative
     #[error("epsilon must be non-negative")]
     NegativeEps,
+
+    /// Exceptions
+    #[error(transparent)]
+    Exception(#[from] Exception),
 }
 
 /// Error with building an Adafactor optimizer.
-#[derive(Debug, Clone, PartialEq, Error)]
+#[derive(Debug, PartialEq, Error)]
 pub enum AdafactorBuildError {
     /// Either learning rate is provided or relative step is set to true.
     #[error("Either learning rate is provided or relative step is set to true")]
     LrIsNoneAndRelativeStepIsFalse,
+
+    /// Exceptions
+    #[error(transparent)]
+    Exception(#[from] Exception),
+}
+
+/// Error with building a learning rate scheduler.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SchedulerBuildError {
+    /// The number of steps must be positive.
+    #[error("The number of steps must be positive, got {0}")]
+    NonPositiveSteps(i32),
+
+    /// The number of boundaries does not match the number of schedules or values.
+    #[error("Expected {expected} boundaries, got {found}")]
+    BoundariesMismatch {
+        /// The expected number of boundaries
+        expected: usize,
+
+        /// The number of boundaries provided
+        found: usize,
+    },
+
+    /// The boundaries are not in increasing order.
+    #[error("The boundaries must be in increasing order")]
+    UnsortedBoundaries,
+}
+
+/// Error with building a gradient accumulator.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum GradientAccumulatorBuildError {
+    /// The number of accumulation steps must be positive.
+    #[error("The number of accumulation steps must be positive, got {0}")]
+    NonPositiveAccumulationSteps(i32),
+
+    /// The evaluation interval must be positive.
+    #[error("The evaluation interval must be positive, got {0}")]
+    NonPositiveEvalEvery(i32),
+}
+
+/// Error with building an [`Ema`](crate::optimizers::Ema) optimizer wrapper.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum EmaBuildError {
+    /// The decay must be in the range [0, 1].
+    #[error("The decay must be in the range [0, 1], got {0}")]
+    InvalidDecay(f32),
+}
+
+/// Error with building a [`Lookahead`](crate::optimizers::Lookahead) optimizer wrapper.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum LookaheadBuildError {
+    /// The synchronization period must be positive.
+    #[error("The synchronization period must be positive, got {0}")]
+    NonPositiveSyncPeriod(i32),
+
+    /// The slow weights step size must be in the range [0, 1].
+    #[error("alpha must be in the range [0, 1], got {0}")]
+    InvalidAlpha(f32),
+}
+
+/// Error with building a [`Swa`](crate::optimizers::Swa) optimizer wrapper.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SwaBuildError {
+    /// The averaging must start at a positive step.
+    #[error("The averaging must start at a positive step, got {0}")]
+    NonPositiveStart(i32),
+
+    /// The averaging frequency must be positive.
+    #[error("The averaging frequency must be positive, got {0}")]
+    NonPositiveFrequency(i32),
+}
+
+/// Error with building a [`LossScaler`](crate::optimizers::LossScaler).
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum LossScalerBuildError {
+    /// The loss scale must be positive and finite.
+    #[error("The loss scale must be positive and finite, got {0}")]
+    InvalidScale(f32),
+
+    /// The growth factor must be at least 1.
+    #[error("The growth factor must be at least 1, got {0}")]
+    InvalidGrowthFactor(f32),
+
+    /// The backoff factor must be in the range (0, 1).
+    #[error("The backoff factor must be in the range (0, 1), got {0}")]
+    InvalidBackoffFactor(f32),
+
+    /// The growth interval must be positive.
+    #[error("The growth interval must be positive, got {0}")]
+    NonPositiveGrowthInterval(i32),
+}
+
+/// Error with building a key-value cache
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum KvCacheBuildError {
+    /// The allocation step must be positive.
+    #[error("The allocation step must be positive, got {0}")]
+    NonPositiveStep(i32),
+
+    /// The maximum size must be positive.
+    #[error("The maximum size must be positive, got {0}")]
+    NonPositiveMaxSize(i32),
+
+    /// The number of kept positions must be in the range [0, max_size).
+    #[error("The number of kept positions must be in the range [0, max_size), got {0}")]
+    InvalidKeep(i32),
+
+    /// The group size of the quantization must be positive.
+    #[error("The group size of the quantization must be positive, got {0}")]
+    NonPositiveGroupSize(i32),
+
+    /// The bits of the quantization must be one of 2, 3, 4, 6 or 8.
+    #[error("The bits of the quantization must be one of 2, 3, 4, 6 or 8, got {0}")]
+    UnsupportedBits(i32),
 }
 
 /// Error with building a dropout layer
@@ -340,6 +513,14 @@ pub enum MultiHeadAttentionBuildError {
     #[error("Invalid number of heads: {0}")]
     InvalidNumHeads(i32),
 
+    /// The number of heads must be a positive multiple of the number of key and value heads
+    #[error("Invalid number of key and value heads: {0}")]
+    InvalidNumKvHeads(i32),
+
+    /// The value dimensions must be a multiple of the number of heads
+    #[error("Invalid value dimensions: {0}")]
+    InvalidValueDims(i32),
+
     /// Exceptions
     #[error(transparent)]
     Exception(#[from] Exception),
@@ -356,6 +537,14 @@ pub enum TransformerBulidError {
     #[error("Invalid number of heads: {0}")]
     InvalidNumHeads(i32),
 
+    /// The number of heads must be a positive multiple of the number of key and value heads
+    #[error("Invalid number of key and value heads: {0}")]
+    InvalidNumKvHeads(i32),
+
+    /// The value dimensions must be a multiple of the number of heads
+    #[error("Invalid value dimensions: {0}")]
+    InvalidValueDims(i32),
+
     /// Exceptions
     #[error(transparent)]
     Exception(#[from] Exception),
@@ -373,6 +562,8 @@ impl From<MultiHeadAttentionBuildError> for TransformerBulidError {
     fn from(e: MultiHeadAttentionBuildError) -> Self {
         match e {
             MultiHeadAttentionBuildError::InvalidNumHeads(n) => Self::InvalidNumHeads(n),
+            MultiHeadAttentionBuildError::InvalidNumKvHeads(n) => Self::InvalidNumKvHeads(n),
+            MultiHeadAttentionBuildError::InvalidValueDims(n) => Self::InvalidValueDims(n),
             MultiHeadAttentionBuildError::Exception(e) => Self::Exception(e),
         }
     }
@@ -251,14 +251,21 @@
 //! See also [MLX python
 //! documentation](https://ml-explore.github.io/mlx/build/html/usage/saving_and_loading.html)
 //!
-//! `mlx-rs` supports loading from `.npy` and `.safetensors` files and saving to
-//! `.safetensors` files. Module parameters and optimizer states can also be saved
-//! and loaded from `.safetensors` files.
+//! `mlx-rs` supports loading from and saving to `.npy`, `.safetensors` and `.gguf` files, and
+//! `.npz` archives with the `npz` feature. Module parameters and optimizer states can also be
+//! saved and loaded from `.safetensors` files. The `.npy` format can also be read from and written
+//! to in-memory buffers or any [`std::io::Read`] and [`std::io::Write`], eg.
+//! [`Array::load_numpy_from_bytes`]. With the `safetensors` feature, so can the `.safetensors`
+//! format, and large checkpoints can be memory-mapped and loaded lazily with
+//! `ops::MmapSafeTensors`. With the `serde` feature, sharded `.safetensors` checkpoints with an
+//! index file are supported.
 //!
 //! | type | load function | save function |
 //! |------|---------------|----------------|
 //! | [`Array`] | [`Array::load_numpy`] | [`Array::save_numpy`] |
 //! | `HashMap<String, Array>` | [`Array::load_safetensors`] | [`Array::save_safetensors`] |
+//! | `HashMap<String, Array>` | [`Array::load_npz`] | [`Array::save_npz`] |
+//! | `HashMap<String, Array>` | [`Array::load_gguf`] | [`Array::save_gguf`] |
 //! | [`module::Module`] | [`module::ModuleParametersExt::load_safetensors`] | [`module::ModuleParametersExt::save_safetensors`] |
 //! | [`optimizers::Optimizer`] | [`optimizers::OptimizerState::load_safetensors`] | [`optimizers::OptimizerState::save_safetensors`] |
 //!
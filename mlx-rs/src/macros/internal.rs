@@ -17,3 +17,13 @@ macro_rules! cfg_safetensors {
         )*
     };
 }
+
+#[allow(unused_macros)]
+macro_rules! cfg_serde {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "serde")]
+            $item
+        )*
+    };
+}
@@ -0,0 +1,223 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fmt,
+    rc::Rc,
+};
+
+use crate::{error::IoError, transforms::eval, Array, Dtype};
+
+use super::ModuleParameters;
+
+/// A loaded array whose shape differs from the shape of the parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShapeMismatch {
+    /// Flattened key of the parameter
+    pub key: Rc<str>,
+
+    /// Shape of the parameter
+    pub expected: Vec<i32>,
+
+    /// Shape of the loaded array
+    pub found: Vec<i32>,
+}
+
+/// A loaded array whose data type differs from the data type of the parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtypeMismatch {
+    /// Flattened key of the parameter
+    pub key: Rc<str>,
+
+    /// Data type of the parameter
+    pub expected: Dtype,
+
+    /// Data type of the loaded array
+    pub found: Dtype,
+}
+
+/// Report of loading arrays into the parameters of a module.
+///
+/// Only the arrays that match a parameter in both shape and data type are loaded. All the lists
+/// are sorted by key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// Parameters of the module that were not found among the loaded arrays
+    pub missing_keys: Vec<Rc<str>>,
+
+    /// Loaded arrays that do not correspond to any parameter of the module
+    pub unexpected_keys: Vec<Rc<str>>,
+
+    /// Loaded arrays whose shape differs from the parameter
+    pub shape_mismatches: Vec<ShapeMismatch>,
+
+    /// Loaded arrays whose data type differs from the parameter
+    pub dtype_mismatches: Vec<DtypeMismatch>,
+}
+
+impl LoadReport {
+    /// Returns `true` if every parameter was loaded and every loaded array was used.
+    pub fn is_clean(&self) -> bool {
+        self.missing_keys.is_empty()
+            && self.unexpected_keys.is_empty()
+            && self.shape_mismatches.is_empty()
+            && self.dtype_mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+
+        if !self.missing_keys.is_empty() {
+            parts.push(format!("missing keys {:?}", self.missing_keys));
+        }
+
+        if !self.unexpected_keys.is_empty() {
+            parts.push(format!("unexpected keys {:?}", self.unexpected_keys));
+        }
+
+        for mismatch in &self.shape_mismatches {
+            parts.push(format!(
+                "shape mismatch for {:?}: expected {:?}, found {:?}",
+                mismatch.key, mismatch.expected, mismatch.found
+            ));
+        }
+
+        for mismatch in &self.dtype_mismatches {
+            parts.push(format!(
+                "dtype mismatch for {:?}: expected {:?}, found {:?}",
+                mismatch.key, mismatch.expected, mismatch.found
+            ));
+        }
+
+        if parts.is_empty() {
+            write!(f, "all parameters loaded")
+        } else {
+            write!(f, "{}", parts.join("; "))
+        }
+    }
+}
+
+/// Loads `arrays` into the parameters of `module` and reports the keys that do not match.
+///
+/// Every key is first passed to `remap`, which returns the flattened key of the corresponding
+/// parameter, or `None` to ignore the array. Only the arrays that match a parameter in both shape
+/// and data type are loaded. If `strict` is `true` and the report is not clean, an
+/// [`IoError::ParameterMismatch`] is returned and the module is left untouched. If two arrays are
+/// remapped to the same key, an [`IoError::DuplicateParameter`] is returned whether `strict` is
+/// `true` or not.
+///
+/// The loaded parameters are evaluated before this function returns.
+pub fn load_parameters<M, I, K, F>(
+    module: &mut M,
+    arrays: I,
+    mut remap: F,
+    strict: bool,
+) -> Result<LoadReport, IoError>
+where
+    M: ModuleParameters + ?Sized,
+    I: IntoIterator<Item = (K, Array)>,
+    K: AsRef<str>,
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut parameters = module.parameters_mut().flatten();
+    let mut report = LoadReport::default();
+    // Source key of the array remapped to each key
+    let mut found: HashMap<Rc<str>, String> = HashMap::new();
+    let mut matched = Vec::new();
+
+    for (source, value) in arrays {
+        let source = source.as_ref();
+        let key: Rc<str> = match remap(source) {
+            Some(key) => key.into(),
+            None => continue,
+        };
+
+        match found.entry(key.clone()) {
+            Entry::Occupied(entry) => {
+                return Err(IoError::DuplicateParameter {
+                    key: key.to_string(),
+                    first: entry.get().clone(),
+                    second: source.to_string(),
+                });
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(source.to_string());
+            }
+        }
+
+        let parameter = match parameters.get(&key) {
+            Some(parameter) => parameter,
+            None => {
+                report.unexpected_keys.push(key);
+                continue;
+            }
+        };
+
+        if parameter.shape() != value.shape() {
+            report.shape_mismatches.push(ShapeMismatch {
+                key,
+                expected: parameter.shape().to_vec(),
+                found: value.shape().to_vec(),
+            });
+        } else if parameter.dtype() != value.dtype() {
+            report.dtype_mismatches.push(DtypeMismatch {
+                key,
+                expected: parameter.dtype(),
+                found: value.dtype(),
+            });
+        } else {
+            matched.push((key, value));
+        }
+    }
+
+    report.missing_keys = parameters
+        .keys()
+        .filter(|key| !found.contains_key(*key))
+        .cloned()
+        .collect();
+
+    report.missing_keys.sort();
+    report.unexpected_keys.sort();
+    report.shape_mismatches.sort_by(|a, b| a.key.cmp(&b.key));
+    report.dtype_mismatches.sort_by(|a, b| a.key.cmp(&b.key));
+
+    if strict && !report.is_clean() {
+        return Err(IoError::ParameterMismatch(report));
+    }
+
+    // Loading is lazy, eval the arrays before they replace the parameters
+    eval(matched.iter().map(|(_, value)| value))?;
+    for (key, value) in matched {
+        if let Some(parameter) = parameters.get_mut(&key) {
+            **parameter = value;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Returns a `remap` for [`load_parameters`] that also accepts the keys of quantized layers saved
+/// by the Python `mlx`.
+///
+/// The weight and bias of a [`QuantizedLinear`](crate::nn::QuantizedLinear) or a
+/// [`QuantizedEmbedding`](crate::nn::QuantizedEmbedding) are nested in its `inner` module, eg.
+/// `lm_head.inner.weight`, while the Python `mlx` saves them as `lm_head.weight`. A key that is
+/// not a parameter of `module` is remapped to the key with `inner` inserted before its last
+/// component if that is a parameter. Every other key is returned unchanged.
+pub fn remap_quantized_keys<M>(module: &M) -> impl Fn(&str) -> Option<String>
+where
+    M: ModuleParameters + ?Sized,
+{
+    let keys: HashSet<Rc<str>> = module.parameters().flatten().into_keys().collect();
+    move |key| {
+        if !keys.contains(key) {
+            if let Some((prefix, name)) = key.rsplit_once('.') {
+                let inner = format!("{}.inner.{}", prefix, name);
+                if keys.contains(inner.as_str()) {
+                    return Some(inner);
+                }
+            }
+        }
+        Some(key.to_string())
+    }
+}
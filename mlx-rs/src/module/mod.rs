@@ -4,9 +4,11 @@
 //! crate. This also allows using the `mlx_macros::ModuleParameters` derive macro in crates other
 //! than `mlx-nn`.
 
+mod loading;
 #[allow(clippy::module_inception)]
 mod module;
 mod param;
 
+pub use loading::*;
 pub use module::*;
 pub use param::*;
@@ -1,11 +1,19 @@
-use std::{borrow::Borrow, collections::HashMap, hash::Hash, path::Path, rc::Rc};
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    path::Path,
+    rc::Rc,
+};
 
 use crate::{
     error::{Exception, IoError},
     nested::{NestedHashMap, NestedValue},
-    Array,
+    Array, Dtype,
 };
 
+use super::{load_parameters, remap_quantized_keys, LoadReport};
+
 /// Type alias for owned module parameters.
 pub type ModuleParam = NestedHashMap<Rc<str>, Array>;
 
@@ -237,6 +245,54 @@ where
     }
 }
 
+/// An optional module, eg. an output projection that is omitted when the weights are tied. `None`
+/// has no parameters.
+impl<T> ModuleParameters for Option<T>
+where
+    T: ModuleParameters,
+{
+    fn parameters(&self) -> ModuleParamRef<'_> {
+        match self {
+            Some(module) => module.parameters(),
+            None => NestedHashMap::new(),
+        }
+    }
+
+    fn parameters_mut(&mut self) -> ModuleParamMut<'_> {
+        match self {
+            Some(module) => module.parameters_mut(),
+            None => NestedHashMap::new(),
+        }
+    }
+
+    fn trainable_parameters(&self) -> ModuleParamRef<'_> {
+        match self {
+            Some(module) => module.trainable_parameters(),
+            None => NestedHashMap::new(),
+        }
+    }
+
+    fn freeze_parameters(&mut self, recursive: bool) {
+        if let Some(module) = self {
+            module.freeze_parameters(recursive);
+        }
+    }
+
+    fn unfreeze_parameters(&mut self, recursive: bool) {
+        if let Some(module) = self {
+            module.unfreeze_parameters(recursive);
+        }
+    }
+
+    fn all_frozen(&self) -> Option<bool> {
+        self.as_ref().and_then(|module| module.all_frozen())
+    }
+
+    fn any_frozen(&self) -> Option<bool> {
+        self.as_ref().and_then(|module| module.any_frozen())
+    }
+}
+
 /// Extension trait for `ModuleParameters`. This is implemented for all types that implement
 /// `ModuleParameters`.
 pub trait ModuleParametersExt: ModuleParameters {
@@ -245,6 +301,42 @@ pub trait ModuleParametersExt: ModuleParameters {
         crate::transforms::eval_params(self.parameters())
     }
 
+    /// Cast every floating point parameter to `dtype`.
+    ///
+    /// Parameters that are not floating point, eg. the packed weights of quantized layers, are left
+    /// unchanged. The cast is lazy like any other operation.
+    fn to_dtype(&mut self, dtype: Dtype) -> Result<(), Exception> {
+        self.to_dtype_where(dtype, |_, _| true)
+    }
+
+    /// Cast the floating point parameters for which `predicate(key, parameter)` returns `true` to
+    /// `dtype`. The key is the flattened key of the parameter, eg. `"layers.0.norm.weight"`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// // Keep the normalization layers in `Float32`
+    /// model.to_dtype_where(Dtype::Bfloat16, |key, _| !key.contains("norm"))?;
+    /// ```
+    fn to_dtype_where<F>(&mut self, dtype: Dtype, mut predicate: F) -> Result<(), Exception>
+    where
+        F: FnMut(&str, &Array) -> bool,
+    {
+        for (key, param) in self.parameters_mut().flatten() {
+            if param.dtype().is_float() && param.dtype() != dtype && predicate(&*key, &*param) {
+                *param = param.as_dtype(dtype)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Cast the floating point parameters that are not frozen to `dtype`.
+    fn trainable_to_dtype(&mut self, dtype: Dtype) -> Result<(), Exception> {
+        let trainable: HashSet<Rc<str>> =
+            self.trainable_parameters().flatten().into_keys().collect();
+        self.to_dtype_where(dtype, |key, _| trainable.contains(key))
+    }
+
     /// Load module parameters from a `safetensors` file.
     fn load_safetensors(&mut self, path: impl AsRef<Path>) -> Result<(), IoError> {
         let loaded = Array::load_safetensors(path)?;
@@ -263,12 +355,133 @@ pub trait ModuleParametersExt: ModuleParameters {
         Ok(())
     }
 
+    /// Load module parameters from a `safetensors` file and report the keys that do not match.
+    ///
+    /// Unlike [`ModuleParametersExt::load_safetensors`], the arrays whose shape or data type
+    /// differs from the parameter are not loaded. The keys of quantized layers saved by the Python
+    /// `mlx` are accepted, see [`remap_quantized_keys`]. See [`load_parameters`] for details.
+    fn load_safetensors_with_report(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<LoadReport, IoError> {
+        let remap = remap_quantized_keys(self);
+        self.load_safetensors_remapped(path, remap, false)
+    }
+
+    /// Load module parameters from a `safetensors` file, failing with
+    /// [`IoError::ParameterMismatch`] unless the file matches the parameters exactly.
+    ///
+    /// The keys of quantized layers saved by the Python `mlx` are accepted, see
+    /// [`remap_quantized_keys`]. The module is left untouched if an error is returned.
+    fn load_safetensors_strict(&mut self, path: impl AsRef<Path>) -> Result<(), IoError> {
+        let remap = remap_quantized_keys(self);
+        self.load_safetensors_remapped(path, remap, true)
+            .map(|_| ())
+    }
+
+    /// Load module parameters from a `safetensors` file whose keys are mapped to the flattened
+    /// parameter keys by `remap`. Keys for which `remap` returns `None` are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// // Strip the "model." prefix and ignore the rotary embedding buffers
+    /// let report = model.load_safetensors_remapped(
+    ///     "model.safetensors",
+    ///     |key| {
+    ///         if key.ends_with("rotary_emb.inv_freq") {
+    ///             return None;
+    ///         }
+    ///         Some(key.trim_start_matches("model.").to_string())
+    ///     },
+    ///     false,
+    /// )?;
+    /// ```
+    fn load_safetensors_remapped<F>(
+        &mut self,
+        path: impl AsRef<Path>,
+        remap: F,
+        strict: bool,
+    ) -> Result<LoadReport, IoError>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let loaded = Array::load_safetensors(path)?;
+        load_parameters(self, loaded, remap, strict)
+    }
+
+    /// Load module parameters from a sharded `safetensors` checkpoint and report the keys that do
+    /// not match.
+    ///
+    /// `path` is either the index file or the directory that contains
+    /// [`SAFETENSORS_INDEX_FILE`](crate::ops::SAFETENSORS_INDEX_FILE). The keys of quantized
+    /// layers saved by the Python `mlx` are accepted, see [`remap_quantized_keys`]. See
+    /// [`load_parameters`] for details.
+    #[cfg(feature = "serde")]
+    fn load_safetensors_sharded(&mut self, path: impl AsRef<Path>) -> Result<LoadReport, IoError> {
+        let remap = remap_quantized_keys(self);
+        self.load_safetensors_sharded_remapped(path, remap, false)
+    }
+
+    /// Load module parameters from a sharded `safetensors` checkpoint whose keys are mapped to the
+    /// flattened parameter keys by `remap`. See [`ModuleParametersExt::load_safetensors_remapped`]
+    /// for details.
+    #[cfg(feature = "serde")]
+    fn load_safetensors_sharded_remapped<F>(
+        &mut self,
+        path: impl AsRef<Path>,
+        remap: F,
+        strict: bool,
+    ) -> Result<LoadReport, IoError>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let loaded = Array::load_safetensors_sharded(path)?;
+        load_parameters(self, loaded, remap, strict)
+    }
+
+    /// Load module parameters from a memory-mapped `safetensors` file and report the keys that do
+    /// not match.
+    ///
+    /// Only the tensors that correspond to a parameter are read from the file. The keys of
+    /// quantized layers saved by the Python `mlx` are accepted, see [`remap_quantized_keys`]. See
+    /// [`MmapSafeTensors`](crate::ops::MmapSafeTensors) and [`load_parameters`] for details.
+    #[cfg(feature = "safetensors")]
+    fn load_safetensors_mmap(&mut self, path: impl AsRef<Path>) -> Result<LoadReport, IoError> {
+        let file = crate::ops::MmapSafeTensors::open(path)?;
+        let keys: HashSet<Rc<str>> = self.parameters().flatten().into_keys().collect();
+        let remap = remap_quantized_keys(self);
+        let (matched, unexpected): (Vec<_>, Vec<_>) = file
+            .keys()
+            .into_iter()
+            .partition(|key| remap(key).is_some_and(|key| keys.contains(key.as_str())));
+        let arrays = file.load_keys(&matched)?;
+
+        let mut report = load_parameters(self, arrays, remap, false)?;
+        report.unexpected_keys = unexpected.into_iter().map(Rc::from).collect();
+        report.unexpected_keys.sort();
+
+        Ok(report)
+    }
+
     /// Save module parameters to a file in `safetensors` format.
     fn save_safetensors(&self, path: impl AsRef<Path>) -> Result<(), IoError> {
         let params = self.parameters().flatten();
         Array::save_safetensors(params, None, path)?;
         Ok(())
     }
+
+    /// Save module parameters as a sharded `safetensors` checkpoint in `dir`, with shards of at most
+    /// `max_shard_size` bytes. See [`Array::save_safetensors_sharded`] for details.
+    #[cfg(feature = "serde")]
+    fn save_safetensors_sharded(
+        &self,
+        dir: impl AsRef<Path>,
+        max_shard_size: u64,
+    ) -> Result<crate::ops::SafeTensorsIndex, IoError> {
+        let params = self.parameters().flatten();
+        Array::save_safetensors_sharded(params, None, dir, max_shard_size)
+    }
 }
 
 impl<T: ModuleParameters> ModuleParametersExt for T {}
@@ -0,0 +1,229 @@
+use std::rc::Rc;
+
+use crate::{
+    error::Exception,
+    module::{update_parameters, Module, ModuleParamMut, ModuleParamRef, ModuleParameters},
+    transforms::checkpoint,
+    Array,
+};
+
+/// Wraps a module so that its activations are recomputed in the backward pass
+/// instead of being kept in memory.
+///
+/// The module parameters are passed to the checkpointed function as inputs so
+/// that gradients with regard to the parameters are computed as usual, eg.
+/// with [`crate::nn::value_and_grad`]. The wrapper is transparent to the
+/// parameter tree, ie. the parameter keys are the same as those of the wrapped
+/// module.
+///
+/// # Example
+///
+/// ```rust
+/// use mlx_rs::{module::Module, nn::{Checkpointed, Linear}, random::uniform};
+///
+/// let mut layer = Checkpointed::new(Linear::new(4, 4).unwrap());
+/// let x = uniform::<_, f32>(0.0, 1.0, &[2, 4], None).unwrap();
+/// let y = layer.forward(&x).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Checkpointed<M> {
+    /// The wrapped module.
+    pub module: M,
+}
+
+impl<M> Checkpointed<M> {
+    /// Wraps `module` in a [`Checkpointed`] module.
+    pub fn new(module: M) -> Self {
+        Self { module }
+    }
+
+    /// Returns the wrapped module.
+    pub fn into_inner(self) -> M {
+        self.module
+    }
+}
+
+impl<M> Checkpointed<M>
+where
+    M: ModuleParameters + Clone + 'static,
+{
+    /// Calls `f` with the wrapped module and `inputs` with gradient checkpointing.
+    ///
+    /// This can be used for modules whose input is not a single [`Array`], eg.
+    /// transformer layers that also take a mask or a cache. All the arrays that
+    /// `f` depends on should be passed in `inputs`.
+    ///
+    /// `f` is called again in the backward pass, possibly after this call has
+    /// returned, so it is called with a clone of the wrapped module rather than
+    /// with the module itself. Changes made by `f` to the state of the module,
+    /// other than its parameters, are therefore not kept.
+    pub fn checkpointed_call<F>(
+        &mut self,
+        inputs: &[Array],
+        mut f: F,
+    ) -> Result<Vec<Array>, Exception>
+    where
+        F: FnMut(&mut M, &[Array]) -> Result<Vec<Array>, Exception> + 'static,
+    {
+        let (keys, params): (Vec<Rc<str>>, Vec<Array>) = self
+            .module
+            .trainable_parameters()
+            .flatten()
+            .into_iter()
+            .map(|(k, v)| (k, v.clone()))
+            .unzip();
+        let num_params = params.len();
+
+        // Cloning a module only clones the handles to its arrays
+        let mut module = self.module.clone();
+        let inner = move |args: &[Array]| -> Result<Vec<Array>, Exception> {
+            let (params, inputs) = args.split_at(num_params);
+            update_parameters(
+                &mut module,
+                keys.iter().cloned().zip(params.iter().cloned()),
+            );
+            f(&mut module, inputs)
+        };
+
+        let args: Vec<Array> = params.into_iter().chain(inputs.iter().cloned()).collect();
+        checkpoint(inner)(&args)
+    }
+}
+
+impl<M> Module<&Array> for Checkpointed<M>
+where
+    M: for<'a> Module<&'a Array, Output = Array, Error = Exception> + Clone + 'static,
+{
+    type Error = Exception;
+    type Output = Array;
+
+    fn forward(&mut self, x: &Array) -> Result<Array, Self::Error> {
+        let outputs = self.checkpointed_call(std::slice::from_ref(x), |module, inputs| {
+            module.forward(&inputs[0]).map(|y| vec![y])
+        })?;
+        Ok(outputs
+            .into_iter()
+            .next()
+            .expect("Expected a single output"))
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.module.training_mode(mode);
+    }
+}
+
+impl<M> ModuleParameters for Checkpointed<M>
+where
+    M: ModuleParameters,
+{
+    fn parameters(&self) -> ModuleParamRef<'_> {
+        self.module.parameters()
+    }
+
+    fn parameters_mut(&mut self) -> ModuleParamMut<'_> {
+        self.module.parameters_mut()
+    }
+
+    fn trainable_parameters(&self) -> ModuleParamRef<'_> {
+        self.module.trainable_parameters()
+    }
+
+    fn freeze_parameters(&mut self, recursive: bool) {
+        self.module.freeze_parameters(recursive);
+    }
+
+    fn unfreeze_parameters(&mut self, recursive: bool) {
+        self.module.unfreeze_parameters(recursive);
+    }
+
+    fn all_frozen(&self) -> Option<bool> {
+        self.module.all_frozen()
+    }
+
+    fn any_frozen(&self) -> Option<bool> {
+        self.module.any_frozen()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        module::{Module, ModuleParameters},
+        nn::{self, Linear, Sequential},
+        random::uniform,
+        Array,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_checkpointed_parameter_keys() {
+        let model = Checkpointed::new(Linear::new(2, 3).unwrap());
+        let params = model.parameters().flatten();
+        assert!(params.contains_key("weight"));
+        assert!(params.contains_key("bias"));
+    }
+
+    #[test]
+    fn test_checkpointed_forward_and_grad() {
+        let linear_0 = Linear::new(4, 8).unwrap();
+        let linear_1 = Linear::new(8, 1).unwrap();
+
+        let mut model = Sequential::new()
+            .append(Checkpointed::new(linear_0.clone()))
+            .append(nn::Relu)
+            .append(Checkpointed::new(linear_1.clone()));
+        let mut reference = Sequential::new()
+            .append(linear_0)
+            .append(nn::Relu)
+            .append(linear_1);
+
+        let x = uniform::<_, f32>(0.0, 1.0, &[3, 4], None).unwrap();
+
+        let loss = |model: &mut Sequential, x: &Array| -> Result<Array, Exception> {
+            model.forward(x)?.sum(None, None)
+        };
+
+        let (v, g) = nn::value_and_grad(loss)(&mut model, &x).unwrap();
+        let (expected_v, expected_g) = nn::value_and_grad(loss)(&mut reference, &x).unwrap();
+
+        assert_eq!(v, expected_v);
+        assert_eq!(g.len(), expected_g.len());
+        for (key, value) in &expected_g {
+            assert!(g[key]
+                .all_close(value, None, None, None)
+                .unwrap()
+                .item::<bool>());
+        }
+    }
+
+    #[test]
+    fn test_checkpointed_module_dropped_before_eval() {
+        let linear = Linear::new(4, 2).unwrap();
+        let x = uniform::<_, f32>(0.0, 1.0, &[3, 4], None).unwrap();
+
+        let loss = |model: &mut Checkpointed<Linear>, x: &Array| -> Result<Array, Exception> {
+            model.forward(x)?.sum(None, None)
+        };
+        // The module is dropped before the lazy gradients are evaluated
+        let (v, g) = {
+            let mut model = Checkpointed::new(linear.clone());
+            nn::value_and_grad(loss)(&mut model, &x).unwrap()
+        };
+
+        let mut reference = linear;
+        let reference_loss = |model: &mut Linear, x: &Array| -> Result<Array, Exception> {
+            model.forward(x)?.sum(None, None)
+        };
+        let (expected_v, expected_g) =
+            nn::value_and_grad(reference_loss)(&mut reference, &x).unwrap();
+
+        assert_eq!(v, expected_v);
+        for (key, value) in &expected_g {
+            assert!(g[key]
+                .all_close(value, None, None, None)
+                .unwrap()
+                .item::<bool>());
+        }
+    }
+}
@@ -0,0 +1,678 @@
+use crate::{
+    error::{Exception, KvCacheBuildError},
+    ops::{
+        concatenate, dequantize,
+        indexing::{Ellipsis, IndexOp, TryIndexMutOp},
+        quantize, zeros_dtype,
+    },
+    Array, Dtype,
+};
+use mlx_internal_macros::{generate_builder, Buildable};
+
+/// Cache of the keys and values of the previous positions of a sequence, used for autoregressive
+/// decoding.
+///
+/// The keys and values have the shape `[batch, num_heads, sequence, head_dim]`. Positional
+/// encodings must be applied to the keys before they are cached, eg. with [`Rope`](crate::nn::Rope)
+/// and [`KeyValueCache::offset`] as the offset.
+pub trait KeyValueCache: std::fmt::Debug {
+    /// Number of positions processed so far.
+    fn offset(&self) -> i32;
+
+    /// Maximum number of positions kept in the cache, or `None` if unbounded.
+    fn max_size(&self) -> Option<i32>;
+
+    /// Appends `keys` and `values` along the sequence axis and returns all the cached keys and
+    /// values, including the new ones.
+    fn update_and_fetch(&mut self, keys: Array, values: Array)
+        -> Result<(Array, Array), Exception>;
+}
+
+impl<T> KeyValueCache for Box<T>
+where
+    T: KeyValueCache + ?Sized,
+{
+    fn offset(&self) -> i32 {
+        (**self).offset()
+    }
+
+    fn max_size(&self) -> Option<i32> {
+        (**self).max_size()
+    }
+
+    fn update_and_fetch(
+        &mut self,
+        keys: Array,
+        values: Array,
+    ) -> Result<(Array, Array), Exception> {
+        (**self).update_and_fetch(keys, values)
+    }
+}
+
+/// Appends `size` positions of zeros along the sequence axis of `buffer`, after dropping the
+/// unused positions starting at `offset`.
+fn grow_buffer(
+    buffer: Option<Array>,
+    offset: i32,
+    size: i32,
+    like: &Array,
+    last_dim: i32,
+    dtype: Dtype,
+) -> Result<Array, Exception> {
+    let shape = [like.dim(0), like.dim(1), size, last_dim];
+    let zeros = zeros_dtype(&shape, dtype)?;
+    match buffer {
+        Some(buffer) if offset < buffer.dim(2) => {
+            concatenate(&[buffer.index((Ellipsis, ..offset, ..)), zeros], 2)
+        }
+        Some(buffer) => concatenate(&[buffer, zeros], 2),
+        None => Ok(zeros),
+    }
+}
+
+/// Rounds `num_steps` up to a multiple of `step`.
+fn round_up(num_steps: i32, step: i32) -> i32 {
+    (num_steps + step - 1) / step * step
+}
+
+fn check_step(step: i32) -> Result<(), KvCacheBuildError> {
+    if step <= 0 {
+        return Err(KvCacheBuildError::NonPositiveStep(step));
+    }
+    Ok(())
+}
+
+generate_builder! {
+    /// Unbounded key-value cache.
+    ///
+    /// The buffers are pre-allocated in chunks of `step` positions, so appending one position
+    /// only writes into the buffers until a new chunk is needed.
+    #[derive(Debug, Clone, Buildable)]
+    #[buildable(root = crate)]
+    #[builder(
+        build_with = build_kv_cache,
+        default_infallible,
+        err = KvCacheBuildError,
+        root = crate
+    )]
+    pub struct KvCache {
+        /// Number of positions allocated at once. Default to [`KvCache::DEFAULT_STEP`].
+        #[builder(optional, default = KvCache::DEFAULT_STEP)]
+        pub step: i32,
+
+        #[builder(ignore)]
+        keys: Option<Array>,
+
+        #[builder(ignore)]
+        values: Option<Array>,
+
+        #[builder(ignore)]
+        offset: i32,
+    }
+}
+
+fn build_kv_cache(builder: KvCacheBuilder) -> Result<KvCache, KvCacheBuildError> {
+    check_step(builder.step)?;
+
+    Ok(KvCache {
+        step: builder.step,
+        keys: None,
+        values: None,
+        offset: 0,
+    })
+}
+
+impl KvCache {
+    /// Default value for `step`.
+    pub const DEFAULT_STEP: i32 = 256;
+}
+
+impl KeyValueCache for KvCache {
+    fn offset(&self) -> i32 {
+        self.offset
+    }
+
+    fn max_size(&self) -> Option<i32> {
+        None
+    }
+
+    fn update_and_fetch(
+        &mut self,
+        keys: Array,
+        values: Array,
+    ) -> Result<(Array, Array), Exception> {
+        let prev = self.offset;
+        let num_steps = keys.dim(2);
+
+        let (mut cached_keys, mut cached_values) = match (self.keys.take(), self.values.take()) {
+            (Some(k), Some(v)) if prev + num_steps <= k.dim(2) => (k, v),
+            (k, v) => {
+                let size = round_up(num_steps, self.step);
+                (
+                    grow_buffer(k, prev, size, &keys, keys.dim(3), keys.dtype())?,
+                    grow_buffer(v, prev, size, &values, values.dim(3), values.dtype())?,
+                )
+            }
+        };
+
+        self.offset += num_steps;
+        cached_keys.try_index_mut((Ellipsis, prev..self.offset, ..), &keys)?;
+        cached_values.try_index_mut((Ellipsis, prev..self.offset, ..), &values)?;
+
+        let output = (
+            cached_keys.index((Ellipsis, ..self.offset, ..)),
+            cached_values.index((Ellipsis, ..self.offset, ..)),
+        );
+        self.keys = Some(cached_keys);
+        self.values = Some(cached_values);
+        Ok(output)
+    }
+}
+
+generate_builder! {
+    /// Key-value cache that keeps at most `max_size` positions, eg. for sliding window attention.
+    ///
+    /// The first `keep` positions are always kept, and the other positions are overwritten in a
+    /// circular way once the cache is full. When several positions are added at once, the
+    /// returned keys and values contain the `max_size - 1` previous positions followed by the new
+    /// ones, so that every new query can attend to a full window. The caller is responsible for
+    /// the sliding window mask in that case.
+    #[derive(Debug, Clone, Buildable)]
+    #[buildable(root = crate)]
+    #[builder(
+        build_with = build_rotating_kv_cache,
+        err = KvCacheBuildError,
+        root = crate
+    )]
+    pub struct RotatingKvCache {
+        /// Maximum number of positions kept in the cache
+        pub max_size: i32,
+
+        /// Number of positions at the start of the sequence that are never evicted. Default to
+        /// [`RotatingKvCache::DEFAULT_KEEP`].
+        #[builder(optional, default = RotatingKvCache::DEFAULT_KEEP)]
+        pub keep: i32,
+
+        /// Number of positions allocated at once. Default to [`KvCache::DEFAULT_STEP`].
+        #[builder(optional, default = KvCache::DEFAULT_STEP)]
+        pub step: i32,
+
+        #[builder(ignore)]
+        keys: Option<Array>,
+
+        #[builder(ignore)]
+        values: Option<Array>,
+
+        #[builder(ignore)]
+        offset: i32,
+
+        /// Index of the next write in the buffers
+        #[builder(ignore)]
+        idx: i32,
+    }
+}
+
+fn build_rotating_kv_cache(
+    builder: RotatingKvCacheBuilder,
+) -> Result<RotatingKvCache, KvCacheBuildError> {
+    check_step(builder.step)?;
+
+    if builder.max_size <= 0 {
+        return Err(KvCacheBuildError::NonPositiveMaxSize(builder.max_size));
+    }
+
+    if builder.keep < 0 || builder.keep >= builder.max_size {
+        return Err(KvCacheBuildError::InvalidKeep(builder.keep));
+    }
+
+    Ok(RotatingKvCache {
+        max_size: builder.max_size,
+        keep: builder.keep,
+        step: builder.step,
+        keys: None,
+        values: None,
+        offset: 0,
+        idx: 0,
+    })
+}
+
+impl RotatingKvCache {
+    /// Default value for `keep`.
+    pub const DEFAULT_KEEP: i32 = 0;
+
+    /// Drops `trim_size` positions after the first `keep` ones and appends `append`.
+    fn trim(
+        &self,
+        trim_size: i32,
+        buffer: &Array,
+        append: Option<&Array>,
+    ) -> Result<Array, Exception> {
+        let mut parts = if trim_size > 0 {
+            vec![
+                buffer.index((Ellipsis, ..self.keep, ..)),
+                buffer.index((Ellipsis, (trim_size + self.keep).., ..)),
+            ]
+        } else {
+            vec![buffer.clone()]
+        };
+        parts.extend(append.cloned());
+        concatenate(&parts, 2)
+    }
+
+    /// Reorders a rotated buffer so that the positions are in the order they were added.
+    fn temporal_order(&self, buffer: &Array) -> Result<Array, Exception> {
+        if self.idx == buffer.dim(2) {
+            Ok(buffer.clone())
+        } else if self.idx < self.offset {
+            concatenate(
+                &[
+                    buffer.index((Ellipsis, ..self.keep, ..)),
+                    buffer.index((Ellipsis, self.idx.., ..)),
+                    buffer.index((Ellipsis, self.keep..self.idx, ..)),
+                ],
+                2,
+            )
+        } else {
+            Ok(buffer.index((Ellipsis, ..self.idx, ..)))
+        }
+    }
+
+    fn update_concat(&mut self, keys: Array, values: Array) -> Result<(Array, Array), Exception> {
+        let num_steps = keys.dim(2);
+        let (cached_keys, cached_values) = match (self.keys.take(), self.values.take()) {
+            (Some(cached_keys), Some(cached_values)) => {
+                let cached_keys = self.temporal_order(&cached_keys)?;
+                let cached_values = self.temporal_order(&cached_values)?;
+
+                // Every new query attends to at most `max_size - 1` previous positions
+                let trim_size = cached_keys.dim(2) - self.max_size + 1;
+                (
+                    self.trim(trim_size, &cached_keys, Some(&keys))?,
+                    self.trim(trim_size, &cached_values, Some(&values))?,
+                )
+            }
+            _ => (keys, values),
+        };
+
+        self.offset += num_steps;
+        self.idx = cached_keys.dim(2);
+        self.keys = Some(cached_keys.clone());
+        self.values = Some(cached_values.clone());
+        Ok((cached_keys, cached_values))
+    }
+
+    fn update_in_place(&mut self, keys: Array, values: Array) -> Result<(Array, Array), Exception> {
+        let prev = self.offset;
+        let num_steps = keys.dim(2);
+
+        let (mut cached_keys, mut cached_values) = match (self.keys.take(), self.values.take()) {
+            (Some(k), Some(v)) if prev < k.dim(2) || k.dim(2) >= self.max_size => (k, v),
+            (k, v) => {
+                let size = self.step.min(self.max_size - prev);
+                self.idx = prev;
+                let end = k.as_ref().map_or(0, |k| k.dim(2));
+                (
+                    grow_buffer(k, end, size, &keys, keys.dim(3), keys.dtype())?,
+                    grow_buffer(v, end, size, &values, values.dim(3), values.dtype())?,
+                )
+            }
+        };
+
+        let trim_size = cached_keys.dim(2) - self.max_size;
+        if trim_size > 0 {
+            cached_keys = self.trim(trim_size, &cached_keys, None)?;
+            cached_values = self.trim(trim_size, &cached_values, None)?;
+            self.idx = self.max_size;
+        }
+
+        // Rotate back to the first position that can be evicted
+        if self.idx == self.max_size {
+            self.idx = self.keep;
+        }
+
+        let end = self.idx + num_steps;
+        cached_keys.try_index_mut((Ellipsis, self.idx..end, ..), &keys)?;
+        cached_values.try_index_mut((Ellipsis, self.idx..end, ..), &values)?;
+        self.offset += num_steps;
+        self.idx = end;
+
+        let output = if self.offset < self.max_size {
+            (
+                cached_keys.index((Ellipsis, ..self.offset, ..)),
+                cached_values.index((Ellipsis, ..self.offset, ..)),
+            )
+        } else {
+            (cached_keys.clone(), cached_values.clone())
+        };
+        self.keys = Some(cached_keys);
+        self.values = Some(cached_values);
+        Ok(output)
+    }
+}
+
+impl KeyValueCache for RotatingKvCache {
+    fn offset(&self) -> i32 {
+        self.offset
+    }
+
+    fn max_size(&self) -> Option<i32> {
+        Some(self.max_size)
+    }
+
+    fn update_and_fetch(
+        &mut self,
+        keys: Array,
+        values: Array,
+    ) -> Result<(Array, Array), Exception> {
+        if keys.dim(2) == 1 {
+            self.update_in_place(keys, values)
+        } else {
+            self.update_concat(keys, values)
+        }
+    }
+}
+
+/// Quantized keys or values, in the layout of [`quantize`].
+#[derive(Debug, Clone)]
+struct QuantizedBuffer {
+    weight: Array,
+    scales: Array,
+    biases: Array,
+}
+
+generate_builder! {
+    /// Unbounded key-value cache that stores the keys and values quantized.
+    ///
+    /// This reduces the memory used by long sequences at the cost of the quantization error. The
+    /// cached keys and values are dequantized when they are fetched.
+    #[derive(Debug, Clone, Buildable)]
+    #[buildable(root = crate)]
+    #[builder(
+        build_with = build_quantized_kv_cache,
+        default_infallible,
+        err = KvCacheBuildError,
+        root = crate
+    )]
+    pub struct QuantizedKvCache {
+        /// Group size of the quantization. Default to [`QuantizedKvCache::DEFAULT_GROUP_SIZE`].
+        #[builder(optional, default = QuantizedKvCache::DEFAULT_GROUP_SIZE)]
+        pub group_size: i32,
+
+        /// Bits of the quantization. Default to [`QuantizedKvCache::DEFAULT_BITS`].
+        #[builder(optional, default = QuantizedKvCache::DEFAULT_BITS)]
+        pub bits: i32,
+
+        /// Number of positions allocated at once. Default to [`KvCache::DEFAULT_STEP`].
+        #[builder(optional, default = KvCache::DEFAULT_STEP)]
+        pub step: i32,
+
+        #[builder(ignore)]
+        keys: Option<QuantizedBuffer>,
+
+        #[builder(ignore)]
+        values: Option<QuantizedBuffer>,
+
+        #[builder(ignore)]
+        offset: i32,
+    }
+}
+
+fn build_quantized_kv_cache(
+    builder: QuantizedKvCacheBuilder,
+) -> Result<QuantizedKvCache, KvCacheBuildError> {
+    check_step(builder.step)?;
+
+    if builder.group_size <= 0 {
+        return Err(KvCacheBuildError::NonPositiveGroupSize(builder.group_size));
+    }
+
+    if !QuantizedKvCache::SUPPORTED_BITS.contains(&builder.bits) {
+        return Err(KvCacheBuildError::UnsupportedBits(builder.bits));
+    }
+
+    Ok(QuantizedKvCache {
+        group_size: builder.group_size,
+        bits: builder.bits,
+        step: builder.step,
+        keys: None,
+        values: None,
+        offset: 0,
+    })
+}
+
+impl QuantizedKvCache {
+    /// Default value for `group_size`.
+    pub const DEFAULT_GROUP_SIZE: i32 = 64;
+
+    /// Default value for `bits`.
+    pub const DEFAULT_BITS: i32 = 8;
+
+    /// Supported values for `bits`.
+    pub const SUPPORTED_BITS: [i32; 5] = [2, 3, 4, 6, 8];
+
+    fn grow(
+        &self,
+        buffer: Option<QuantizedBuffer>,
+        size: i32,
+        like: &Array,
+    ) -> Result<QuantizedBuffer, Exception> {
+        let dim = like.dim(3);
+        let (weight, scales, biases) = match buffer {
+            Some(buffer) => (
+                Some(buffer.weight),
+                Some(buffer.scales),
+                Some(buffer.biases),
+            ),
+            None => (None, None, None),
+        };
+
+        // `uint32` packs `32 / bits` quantized elements
+        Ok(QuantizedBuffer {
+            weight: grow_buffer(
+                weight,
+                self.offset,
+                size,
+                like,
+                dim * self.bits / 32,
+                Dtype::Uint32,
+            )?,
+            scales: grow_buffer(
+                scales,
+                self.offset,
+                size,
+                like,
+                dim / self.group_size,
+                like.dtype(),
+            )?,
+            biases: grow_buffer(
+                biases,
+                self.offset,
+                size,
+                like,
+                dim / self.group_size,
+                like.dtype(),
+            )?,
+        })
+    }
+
+    fn write(
+        &self,
+        buffer: &mut QuantizedBuffer,
+        array: &Array,
+        prev: i32,
+    ) -> Result<(), Exception> {
+        let (weight, scales, biases) = quantize(array, self.group_size, self.bits)?;
+        let end = self.offset;
+        buffer
+            .weight
+            .try_index_mut((Ellipsis, prev..end, ..), &weight)?;
+        buffer
+            .scales
+            .try_index_mut((Ellipsis, prev..end, ..), &scales)?;
+        buffer
+            .biases
+            .try_index_mut((Ellipsis, prev..end, ..), &biases)?;
+        Ok(())
+    }
+
+    fn fetch(&self, buffer: &QuantizedBuffer) -> Result<Array, Exception> {
+        let index = (Ellipsis, ..self.offset, ..);
+        dequantize(
+            buffer.weight.index(index),
+            buffer.scales.index(index),
+            buffer.biases.index(index),
+            self.group_size,
+            self.bits,
+        )
+    }
+}
+
+impl KeyValueCache for QuantizedKvCache {
+    fn offset(&self) -> i32 {
+        self.offset
+    }
+
+    fn max_size(&self) -> Option<i32> {
+        None
+    }
+
+    fn update_and_fetch(
+        &mut self,
+        keys: Array,
+        values: Array,
+    ) -> Result<(Array, Array), Exception> {
+        let prev = self.offset;
+        let num_steps = keys.dim(2);
+
+        let (mut cached_keys, mut cached_values) = match (self.keys.take(), self.values.take()) {
+            (Some(k), Some(v)) if prev + num_steps <= k.weight.dim(2) => (k, v),
+            (k, v) => {
+                let size = round_up(num_steps, self.step);
+                (self.grow(k, size, &keys)?, self.grow(v, size, &values)?)
+            }
+        };
+
+        self.offset += num_steps;
+        self.write(&mut cached_keys, &keys, prev)?;
+        self.write(&mut cached_values, &values, prev)?;
+
+        let output = (self.fetch(&cached_keys)?, self.fetch(&cached_values)?);
+        self.keys = Some(cached_keys);
+        self.values = Some(cached_values);
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::Builder,
+        module::Module,
+        nn::{MultiHeadAttention, MultiHeadAttentionInput},
+        random::uniform,
+    };
+
+    fn random_keys(num_steps: i32) -> Array {
+        uniform::<_, f32>(0.0, 1.0, &[1, 2, num_steps, 64], None).unwrap()
+    }
+
+    #[test]
+    fn test_kv_cache_grows_by_step() {
+        let mut cache = KvCacheBuilder::new().step(4).build().unwrap();
+
+        let keys = random_keys(3);
+        let (k, v) = cache.update_and_fetch(keys.clone(), keys.clone()).unwrap();
+        assert_eq!(k.shape(), &[1, 2, 3, 64]);
+        assert_eq!(v, keys);
+        assert_eq!(cache.keys.as_ref().unwrap().dim(2), 4);
+
+        let next = random_keys(2);
+        let (k, _) = cache.update_and_fetch(next.clone(), next.clone()).unwrap();
+        assert_eq!(cache.offset(), 5);
+        assert_eq!(k, concatenate(&[&keys, &next], 2).unwrap());
+        assert_eq!(cache.keys.as_ref().unwrap().dim(2), 7);
+    }
+
+    #[test]
+    fn test_rotating_kv_cache() {
+        let mut cache = RotatingKvCacheBuilder::new(4).keep(1).build().unwrap();
+
+        let steps = (0..6).map(|_| random_keys(1)).collect::<Vec<_>>();
+        for step in &steps {
+            let (k, _) = cache.update_and_fetch(step.clone(), step.clone()).unwrap();
+            assert!(k.dim(2) <= 4);
+        }
+        assert_eq!(cache.offset(), 6);
+
+        // The first position is kept and the others are the most recent ones
+        let (k, _) = cache
+            .update_and_fetch(random_keys(2), random_keys(2))
+            .unwrap();
+        assert_eq!(k.dim(2), 5);
+        assert_eq!(k.index((Ellipsis, ..1, ..)), steps[0]);
+        assert_eq!(k.index((Ellipsis, 1..2, ..)), steps[4]);
+        assert_eq!(k.index((Ellipsis, 2..3, ..)), steps[5]);
+    }
+
+    #[test]
+    fn test_rotating_kv_cache_invalid_keep() {
+        let result = RotatingKvCacheBuilder::new(4).keep(4).build();
+        assert!(matches!(result, Err(KvCacheBuildError::InvalidKeep(4))));
+    }
+
+    #[test]
+    fn test_quantized_kv_cache() {
+        let mut cache = QuantizedKvCache::new();
+
+        let keys = random_keys(3);
+        let (k, _) = cache.update_and_fetch(keys.clone(), keys.clone()).unwrap();
+        assert_eq!(k.shape(), &[1, 2, 3, 64]);
+        assert!(k.all_close(&keys, 1e-2, 1e-2, None).unwrap().item::<bool>());
+
+        let (k, _) = cache
+            .update_and_fetch(random_keys(1), random_keys(1))
+            .unwrap();
+        assert_eq!(k.shape(), &[1, 2, 4, 64]);
+    }
+
+    #[test]
+    fn test_quantized_kv_cache_invalid_group_size() {
+        let result = QuantizedKvCacheBuilder::new().group_size(0).build();
+        assert!(matches!(
+            result,
+            Err(KvCacheBuildError::NonPositiveGroupSize(0))
+        ));
+    }
+
+    #[test]
+    fn test_quantized_kv_cache_unsupported_bits() {
+        let result = QuantizedKvCacheBuilder::new().bits(5).build();
+        assert!(matches!(result, Err(KvCacheBuildError::UnsupportedBits(5))));
+    }
+
+    #[test]
+    fn test_multi_head_attention_with_cache() {
+        crate::random::seed(42).unwrap();
+        let mut attention = MultiHeadAttention::new(16, 4).unwrap();
+        let x = uniform::<_, f32>(0.0, 1.0, &[1, 5, 16], None).unwrap();
+
+        let mask = MultiHeadAttention::create_additive_causal_mask::<f32>(5).unwrap();
+        let expected = attention.forward((&x, &x, &x, &mask)).unwrap();
+
+        let mut cache = KvCache::new();
+        for i in 0..5 {
+            let token = x.index((.., i..i + 1, ..));
+            let input = MultiHeadAttentionInput::from((&token, &token, &token));
+            let output = attention
+                .forward_with_cache(input, Some(&mut cache))
+                .unwrap();
+            let expected = expected.index((.., i..i + 1, ..));
+            assert!(output
+                .all_close(&expected, 1e-5, 1e-5, None)
+                .unwrap()
+                .item::<bool>());
+        }
+        assert_eq!(cache.offset(), 5);
+    }
+}
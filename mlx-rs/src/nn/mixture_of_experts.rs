@@ -0,0 +1,532 @@
+use crate::{
+    array,
+    error::Exception,
+    module::{Module, ModuleParameters, Param},
+    ops::{
+        arange, argpartition, expand_dims, gather_mm, gather_qmm,
+        indexing::{take_along_axis, Ellipsis, IndexOp},
+        quantize, softmax, zeros,
+    },
+    quantization::{MaybeQuantized, Quantizable},
+    random::uniform,
+    stop_gradient, Array,
+};
+use mlx_internal_macros::{Buildable, Builder};
+use mlx_macros::{ModuleParameters, Quantizable};
+
+use crate::nn::{silu, Linear, LinearBuilder};
+
+/// Builder for [`SwitchLinear`]
+#[derive(Debug, Clone, Builder)]
+#[builder(
+    root = crate,
+    build_with = build_switch_linear,
+    err = Exception,
+)]
+pub struct SwitchLinearBuilder {
+    /// The number of input dimensions.
+    pub input_dims: i32,
+
+    /// The number of output dimensions.
+    pub output_dims: i32,
+
+    /// The number of experts.
+    pub num_experts: i32,
+
+    /// Whether to include bias in the layer. Default to [`SwitchLinear::DEFAULT_BIAS`].
+    #[builder(optional, default = SwitchLinear::DEFAULT_BIAS)]
+    pub bias: bool,
+}
+
+fn build_switch_linear(builder: SwitchLinearBuilder) -> Result<SwitchLinear, Exception> {
+    let input_dims = builder.input_dims;
+    let output_dims = builder.output_dims;
+    let num_experts = builder.num_experts;
+
+    let scale = f32::sqrt(1.0 / (input_dims as f32));
+    let weight = uniform::<_, f32>(-scale, scale, &[num_experts, output_dims, input_dims], None)?;
+
+    let bias = if builder.bias {
+        Some(zeros::<f32>(&[num_experts, output_dims])?)
+    } else {
+        None
+    };
+
+    Ok(SwitchLinear {
+        weight: Param::new(weight),
+        bias: Param::new(bias),
+    })
+}
+
+/// A set of [`Linear`] layers, one per expert, with the weights stacked along the first axis.
+///
+/// The input is a tuple `(x, indices)` where `indices` selects the experts applied to each
+/// input. `x` must have shape `[..., 1, 1, input_dims]` and `indices` shape `[..., k]`, and the
+/// output has shape `[..., k, 1, output_dims]`.
+#[derive(Debug, Clone, ModuleParameters, Buildable)]
+#[module(root = crate)]
+#[buildable(root = crate)]
+pub struct SwitchLinear {
+    /// The weights of the experts, with shape `[num_experts, output_dims, input_dims]`.
+    #[param]
+    pub weight: Param<Array>,
+
+    /// The biases of the experts, with shape `[num_experts, output_dims]`.
+    #[param]
+    pub bias: Param<Option<Array>>,
+}
+
+impl SwitchLinear {
+    /// Default value for `bias`
+    pub const DEFAULT_BIAS: bool = true;
+
+    /// Returns the number of experts, output and input dimensions.
+    pub fn shape(&self) -> (i32, i32, i32) {
+        let weight_shape = self.weight.as_ref().shape();
+        (weight_shape[0], weight_shape[1], weight_shape[2])
+    }
+}
+
+impl<'a> Module<(&'a Array, &'a Array)> for SwitchLinear {
+    type Error = Exception;
+    type Output = Array;
+
+    fn forward(&mut self, (x, indices): (&'a Array, &'a Array)) -> Result<Array, Self::Error> {
+        let weight = self.weight.value.swap_axes(-1, -2)?;
+        let mut x = gather_mm(x, &weight, None, indices)?;
+        if let Some(bias) = &self.bias.value {
+            x = x.add(expand_dims(bias.take(indices, 0)?, &[-2])?)?;
+        }
+        Ok(x)
+    }
+
+    fn training_mode(&mut self, _: bool) {}
+}
+
+impl Quantizable for SwitchLinear {
+    type Quantized = QuantizedSwitchLinear;
+    type QuantizationError = Exception;
+
+    fn try_into_quantized(
+        self,
+        group_size: i32,
+        bits: i32,
+    ) -> Result<Self::Quantized, Self::QuantizationError> {
+        QuantizedSwitchLinear::try_from_switch_linear(self, group_size, bits)
+    }
+}
+
+/// The quantized equivalent of [`SwitchLinear`].
+///
+/// Like [`QuantizedLinear`](crate::nn::QuantizedLinear), its parameters are frozen.
+#[derive(Debug, Clone, ModuleParameters)]
+#[module(root = crate)]
+pub struct QuantizedSwitchLinear {
+    /// Quantization group size
+    pub group_size: i32,
+
+    /// Bits per parameter
+    pub bits: i32,
+
+    /// Scales
+    #[param]
+    pub scales: Param<Array>,
+
+    /// Biases
+    #[param]
+    pub biases: Param<Array>,
+
+    /// Inner layer with the quantized weights
+    #[param]
+    pub inner: SwitchLinear,
+}
+
+impl QuantizedSwitchLinear {
+    /// Convert a [`SwitchLinear`] to a quantized layer.
+    ///
+    /// # Params
+    ///
+    /// - `layer`: The layer to convert.
+    /// - `group_size`: The group size to use for the quantized weight. Default to [`Quantizable::DEFAULT_GROUP_SIZE`]
+    /// - `bits`: The bit width to use for the quantized weight. Default to [`Quantizable::DEFAULT_BITS`]
+    pub fn try_from_switch_linear(
+        layer: SwitchLinear,
+        group_size: impl Into<Option<i32>>,
+        bits: impl Into<Option<i32>>,
+    ) -> Result<Self, Exception> {
+        let group_size = group_size
+            .into()
+            .unwrap_or(<SwitchLinear as Quantizable>::DEFAULT_GROUP_SIZE);
+        let bits = bits
+            .into()
+            .unwrap_or(<SwitchLinear as Quantizable>::DEFAULT_BITS);
+        let (weight, scales, biases) = quantize(layer.weight.value, group_size, bits)?;
+
+        let mut layer = QuantizedSwitchLinear {
+            group_size,
+            bits,
+            scales: Param::new(scales),
+            biases: Param::new(biases),
+            inner: SwitchLinear {
+                weight: Param::new(weight),
+                bias: layer.bias,
+            },
+        };
+
+        // Freeze all parameters
+        layer.freeze_parameters(true);
+
+        Ok(layer)
+    }
+}
+
+impl<'a> Module<(&'a Array, &'a Array)> for QuantizedSwitchLinear {
+    type Error = Exception;
+    type Output = Array;
+
+    fn forward(&mut self, (x, indices): (&'a Array, &'a Array)) -> Result<Array, Self::Error> {
+        let mut x = gather_qmm(
+            x,
+            &self.inner.weight,
+            &self.scales,
+            &self.biases,
+            None,
+            indices,
+            true,
+            self.group_size,
+            self.bits,
+        )?;
+        if let Some(bias) = &self.inner.bias.value {
+            x = x.add(expand_dims(bias.take(indices, 0)?, &[-2])?)?;
+        }
+        Ok(x)
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.inner.training_mode(mode);
+    }
+}
+
+/// Builder for [`SwitchGlu`]
+#[derive(Debug, Clone, Builder)]
+#[builder(
+    root = crate,
+    build_with = build_switch_glu,
+    err = Exception,
+)]
+pub struct SwitchGluBuilder {
+    /// The number of input and output dimensions.
+    pub input_dims: i32,
+
+    /// The number of hidden dimensions of each expert.
+    pub hidden_dims: i32,
+
+    /// The number of experts.
+    pub num_experts: i32,
+
+    /// Whether to include bias in the projections. Default to [`SwitchGlu::DEFAULT_BIAS`].
+    #[builder(optional, default = SwitchGlu::DEFAULT_BIAS)]
+    pub bias: bool,
+}
+
+fn build_switch_glu(builder: SwitchGluBuilder) -> Result<SwitchGlu, Exception> {
+    let input_dims = builder.input_dims;
+    let hidden_dims = builder.hidden_dims;
+    let num_experts = builder.num_experts;
+    let bias = builder.bias;
+
+    let gate_proj = SwitchLinearBuilder::new(input_dims, hidden_dims, num_experts)
+        .bias(bias)
+        .build()?;
+    let up_proj = SwitchLinearBuilder::new(input_dims, hidden_dims, num_experts)
+        .bias(bias)
+        .build()?;
+    let down_proj = SwitchLinearBuilder::new(hidden_dims, input_dims, num_experts)
+        .bias(bias)
+        .build()?;
+
+    Ok(SwitchGlu {
+        gate_proj: MaybeQuantized::new(gate_proj),
+        up_proj: MaybeQuantized::new(up_proj),
+        down_proj: MaybeQuantized::new(down_proj),
+    })
+}
+
+/// Gated feed-forward blocks of a set of experts, ie. `down(silu(gate(x)) * up(x))` with the
+/// projections of the selected experts.
+///
+/// The input is a tuple `(x, indices)` where `x` has shape `[..., input_dims]` and `indices`
+/// has shape `[..., k]`. The output has shape `[..., k, input_dims]`, with the output of each
+/// selected expert.
+#[derive(Debug, Clone, ModuleParameters, Quantizable, Buildable)]
+#[module(root = crate)]
+#[quantizable(root = crate)]
+#[buildable(root = crate)]
+pub struct SwitchGlu {
+    /// Gate projections of the experts
+    #[quantizable]
+    #[param]
+    pub gate_proj: MaybeQuantized<SwitchLinear>,
+
+    /// Up projections of the experts
+    #[quantizable]
+    #[param]
+    pub up_proj: MaybeQuantized<SwitchLinear>,
+
+    /// Down projections of the experts
+    #[quantizable]
+    #[param]
+    pub down_proj: MaybeQuantized<SwitchLinear>,
+}
+
+impl SwitchGlu {
+    /// Default value for `bias`
+    pub const DEFAULT_BIAS: bool = false;
+}
+
+impl<'a> Module<(&'a Array, &'a Array)> for SwitchGlu {
+    type Error = Exception;
+    type Output = Array;
+
+    fn forward(&mut self, (x, indices): (&'a Array, &'a Array)) -> Result<Array, Self::Error> {
+        // Add the expert and the matrix row axes so that each selected expert gets a single row
+        let x = expand_dims(x, &[-2, -3])?;
+
+        let up = self.up_proj.forward((&x, indices))?;
+        let gate = self.gate_proj.forward((&x, indices))?;
+        let hidden = silu(gate)?.multiply(up)?;
+        let out = self.down_proj.forward((&hidden, indices))?;
+
+        out.squeeze(&[-2])
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.gate_proj.training_mode(mode);
+        self.up_proj.training_mode(mode);
+        self.down_proj.training_mode(mode);
+    }
+}
+
+/// Builder for [`MixtureOfExperts`]
+#[derive(Debug, Clone, Builder)]
+#[builder(
+    root = crate,
+    build_with = build_mixture_of_experts,
+    err = Exception,
+)]
+pub struct MixtureOfExpertsBuilder {
+    /// The number of input and output dimensions.
+    pub input_dims: i32,
+
+    /// The number of hidden dimensions of each expert.
+    pub hidden_dims: i32,
+
+    /// The number of experts.
+    pub num_experts: i32,
+
+    /// The number of experts each token is routed to. Default to
+    /// [`MixtureOfExperts::DEFAULT_TOP_K`].
+    #[builder(optional, default = MixtureOfExperts::DEFAULT_TOP_K)]
+    pub top_k: i32,
+
+    /// Whether to renormalize the routing weights of the selected experts to sum to one. Default
+    /// to [`MixtureOfExperts::DEFAULT_NORM_TOP_K_PROB`].
+    #[builder(optional, default = MixtureOfExperts::DEFAULT_NORM_TOP_K_PROB)]
+    pub norm_top_k_prob: bool,
+
+    /// Whether to include bias in the expert projections. Default to [`SwitchGlu::DEFAULT_BIAS`].
+    #[builder(optional, default = SwitchGlu::DEFAULT_BIAS)]
+    pub bias: bool,
+}
+
+fn build_mixture_of_experts(
+    builder: MixtureOfExpertsBuilder,
+) -> Result<MixtureOfExperts, Exception> {
+    let num_experts = builder.num_experts;
+    let top_k = builder.top_k;
+    if top_k <= 0 || top_k > num_experts {
+        return Err(Exception::custom(format!(
+            "top_k must be in the range [1, {}], got {}",
+            num_experts, top_k
+        )));
+    }
+
+    let gate = LinearBuilder::new(builder.input_dims, num_experts)
+        .bias(false)
+        .build()?;
+    let switch_mlp = SwitchGluBuilder::new(builder.input_dims, builder.hidden_dims, num_experts)
+        .bias(builder.bias)
+        .build()?;
+
+    Ok(MixtureOfExperts {
+        num_experts,
+        top_k,
+        norm_top_k_prob: builder.norm_top_k_prob,
+        gate: MaybeQuantized::new(gate),
+        switch_mlp,
+    })
+}
+
+/// Sparse mixture of experts block as used by Mixtral and Qwen MoE.
+///
+/// A router [`Linear`] layer scores the experts for each token, and each token goes through the
+/// `top_k` experts with the highest scores. The outputs of the experts are summed, weighted by
+/// the routing probabilities.
+#[derive(Debug, Clone, ModuleParameters, Quantizable, Buildable)]
+#[module(root = crate)]
+#[quantizable(root = crate)]
+#[buildable(root = crate)]
+pub struct MixtureOfExperts {
+    /// Number of experts
+    pub num_experts: i32,
+
+    /// Number of experts each token is routed to
+    pub top_k: i32,
+
+    /// If `true`, the routing weights of the selected experts are renormalized to sum to one
+    pub norm_top_k_prob: bool,
+
+    /// Router
+    #[quantizable]
+    #[param]
+    pub gate: MaybeQuantized<Linear>,
+
+    /// Experts
+    #[quantizable]
+    #[param]
+    pub switch_mlp: SwitchGlu,
+}
+
+impl MixtureOfExperts {
+    /// Default value for `top_k`
+    pub const DEFAULT_TOP_K: i32 = 2;
+
+    /// Default value for `norm_top_k_prob`
+    pub const DEFAULT_NORM_TOP_K_PROB: bool = true;
+}
+
+/// Output of the [`MixtureOfExperts`] module
+#[derive(Debug, Clone)]
+pub struct MixtureOfExpertsOutput {
+    /// Output with the same shape as the input
+    pub output: Array,
+
+    /// Auxiliary load balancing loss of the router.
+    ///
+    /// This is `num_experts` times the dot product of the fraction of the tokens routed to each
+    /// expert and the mean routing probability of each expert, following the Switch Transformer
+    /// (<https://arxiv.org/abs/2101.03961>). It is `1` when the routing is balanced and is
+    /// usually added to the training loss with a small coefficient.
+    pub aux_loss: Array,
+}
+
+impl Module<&Array> for MixtureOfExperts {
+    type Error = Exception;
+    type Output = MixtureOfExpertsOutput;
+
+    fn forward(&mut self, x: &Array) -> Result<MixtureOfExpertsOutput, Self::Error> {
+        let k = self.top_k;
+
+        let logits = self.gate.forward(x)?;
+        let probs = softmax(&logits, &[-1], true)?;
+
+        let indices = argpartition(probs.negative()?, k - 1, -1)?;
+        let indices = stop_gradient(indices.index((Ellipsis, ..k)))?;
+        let mut scores = take_along_axis(&probs, &indices, -1)?;
+        if self.norm_top_k_prob {
+            scores = scores.divide(scores.sum(&[-1], true)?)?;
+        }
+
+        let y = self.switch_mlp.forward((x, &indices))?;
+        let output = y
+            .multiply(expand_dims(&scores, &[-1])?)?
+            .sum(&[-2], false)?
+            .as_dtype(y.dtype())?;
+
+        let aux_loss = self.load_balancing_loss(&probs, &indices)?;
+
+        Ok(MixtureOfExpertsOutput { output, aux_loss })
+    }
+
+    fn training_mode(&mut self, mode: bool) {
+        self.gate.training_mode(mode);
+        self.switch_mlp.training_mode(mode);
+    }
+}
+
+impl MixtureOfExperts {
+    fn load_balancing_loss(&self, probs: &Array, indices: &Array) -> Result<Array, Exception> {
+        let probs = probs.reshape(&[-1, self.num_experts])?;
+        let indices = indices.reshape(&[-1, self.top_k, 1])?;
+
+        // Fraction of the routing assignments that go to each expert
+        let experts = arange::<_, u32>(0, self.num_experts, None)?;
+        let assigned = indices.eq(&experts)?.as_dtype(probs.dtype())?;
+        let fraction = assigned.mean(&[0, 1], false)?;
+
+        let mean_probs = probs.mean(&[0], false)?;
+        fraction
+            .multiply(mean_probs)?
+            .sum(None, false)?
+            .multiply(array!(self.num_experts as f32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::Builder, nn::quantize, ops::indexing::IndexOp};
+
+    #[test]
+    fn test_switch_linear_matches_expert() {
+        let mut layer = SwitchLinear::new(8, 4, 3).unwrap();
+        let x = uniform::<_, f32>(0.0, 1.0, &[5, 1, 1, 8], None).unwrap();
+        let indices = Array::from_slice(&[2u32, 0, 1, 2, 1], &[5, 1]);
+
+        let y = layer.forward((&x, &indices)).unwrap();
+        assert_eq!(y.shape(), &[5, 1, 1, 4]);
+
+        // The third token goes through the second expert
+        let weight = layer.weight.value.index(1);
+        let bias = layer.bias.value.as_ref().unwrap().index(1);
+        let expected = x
+            .index((2, 0))
+            .matmul(weight.t())
+            .unwrap()
+            .add(bias)
+            .unwrap();
+        assert!(y
+            .index((2, 0))
+            .all_close(&expected, 1e-5, 1e-5, None)
+            .unwrap()
+            .item::<bool>());
+    }
+
+    #[test]
+    fn test_mixture_of_experts() {
+        let mut moe = MixtureOfExpertsBuilder::new(64, 64, 4)
+            .top_k(2)
+            .build()
+            .unwrap();
+        let x = uniform::<_, f32>(0.0, 1.0, &[2, 3, 64], None).unwrap();
+
+        let MixtureOfExpertsOutput { output, aux_loss } = moe.forward(&x).unwrap();
+        assert_eq!(output.shape(), &[2, 3, 64]);
+        assert_eq!(aux_loss.ndim(), 0);
+        assert!(aux_loss.item::<f32>() > 0.0);
+
+        let mut quantized = quantize(moe, None, None).unwrap();
+        assert!(quantized.gate.is_quantized());
+        assert!(quantized.switch_mlp.down_proj.is_quantized());
+        let quantized_output = quantized.forward(&x).unwrap().output;
+        assert_eq!(quantized_output.shape(), &[2, 3, 64]);
+    }
+
+    #[test]
+    fn test_mixture_of_experts_invalid_top_k() {
+        assert!(MixtureOfExpertsBuilder::new(8, 8, 4)
+            .top_k(5)
+            .build()
+            .is_err());
+    }
+}
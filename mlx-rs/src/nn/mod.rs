@@ -6,12 +6,15 @@
 //! to set optional parameters.
 
 mod activation;
+mod checkpoint;
 mod container;
 mod convolution;
 mod convolution_transpose;
 mod dropout;
 mod embedding;
+mod kv_cache;
 mod linear;
+mod mixture_of_experts;
 mod normalization;
 mod pooling;
 mod positional_encoding;
@@ -22,12 +25,15 @@ mod upsample;
 mod value_and_grad;
 
 pub use activation::*;
+pub use checkpoint::*;
 pub use container::*;
 pub use convolution::*;
 pub use convolution_transpose::*;
 pub use dropout::*;
 pub use embedding::*;
+pub use kv_cache::*;
 pub use linear::*;
+pub use mixture_of_experts::*;
 pub use normalization::*;
 pub use pooling::*;
 pub use positional_encoding::*;
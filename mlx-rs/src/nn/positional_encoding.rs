@@ -1,10 +1,10 @@
-use std::{cell::RefCell, collections::HashMap};
+use std::{cell::RefCell, collections::HashMap, f64::consts::PI};
 
 use crate::{
     array,
     error::Exception,
     module::{Module, Param},
-    ops::indexing::NewAxis,
+    ops::indexing::{Ellipsis, IndexOp, NewAxis, TryIndexMutOp},
     ops::{arange, concatenate, exp, indexing::TryIndexOp, log},
     Array, Dtype,
 };
@@ -26,10 +26,18 @@ generate_builder! {
     ///
     /// For more details see _RoFormer: Enhanced Transformer with Rotary Position
     /// Embedding_ ([https://arxiv.org/abs/2104.09864](https://arxiv.org/abs/2104.09864))
+    ///
+    /// The frequencies can be scaled to extend the context length of a pretrained model, see
+    /// [`RopeScaling`].
     #[derive(Debug, Clone, ModuleParameters, Buildable)]
     #[module(root = crate)]
     #[buildable(root = crate)]
-    #[builder(root = crate)]
+    #[builder(
+        build_with = build_rope,
+        default_infallible,
+        err = Exception,
+        root = crate
+    )]
     pub struct RotaryPositionalEncoding {
         /// The feature dimensions to be rotated. If the input feature is larger
         /// than dims then the rest is left unchanged
@@ -48,9 +56,49 @@ generate_builder! {
         /// scale used to scale the positions
         #[builder(optional, default = RotaryPositionalEncoding::DEFAULT_SCALE)]
         pub scale: f32,
+
+        /// Scaling of the frequencies for long contexts
+        #[builder(optional, default = None)]
+        pub scaling: Option<RopeScaling>,
+
+        /// Frequencies computed from `scaling`, passed to [`crate::fast::rope`] instead of `base`
+        #[builder(ignore)]
+        freqs: Option<Array>,
+
+        /// Multiplier of the rotated features computed from `scaling`
+        #[builder(ignore)]
+        mscale: f32,
     }
 }
 
+fn build_rope(
+    builder: RotaryPositionalEncodingBuilder,
+) -> Result<RotaryPositionalEncoding, Exception> {
+    let dimensions = builder.dimensions;
+    let base = builder.base;
+
+    let (freqs, mscale) = match &builder.scaling {
+        Some(scaling) => {
+            scaling.validate()?;
+            let freqs = scaling
+                .freqs(dimensions, base)
+                .map(|freqs| Array::from_slice(&freqs, &[dimensions / 2]));
+            (freqs, scaling.mscale())
+        }
+        None => (None, 1.0),
+    };
+
+    Ok(RotaryPositionalEncoding {
+        dimensions,
+        traditional: builder.traditional,
+        base,
+        scale: builder.scale,
+        scaling: builder.scaling,
+        freqs,
+        mscale,
+    })
+}
+
 impl RotaryPositionalEncoding {
     /// Default value for `traditional` field.
     pub const DEFAULT_TRADITIONAL: bool = false;
@@ -62,6 +110,287 @@ impl RotaryPositionalEncoding {
     pub const DEFAULT_SCALE: f32 = 1.0;
 }
 
+/// Method used to scale the rotary positional encoding, see [`RopeScaling`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum RopeScalingType {
+    /// Linear position interpolation, ie. the positions are divided by the factor
+    Linear,
+
+    /// Dynamic NTK scaling, ie. the base grows with the sequence length once it exceeds
+    /// the original context length
+    Dynamic,
+
+    /// YaRN, ie. NTK-by-parts interpolation with an attention factor correction
+    Yarn,
+
+    /// Llama 3 frequency smoothing
+    Llama3,
+}
+
+/// Scaling of the rotary positional encoding for contexts longer than the one the model was
+/// trained with.
+///
+/// The fields follow the `rope_scaling` entry of the Hugging Face `config.json`, so it can be
+/// deserialized from it directly with the `serde` feature. The fields that are not used by
+/// [`RopeScaling::rope_type`] are ignored and the missing ones take the default values of the
+/// reference implementations.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RopeScaling {
+    /// Scaling method
+    #[cfg_attr(feature = "serde", serde(alias = "type"))]
+    pub rope_type: RopeScalingType,
+
+    /// Ratio between the extended and the original context length
+    pub factor: f32,
+
+    /// Context length the model was trained with. Required by [`RopeScalingType::Dynamic`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub original_max_position_embeddings: Option<i32>,
+
+    /// Llama 3 only. Default to [`RopeScaling::DEFAULT_LOW_FREQ_FACTOR`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub low_freq_factor: Option<f32>,
+
+    /// Llama 3 only. Default to [`RopeScaling::DEFAULT_HIGH_FREQ_FACTOR`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub high_freq_factor: Option<f32>,
+
+    /// YaRN only. Default to [`RopeScaling::DEFAULT_BETA_FAST`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub beta_fast: Option<f32>,
+
+    /// YaRN only. Default to [`RopeScaling::DEFAULT_BETA_SLOW`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub beta_slow: Option<f32>,
+
+    /// YaRN only. Default to [`RopeScaling::DEFAULT_MSCALE`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub mscale: Option<f32>,
+
+    /// YaRN only. Default to [`RopeScaling::DEFAULT_MSCALE_ALL_DIM`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub mscale_all_dim: Option<f32>,
+}
+
+impl RopeScaling {
+    /// Default value of `original_max_position_embeddings` for YaRN.
+    pub const DEFAULT_YARN_ORIGINAL_MAX_POSITION_EMBEDDINGS: i32 = 4096;
+
+    /// Default value of `original_max_position_embeddings` for Llama 3.
+    pub const DEFAULT_LLAMA3_ORIGINAL_MAX_POSITION_EMBEDDINGS: i32 = 8192;
+
+    /// Default value for `low_freq_factor`.
+    pub const DEFAULT_LOW_FREQ_FACTOR: f32 = 1.0;
+
+    /// Default value for `high_freq_factor`.
+    pub const DEFAULT_HIGH_FREQ_FACTOR: f32 = 4.0;
+
+    /// Default value for `beta_fast`.
+    pub const DEFAULT_BETA_FAST: f32 = 32.0;
+
+    /// Default value for `beta_slow`.
+    pub const DEFAULT_BETA_SLOW: f32 = 1.0;
+
+    /// Default value for `mscale`.
+    pub const DEFAULT_MSCALE: f32 = 1.0;
+
+    /// Default value for `mscale_all_dim`.
+    pub const DEFAULT_MSCALE_ALL_DIM: f32 = 0.0;
+
+    /// Creates a scaling of type `rope_type` with the default values for the other fields.
+    pub fn new(rope_type: RopeScalingType, factor: f32) -> Self {
+        Self {
+            rope_type,
+            factor,
+            original_max_position_embeddings: None,
+            low_freq_factor: None,
+            high_freq_factor: None,
+            beta_fast: None,
+            beta_slow: None,
+            mscale: None,
+            mscale_all_dim: None,
+        }
+    }
+
+    /// Linear position interpolation by `factor`.
+    pub fn linear(factor: f32) -> Self {
+        Self::new(RopeScalingType::Linear, factor)
+    }
+
+    /// Dynamic NTK scaling for a model trained with `original_max_position_embeddings`
+    /// positions.
+    pub fn dynamic(factor: f32, original_max_position_embeddings: i32) -> Self {
+        Self {
+            original_max_position_embeddings: Some(original_max_position_embeddings),
+            ..Self::new(RopeScalingType::Dynamic, factor)
+        }
+    }
+
+    /// YaRN scaling by `factor`.
+    pub fn yarn(factor: f32) -> Self {
+        Self::new(RopeScalingType::Yarn, factor)
+    }
+
+    /// Llama 3 scaling by `factor`.
+    pub fn llama3(factor: f32) -> Self {
+        Self::new(RopeScalingType::Llama3, factor)
+    }
+
+    fn validate(&self) -> Result<(), Exception> {
+        if !(self.factor.is_finite() && self.factor > 0.0) {
+            return Err(Exception::custom(format!(
+                "The rope scaling factor must be positive, got {}",
+                self.factor
+            )));
+        }
+
+        match self.rope_type {
+            RopeScalingType::Dynamic if self.original_max_position_embeddings.is_none() => Err(
+                Exception::custom("Dynamic rope scaling requires original_max_position_embeddings"),
+            ),
+            RopeScalingType::Llama3 => {
+                let low = self
+                    .low_freq_factor
+                    .unwrap_or(Self::DEFAULT_LOW_FREQ_FACTOR);
+                let high = self
+                    .high_freq_factor
+                    .unwrap_or(Self::DEFAULT_HIGH_FREQ_FACTOR);
+                if low <= 0.0 || high <= low {
+                    return Err(Exception::custom(format!(
+                        "Invalid llama3 rope scaling frequency factors: low {}, high {}",
+                        low, high
+                    )));
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Custom frequencies, ie. the periods of the rotated pairs, or `None` if the scaling does
+    /// not change the frequencies.
+    fn freqs(&self, dimensions: i32, base: f32) -> Option<Vec<f32>> {
+        let factor = self.factor as f64;
+        let dims = dimensions as f64;
+        let base = base as f64;
+        let periods = (0..dimensions / 2).map(move |i| base.powf(2.0 * i as f64 / dims));
+
+        match self.rope_type {
+            RopeScalingType::Linear | RopeScalingType::Dynamic => None,
+            RopeScalingType::Yarn => {
+                let original = self
+                    .original_max_position_embeddings
+                    .unwrap_or(Self::DEFAULT_YARN_ORIGINAL_MAX_POSITION_EMBEDDINGS)
+                    as f64;
+                let beta_fast = self.beta_fast.unwrap_or(Self::DEFAULT_BETA_FAST) as f64;
+                let beta_slow = self.beta_slow.unwrap_or(Self::DEFAULT_BETA_SLOW) as f64;
+
+                // Dimensions with fewer than `beta_fast` rotations over the original context are
+                // interpolated, the ones with more than `beta_slow` are extrapolated
+                let correction_dim = |rotations: f64| {
+                    dims * (original / (rotations * 2.0 * PI)).ln() / (2.0 * base.ln())
+                };
+                let low = correction_dim(beta_fast).floor().max(0.0);
+                let mut high = correction_dim(beta_slow).ceil().min(dims - 1.0);
+                if low == high {
+                    high += 0.001;
+                }
+
+                let freqs = periods
+                    .enumerate()
+                    .map(|(i, extrapolation)| {
+                        let interpolation = factor * extrapolation;
+                        let ramp = ((i as f64 - low) / (high - low)).clamp(0.0, 1.0);
+                        let mask = 1.0 - ramp;
+                        let freq = interpolation * extrapolation
+                            / (interpolation * mask + extrapolation * (1.0 - mask));
+                        freq as f32
+                    })
+                    .collect();
+                Some(freqs)
+            }
+            RopeScalingType::Llama3 => {
+                let original = self
+                    .original_max_position_embeddings
+                    .unwrap_or(Self::DEFAULT_LLAMA3_ORIGINAL_MAX_POSITION_EMBEDDINGS)
+                    as f64;
+                let low_freq_factor =
+                    self.low_freq_factor
+                        .unwrap_or(Self::DEFAULT_LOW_FREQ_FACTOR) as f64;
+                let high_freq_factor =
+                    self.high_freq_factor
+                        .unwrap_or(Self::DEFAULT_HIGH_FREQ_FACTOR) as f64;
+                let low_freq_wavelen = original / low_freq_factor;
+                let high_freq_wavelen = original / high_freq_factor;
+
+                let freqs = periods
+                    .map(|period| {
+                        let wavelen = 2.0 * PI * period;
+                        let freq = if wavelen < high_freq_wavelen {
+                            period
+                        } else if wavelen > low_freq_wavelen {
+                            period * factor
+                        } else {
+                            // Interpolate smoothly between the two regimes
+                            let smooth = (original / wavelen - low_freq_factor)
+                                / (high_freq_factor - low_freq_factor);
+                            period / ((1.0 - smooth) / factor + smooth)
+                        };
+                        freq as f32
+                    })
+                    .collect();
+                Some(freqs)
+            }
+        }
+    }
+
+    /// Multiplier of the rotated features, ie. the YaRN attention factor.
+    fn mscale(&self) -> f32 {
+        let get_mscale = |mscale: f32| {
+            if self.factor <= 1.0 {
+                1.0
+            } else {
+                0.1 * mscale * self.factor.ln() + 1.0
+            }
+        };
+
+        match self.rope_type {
+            RopeScalingType::Yarn => {
+                get_mscale(self.mscale.unwrap_or(Self::DEFAULT_MSCALE))
+                    / get_mscale(self.mscale_all_dim.unwrap_or(Self::DEFAULT_MSCALE_ALL_DIM))
+            }
+            _ => 1.0,
+        }
+    }
+}
+
 generate_builder! {
     /// Input for the [`RotaryPositionalEncoding`] module.
     #[derive(Debug, Buildable, Clone)]
@@ -117,15 +446,48 @@ where
     fn forward(&mut self, input: Input) -> Result<Self::Output, Self::Error> {
         let RopeInput { x, offset } = input.into();
         let shape = x.shape();
-        let x = x.reshape(&[-1, x.dim(-2), x.dim(-1)])?;
+        let mut x = x.reshape(&[-1, x.dim(-2), x.dim(-1)])?;
+
+        if self.mscale != 1.0 {
+            let mscale = array!(self.mscale).as_dtype(x.dtype())?;
+            let rotated = x.index((Ellipsis, ..self.dimensions)).multiply(mscale)?;
+            x.try_index_mut((Ellipsis, ..self.dimensions), &rotated)?;
+        }
+
+        let mut base = Some(self.base);
+        let mut scale = self.scale;
+        match &self.scaling {
+            Some(_) if self.freqs.is_some() => base = None,
+            Some(RopeScaling {
+                rope_type: RopeScalingType::Linear,
+                factor,
+                ..
+            }) => scale /= *factor,
+            Some(RopeScaling {
+                rope_type: RopeScalingType::Dynamic,
+                factor,
+                original_max_position_embeddings: Some(original),
+                ..
+            }) => {
+                let seq_len = (offset + x.dim(-2)) as f32;
+                let original = *original as f32;
+                if seq_len > original {
+                    let dims = self.dimensions as f32;
+                    let ratio = *factor * seq_len / original - (*factor - 1.0);
+                    base = Some(self.base * ratio.powf(dims / (dims - 2.0)));
+                }
+            }
+            _ => {}
+        }
+
         let x = crate::fast::rope(
             x,
             self.dimensions,
             self.traditional,
-            self.base,
-            self.scale,
+            base,
+            scale,
             offset,
-            None,
+            self.freqs.as_ref(),
         )?;
         x.reshape(shape)
     }
@@ -422,7 +784,10 @@ mod tests {
     use crate::{module::Module, nn::AlibiInput, random::uniform, Dtype};
     use float_eq::assert_float_eq;
 
-    use crate::nn::Rope;
+    use crate::{
+        builder::Builder,
+        nn::{Rope, RopeBuilder, RopeScaling, RopeScalingType},
+    };
 
     // The unit test below is adapted from the swift binding at:
     // mlx-swift/Tests/MLXTests/IntegrationTests.swift
@@ -459,6 +824,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_rope_linear_scaling() {
+        let a = uniform::<_, f32>(0, 1, &[2, 8, 16], None).unwrap();
+
+        let mut scaled = RopeBuilder::new(16)
+            .scaling(RopeScaling::linear(4.0))
+            .build()
+            .unwrap();
+        let mut expected = RopeBuilder::new(16).scale(0.25).build().unwrap();
+        assert_eq!(
+            scaled.forward((&a, 3)).unwrap(),
+            expected.forward((&a, 3)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rope_scaling_freqs() {
+        let llama3 = RopeScaling::llama3(8.0).freqs(128, 500_000.0).unwrap();
+        assert_eq!(llama3.len(), 64);
+        // High frequencies are kept and low frequencies are divided by the factor
+        assert_eq!(llama3[0], 1.0);
+        let period = 500_000f64.powf(126.0 / 128.0) as f32;
+        assert_float_eq!(llama3[63], period * 8.0, r2nd <= 1e-6);
+
+        let yarn = RopeScaling::yarn(4.0);
+        let freqs = yarn.freqs(64, 10_000.0).unwrap();
+        assert_eq!(freqs[0], 1.0);
+        assert_float_eq!(
+            freqs[31],
+            10_000f64.powf(62.0 / 64.0) as f32 * 4.0,
+            r2nd <= 1e-6
+        );
+        assert_float_eq!(yarn.mscale(), 0.1 * 4f32.ln() + 1.0, abs <= 1e-6);
+
+        let mut rope = RopeBuilder::new(64).scaling(yarn).build().unwrap();
+        let a = uniform::<_, f32>(0, 1, &[2, 8, 64], None).unwrap();
+        assert_eq!(rope.forward(&a).unwrap().shape(), &[2, 8, 64]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_rope_scaling_from_config() {
+        let scaling: RopeScaling = serde_json::from_str(
+            r#"{"type": "dynamic", "factor": 2.0, "original_max_position_embeddings": 4096}"#,
+        )
+        .unwrap();
+        assert_eq!(scaling, RopeScaling::dynamic(2.0, 4096));
+
+        let scaling: RopeScaling = serde_json::from_str(
+            r#"{"rope_type": "llama3", "factor": 8.0, "low_freq_factor": 1.0, "high_freq_factor": 4.0, "original_max_position_embeddings": 8192}"#,
+        )
+        .unwrap();
+        assert_eq!(scaling.rope_type, RopeScalingType::Llama3);
+
+        let invalid = RopeScaling::new(RopeScalingType::Dynamic, 2.0);
+        assert!(RopeBuilder::new(8).scaling(invalid).build().is_err());
+    }
+
     // The unit test below is adapted from the swift binding at:
     // mlx-swift/Tests/MLXTests/IntegrationTests.swift
     #[test]
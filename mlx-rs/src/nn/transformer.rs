@@ -4,8 +4,9 @@ use crate::{
     array,
     builder::Builder,
     error::Exception,
+    fast::scaled_dot_product_attention,
     module::{Module, UnaryModule},
-    ops::{arange, expand_dims, matmul, softmax},
+    ops::{arange, expand_dims},
     quantization::MaybeQuantized,
     Array, ArrayElement, FromScalar,
 };
@@ -16,7 +17,10 @@ use num_traits::bounds::LowerBounded;
 
 use crate::{
     error::{MultiHeadAttentionBuildError, TransformerBulidError},
-    nn::{Dropout, DropoutBuilder, LayerNorm, Linear, LinearBuilder, Relu},
+    nn::{
+        Dropout, DropoutBuilder, KeyValueCache, KvCache, LayerNorm, Linear, LinearBuilder, Relu,
+        Rope, RopeInput,
+    },
 };
 
 /// A marker trait for activation functions used in transformers.
@@ -38,6 +42,13 @@ pub struct MultiHeadAttentionBuilder {
     /// Number of attention heads
     pub num_heads: i32,
 
+    /// Number of key and value heads. Default to `num_heads`.
+    ///
+    /// Each key and value head is shared by `num_heads / num_kv_heads` query heads, ie. grouped
+    /// query attention, or multi query attention if `num_kv_heads` is 1.
+    #[builder(optional, default = None)]
+    pub num_kv_heads: Option<i32>,
+
     /// Input dimensions of queries
     #[builder(optional, default = None)]
     pub query_input_dims: Option<i32>,
@@ -61,12 +72,16 @@ pub struct MultiHeadAttentionBuilder {
     /// If `true`, use a bias in the [`Linear`] layers
     #[builder(optional, default = MultiHeadAttention::DEFAULT_BIAS)]
     pub bias: bool,
+
+    /// Rotary positional encoding applied to the queries and keys of each head
+    #[builder(optional, default = None)]
+    pub rope: Option<Rope>,
 }
 
 fn build_multi_head_attention(
     builder: MultiHeadAttentionBuilder,
 ) -> Result<MultiHeadAttention, MultiHeadAttentionBuildError> {
-    if builder.dims % builder.num_heads != 0 {
+    if builder.num_heads <= 0 || builder.dims % builder.num_heads != 0 {
         return Err(MultiHeadAttentionBuildError::InvalidNumHeads(
             builder.num_heads,
         ));
@@ -81,14 +96,28 @@ fn build_multi_head_attention(
     let value_output_dims = builder.value_output_dims.unwrap_or(builder.dims);
 
     let num_heads = builder.num_heads;
+    if value_dims % num_heads != 0 {
+        return Err(MultiHeadAttentionBuildError::InvalidValueDims(value_dims));
+    }
+
+    let num_kv_heads = builder.num_kv_heads.unwrap_or(num_heads);
+    if num_kv_heads <= 0 || num_heads % num_kv_heads != 0 {
+        return Err(MultiHeadAttentionBuildError::InvalidNumKvHeads(
+            num_kv_heads,
+        ));
+    }
+
+    // Keys and values are projected to `num_kv_heads` heads of the same size as the query heads
+    let kv_dims = dims / num_heads * num_kv_heads;
+    let kv_value_dims = value_dims / num_heads * num_kv_heads;
 
     let query_proj = LinearBuilder::new(query_input_dims, dims)
         .bias(bias)
         .build()?;
-    let key_proj = LinearBuilder::new(key_input_dims, dims)
+    let key_proj = LinearBuilder::new(key_input_dims, kv_dims)
         .bias(bias)
         .build()?;
-    let value_proj = LinearBuilder::new(value_input_dims, value_dims)
+    let value_proj = LinearBuilder::new(value_input_dims, kv_value_dims)
         .bias(bias)
         .build()?;
     let output_proj = LinearBuilder::new(value_dims, value_output_dims)
@@ -97,6 +126,8 @@ fn build_multi_head_attention(
 
     Ok(MultiHeadAttention {
         num_heads,
+        num_kv_heads,
+        rope: builder.rope,
         query_proj: MaybeQuantized::new(query_proj),
         key_proj: MaybeQuantized::new(key_proj),
         value_proj: MaybeQuantized::new(value_proj),
@@ -105,6 +136,9 @@ fn build_multi_head_attention(
 }
 
 /// Implements the scaled dot product attention with multiple heads.
+///
+/// The keys and values can have fewer heads than the queries, see
+/// [`MultiHeadAttentionBuilder::num_kv_heads`].
 #[derive(Debug, Clone, ModuleParameters, Quantizable, Buildable)]
 #[module(root = crate)]
 #[quantizable(root = crate)]
@@ -113,6 +147,12 @@ pub struct MultiHeadAttention {
     /// Number of attention heads
     pub num_heads: i32,
 
+    /// Number of key and value heads
+    pub num_kv_heads: i32,
+
+    /// Rotary positional encoding applied to the queries and keys
+    pub rope: Option<Rope>,
+
     /// Query projection layer
     #[quantizable]
     #[param]
@@ -151,6 +191,60 @@ impl MultiHeadAttention {
         let mask = mask.as_type::<T>()?.multiply(array!(T::min_value()))?; // TODO: replace with f32::MIN?
         Ok(mask)
     }
+
+    /// Same as [`Module::forward`] but with a cache of the keys and values of the previous
+    /// positions.
+    ///
+    /// The keys and values of `input` are appended to `cache` and the queries attend to all the
+    /// cached positions.
+    #[allow(non_snake_case)]
+    pub fn forward_with_cache<'a>(
+        &mut self,
+        input: impl Into<MultiHeadAttentionInput<'a>>,
+        cache: Option<&mut dyn KeyValueCache>,
+    ) -> Result<Array, Exception> {
+        let input = input.into();
+        let queries = self.query_proj.forward(input.queries)?;
+        let keys = self.key_proj.forward(input.keys)?;
+        let values = self.value_proj.forward(input.values)?;
+
+        let B = queries.dim(0);
+        let L = queries.dim(1);
+        let S = keys.dim(1);
+
+        let mut queries = queries
+            .reshape(&[B, L, self.num_heads, -1])?
+            .transpose(&[0, 2, 1, 3])?;
+        let mut keys = keys
+            .reshape(&[B, S, self.num_kv_heads, -1])?
+            .transpose(&[0, 2, 1, 3])?;
+        let mut values = values
+            .reshape(&[B, S, self.num_kv_heads, -1])?
+            .transpose(&[0, 2, 1, 3])?;
+
+        // Dimensions are [batch x num_heads x sequence x hidden_dim]
+        if let Some(rope) = &mut self.rope {
+            let offset = cache.as_ref().map_or(0, |cache| cache.offset());
+            queries = rope.forward(RopeInput::from((&queries, offset)))?;
+            keys = rope.forward(RopeInput::from((&keys, offset)))?;
+        }
+
+        if let Some(cache) = cache {
+            (keys, values) = cache.update_and_fetch(keys, values)?;
+        }
+
+        let scale = f32::sqrt(1.0 / queries.dim(-1) as f32);
+        let mask = input
+            .mask
+            .map(|mask| mask.as_dtype(queries.dtype()))
+            .transpose()?;
+        let value_hat =
+            scaled_dot_product_attention(&queries, &keys, &values, scale, mask.as_ref(), None)?
+                .transpose(&[0, 2, 1, 3])?
+                .reshape(&[B, L, -1])?;
+
+        self.output_proj.forward(&value_hat)
+    }
 }
 
 generate_builder! {
@@ -218,39 +312,8 @@ where
     type Error = Exception;
     type Output = Array;
 
-    #[allow(non_snake_case)]
     fn forward(&mut self, input: Input) -> Result<Self::Output, Self::Error> {
-        let input = input.into();
-        let queries = self.query_proj.forward(input.queries)?;
-        let keys = self.key_proj.forward(input.keys)?;
-        let values = self.value_proj.forward(input.values)?;
-
-        let B = queries.dim(0);
-        let L = queries.dim(1);
-        let S = keys.dim(1);
-
-        let queries = queries
-            .reshape(&[B, L, self.num_heads, -1])?
-            .transpose(&[0, 2, 1, 3])?;
-        let keys = keys
-            .reshape(&[B, S, self.num_heads, -1])?
-            .transpose(&[0, 2, 3, 1])?;
-        let values = values
-            .reshape(&[B, S, self.num_heads, -1])?
-            .transpose(&[0, 2, 1, 3])?;
-
-        // Dimensions are [batch x num_heads x sequence x hidden_dim]
-        let scale = f32::sqrt(1.0 / queries.dim(-1) as f32);
-        let mut scores = (queries * scale).matmul(&keys)?;
-        if let Some(mask) = input.mask {
-            scores = scores.add(mask.as_dtype(scores.dtype())?)?;
-        }
-        scores = softmax(&scores, &[-1], None)?;
-        let value_hat = matmul(&scores, &values)?
-            .transpose(&[0, 2, 1, 3])?
-            .reshape(&[B, L, -1])?;
-
-        self.output_proj.forward(&value_hat)
+        self.forward_with_cache(input, None)
     }
 
     fn training_mode(&mut self, mode: bool) {
@@ -717,8 +780,11 @@ impl Clone for TransformerDecoderLayer {
 struct TransformerDecoderInput<'a> {
     pub x: &'a Array,
     pub memory: &'a Array,
-    pub x_mask: &'a Array,
-    pub memory_mask: &'a Array,
+    pub x_mask: Option<&'a Array>,
+    pub memory_mask: Option<&'a Array>,
+
+    /// Cache of the self attention
+    pub cache: Option<&'a mut dyn KeyValueCache>,
 }
 
 impl<'a> From<(&'a Array, &'a Array, &'a Array, &'a Array)> for TransformerDecoderInput<'a> {
@@ -728,8 +794,9 @@ impl<'a> From<(&'a Array, &'a Array, &'a Array, &'a Array)> for TransformerDecod
         TransformerDecoderInput {
             x,
             memory,
-            x_mask,
-            memory_mask,
+            x_mask: Some(x_mask),
+            memory_mask: Some(memory_mask),
+            cache: None,
         }
     }
 }
@@ -750,9 +817,10 @@ where
 
         if self.norm_first {
             let mut y = self.ln1.forward(x)?;
+            let attention_input = MultiHeadAttentionInput::from((&y, &y, &y, x_mask));
             y = self
                 .self_attention
-                .forward(MultiHeadAttentionInput::from((&y, &y, &y, x_mask)))?;
+                .forward_with_cache(attention_input, input.cache)?;
             y = self.dropout1.forward(&y)?;
             let x = x.add(&y)?;
 
@@ -775,9 +843,10 @@ where
             y = self.linear2.forward(&y)?;
             x.add(&y)
         } else {
+            let attention_input = MultiHeadAttentionInput::from((x, x, x, x_mask));
             let mut y = self
                 .self_attention
-                .forward(MultiHeadAttentionInput::from((x, x, x, x_mask)))?;
+                .forward_with_cache(attention_input, input.cache)?;
             y = self.dropout1.forward(&y)?;
             let mut x = x.add(&y)?;
             x = self.ln1.forward(&x)?;
@@ -905,6 +974,33 @@ struct TransformerDecoder {
     pub ln: LayerNorm,
 }
 
+impl TransformerDecoder {
+    /// Runs the layers, each with the self attention cache yielded by `caches`.
+    fn forward_layers<'c>(
+        &mut self,
+        x: &Array,
+        memory: &Array,
+        x_mask: Option<&Array>,
+        memory_mask: Option<&Array>,
+        caches: impl Iterator<Item = Option<&'c mut dyn KeyValueCache>>,
+    ) -> Result<Array, Exception> {
+        let mut x = Cow::Borrowed(x);
+
+        for (l, cache) in self.layers.iter_mut().zip(caches) {
+            let layer_input = TransformerDecoderInput {
+                x: &*x,
+                memory,
+                x_mask,
+                memory_mask,
+                cache,
+            };
+            x = Cow::Owned(l.forward(layer_input)?);
+        }
+
+        self.ln.forward(&*x)
+    }
+}
+
 impl<'a, Input> Module<Input> for TransformerDecoder
 where
     Input: Into<TransformerDecoderInput<'a>>,
@@ -914,19 +1010,14 @@ where
 
     fn forward(&mut self, input: Input) -> Result<Self::Output, Self::Error> {
         let input = input.into();
-        let x = input.x;
-        let memory = input.memory;
-        let x_mask = input.x_mask;
-        let memory_mask = input.memory_mask;
-
-        let mut x = Cow::Borrowed(x);
-
-        for l in &mut self.layers {
-            let layer_input = TransformerDecoderInput::from((&*x, memory, x_mask, memory_mask));
-            x = Cow::Owned(l.forward(layer_input)?);
-        }
-
-        self.ln.forward(&*x)
+        let caches = std::iter::repeat_with(|| None);
+        self.forward_layers(
+            input.x,
+            input.memory,
+            input.x_mask,
+            input.memory_mask,
+            caches,
+        )
     }
 
     fn training_mode(&mut self, mode: bool) {
@@ -1070,6 +1161,54 @@ impl Transformer {
 
     /// Default value for `activation`
     pub const DEFAULT_NORM_FIRST: bool = false;
+
+    /// Encodes the source sequence into the memory attended to by the decoder.
+    pub fn encode(&mut self, source: &Array, source_mask: &Array) -> Result<Array, Exception> {
+        self.encoder
+            .forward(TransformerEncoderInput::from((source, source_mask)))
+    }
+
+    /// Decodes the target sequence incrementally with one key-value cache per decoder layer.
+    ///
+    /// Only the new positions of the target are passed in, and the keys and values of the self
+    /// attention are appended to `cache`. A `target_mask` is only needed when more than one
+    /// position is decoded at once, in which case it must cover the cached positions too.
+    ///
+    /// # Params
+    ///
+    /// - `target`: new positions of the target sequence
+    /// - `memory`: output of [`Transformer::encode`]
+    /// - `target_mask`: mask of the self attention
+    /// - `memory_mask`: mask of the cross attention
+    /// - `cache`: caches of the decoder layers, eg. from [`Transformer::make_cache`]
+    pub fn decode<C>(
+        &mut self,
+        target: &Array,
+        memory: &Array,
+        target_mask: Option<&Array>,
+        memory_mask: Option<&Array>,
+        cache: &mut [C],
+    ) -> Result<Array, Exception>
+    where
+        C: KeyValueCache,
+    {
+        if cache.len() != self.decoder.layers.len() {
+            return Err(Exception::custom(format!(
+                "Expected {} caches, got {}",
+                self.decoder.layers.len(),
+                cache.len()
+            )));
+        }
+
+        let caches = cache.iter_mut().map(|c| Some(c as &mut dyn KeyValueCache));
+        self.decoder
+            .forward_layers(target, memory, target_mask, memory_mask, caches)
+    }
+
+    /// Creates an empty [`KvCache`] for each decoder layer.
+    pub fn make_cache(&self) -> Vec<KvCache> {
+        self.decoder.layers.iter().map(|_| KvCache::new()).collect()
+    }
 }
 
 /// Input to the [`Transformer`] module
@@ -1148,3 +1287,171 @@ where
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        module::{FlattenedModuleParam, ModuleParameters},
+        ops::{broadcast_to, indexing::IndexOp, zeros},
+        random::uniform,
+    };
+
+    #[test]
+    fn test_grouped_query_attention() {
+        let mut attention = MultiHeadAttentionBuilder::new(32, 8)
+            .num_kv_heads(2)
+            .build()
+            .unwrap();
+        let params = attention.parameters().flatten();
+        assert_eq!(params["key_proj.weight"].shape(), &[8, 32]);
+        assert_eq!(params["value_proj.weight"].shape(), &[8, 32]);
+
+        let x = uniform::<_, f32>(0.0, 1.0, &[2, 5, 32], None).unwrap();
+        let mask = MultiHeadAttention::create_additive_causal_mask::<f32>(5).unwrap();
+        let y = attention.forward((&x, &x, &x, &mask)).unwrap();
+        assert_eq!(y.shape(), &[2, 5, 32]);
+    }
+
+    #[test]
+    fn test_grouped_query_attention_matches_repeated_heads() {
+        let mut gqa = MultiHeadAttentionBuilder::new(32, 8)
+            .num_kv_heads(2)
+            .build()
+            .unwrap();
+        let mut mha = MultiHeadAttentionBuilder::new(32, 8).build().unwrap();
+
+        // Each of the 2 key and value heads is shared by 4 consecutive query heads
+        let repeat_heads = |weight: &Array| -> Result<Array, Exception> {
+            let weight = weight.reshape(&[2, 1, 4, 32])?;
+            broadcast_to(&weight, &[2, 4, 4, 32])?.reshape(&[32, 32])
+        };
+        let mut params: FlattenedModuleParam = gqa
+            .parameters()
+            .flatten()
+            .into_iter()
+            .map(|(key, value)| (key, value.clone()))
+            .collect();
+        for key in ["key_proj.weight", "value_proj.weight"] {
+            let repeated = repeat_heads(&params[key]).unwrap();
+            params.insert(key.into(), repeated);
+        }
+        mha.update_flattened(params);
+
+        let x = uniform::<_, f32>(0.0, 1.0, &[2, 5, 32], None).unwrap();
+        let mask = MultiHeadAttention::create_additive_causal_mask::<f32>(5).unwrap();
+        let y = gqa.forward((&x, &x, &x, &mask)).unwrap();
+        let expected = mha.forward((&x, &x, &x, &mask)).unwrap();
+        assert!(y
+            .all_close(&expected, 1e-5, 1e-5, None)
+            .unwrap()
+            .item::<bool>());
+    }
+
+    #[test]
+    fn test_rope_attention_with_cache() {
+        let mut attention = MultiHeadAttentionBuilder::new(32, 4)
+            .num_kv_heads(2)
+            .rope(Rope::new(8))
+            .build()
+            .unwrap();
+
+        let x = uniform::<_, f32>(0.0, 1.0, &[1, 5, 32], None).unwrap();
+        let mask = MultiHeadAttention::create_additive_causal_mask::<f32>(5).unwrap();
+        let expected = attention.forward((&x, &x, &x, &mask)).unwrap();
+
+        // Prefill the first 3 positions, then decode the rest one at a time so that the rotary
+        // encoding has to pick up the offset from the cache.
+        let mut cache = KvCache::new();
+        let prefix = x.index((.., 0..3, ..));
+        let prefix_mask = MultiHeadAttention::create_additive_causal_mask::<f32>(3).unwrap();
+        let y = attention
+            .forward_with_cache((&prefix, &prefix, &prefix, &prefix_mask), Some(&mut cache))
+            .unwrap();
+        assert!(y
+            .all_close(&expected.index((.., 0..3, ..)), 1e-5, 1e-5, None)
+            .unwrap()
+            .item::<bool>());
+
+        for i in 3..5 {
+            let token = x.index((.., i..i + 1, ..));
+            let y = attention
+                .forward_with_cache((&token, &token, &token), Some(&mut cache))
+                .unwrap();
+            assert!(y
+                .all_close(&expected.index((.., i..i + 1, ..)), 1e-5, 1e-5, None)
+                .unwrap()
+                .item::<bool>());
+        }
+        assert_eq!(cache.offset(), 5);
+    }
+
+    #[test]
+    fn test_transformer_decode_matches_forward() {
+        crate::random::seed(7).unwrap();
+        let mut transformer = TransformerBuilder::new()
+            .dimensions(16)
+            .num_heads(4)
+            .encoder_layer_count(2)
+            .decoder_layer_count(2)
+            .build()
+            .unwrap();
+
+        let source = uniform::<_, f32>(0.0, 1.0, &[1, 3, 16], None).unwrap();
+        let target = uniform::<_, f32>(0.0, 1.0, &[1, 4, 16], None).unwrap();
+        let source_mask = zeros::<f32>(&[3, 3]).unwrap();
+        let target_mask = MultiHeadAttention::create_additive_causal_mask::<f32>(4).unwrap();
+        let memory_mask = zeros::<f32>(&[1, 3]).unwrap();
+
+        let expected = transformer
+            .forward((
+                &source,
+                &target,
+                &source_mask,
+                &target_mask,
+                &zeros::<f32>(&[4, 3]).unwrap(),
+            ))
+            .unwrap();
+
+        let memory = transformer.encode(&source, &source_mask).unwrap();
+        let mut cache = transformer.make_cache();
+        for i in 0..4 {
+            let token = target.index((.., i..i + 1, ..));
+            let output = transformer
+                .decode(&token, &memory, None, Some(&memory_mask), &mut cache)
+                .unwrap();
+            let expected = expected.index((.., i..i + 1, ..));
+            assert!(output
+                .all_close(&expected, 1e-5, 1e-5, None)
+                .unwrap()
+                .item::<bool>());
+        }
+        assert!(cache.iter().all(|cache| cache.offset() == 4));
+    }
+
+    #[test]
+    fn test_invalid_num_kv_heads() {
+        let result = MultiHeadAttentionBuilder::new(32, 8)
+            .num_kv_heads(3)
+            .build();
+        assert!(matches!(
+            result,
+            Err(MultiHeadAttentionBuildError::InvalidNumKvHeads(3))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_value_dims() {
+        let result = MultiHeadAttentionBuilder::new(32, 8).value_dims(36).build();
+        assert!(matches!(
+            result,
+            Err(MultiHeadAttentionBuildError::InvalidValueDims(36))
+        ));
+
+        let result = MultiHeadAttentionBuilder::new(32, 0).build();
+        assert!(matches!(
+            result,
+            Err(MultiHeadAttentionBuildError::InvalidNumHeads(0))
+        ));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::module::{update_parameters, ModuleParameters};
-use crate::transforms::keyed_value_and_grad;
+use crate::transforms::{keyed_value_and_grad, split_aux};
 use crate::{error::Exception, Array};
 
 use crate::module::FlattenedModuleParam;
@@ -145,6 +145,115 @@ where
     f.into_module_value_and_grad()
 }
 
+/// Helper trait for [`value_and_grad_with_aux`]
+pub trait IntoModuleValueAndGradWithAux<'a, M, Args, Err>
+where
+    M: ModuleParameters + 'a,
+    Args: Clone,
+{
+    /// Computes the value and gradient of the passed function `f(model, args)` with regard to the
+    /// model's trainable parameters, where `f` also returns auxiliary outputs.
+    #[allow(clippy::type_complexity)]
+    fn into_module_value_and_grad_with_aux(
+        self,
+    ) -> impl FnMut(&mut M, Args) -> Result<((Array, Vec<Array>), FlattenedModuleParam), Exception> + 'a;
+}
+
+impl<'a, F, M, Args> IntoModuleValueAndGradWithAux<'a, M, Args, ()> for F
+where
+    M: ModuleParameters + 'a,
+    F: FnMut(&mut M, Args) -> (Array, Vec<Array>) + 'a,
+    Args: Clone,
+{
+    fn into_module_value_and_grad_with_aux(
+        mut self,
+    ) -> impl FnMut(&mut M, Args) -> Result<((Array, Vec<Array>), FlattenedModuleParam), Exception> + 'a
+    {
+        move |model, arrays| {
+            let trainable_parameters = trainable_params(model);
+            let inner = |parameters: FlattenedModuleParam, arrays: Args| -> Vec<Array> {
+                update_parameters(model, parameters.into_iter());
+
+                let (value, aux) = self(model, arrays);
+                std::iter::once(value).chain(aux).collect()
+            };
+            let mut vg = keyed_value_and_grad(inner);
+
+            let (v, g) = vg(trainable_parameters, arrays)?;
+            Ok((split_aux(v), g))
+        }
+    }
+}
+
+impl<'a, F, M, Args> IntoModuleValueAndGradWithAux<'a, M, Args, Exception> for F
+where
+    M: ModuleParameters + 'a,
+    F: FnMut(&mut M, Args) -> Result<(Array, Vec<Array>), Exception> + 'a,
+    Args: Clone,
+{
+    fn into_module_value_and_grad_with_aux(
+        mut self,
+    ) -> impl FnMut(&mut M, Args) -> Result<((Array, Vec<Array>), FlattenedModuleParam), Exception> + 'a
+    {
+        move |model, arrays| {
+            let trainable_parameters = trainable_params(model);
+            let inner =
+                |parameters: FlattenedModuleParam, arrays: Args| -> Result<Vec<Array>, Exception> {
+                    update_parameters(model, parameters.into_iter());
+
+                    let (value, aux) = self(model, arrays)?;
+                    Ok(std::iter::once(value).chain(aux).collect())
+                };
+            let mut vg = keyed_value_and_grad(inner);
+
+            let (v, g) = vg(trainable_parameters, arrays)?;
+            Ok((split_aux(v), g))
+        }
+    }
+}
+
+/// Similar to [`value_and_grad`] but the passed function `f(model, args)` returns a tuple of the
+/// loss and auxiliary outputs, eg. the accuracy, per-token losses or updated hidden states.
+///
+/// The loss must be a scalar. Only the loss is differentiated, and the returned function returns
+/// `((loss, aux), grads)` so that the auxiliary outputs do not need to be computed again.
+///
+/// # Example
+///
+/// ```rust
+/// use mlx_rs::{
+///     error::Exception,
+///     module::Module,
+///     nn::{self, Linear},
+///     random::uniform,
+///     Array,
+/// };
+///
+/// let mut model = Linear::new(2, 2).unwrap();
+/// let x = uniform::<_, f32>(0.0, 1.0, &[4, 2], None).unwrap();
+///
+/// let loss_fn = |model: &mut Linear, x: &Array| -> Result<(Array, Vec<Array>), Exception> {
+///     let y = model.forward(x)?;
+///     let loss = y.square()?.mean(None, None)?;
+///     Ok((loss, vec![y]))
+/// };
+///
+/// let mut vg = nn::value_and_grad_with_aux(loss_fn);
+/// let ((loss, aux), grads) = vg(&mut model, &x).unwrap();
+/// assert_eq!(aux[0].shape(), &[4, 2]);
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn value_and_grad_with_aux<'a, F, M, Args, Err>(
+    f: F,
+) -> impl FnMut(&mut M, Args) -> Result<((Array, Vec<Array>), FlattenedModuleParam), Exception> + 'a
+where
+    M: ModuleParameters + 'a,
+    F: IntoModuleValueAndGradWithAux<'a, M, Args, Err>,
+    Args: Clone,
+{
+    f.into_module_value_and_grad_with_aux()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::module::Module;
@@ -248,4 +357,29 @@ mod tests {
         let err = result.unwrap_err();
         assert!(!err.what().contains("non-zero value"))
     }
+
+    #[test]
+    fn test_value_and_grad_with_aux() {
+        let mut model = Linear::new(2, 2).unwrap();
+        let x = crate::random::uniform::<_, f32>(1.0, 2.0, &[2, 2], None).unwrap();
+
+        let loss = |model: &mut Linear, x: &Array| -> Result<(Array, Vec<Array>), Exception> {
+            let y = model.forward(x)?;
+            Ok((y.sum(None, None)?, vec![y]))
+        };
+        let reference_loss = |model: &mut Linear, x: &Array| -> Result<Array, Exception> {
+            model.forward(x)?.sum(None, None)
+        };
+
+        let ((v, aux), g) = nn::value_and_grad_with_aux(loss)(&mut model, &x).unwrap();
+        let (expected_v, expected_g) = nn::value_and_grad(reference_loss)(&mut model, &x).unwrap();
+
+        assert_eq!(v, expected_v);
+        assert_eq!(aux.len(), 1);
+        assert_eq!(aux[0], model.forward(&x).unwrap());
+
+        // The auxiliary outputs do not contribute to the gradient
+        assert_eq!(g["weight"], expected_g["weight"]);
+        assert_eq!(g["bias"], expected_g["bias"]);
+    }
 }
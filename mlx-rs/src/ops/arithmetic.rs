@@ -1339,6 +1339,57 @@ pub fn block_masked_mm_device<'mo, 'lhs, 'rhs>(
     }
 }
 
+/// Matrix multiplication with matrix-level gather.
+///
+/// Performs a gather of the operands with the given indices followed by a (possibly batched)
+/// matrix multiplication of the gathered operands. The indices index the batch dimensions of the
+/// operands, which are broadcast against the indices. This is useful for the experts of a
+/// mixture of experts layer, where each token is multiplied by the weight of its own expert.
+///
+/// See the [python API docs](
+/// https://ml-explore.github.io/mlx/build/html/python/_autosummary/mlx.core.gather_mm.html
+/// ) for more information.
+///
+/// # Params
+///
+/// - `a`: input array
+/// - `b`: input array
+/// - `lhs_indices`: integer indices for `a`. Default to the batch indices of `a`
+/// - `rhs_indices`: integer indices for `b`. Default to the batch indices of `b`
+#[generate_macro]
+#[default_device]
+pub fn gather_mm_device<'lhs, 'rhs>(
+    a: impl AsRef<Array>,
+    b: impl AsRef<Array>,
+    #[optional] lhs_indices: impl Into<Option<&'lhs Array>>,
+    #[optional] rhs_indices: impl Into<Option<&'rhs Array>>,
+    #[optional] stream: impl AsRef<Stream>,
+) -> Result<Array> {
+    let a_ptr = a.as_ref().as_ptr();
+    let b_ptr = b.as_ref().as_ptr();
+    unsafe {
+        let lhs_indices_ptr = lhs_indices
+            .into()
+            .map(|i| i.as_ptr())
+            .unwrap_or(mlx_sys::mlx_array_new());
+        let rhs_indices_ptr = rhs_indices
+            .into()
+            .map(|i| i.as_ptr())
+            .unwrap_or(mlx_sys::mlx_array_new());
+
+        Array::try_from_op(|res| {
+            mlx_sys::mlx_gather_mm(
+                res,
+                a_ptr,
+                b_ptr,
+                lhs_indices_ptr,
+                rhs_indices_ptr,
+                stream.as_ref().as_ptr(),
+            )
+        })
+    }
+}
+
 /// Matrix multiplication with addition and optional scaling.
 ///
 /// Perform the (possibly batched) matrix multiplication of two arrays and add to the result with
@@ -1491,6 +1542,7 @@ mod tests {
     use super::*;
     use crate::{
         array, complex64,
+        ops::indexing::IndexOp,
         ops::{all_close, arange, broadcast_to, eye, full, linspace, ones, reshape, split_equal},
         transforms::eval,
         Dtype,
@@ -2858,4 +2910,22 @@ mod tests {
         eval(out_holder.iter()).unwrap();
         assert_eq!(out_holder[0].item::<f32>(), 1.0);
     }
+
+    #[test]
+    fn test_gather_mm() {
+        let a = crate::random::normal::<f32>(&[2, 3, 4], None, None, None).unwrap();
+        let b = crate::random::normal::<f32>(&[5, 4, 6], None, None, None).unwrap();
+        let lhs_indices = array!([1u32, 0, 1]);
+        let rhs_indices = array!([4u32, 2, 0]);
+
+        let out = gather_mm(&a, &b, &lhs_indices, &rhs_indices).unwrap();
+        assert_eq!(out.shape(), &[3, 3, 6]);
+
+        for (i, (lhs, rhs)) in [(1, 4), (0, 2), (1, 0)].into_iter().enumerate() {
+            let expected = a.index(lhs).matmul(b.index(rhs)).unwrap();
+            assert!(all_close(out.index(i as i32), &expected, None, 1e-5, None)
+                .unwrap()
+                .item::<bool>());
+        }
+    }
 }
@@ -0,0 +1,879 @@
+//! Reading and writing of the [GGUF](https://github.com/ggerganov/ggml/blob/master/docs/gguf.md)
+//! file format.
+//!
+//! Tensors with a plain data type are loaded as is. Tensors quantized with `Q4_0`, `Q4_1` or `Q8_0`
+//! are either dequantized or converted to the layout of [`quantize`](crate::ops::quantize) with a
+//! group size of 32. Loading a file with a tensor of any other type returns
+//! [`IoError::UnsupportedGgufType`].
+
+use std::{
+    collections::HashMap,
+    ffi::c_void,
+    fmt,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use half::f16;
+
+use mlx_internal_macros::default_device;
+
+use crate::{error::IoError, ops::dequantize_device, Array, Dtype, Stream, StreamOrDevice};
+
+use super::{check_file_extension, dtype_size, with_array_bytes};
+
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+const GGUF_VERSION: u32 = 3;
+
+/// Metadata key of the alignment of the tensor data.
+const ALIGNMENT_KEY: &str = "general.alignment";
+const DEFAULT_ALIGNMENT: u64 = 32;
+
+/// Number of elements in a block of the supported quantized types.
+const QUANT_BLOCK_SIZE: usize = 32;
+
+// Metadata value types
+const TYPE_UINT8: u32 = 0;
+const TYPE_INT8: u32 = 1;
+const TYPE_UINT16: u32 = 2;
+const TYPE_INT16: u32 = 3;
+const TYPE_UINT32: u32 = 4;
+const TYPE_INT32: u32 = 5;
+const TYPE_FLOAT32: u32 = 6;
+const TYPE_BOOL: u32 = 7;
+const TYPE_STRING: u32 = 8;
+const TYPE_ARRAY: u32 = 9;
+const TYPE_UINT64: u32 = 10;
+const TYPE_INT64: u32 = 11;
+const TYPE_FLOAT64: u32 = 12;
+
+// Tensor types, ie. `ggml_type`
+const GGML_F32: u32 = 0;
+const GGML_F16: u32 = 1;
+const GGML_Q4_0: u32 = 2;
+const GGML_Q4_1: u32 = 3;
+const GGML_Q8_0: u32 = 8;
+const GGML_I8: u32 = 24;
+const GGML_I16: u32 = 25;
+const GGML_I32: u32 = 26;
+const GGML_I64: u32 = 27;
+const GGML_F64: u32 = 28;
+const GGML_BF16: u32 = 30;
+
+/// Value of a GGUF metadata key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GgufValue {
+    /// u8
+    Uint8(u8),
+
+    /// i8
+    Int8(i8),
+
+    /// u16
+    Uint16(u16),
+
+    /// i16
+    Int16(i16),
+
+    /// u32
+    Uint32(u32),
+
+    /// i32
+    Int32(i32),
+
+    /// f32
+    Float32(f32),
+
+    /// bool
+    Bool(bool),
+
+    /// UTF-8 string
+    String(String),
+
+    /// Array of values of the same type
+    Array(Vec<GgufValue>),
+
+    /// u64
+    Uint64(u64),
+
+    /// i64
+    Int64(i64),
+
+    /// f64
+    Float64(f64),
+}
+
+impl GgufValue {
+    fn value_type(&self) -> u32 {
+        match self {
+            GgufValue::Uint8(_) => TYPE_UINT8,
+            GgufValue::Int8(_) => TYPE_INT8,
+            GgufValue::Uint16(_) => TYPE_UINT16,
+            GgufValue::Int16(_) => TYPE_INT16,
+            GgufValue::Uint32(_) => TYPE_UINT32,
+            GgufValue::Int32(_) => TYPE_INT32,
+            GgufValue::Float32(_) => TYPE_FLOAT32,
+            GgufValue::Bool(_) => TYPE_BOOL,
+            GgufValue::String(_) => TYPE_STRING,
+            GgufValue::Array(_) => TYPE_ARRAY,
+            GgufValue::Uint64(_) => TYPE_UINT64,
+            GgufValue::Int64(_) => TYPE_INT64,
+            GgufValue::Float64(_) => TYPE_FLOAT64,
+        }
+    }
+}
+
+impl fmt::Display for GgufValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GgufValue::Uint8(v) => write!(f, "{}", v),
+            GgufValue::Int8(v) => write!(f, "{}", v),
+            GgufValue::Uint16(v) => write!(f, "{}", v),
+            GgufValue::Int16(v) => write!(f, "{}", v),
+            GgufValue::Uint32(v) => write!(f, "{}", v),
+            GgufValue::Int32(v) => write!(f, "{}", v),
+            GgufValue::Float32(v) => write!(f, "{}", v),
+            GgufValue::Bool(v) => write!(f, "{}", v),
+            GgufValue::String(v) => write!(f, "{}", v),
+            GgufValue::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            GgufValue::Uint64(v) => write!(f, "{}", v),
+            GgufValue::Int64(v) => write!(f, "{}", v),
+            GgufValue::Float64(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl From<&str> for GgufValue {
+    fn from(value: &str) -> Self {
+        GgufValue::String(value.to_string())
+    }
+}
+
+impl From<String> for GgufValue {
+    fn from(value: String) -> Self {
+        GgufValue::String(value)
+    }
+}
+
+impl From<u32> for GgufValue {
+    fn from(value: u32) -> Self {
+        GgufValue::Uint32(value)
+    }
+}
+
+impl From<i32> for GgufValue {
+    fn from(value: i32) -> Self {
+        GgufValue::Int32(value)
+    }
+}
+
+impl From<u64> for GgufValue {
+    fn from(value: u64) -> Self {
+        GgufValue::Uint64(value)
+    }
+}
+
+impl From<f32> for GgufValue {
+    fn from(value: f32) -> Self {
+        GgufValue::Float32(value)
+    }
+}
+
+impl From<bool> for GgufValue {
+    fn from(value: bool) -> Self {
+        GgufValue::Bool(value)
+    }
+}
+
+fn invalid(message: impl fmt::Display) -> IoError {
+    IoError::InvalidGguf(message.to_string())
+}
+
+fn align(offset: u64, alignment: u64) -> u64 {
+    offset.div_ceil(alignment) * alignment
+}
+
+fn alignment(metadata: &HashMap<String, GgufValue>) -> u64 {
+    match metadata.get(ALIGNMENT_KEY) {
+        Some(GgufValue::Uint32(alignment)) if *alignment > 0 => *alignment as u64,
+        _ => DEFAULT_ALIGNMENT,
+    }
+}
+
+fn plain_dtype(ggml_type: u32) -> Option<Dtype> {
+    match ggml_type {
+        GGML_F32 => Some(Dtype::Float32),
+        GGML_F16 => Some(Dtype::Float16),
+        GGML_BF16 => Some(Dtype::Bfloat16),
+        GGML_F64 => Some(Dtype::Float64),
+        GGML_I8 => Some(Dtype::Int8),
+        GGML_I16 => Some(Dtype::Int16),
+        GGML_I32 => Some(Dtype::Int32),
+        GGML_I64 => Some(Dtype::Int64),
+        _ => None,
+    }
+}
+
+fn ggml_type(dtype: Dtype) -> Option<u32> {
+    match dtype {
+        Dtype::Float32 => Some(GGML_F32),
+        Dtype::Float16 => Some(GGML_F16),
+        Dtype::Bfloat16 => Some(GGML_BF16),
+        Dtype::Float64 => Some(GGML_F64),
+        Dtype::Int8 => Some(GGML_I8),
+        Dtype::Int16 => Some(GGML_I16),
+        Dtype::Int32 => Some(GGML_I32),
+        Dtype::Int64 => Some(GGML_I64),
+        _ => None,
+    }
+}
+
+/// Returns the size in bytes of a block and the number of bits per element of the MLX layout.
+fn quantized_block(ggml_type: u32) -> Option<(usize, i32)> {
+    match ggml_type {
+        GGML_Q4_0 => Some((18, 4)),
+        GGML_Q4_1 => Some((20, 4)),
+        GGML_Q8_0 => Some((34, 8)),
+        _ => None,
+    }
+}
+
+fn is_supported(ggml_type: u32) -> bool {
+    plain_dtype(ggml_type).is_some() || quantized_block(ggml_type).is_some()
+}
+
+/// Returns the size in bytes of a tensor of a supported type, or `None` if it overflows.
+fn tensor_nbytes(ggml_type: u32, num_elements: usize) -> Option<usize> {
+    match plain_dtype(ggml_type) {
+        Some(dtype) => num_elements.checked_mul(dtype_size(dtype)),
+        None => {
+            let (block_bytes, _) = quantized_block(ggml_type)?;
+            (num_elements / QUANT_BLOCK_SIZE).checked_mul(block_bytes)
+        }
+    }
+}
+
+struct GgufReader<R> {
+    reader: R,
+}
+
+impl<R: Read> GgufReader<R> {
+    fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], IoError> {
+        let mut buf = [0; N];
+        self.reader.read_exact(&mut buf).map_err(invalid)?;
+        Ok(buf)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, IoError> {
+        self.read_bytes().map(u32::from_le_bytes)
+    }
+
+    fn read_u64(&mut self) -> Result<u64, IoError> {
+        self.read_bytes().map(u64::from_le_bytes)
+    }
+
+    fn read_string(&mut self) -> Result<String, IoError> {
+        let len = self.read_u64()?;
+        let mut buf = Vec::new();
+        (&mut self.reader)
+            .take(len)
+            .read_to_end(&mut buf)
+            .map_err(invalid)?;
+        if buf.len() as u64 != len {
+            return Err(invalid("unexpected end of file"));
+        }
+        String::from_utf8(buf).map_err(invalid)
+    }
+
+    fn read_value(&mut self, value_type: u32) -> Result<GgufValue, IoError> {
+        let value = match value_type {
+            TYPE_UINT8 => GgufValue::Uint8(u8::from_le_bytes(self.read_bytes()?)),
+            TYPE_INT8 => GgufValue::Int8(i8::from_le_bytes(self.read_bytes()?)),
+            TYPE_UINT16 => GgufValue::Uint16(u16::from_le_bytes(self.read_bytes()?)),
+            TYPE_INT16 => GgufValue::Int16(i16::from_le_bytes(self.read_bytes()?)),
+            TYPE_UINT32 => GgufValue::Uint32(self.read_u32()?),
+            TYPE_INT32 => GgufValue::Int32(i32::from_le_bytes(self.read_bytes()?)),
+            TYPE_FLOAT32 => GgufValue::Float32(f32::from_le_bytes(self.read_bytes()?)),
+            TYPE_BOOL => GgufValue::Bool(self.read_bytes::<1>()?[0] != 0),
+            TYPE_STRING => GgufValue::String(self.read_string()?),
+            TYPE_ARRAY => {
+                let element_type = self.read_u32()?;
+                let len = self.read_u64()?;
+                let values = (0..len)
+                    .map(|_| self.read_value(element_type))
+                    .collect::<Result<Vec<_>, _>>()?;
+                GgufValue::Array(values)
+            }
+            TYPE_UINT64 => GgufValue::Uint64(self.read_u64()?),
+            TYPE_INT64 => GgufValue::Int64(i64::from_le_bytes(self.read_bytes()?)),
+            TYPE_FLOAT64 => GgufValue::Float64(f64::from_le_bytes(self.read_bytes()?)),
+            _ => return Err(invalid(format!("unknown metadata type {}", value_type))),
+        };
+        Ok(value)
+    }
+}
+
+struct GgufWriter<W> {
+    writer: W,
+    position: u64,
+}
+
+impl<W: Write> GgufWriter<W> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), IoError> {
+        self.writer.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn write_u32(&mut self, value: u32) -> Result<(), IoError> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, value: u64) -> Result<(), IoError> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    fn write_string(&mut self, value: &str) -> Result<(), IoError> {
+        self.write_u64(value.len() as u64)?;
+        self.write_bytes(value.as_bytes())
+    }
+
+    fn write_value(&mut self, value: &GgufValue) -> Result<(), IoError> {
+        match value {
+            GgufValue::Uint8(v) => self.write_bytes(&v.to_le_bytes()),
+            GgufValue::Int8(v) => self.write_bytes(&v.to_le_bytes()),
+            GgufValue::Uint16(v) => self.write_bytes(&v.to_le_bytes()),
+            GgufValue::Int16(v) => self.write_bytes(&v.to_le_bytes()),
+            GgufValue::Uint32(v) => self.write_u32(*v),
+            GgufValue::Int32(v) => self.write_bytes(&v.to_le_bytes()),
+            GgufValue::Float32(v) => self.write_bytes(&v.to_le_bytes()),
+            GgufValue::Bool(v) => self.write_bytes(&[*v as u8]),
+            GgufValue::String(v) => self.write_string(v),
+            GgufValue::Array(values) => {
+                let element_type = values.first().map_or(TYPE_UINT8, GgufValue::value_type);
+                if values.iter().any(|v| v.value_type() != element_type) {
+                    return Err(invalid(
+                        "the elements of a metadata array must have the same type",
+                    ));
+                }
+
+                self.write_u32(element_type)?;
+                self.write_u64(values.len() as u64)?;
+                values.iter().try_for_each(|v| self.write_value(v))
+            }
+            GgufValue::Uint64(v) => self.write_u64(*v),
+            GgufValue::Int64(v) => self.write_bytes(&v.to_le_bytes()),
+            GgufValue::Float64(v) => self.write_bytes(&v.to_le_bytes()),
+        }
+    }
+
+    fn pad(&mut self, alignment: u64) -> Result<(), IoError> {
+        let padding = align(self.position, alignment) - self.position;
+        self.write_bytes(&vec![0; padding as usize])
+    }
+}
+
+struct TensorInfo {
+    name: String,
+    shape: Vec<i32>,
+    ggml_type: u32,
+    offset: u64,
+}
+
+/// Converts quantized blocks to the layout of [`quantize`](crate::ops::quantize), ie. a packed
+/// `uint32` weight with `float16` scales and biases for groups of 32 elements.
+fn unpack_quantized(
+    ggml_type: u32,
+    data: &[u8],
+    shape: &[i32],
+) -> Result<(Array, Array, Array, i32), IoError> {
+    let (block_bytes, bits) =
+        quantized_block(ggml_type).ok_or_else(|| invalid("not a quantized type"))?;
+    let last_dim = match shape.last() {
+        Some(dim) if *dim as usize % QUANT_BLOCK_SIZE == 0 => *dim as usize,
+        _ => {
+            return Err(invalid(
+                "the last dimension must be a multiple of the block size",
+            ))
+        }
+    };
+
+    let num_blocks = data.len() / block_bytes;
+    let mut weight = Vec::with_capacity(num_blocks * QUANT_BLOCK_SIZE * bits as usize / 8);
+    let mut scales = Vec::with_capacity(num_blocks);
+    let mut biases = Vec::with_capacity(num_blocks);
+
+    for block in data.chunks_exact(block_bytes) {
+        let scale = f16::from_le_bytes([block[0], block[1]]);
+        scales.push(scale);
+
+        match ggml_type {
+            GGML_Q4_0 | GGML_Q4_1 => {
+                let (bias, qs) = if ggml_type == GGML_Q4_0 {
+                    (f16::from_f32(-8.0 * scale.to_f32()), &block[2..])
+                } else {
+                    (f16::from_le_bytes([block[2], block[3]]), &block[4..])
+                };
+                biases.push(bias);
+
+                // GGML stores the elements `j` and `j + 16` in the low and high bits of the byte
+                // `j`, while MLX packs consecutive elements starting from the low bits.
+                let element = |i: usize| {
+                    if i < QUANT_BLOCK_SIZE / 2 {
+                        qs[i] & 0x0F
+                    } else {
+                        qs[i - QUANT_BLOCK_SIZE / 2] >> 4
+                    }
+                };
+                weight.extend(
+                    (0..QUANT_BLOCK_SIZE)
+                        .step_by(2)
+                        .map(|i| element(i) | (element(i + 1) << 4)),
+                );
+            }
+            _ => {
+                // GGML stores signed values while MLX stores unsigned values with a bias
+                biases.push(f16::from_f32(-128.0 * scale.to_f32()));
+                weight.extend(block[2..].iter().map(|q| (*q as i8 as i16 + 128) as u8));
+            }
+        }
+    }
+
+    let mut weight_shape = shape.to_vec();
+    let mut group_shape = shape.to_vec();
+    let last = shape.len() - 1;
+    weight_shape[last] = (last_dim * bits as usize / 32) as i32;
+    group_shape[last] = (last_dim / QUANT_BLOCK_SIZE) as i32;
+
+    let weight = unsafe {
+        Array::from_raw_data(
+            weight.as_ptr() as *const c_void,
+            &weight_shape,
+            Dtype::Uint32,
+        )
+    };
+    let scales = Array::from_slice(&scales, &group_shape);
+    let biases = Array::from_slice(&biases, &group_shape);
+
+    Ok((weight, scales, biases, bits))
+}
+
+#[allow(clippy::type_complexity)]
+fn read_gguf(
+    path: &Path,
+    dequantize_blocks: bool,
+    stream: &Stream,
+) -> Result<(HashMap<String, Array>, HashMap<String, GgufValue>), IoError> {
+    if !path.is_file() {
+        return Err(IoError::NotFile);
+    }
+    check_file_extension(path, "gguf")?;
+
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = GgufReader {
+        reader: BufReader::new(file),
+    };
+
+    if &reader.read_bytes::<4>()? != GGUF_MAGIC {
+        return Err(invalid("invalid magic number"));
+    }
+
+    // Version 1 uses 32-bit counts and lengths and is not supported
+    let version = reader.read_u32()?;
+    if !(2..=GGUF_VERSION).contains(&version) {
+        return Err(invalid(format!("unsupported version {}", version)));
+    }
+
+    let tensor_count = reader.read_u64()?;
+    let metadata_count = reader.read_u64()?;
+
+    let mut metadata = HashMap::new();
+    for _ in 0..metadata_count {
+        let key = reader.read_string()?;
+        let value_type = reader.read_u32()?;
+        let value = reader.read_value(value_type)?;
+        metadata.insert(key, value);
+    }
+
+    let mut tensor_infos = Vec::new();
+    for _ in 0..tensor_count {
+        let name = reader.read_string()?;
+        let ndim = reader.read_u32()?;
+        // GGUF lists the dimensions from the innermost to the outermost
+        let mut shape = (0..ndim)
+            .map(|_| {
+                let dim = reader.read_u64()?;
+                i32::try_from(dim)
+                    .map_err(|_| invalid(format!("dimension {} of {:?} is too large", dim, name)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        shape.reverse();
+        let ggml_type = reader.read_u32()?;
+        if !is_supported(ggml_type) {
+            return Err(IoError::UnsupportedGgufType { name, ggml_type });
+        }
+        let offset = reader.read_u64()?;
+
+        tensor_infos.push(TensorInfo {
+            name,
+            shape,
+            ggml_type,
+            offset,
+        });
+    }
+
+    let position = reader.reader.stream_position().map_err(invalid)?;
+    let data_start = align(position, alignment(&metadata));
+
+    let mut arrays = HashMap::new();
+    for info in tensor_infos {
+        // The dimensions are checked to be non-negative when they are read
+        let too_large = || invalid(format!("tensor {:?} is too large", info.name));
+        let num_elements = info
+            .shape
+            .iter()
+            .try_fold(1usize, |acc, dim| acc.checked_mul(*dim as usize))
+            .ok_or_else(too_large)?;
+        let nbytes = tensor_nbytes(info.ggml_type, num_elements).ok_or_else(too_large)?;
+
+        let start = data_start.checked_add(info.offset).ok_or_else(too_large)?;
+        if start.saturating_add(nbytes as u64) > file_len {
+            return Err(invalid(format!(
+                "unexpected end of data for {:?}",
+                info.name
+            )));
+        }
+
+        let mut data = vec![0; nbytes];
+        reader
+            .reader
+            .seek(SeekFrom::Start(start))
+            .map_err(invalid)?;
+        reader.reader.read_exact(&mut data).map_err(invalid)?;
+
+        if let Some(dtype) = plain_dtype(info.ggml_type) {
+            let array =
+                unsafe { Array::from_raw_data(data.as_ptr() as *const c_void, &info.shape, dtype) };
+            arrays.insert(info.name, array);
+            continue;
+        }
+
+        let (weight, scales, biases, bits) = unpack_quantized(info.ggml_type, &data, &info.shape)?;
+        if dequantize_blocks {
+            let group_size = QUANT_BLOCK_SIZE as i32;
+            let array = dequantize_device(&weight, &scales, &biases, group_size, bits, stream)?;
+            arrays.insert(info.name, array);
+        } else {
+            let prefix = info.name.strip_suffix(".weight").unwrap_or(&info.name);
+            arrays.insert(format!("{}.scales", prefix), scales);
+            arrays.insert(format!("{}.biases", prefix), biases);
+            arrays.insert(info.name, weight);
+        }
+    }
+
+    Ok((arrays, metadata))
+}
+
+impl Array {
+    /// Load dictionary of ``MLXArray`` and the metadata from a `gguf` file.
+    ///
+    /// Tensors quantized with `Q4_0`, `Q4_1` or `Q8_0` are dequantized to `float16`. Other
+    /// quantized types are not supported and return [`IoError::UnsupportedGgufType`].
+    ///
+    /// # Params
+    ///
+    /// - path: path of file to load
+    /// - stream: stream or device to evaluate on
+    #[allow(clippy::type_complexity)]
+    #[default_device]
+    pub fn load_gguf_device(
+        path: impl AsRef<Path>,
+        stream: impl AsRef<Stream>,
+    ) -> Result<(HashMap<String, Array>, HashMap<String, GgufValue>), IoError> {
+        read_gguf(path.as_ref(), true, stream.as_ref())
+    }
+
+    /// Load dictionary of ``MLXArray`` and the metadata from a `gguf` file, keeping the quantized
+    /// tensors quantized.
+    ///
+    /// A tensor quantized with `Q4_0`, `Q4_1` or `Q8_0` is converted to the layout of
+    /// [`quantize`](crate::ops::quantize) with a group size of 32 and 4 or 8 bits. The packed
+    /// weight keeps the name of the tensor, and the scales and biases are named after it with the
+    /// `.weight` suffix replaced by `.scales` and `.biases`, which matches the parameters of
+    /// [`QuantizedLinear`](crate::nn::QuantizedLinear). Other quantized types are not supported
+    /// and return [`IoError::UnsupportedGgufType`].
+    ///
+    /// # Params
+    ///
+    /// - path: path of file to load
+    /// - stream: stream or device to evaluate on
+    #[allow(clippy::type_complexity)]
+    #[default_device]
+    pub fn load_gguf_quantized_device(
+        path: impl AsRef<Path>,
+        stream: impl AsRef<Stream>,
+    ) -> Result<(HashMap<String, Array>, HashMap<String, GgufValue>), IoError> {
+        read_gguf(path.as_ref(), false, stream.as_ref())
+    }
+
+    /// Save dictionary of arrays and metadata in `gguf` format.
+    ///
+    /// Only the floating point and signed integer data types are supported. The tensors are saved
+    /// unquantized.
+    ///
+    /// # Params
+    ///
+    /// - arrays: arrays to save
+    /// - metadata: metadata to save
+    /// - path: path of file to save
+    pub fn save_gguf<I, S, V>(
+        arrays: I,
+        metadata: &HashMap<String, GgufValue>,
+        path: impl AsRef<Path>,
+    ) -> Result<(), IoError>
+    where
+        I: IntoIterator<Item = (S, V)>,
+        S: AsRef<str>,
+        V: AsRef<Array>,
+    {
+        let path = path.as_ref();
+        check_file_extension(path, "gguf")?;
+
+        let alignment = alignment(metadata);
+        let mut arrays: Vec<_> = arrays.into_iter().collect();
+        arrays.sort_by(|a, b| a.0.as_ref().cmp(b.0.as_ref()));
+
+        let mut tensor_types = Vec::with_capacity(arrays.len());
+        for (_, array) in &arrays {
+            let array = array.as_ref();
+            let dtype = array.dtype();
+            tensor_types.push(ggml_type(dtype).ok_or(IoError::UnsupportedDtype(dtype))?);
+            array.eval()?;
+        }
+
+        let file = File::create(path)?;
+        let mut writer = GgufWriter {
+            writer: BufWriter::new(file),
+            position: 0,
+        };
+
+        writer.write_bytes(GGUF_MAGIC)?;
+        writer.write_u32(GGUF_VERSION)?;
+        writer.write_u64(arrays.len() as u64)?;
+        writer.write_u64(metadata.len() as u64)?;
+
+        let mut keys: Vec<_> = metadata.keys().collect();
+        keys.sort();
+        for key in keys {
+            let value = &metadata[key];
+            writer.write_string(key)?;
+            writer.write_u32(value.value_type())?;
+            writer.write_value(value)?;
+        }
+
+        let mut offset = 0;
+        for ((key, array), tensor_type) in arrays.iter().zip(&tensor_types) {
+            let array = array.as_ref();
+            writer.write_string(key.as_ref())?;
+            writer.write_u32(array.ndim() as u32)?;
+            for dim in array.shape().iter().rev() {
+                writer.write_u64(*dim as u64)?;
+            }
+            writer.write_u32(*tensor_type)?;
+            writer.write_u64(offset)?;
+
+            offset = align(offset + array.nbytes() as u64, alignment);
+        }
+
+        writer.pad(alignment)?;
+        for (_, array) in &arrays {
+            with_array_bytes(array.as_ref(), |bytes| writer.write_bytes(bytes))??;
+            writer.pad(alignment)?;
+        }
+
+        writer.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array;
+
+    /// Writes a `gguf` file with a single tensor of shape `[1, 32]` made of one raw block.
+    fn write_single_block(path: &Path, ggml_type: u32, block: &[u8]) {
+        write_single_tensor(path, ggml_type, &[32, 1], block);
+    }
+
+    /// Writes a `gguf` file with a single tensor whose dimensions are listed from the innermost to
+    /// the outermost.
+    fn write_single_tensor(path: &Path, ggml_type: u32, dims: &[u64], data: &[u8]) {
+        let file = File::create(path).unwrap();
+        let mut writer = GgufWriter {
+            writer: BufWriter::new(file),
+            position: 0,
+        };
+
+        writer.write_bytes(GGUF_MAGIC).unwrap();
+        writer.write_u32(GGUF_VERSION).unwrap();
+        writer.write_u64(1).unwrap();
+        writer.write_u64(0).unwrap();
+
+        writer.write_string("blk.0.weight").unwrap();
+        writer.write_u32(dims.len() as u32).unwrap();
+        for dim in dims {
+            writer.write_u64(*dim).unwrap();
+        }
+        writer.write_u32(ggml_type).unwrap();
+        writer.write_u64(0).unwrap();
+
+        writer.pad(DEFAULT_ALIGNMENT).unwrap();
+        writer.write_bytes(data).unwrap();
+        writer.writer.flush().unwrap();
+    }
+
+    #[test]
+    fn test_save_and_load_gguf() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("test.gguf");
+
+        let mut arrays = HashMap::new();
+        arrays.insert("a", Array::ones::<f32>(&[2, 3]).unwrap());
+        arrays.insert("b", Array::zeros::<i32>(&[5]).unwrap());
+        arrays.insert(
+            "c",
+            Array::ones::<f32>(&[3])
+                .unwrap()
+                .as_dtype(Dtype::Float16)
+                .unwrap(),
+        );
+
+        let mut metadata = HashMap::new();
+        metadata.insert("general.name".to_string(), GgufValue::from("test"));
+        metadata.insert("test.count".to_string(), GgufValue::from(3u32));
+        metadata.insert(
+            "test.tokens".to_string(),
+            GgufValue::Array(vec!["a".into(), "b".into()]),
+        );
+
+        Array::save_gguf(&arrays, &metadata, &path).unwrap();
+        let (loaded_arrays, loaded_metadata) = Array::load_gguf(&path).unwrap();
+
+        assert_eq!(loaded_metadata, metadata);
+        assert_eq!(loaded_arrays.len(), 3);
+        for (key, array) in arrays {
+            assert_eq!(loaded_arrays[key].dtype(), array.dtype());
+            assert_eq!(loaded_arrays[key], array);
+        }
+    }
+
+    #[test]
+    fn test_load_gguf_q8_0() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("test.gguf");
+
+        let mut block = f16::from_f32(0.5).to_le_bytes().to_vec();
+        block.extend((-16i8..16).map(|q| q as u8));
+        write_single_block(&path, GGML_Q8_0, &block);
+
+        let expected: Vec<f32> = (-16..16).map(|q| q as f32 * 0.5).collect();
+        let expected = Array::from_slice(&expected, &[1, 32]);
+
+        let (arrays, _) = Array::load_gguf(&path).unwrap();
+        let dequantized = arrays["blk.0.weight"].as_dtype(Dtype::Float32).unwrap();
+        assert_eq!(dequantized, expected);
+
+        let (arrays, _) = Array::load_gguf_quantized(&path).unwrap();
+        assert_eq!(arrays["blk.0.weight"].shape(), &[1, 8]);
+        assert_eq!(arrays["blk.0.scales"].shape(), &[1, 1]);
+        assert_eq!(
+            arrays["blk.0.biases"],
+            array!([[-64.0]]).as_dtype(Dtype::Float16).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_load_gguf_q4_0() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("test.gguf");
+
+        // The element `j` is `j - 8` and the element `j + 16` is `7 - j`
+        let mut block = f16::from_f32(1.0).to_le_bytes().to_vec();
+        block.extend((0u8..16).map(|j| j | ((15 - j) << 4)));
+        write_single_block(&path, GGML_Q4_0, &block);
+
+        let expected: Vec<f32> = (0..16)
+            .map(|j| j as f32 - 8.0)
+            .chain((0..16).map(|j| 7.0 - j as f32))
+            .collect();
+        let expected = Array::from_slice(&expected, &[1, 32]);
+
+        let (arrays, _) = Array::load_gguf(&path).unwrap();
+        let dequantized = arrays["blk.0.weight"].as_dtype(Dtype::Float32).unwrap();
+        assert_eq!(dequantized, expected);
+
+        let (arrays, _) = Array::load_gguf_quantized(&path).unwrap();
+        assert_eq!(arrays["blk.0.weight"].shape(), &[1, 4]);
+        assert_eq!(arrays["blk.0.weight"].dtype(), Dtype::Uint32);
+    }
+
+    #[test]
+    fn test_load_gguf_invalid_shape() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("test.gguf");
+
+        // The dimension does not fit in an `i32`
+        write_single_tensor(&path, GGML_F32, &[1 << 31], &[0; 4]);
+        let result = Array::load_gguf(&path);
+        assert!(matches!(result, Err(IoError::InvalidGguf(_))));
+
+        // The number of bytes overflows
+        let dims = [i32::MAX as u64; 4];
+        write_single_tensor(&path, GGML_F32, &dims, &[0; 4]);
+        let result = Array::load_gguf(&path);
+        assert!(matches!(result, Err(IoError::InvalidGguf(_))));
+
+        // The file is shorter than the tensor
+        write_single_tensor(&path, GGML_F32, &[1 << 20], &[0; 4]);
+        let result = Array::load_gguf(&path);
+        assert!(matches!(result, Err(IoError::InvalidGguf(_))));
+    }
+
+    #[test]
+    fn test_load_gguf_unsupported_type() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("test.gguf");
+
+        // `Q4_K` uses super-blocks of 256 elements and is not supported
+        const GGML_Q4_K: u32 = 12;
+        write_single_tensor(&path, GGML_Q4_K, &[256, 1], &[0; 144]);
+
+        let result = Array::load_gguf_device(&path, StreamOrDevice::cpu());
+        assert!(matches!(
+            result,
+            Err(IoError::UnsupportedGgufType { ref name, ggml_type: GGML_Q4_K })
+                if name == "blk.0.weight"
+        ));
+
+        let result = Array::load_gguf_quantized(&path);
+        assert!(matches!(
+            result,
+            Err(IoError::UnsupportedGgufType {
+                ggml_type: GGML_Q4_K,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+//! Memory-mapped `safetensors` files whose tensors are loaded on demand.
+
+use std::{collections::HashMap, fs::File, path::Path};
+
+use ::safetensors::tensor::{Metadata, SafeTensors, TensorView};
+use memmap2::Mmap;
+
+use crate::{error::IoError, Array, Dtype};
+
+use super::check_file_extension;
+
+fn invalid(message: impl std::fmt::Display) -> IoError {
+    IoError::InvalidSafeTensors(message.to_string())
+}
+
+fn shape(key: &str, shape: &[usize]) -> Result<Vec<i32>, IoError> {
+    shape
+        .iter()
+        .map(|dim| {
+            i32::try_from(*dim)
+                .map_err(|_| invalid(format!("dimension {} of {:?} is too large", dim, key)))
+        })
+        .collect()
+}
+
+/// A memory-mapped `safetensors` file.
+///
+/// Opening the file only parses its header. The data of a tensor is read from the mapping when
+/// the tensor is loaded, so loading a few tensors of a large checkpoint does not read the rest of
+/// the file. Every loaded [`Array`] owns a copy of its data and stays valid after the file is
+/// closed.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use mlx_rs::ops::MmapSafeTensors;
+///
+/// let file = MmapSafeTensors::open("model.safetensors").unwrap();
+/// let embedding = file.load("embed_tokens.weight").unwrap();
+/// let layer = file.load_keys(["layers.0.weight", "layers.0.bias"]).unwrap();
+/// ```
+pub struct MmapSafeTensors {
+    mmap: Mmap,
+    metadata: Metadata,
+
+    /// Offset of the data section in the file
+    data_start: usize,
+}
+
+impl std::fmt::Debug for MmapSafeTensors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MmapSafeTensors")
+            .field("len", &self.len())
+            .field("metadata", self.metadata())
+            .finish()
+    }
+}
+
+impl MmapSafeTensors {
+    /// Memory-maps a `safetensors` file and parses its header.
+    ///
+    /// The file must not be modified while it is mapped.
+    ///
+    /// # Params
+    ///
+    /// - path: path of file to map
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, IoError> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(IoError::NotFile);
+        }
+        check_file_extension(path, "safetensors")?;
+
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read only and the caller must not modify the file while it is
+        // mapped
+        let mmap = unsafe { Mmap::map(&file) }?;
+        let (header_size, metadata) = SafeTensors::read_metadata(&mmap).map_err(invalid)?;
+
+        Ok(Self {
+            mmap,
+            metadata,
+            data_start: header_size + std::mem::size_of::<u64>(),
+        })
+    }
+
+    /// Number of tensors in the file.
+    pub fn len(&self) -> usize {
+        self.metadata.tensors().len()
+    }
+
+    /// Returns `true` if the file contains no tensors.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Keys of the tensors in the file, in no particular order.
+    pub fn keys(&self) -> Vec<String> {
+        self.metadata.tensors().into_keys().collect()
+    }
+
+    /// Returns `true` if the file contains a tensor named `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.metadata.info(key).is_some()
+    }
+
+    /// Data type and shape of the tensor named `key`, without loading it.
+    ///
+    /// Returns an error if there is no such tensor, or if its data type or shape is not supported
+    /// by [`Array`].
+    pub fn info(&self, key: &str) -> Result<(Dtype, Vec<i32>), IoError> {
+        let info = self
+            .metadata
+            .info(key)
+            .ok_or_else(|| invalid(format!("no tensor named {:?}", key)))?;
+        let dtype = Dtype::try_from(info.dtype).map_err(invalid)?;
+        Ok((dtype, shape(key, &info.shape)?))
+    }
+
+    /// Metadata `[String:String]` of the file.
+    pub fn metadata(&self) -> &Option<HashMap<String, String>> {
+        self.metadata.metadata()
+    }
+
+    /// Loads the tensor named `key`.
+    pub fn load(&self, key: &str) -> Result<Array, IoError> {
+        let info = self
+            .metadata
+            .info(key)
+            .ok_or_else(|| invalid(format!("no tensor named {:?}", key)))?;
+        // `Array::try_from` truncates the dimensions that do not fit in an `i32`
+        shape(key, &info.shape)?;
+
+        let (begin, end) = info.data_offsets;
+        let data = self
+            .mmap
+            .get(self.data_start + begin..self.data_start + end)
+            .ok_or_else(|| invalid(format!("invalid data offsets for {:?}", key)))?;
+
+        let view = TensorView::new(info.dtype, info.shape.clone(), data).map_err(invalid)?;
+        Array::try_from(view).map_err(invalid)
+    }
+
+    /// Loads the tensors named by `keys`, skipping the keys that are not in the file.
+    pub fn load_keys<I, S>(&self, keys: I) -> Result<HashMap<String, Array>, IoError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        keys.into_iter()
+            .filter(|key| self.contains_key(key.as_ref()))
+            .map(|key| {
+                let key = key.as_ref();
+                self.load(key).map(|array| (key.to_string(), array))
+            })
+            .collect()
+    }
+
+    /// Loads every tensor of the file.
+    pub fn load_all(&self) -> Result<HashMap<String, Array>, IoError> {
+        self.load_keys(self.keys())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{array, module::ModuleParametersExt, nn::Linear};
+
+    #[test]
+    fn test_mmap_safetensors() {
+        let arrays = HashMap::from([
+            ("a".to_string(), array!([1.0f32, 2.0])),
+            ("b".to_string(), array!([[1i32, 2], [3, 4]])),
+            ("c".to_string(), array!(3u8)),
+        ]);
+        let metadata = HashMap::from([("format".to_string(), "mlx".to_string())]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.safetensors");
+        Array::save_safetensors(&arrays, &metadata, &path).unwrap();
+
+        let file = MmapSafeTensors::open(&path).unwrap();
+        assert_eq!(file.len(), 3);
+        assert!(file.contains_key("b"));
+        assert_eq!(file.info("b").unwrap(), (Dtype::Int32, vec![2, 2]));
+        assert!(file.info("missing").is_err());
+        assert_eq!(file.metadata(), &Some(metadata));
+        assert_eq!(file.load("b").unwrap(), arrays["b"]);
+
+        let subset = file.load_keys(["a", "missing"]).unwrap();
+        assert_eq!(subset.len(), 1);
+        assert_eq!(subset["a"], arrays["a"]);
+
+        // The loaded arrays own their data
+        drop(file);
+        assert_eq!(subset["a"], array!([1.0f32, 2.0]));
+    }
+
+    #[test]
+    fn test_mmap_safetensors_dimension_too_large() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.safetensors");
+
+        // An empty tensor whose first dimension does not fit in an `i32`
+        let header = br#"{"a":{"dtype":"F32","shape":[2147483648,0],"data_offsets":[0,0]}}"#;
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(header);
+        std::fs::write(&path, bytes).unwrap();
+
+        let file = MmapSafeTensors::open(&path).unwrap();
+        assert!(matches!(
+            file.info("a"),
+            Err(IoError::InvalidSafeTensors(_))
+        ));
+        assert!(matches!(
+            file.load("a"),
+            Err(IoError::InvalidSafeTensors(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_safetensors_mmap() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.safetensors");
+
+        let arrays = HashMap::from([
+            ("weight".to_string(), array!([[1.0f32, 2.0], [3.0, 4.0]])),
+            ("bias".to_string(), array!([5.0f32, 6.0])),
+            ("extra".to_string(), array!([0.0f32])),
+        ]);
+        Array::save_safetensors(&arrays, None, &path).unwrap();
+
+        let mut model = Linear::new(2, 2).unwrap();
+        let report = model.load_safetensors_mmap(&path).unwrap();
+
+        assert_eq!(
+            report.unexpected_keys,
+            vec![std::rc::Rc::<str>::from("extra")]
+        );
+        assert!(report.missing_keys.is_empty());
+        assert_eq!(model.weight.as_ref(), &arrays["weight"]);
+        assert_eq!(model.bias.as_ref(), &Some(arrays["bias"].clone()));
+    }
+}
@@ -2,12 +2,34 @@ use crate::error::IoError;
 use crate::utils::guard::Guarded;
 use crate::utils::io::{FilePtr, SafeTensors};
 use crate::utils::SUCCESS;
-use crate::{Array, Stream, StreamOrDevice};
+use crate::{complex64, Array, ArrayElement, Dtype, Stream, StreamOrDevice};
+use half::{bf16, f16};
 use mlx_internal_macros::default_device;
 use std::collections::HashMap;
 use std::ffi::CString;
 use std::path::Path;
 
+mod gguf;
+mod numpy;
+
+#[cfg(feature = "npz")]
+mod npz;
+
+pub use gguf::*;
+
+cfg_serde! {
+    mod sharded;
+
+    pub use sharded::*;
+}
+
+cfg_safetensors! {
+    mod mmap;
+    mod safetensors;
+
+    pub use mmap::*;
+}
+
 fn check_file_extension(path: &Path, expected: &str) -> Result<(), IoError> {
     match path.extension().and_then(|ext| ext.to_str()) {
         Some(ext) if ext == expected => Ok(()),
@@ -15,6 +37,47 @@ fn check_file_extension(path: &Path, expected: &str) -> Result<(), IoError> {
     }
 }
 
+fn dtype_size(dtype: Dtype) -> usize {
+    match dtype {
+        Dtype::Bool | Dtype::Uint8 | Dtype::Int8 => 1,
+        Dtype::Uint16 | Dtype::Int16 | Dtype::Float16 | Dtype::Bfloat16 => 2,
+        Dtype::Uint32 | Dtype::Int32 | Dtype::Float32 => 4,
+        Dtype::Uint64 | Dtype::Int64 | Dtype::Float64 | Dtype::Complex64 => 8,
+    }
+}
+
+fn array_bytes<T: ArrayElement>(array: &Array) -> &[u8] {
+    let data = array.as_slice::<T>();
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
+
+/// Calls `f` with the data of `array` in row-major order.
+fn with_array_bytes<R>(array: &Array, f: impl FnOnce(&[u8]) -> R) -> Result<R, IoError> {
+    if array.size() == 0 {
+        return Ok(f(&[]));
+    }
+
+    // Flattening copies transposed or broadcast arrays to a contiguous buffer
+    let array = array.flatten(None, None)?;
+    let bytes = match array.dtype() {
+        Dtype::Bool => array_bytes::<bool>(&array),
+        Dtype::Uint8 => array_bytes::<u8>(&array),
+        Dtype::Uint16 => array_bytes::<u16>(&array),
+        Dtype::Uint32 => array_bytes::<u32>(&array),
+        Dtype::Uint64 => array_bytes::<u64>(&array),
+        Dtype::Int8 => array_bytes::<i8>(&array),
+        Dtype::Int16 => array_bytes::<i16>(&array),
+        Dtype::Int32 => array_bytes::<i32>(&array),
+        Dtype::Int64 => array_bytes::<i64>(&array),
+        Dtype::Float16 => array_bytes::<f16>(&array),
+        Dtype::Bfloat16 => array_bytes::<bf16>(&array),
+        Dtype::Float32 => array_bytes::<f32>(&array),
+        Dtype::Float64 => array_bytes::<f64>(&array),
+        Dtype::Complex64 => array_bytes::<complex64>(&array),
+    };
+    Ok(f(bytes))
+}
+
 impl Array {
     /// Load array from a binary file in `.npy` format.
     ///
@@ -0,0 +1,146 @@
+//! Reading and writing of NumPy `.npz` archives, compressed or not.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, Write},
+    path::Path,
+};
+
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{error::IoError, Array};
+
+use super::{
+    check_file_extension,
+    numpy::{invalid, read_npy, write_npy},
+};
+
+fn write_npz<I, S, V>(arrays: I, path: &Path, compression: CompressionMethod) -> Result<(), IoError>
+where
+    I: IntoIterator<Item = (S, V)>,
+    S: AsRef<str>,
+    V: AsRef<Array>,
+{
+    check_file_extension(path, "npz")?;
+
+    let file = File::create(path)?;
+    let mut writer = ZipWriter::new(BufWriter::new(file));
+
+    for (key, array) in arrays {
+        let array = array.as_ref();
+
+        // Like NumPy, always use zip64 so that arrays larger than 4GB can be stored
+        let options = SimpleFileOptions::default()
+            .compression_method(compression)
+            .large_file(true);
+        writer
+            .start_file(format!("{}.npy", key.as_ref()), options)
+            .map_err(invalid)?;
+        write_npy(array, &mut writer)?;
+    }
+
+    let mut file = writer.finish().map_err(invalid)?;
+    file.flush()?;
+    Ok(())
+}
+
+fn read_npz(reader: impl Read + Seek) -> Result<HashMap<String, Array>, IoError> {
+    let mut archive = ZipArchive::new(reader).map_err(invalid)?;
+
+    let mut arrays = HashMap::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(invalid)?;
+        let key = file.name();
+        let key = key.strip_suffix(".npy").unwrap_or(key).to_string();
+        arrays.insert(key, read_npy(&mut file)?);
+    }
+
+    Ok(arrays)
+}
+
+impl Array {
+    /// Load dictionary of ``MLXArray`` from a `.npz` archive, compressed or not.
+    ///
+    /// The keys are the names of the arrays in the archive without the `.npy` extension.
+    ///
+    /// # Params
+    ///
+    /// - path: path of file to load
+    pub fn load_npz(path: impl AsRef<Path>) -> Result<HashMap<String, Array>, IoError> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(IoError::NotFile);
+        }
+        check_file_extension(path, "npz")?;
+
+        let file = File::open(path)?;
+        read_npz(BufReader::new(file))
+    }
+
+    /// Save dictionary of arrays in an uncompressed `.npz` archive, like `numpy.savez`.
+    ///
+    /// # Params
+    ///
+    /// - arrays: arrays to save
+    /// - path: path of file to save
+    pub fn save_npz<I, S, V>(arrays: I, path: impl AsRef<Path>) -> Result<(), IoError>
+    where
+        I: IntoIterator<Item = (S, V)>,
+        S: AsRef<str>,
+        V: AsRef<Array>,
+    {
+        write_npz(arrays, path.as_ref(), CompressionMethod::Stored)
+    }
+
+    /// Save dictionary of arrays in a compressed `.npz` archive, like `numpy.savez_compressed`.
+    ///
+    /// # Params
+    ///
+    /// - arrays: arrays to save
+    /// - path: path of file to save
+    pub fn save_npz_compressed<I, S, V>(arrays: I, path: impl AsRef<Path>) -> Result<(), IoError>
+    where
+        I: IntoIterator<Item = (S, V)>,
+        S: AsRef<str>,
+        V: AsRef<Array>,
+    {
+        write_npz(arrays, path.as_ref(), CompressionMethod::Deflated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{array, Dtype};
+
+    #[test]
+    fn test_save_and_load_npz() {
+        let arrays = HashMap::from([
+            ("a".to_string(), array!([[1.0f32, 2.0], [3.0, 4.0]])),
+            ("b".to_string(), array!([1i32, 2, 3])),
+            ("c.d".to_string(), array!([true, false])),
+            (
+                "e".to_string(),
+                array!(1.5f32).as_dtype(Dtype::Bfloat16).unwrap(),
+            ),
+        ]);
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, compressed) in [("plain.npz", false), ("compressed.npz", true)] {
+            let path = dir.path().join(name);
+            if compressed {
+                Array::save_npz_compressed(&arrays, &path).unwrap();
+            } else {
+                Array::save_npz(&arrays, &path).unwrap();
+            }
+
+            let loaded = Array::load_npz(&path).unwrap();
+            assert_eq!(loaded.len(), arrays.len());
+            for (key, value) in &arrays {
+                assert_eq!(&loaded[key], value);
+                assert_eq!(loaded[key].dtype(), value.dtype());
+            }
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! Reading and writing of the NumPy [`.npy`](https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html)
+//! format.
+//!
+//! `bfloat16` has no NumPy equivalent and is stored as the opaque type `V2`, like MLX does.
+
+use std::{
+    ffi::c_void,
+    fmt,
+    io::{Read, Write},
+};
+
+use crate::{error::IoError, Array, Dtype};
+
+use super::{dtype_size, with_array_bytes};
+
+const NPY_MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// The header, including the magic string and its length, is padded to a multiple of this.
+const NPY_HEADER_ALIGNMENT: usize = 64;
+
+pub(super) fn invalid(message: impl fmt::Display) -> IoError {
+    IoError::InvalidNumpy(message.to_string())
+}
+
+fn descr(dtype: Dtype) -> &'static str {
+    match dtype {
+        Dtype::Bool => "|b1",
+        Dtype::Uint8 => "|u1",
+        Dtype::Uint16 => "<u2",
+        Dtype::Uint32 => "<u4",
+        Dtype::Uint64 => "<u8",
+        Dtype::Int8 => "|i1",
+        Dtype::Int16 => "<i2",
+        Dtype::Int32 => "<i4",
+        Dtype::Int64 => "<i8",
+        Dtype::Float16 => "<f2",
+        Dtype::Bfloat16 => "<V2",
+        Dtype::Float32 => "<f4",
+        Dtype::Float64 => "<f8",
+        Dtype::Complex64 => "<c8",
+    }
+}
+
+fn dtype_from_descr(descr: &str) -> Result<Dtype, IoError> {
+    // Single byte types have no byte order, and `=` is the native little endian order
+    let (byte_order, type_code) = descr.split_at(descr.len().min(1));
+    if !matches!(byte_order, "<" | "|" | "=") {
+        return Err(invalid(format!("unsupported byte order in {:?}", descr)));
+    }
+
+    match type_code {
+        "b1" => Ok(Dtype::Bool),
+        "u1" => Ok(Dtype::Uint8),
+        "u2" => Ok(Dtype::Uint16),
+        "u4" => Ok(Dtype::Uint32),
+        "u8" => Ok(Dtype::Uint64),
+        "i1" => Ok(Dtype::Int8),
+        "i2" => Ok(Dtype::Int16),
+        "i4" => Ok(Dtype::Int32),
+        "i8" => Ok(Dtype::Int64),
+        "f2" => Ok(Dtype::Float16),
+        "V2" => Ok(Dtype::Bfloat16),
+        "f4" => Ok(Dtype::Float32),
+        "f8" => Ok(Dtype::Float64),
+        "c8" => Ok(Dtype::Complex64),
+        _ => Err(invalid(format!("unsupported data type {:?}", descr))),
+    }
+}
+
+/// Returns the text following `'key':` in the header dictionary.
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, IoError> {
+    let pattern = format!("'{}':", key);
+    header
+        .find(&pattern)
+        .map(|start| header[start + pattern.len()..].trim_start())
+        .ok_or_else(|| invalid(format!("missing {:?} in header", key)))
+}
+
+fn parse_header(header: &str) -> Result<(Dtype, bool, Vec<i32>), IoError> {
+    let descr = header_value(header, "descr")?;
+    let descr = descr
+        .strip_prefix('\'')
+        .and_then(|descr| descr.split('\'').next())
+        .ok_or_else(|| invalid("invalid descr in header"))?;
+    let dtype = dtype_from_descr(descr)?;
+
+    let fortran_order = header_value(header, "fortran_order")?.starts_with("True");
+
+    let shape = header_value(header, "shape")?;
+    let shape = shape
+        .strip_prefix('(')
+        .and_then(|shape| shape.split(')').next())
+        .ok_or_else(|| invalid("invalid shape in header"))?;
+    let shape = shape
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| match dim.trim_end_matches('L').parse::<i32>() {
+            Ok(dim) if dim >= 0 => Ok(dim),
+            _ => Err(invalid(format!("invalid dimension {:?} in header", dim))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((dtype, fortran_order, shape))
+}
+
+/// Reads an array in `.npy` format.
+pub(super) fn read_npy(reader: &mut impl Read) -> Result<Array, IoError> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic).map_err(invalid)?;
+    if &magic[..6] != NPY_MAGIC {
+        return Err(invalid("invalid magic string"));
+    }
+
+    // Version 1 uses a 16-bit header length, versions 2 and 3 a 32-bit one
+    let header_len = match magic[6] {
+        1 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len).map_err(invalid)?;
+            u16::from_le_bytes(len) as usize
+        }
+        2 | 3 => {
+            let mut len = [0; 4];
+            reader.read_exact(&mut len).map_err(invalid)?;
+            u32::from_le_bytes(len) as usize
+        }
+        version => return Err(invalid(format!("unsupported version {}", version))),
+    };
+
+    let mut header = vec![0; header_len];
+    reader.read_exact(&mut header).map_err(invalid)?;
+    let header = String::from_utf8_lossy(&header);
+    let (dtype, fortran_order, mut shape) = parse_header(&header)?;
+
+    let nbytes = shape
+        .iter()
+        .try_fold(dtype_size(dtype), |acc, dim| acc.checked_mul(*dim as usize))
+        .ok_or_else(|| invalid("array is too large"))?;
+
+    // The data is not allocated upfront, as the size comes from an untrusted header
+    let mut data = Vec::new();
+    reader
+        .by_ref()
+        .take(nbytes as u64)
+        .read_to_end(&mut data)
+        .map_err(invalid)?;
+    if data.len() != nbytes {
+        return Err(invalid("unexpected end of data"));
+    }
+
+    // Column-major data is read as the transpose of the reversed shape
+    if fortran_order {
+        shape.reverse();
+    }
+    let array = unsafe { Array::from_raw_data(data.as_ptr() as *const c_void, &shape, dtype) };
+    if fortran_order {
+        return array.transpose_all().map_err(Into::into);
+    }
+
+    Ok(array)
+}
+
+/// Writes an array in `.npy` format.
+pub(super) fn write_npy(array: &Array, writer: &mut impl Write) -> Result<(), IoError> {
+    let shape = match array.shape() {
+        [dim] => format!("({},)", dim),
+        shape => format!(
+            "({})",
+            shape
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr(array.dtype()),
+        shape
+    );
+
+    // Version 1 stores the header length in 2 bytes and version 2 in 4 bytes
+    let (version, preamble_len) = if header.len() + NPY_HEADER_ALIGNMENT < u16::MAX as usize {
+        (1, NPY_MAGIC.len() + 4)
+    } else {
+        (2, NPY_MAGIC.len() + 6)
+    };
+
+    // Pad with spaces so that the data is aligned, and terminate the header with a newline
+    let padded_len =
+        (preamble_len + header.len() + 1).div_ceil(NPY_HEADER_ALIGNMENT) * NPY_HEADER_ALIGNMENT;
+    header.push_str(&" ".repeat(padded_len - preamble_len - header.len() - 1));
+    header.push('\n');
+
+    let mut preamble = NPY_MAGIC.to_vec();
+    preamble.extend([version, 0]);
+    if version == 1 {
+        preamble.extend((header.len() as u16).to_le_bytes());
+    } else {
+        preamble.extend((header.len() as u32).to_le_bytes());
+    }
+
+    writer.write_all(&preamble)?;
+    writer.write_all(header.as_bytes())?;
+    with_array_bytes(array, |bytes| writer.write_all(bytes))??;
+    Ok(())
+}
+
+impl Array {
+    /// Load array from `.npy` data read from `reader`.
+    ///
+    /// # Params
+    ///
+    /// - reader: reader of the `.npy` data
+    pub fn load_numpy_from_reader(mut reader: impl Read) -> Result<Array, IoError> {
+        read_npy(&mut reader)
+    }
+
+    /// Load array from `.npy` data in memory.
+    ///
+    /// # Params
+    ///
+    /// - bytes: content of a `.npy` file
+    pub fn load_numpy_from_bytes(bytes: &[u8]) -> Result<Array, IoError> {
+        Array::load_numpy_from_reader(bytes)
+    }
+
+    /// Write array in `.npy` format to `writer`.
+    ///
+    /// # Params
+    ///
+    /// - writer: writer of the `.npy` data
+    pub fn save_numpy_to_writer(&self, mut writer: impl Write) -> Result<(), IoError> {
+        write_npy(self, &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Save array in `.npy` format to a buffer.
+    pub fn save_numpy_to_bytes(&self) -> Result<Vec<u8>, IoError> {
+        let mut bytes = Vec::new();
+        write_npy(self, &mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array;
+
+    #[test]
+    fn test_write_npy_matches_save_numpy() {
+        let a = array!([[1.0f32, 2.0, 3.0], [4.0, 5.0, 6.0]]).t();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.npy");
+        a.save_numpy(&path).unwrap();
+
+        let mut file = std::fs::File::open(&path).unwrap();
+        assert_eq!(read_npy(&mut file).unwrap(), a);
+
+        let mut buffer = Vec::new();
+        write_npy(&a, &mut buffer).unwrap();
+        assert_eq!((buffer.len() - a.nbytes()) % NPY_HEADER_ALIGNMENT, 0);
+        std::fs::write(&path, &buffer).unwrap();
+        assert_eq!(Array::load_numpy(&path).unwrap(), a);
+    }
+
+    #[test]
+    fn test_save_and_load_numpy_bytes() {
+        let a = array!([[1u8, 2], [3, 4]]);
+        let bytes = a.save_numpy_to_bytes().unwrap();
+        assert_eq!(Array::load_numpy_from_bytes(&bytes).unwrap(), a);
+
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        a.save_numpy_to_writer(&mut buffer).unwrap();
+        assert_eq!(buffer.into_inner(), bytes);
+    }
+
+    #[test]
+    fn test_read_npy_fortran_order() {
+        let header = "{'descr': '<i4', 'fortran_order': True, 'shape': (2, 3), }";
+        let mut buffer = NPY_MAGIC.to_vec();
+        buffer.extend([1, 0]);
+        buffer.extend((header.len() as u16).to_le_bytes());
+        buffer.extend(header.as_bytes());
+        for value in [1i32, 4, 2, 5, 3, 6] {
+            buffer.extend(value.to_le_bytes());
+        }
+
+        let array = read_npy(&mut buffer.as_slice()).unwrap();
+        assert_eq!(array, array!([[1, 2, 3], [4, 5, 6]]));
+    }
+
+    #[test]
+    fn test_read_npy_unsupported_descr() {
+        let header = "{'descr': '>f4', 'fortran_order': False, 'shape': (1,), }";
+        let mut buffer = NPY_MAGIC.to_vec();
+        buffer.extend([1, 0]);
+        buffer.extend((header.len() as u16).to_le_bytes());
+        buffer.extend(header.as_bytes());
+        buffer.extend([0; 4]);
+
+        let result = read_npy(&mut buffer.as_slice());
+        assert!(matches!(result, Err(IoError::InvalidNumpy(_))));
+    }
+
+    #[test]
+    fn test_read_npy_invalid_shape() {
+        for shape in ["(-1, 4)", "(2147483647, 2147483647, 2147483647)", "(1024,)"] {
+            let header = format!(
+                "{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}",
+                shape
+            );
+            let mut buffer = NPY_MAGIC.to_vec();
+            buffer.extend([1, 0]);
+            buffer.extend((header.len() as u16).to_le_bytes());
+            buffer.extend(header.as_bytes());
+            buffer.extend([0; 4]);
+
+            let result = read_npy(&mut buffer.as_slice());
+            assert!(matches!(result, Err(IoError::InvalidNumpy(_))), "{}", shape);
+        }
+    }
+}
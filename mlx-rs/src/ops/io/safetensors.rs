@@ -0,0 +1,363 @@
+//! Reading and writing of the [`safetensors`](https://github.com/huggingface/safetensors) format
+//! from and to any [`Read`] or [`Write`], without going through a file.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::c_void,
+    fmt,
+    io::{self, Read, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::IoError, Array, Dtype};
+
+use super::{dtype_size, with_array_bytes};
+
+/// Key of the metadata in the header.
+const METADATA_KEY: &str = "__metadata__";
+
+/// The header is padded with spaces to a multiple of this.
+const HEADER_ALIGNMENT: usize = 8;
+
+/// Upper bound of the header size, to avoid allocating for a corrupted length.
+const MAX_HEADER_SIZE: u64 = 100_000_000;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TensorInfo {
+    dtype: String,
+    shape: Vec<i32>,
+    data_offsets: [u64; 2],
+}
+
+fn invalid(message: impl fmt::Display) -> IoError {
+    IoError::InvalidSafeTensors(message.to_string())
+}
+
+fn dtype_name(dtype: Dtype) -> Result<&'static str, IoError> {
+    match dtype {
+        Dtype::Bool => Ok("BOOL"),
+        Dtype::Uint8 => Ok("U8"),
+        Dtype::Uint16 => Ok("U16"),
+        Dtype::Uint32 => Ok("U32"),
+        Dtype::Uint64 => Ok("U64"),
+        Dtype::Int8 => Ok("I8"),
+        Dtype::Int16 => Ok("I16"),
+        Dtype::Int32 => Ok("I32"),
+        Dtype::Int64 => Ok("I64"),
+        Dtype::Float16 => Ok("F16"),
+        Dtype::Bfloat16 => Ok("BF16"),
+        Dtype::Float32 => Ok("F32"),
+        Dtype::Float64 => Ok("F64"),
+        Dtype::Complex64 => Err(IoError::UnsupportedDtype(dtype)),
+    }
+}
+
+fn dtype_from_name(name: &str) -> Result<Dtype, IoError> {
+    match name {
+        "BOOL" => Ok(Dtype::Bool),
+        "U8" => Ok(Dtype::Uint8),
+        "U16" => Ok(Dtype::Uint16),
+        "U32" => Ok(Dtype::Uint32),
+        "U64" => Ok(Dtype::Uint64),
+        "I8" => Ok(Dtype::Int8),
+        "I16" => Ok(Dtype::Int16),
+        "I32" => Ok(Dtype::Int32),
+        "I64" => Ok(Dtype::Int64),
+        "F16" => Ok(Dtype::Float16),
+        "BF16" => Ok(Dtype::Bfloat16),
+        "F32" => Ok(Dtype::Float32),
+        "F64" => Ok(Dtype::Float64),
+        _ => Err(invalid(format!("unsupported data type {:?}", name))),
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn read_safetensors(
+    reader: &mut impl Read,
+) -> Result<(HashMap<String, Array>, HashMap<String, String>), IoError> {
+    let mut header_size = [0; 8];
+    reader.read_exact(&mut header_size).map_err(invalid)?;
+    let header_size = u64::from_le_bytes(header_size);
+    if header_size > MAX_HEADER_SIZE {
+        return Err(invalid("header is too large"));
+    }
+
+    let mut header = vec![0; header_size as usize];
+    reader.read_exact(&mut header).map_err(invalid)?;
+    let mut header: BTreeMap<String, serde_json::Value> =
+        serde_json::from_slice(&header).map_err(invalid)?;
+
+    let metadata = match header.remove(METADATA_KEY) {
+        Some(metadata) => serde_json::from_value(metadata).map_err(invalid)?,
+        None => HashMap::new(),
+    };
+
+    let mut tensors = header
+        .into_iter()
+        .map(|(key, info)| Ok((key, serde_json::from_value::<TensorInfo>(info)?)))
+        .collect::<Result<Vec<_>, serde_json::Error>>()
+        .map_err(invalid)?;
+
+    // Read the data in order so that the reader does not need to seek
+    tensors.sort_by_key(|(_, info)| info.data_offsets);
+
+    let mut arrays = HashMap::with_capacity(tensors.len());
+    let mut position = 0;
+    for (key, info) in tensors {
+        let dtype = dtype_from_name(&info.dtype)?;
+        let [begin, end] = info.data_offsets;
+        if info.shape.iter().any(|dim| *dim < 0) {
+            return Err(invalid(format!("invalid shape for {:?}", key)));
+        }
+        let nbytes = info
+            .shape
+            .iter()
+            .try_fold(dtype_size(dtype) as u64, |acc, dim| {
+                acc.checked_mul(*dim as u64)
+            })
+            .ok_or_else(|| invalid(format!("{:?} is too large", key)))?;
+        if begin < position || end < begin || end - begin != nbytes {
+            return Err(invalid(format!("invalid data offsets for {:?}", key)));
+        }
+
+        let skipped = io::copy(&mut reader.by_ref().take(begin - position), &mut io::sink())
+            .map_err(invalid)?;
+        if skipped != begin - position {
+            return Err(invalid("unexpected end of data"));
+        }
+
+        // The data is not allocated upfront, as the size comes from an untrusted header
+        let mut data = Vec::new();
+        reader
+            .by_ref()
+            .take(nbytes)
+            .read_to_end(&mut data)
+            .map_err(invalid)?;
+        if data.len() as u64 != nbytes {
+            return Err(invalid("unexpected end of data"));
+        }
+        position = end;
+
+        let array =
+            unsafe { Array::from_raw_data(data.as_ptr() as *const c_void, &info.shape, dtype) };
+        arrays.insert(key, array);
+    }
+
+    Ok((arrays, metadata))
+}
+
+fn write_safetensors<I, S, V>(
+    arrays: I,
+    metadata: Option<&HashMap<String, String>>,
+    writer: &mut impl Write,
+) -> Result<(), IoError>
+where
+    I: IntoIterator<Item = (S, V)>,
+    S: AsRef<str>,
+    V: AsRef<Array>,
+{
+    let mut arrays: Vec<_> = arrays.into_iter().collect();
+    arrays.sort_by(|a, b| a.0.as_ref().cmp(b.0.as_ref()));
+
+    let mut header = serde_json::Map::new();
+    if let Some(metadata) = metadata {
+        let metadata = serde_json::to_value(metadata).map_err(invalid)?;
+        header.insert(METADATA_KEY.to_string(), metadata);
+    }
+
+    let mut offset = 0;
+    for (key, array) in &arrays {
+        let array = array.as_ref();
+        let nbytes = array.nbytes() as u64;
+        let info = TensorInfo {
+            dtype: dtype_name(array.dtype())?.to_string(),
+            shape: array.shape().to_vec(),
+            data_offsets: [offset, offset + nbytes],
+        };
+        header.insert(
+            key.as_ref().to_string(),
+            serde_json::to_value(info).map_err(invalid)?,
+        );
+        offset += nbytes;
+    }
+
+    let mut header = serde_json::to_string(&header).map_err(invalid)?;
+    let padded_len = header.len().div_ceil(HEADER_ALIGNMENT) * HEADER_ALIGNMENT;
+    header.push_str(&" ".repeat(padded_len - header.len()));
+
+    writer.write_all(&(header.len() as u64).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for (_, array) in &arrays {
+        with_array_bytes(array.as_ref(), |bytes| writer.write_all(bytes))??;
+    }
+
+    Ok(())
+}
+
+impl Array {
+    /// Load dictionary of ``MLXArray`` from `safetensors` data read from `reader`.
+    ///
+    /// # Params
+    ///
+    /// - reader: reader of the `safetensors` data
+    pub fn load_safetensors_from_reader(
+        mut reader: impl Read,
+    ) -> Result<HashMap<String, Array>, IoError> {
+        read_safetensors(&mut reader).map(|(arrays, _)| arrays)
+    }
+
+    /// Load dictionary of ``MLXArray`` from `safetensors` data in memory.
+    ///
+    /// # Params
+    ///
+    /// - bytes: content of a `safetensors` file
+    pub fn load_safetensors_from_bytes(bytes: &[u8]) -> Result<HashMap<String, Array>, IoError> {
+        Array::load_safetensors_from_reader(bytes)
+    }
+
+    /// Load dictionary of ``MLXArray`` and metadata `[String:String]` from `safetensors` data read
+    /// from `reader`.
+    ///
+    /// # Params
+    ///
+    /// - reader: reader of the `safetensors` data
+    #[allow(clippy::type_complexity)]
+    pub fn load_safetensors_with_metadata_from_reader(
+        mut reader: impl Read,
+    ) -> Result<(HashMap<String, Array>, HashMap<String, String>), IoError> {
+        read_safetensors(&mut reader)
+    }
+
+    /// Load dictionary of ``MLXArray`` and metadata `[String:String]` from `safetensors` data in
+    /// memory.
+    ///
+    /// # Params
+    ///
+    /// - bytes: content of a `safetensors` file
+    #[allow(clippy::type_complexity)]
+    pub fn load_safetensors_with_metadata_from_bytes(
+        bytes: &[u8],
+    ) -> Result<(HashMap<String, Array>, HashMap<String, String>), IoError> {
+        Array::load_safetensors_with_metadata_from_reader(bytes)
+    }
+
+    /// Write dictionary of arrays in `safetensors` format to `writer`.
+    ///
+    /// The `complex64` data type is not supported by the format.
+    ///
+    /// # Params
+    ///
+    /// - arrays: arrays to save
+    /// - metadata: metadata to save
+    /// - writer: writer of the `safetensors` data
+    pub fn save_safetensors_to_writer<'a, I, S, V>(
+        arrays: I,
+        metadata: impl Into<Option<&'a HashMap<String, String>>>,
+        mut writer: impl Write,
+    ) -> Result<(), IoError>
+    where
+        I: IntoIterator<Item = (S, V)>,
+        S: AsRef<str>,
+        V: AsRef<Array>,
+    {
+        write_safetensors(arrays, metadata.into(), &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Save dictionary of arrays in `safetensors` format to a buffer.
+    ///
+    /// # Params
+    ///
+    /// - arrays: arrays to save
+    /// - metadata: metadata to save
+    pub fn save_safetensors_to_bytes<'a, I, S, V>(
+        arrays: I,
+        metadata: impl Into<Option<&'a HashMap<String, String>>>,
+    ) -> Result<Vec<u8>, IoError>
+    where
+        I: IntoIterator<Item = (S, V)>,
+        S: AsRef<str>,
+        V: AsRef<Array>,
+    {
+        let mut bytes = Vec::new();
+        write_safetensors(arrays, metadata.into(), &mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array;
+
+    #[test]
+    fn test_write_matches_save_safetensors() {
+        let arrays = HashMap::from([
+            ("a".to_string(), array!([[1.0f32, 2.0], [3.0, 4.0]]).t()),
+            ("b".to_string(), array!([1i32, 2, 3])),
+        ]);
+        let metadata = HashMap::from([("format".to_string(), "mlx".to_string())]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.safetensors");
+
+        // Bytes written here are readable by MLX
+        let mut buffer = Vec::new();
+        write_safetensors(&arrays, Some(&metadata), &mut buffer).unwrap();
+        std::fs::write(&path, &buffer).unwrap();
+        let (loaded, loaded_metadata) = Array::load_safetensors_with_metadata(&path).unwrap();
+        assert_eq!(loaded, arrays);
+        assert_eq!(loaded_metadata, metadata);
+
+        // Files written by MLX are readable here
+        Array::save_safetensors(&arrays, &metadata, &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let (loaded, loaded_metadata) = read_safetensors(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded, arrays);
+        assert_eq!(loaded_metadata, metadata);
+    }
+
+    #[test]
+    fn test_save_and_load_safetensors_bytes() {
+        let arrays = HashMap::from([
+            ("a".to_string(), array!([1.0f32, 2.0])),
+            ("b".to_string(), array!([true, false])),
+        ]);
+
+        let bytes = Array::save_safetensors_to_bytes(&arrays, None).unwrap();
+        assert_eq!(Array::load_safetensors_from_bytes(&bytes).unwrap(), arrays);
+
+        let (loaded, metadata) = Array::load_safetensors_with_metadata_from_bytes(&bytes).unwrap();
+        assert_eq!(loaded, arrays);
+        assert!(metadata.is_empty());
+    }
+
+    #[test]
+    fn test_read_invalid_shape() {
+        for shape in ["[-1,4]", "[2147483647,2147483647,2147483647]"] {
+            let header = format!(
+                r#"{{"a":{{"dtype":"F32","shape":{},"data_offsets":[0,16]}}}}"#,
+                shape
+            );
+            let mut buffer = (header.len() as u64).to_le_bytes().to_vec();
+            buffer.extend(header.as_bytes());
+            buffer.extend([0; 16]);
+
+            let result = read_safetensors(&mut buffer.as_slice());
+            assert!(matches!(result, Err(IoError::InvalidSafeTensors(_))));
+        }
+    }
+
+    #[test]
+    fn test_read_invalid_offsets() {
+        let header = r#"{"a":{"dtype":"F32","shape":[2],"data_offsets":[0,4]}}"#;
+        let mut buffer = (header.len() as u64).to_le_bytes().to_vec();
+        buffer.extend(header.as_bytes());
+        buffer.extend([0; 4]);
+
+        let result = read_safetensors(&mut buffer.as_slice());
+        assert!(matches!(result, Err(IoError::InvalidSafeTensors(_))));
+    }
+}
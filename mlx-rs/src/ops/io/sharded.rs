@@ -0,0 +1,266 @@
+//! Sharded `safetensors` checkpoints, ie. `model-0000X-of-0000N.safetensors` files together with
+//! an index that maps every array to its shard.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use mlx_internal_macros::default_device;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::IoError, Array, Stream, StreamOrDevice};
+
+/// Name of the index file of a sharded `safetensors` checkpoint.
+pub const SAFETENSORS_INDEX_FILE: &str = "model.safetensors.index.json";
+
+/// Default maximum size in bytes of a shard of a `safetensors` checkpoint, ie. 5GB.
+pub const DEFAULT_MAX_SHARD_SIZE: u64 = 5_000_000_000;
+
+/// Metadata of a sharded `safetensors` checkpoint.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafeTensorsIndexMetadata {
+    /// Total size in bytes of the arrays in all the shards
+    #[serde(default)]
+    pub total_size: u64,
+}
+
+/// Index of a sharded `safetensors` checkpoint, ie. the content of
+/// [`SAFETENSORS_INDEX_FILE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafeTensorsIndex {
+    /// Metadata of the checkpoint
+    #[serde(default)]
+    pub metadata: SafeTensorsIndexMetadata,
+
+    /// Maps the key of every array to the file name of the shard that contains it
+    pub weight_map: BTreeMap<String, String>,
+}
+
+impl SafeTensorsIndex {
+    /// Read the index from a json file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, IoError> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        serde_json::from_str(&content).map_err(|err| IoError::InvalidIndex(err.to_string()))
+    }
+
+    /// Write the index to a json file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), IoError> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|err| IoError::InvalidIndex(err.to_string()))?;
+        std::fs::write(path.as_ref(), content)?;
+        Ok(())
+    }
+
+    /// File names of the shards, sorted and without duplicates.
+    pub fn shard_files(&self) -> BTreeSet<&str> {
+        self.weight_map.values().map(String::as_str).collect()
+    }
+}
+
+/// Returns the path of the index file given either the index file itself or the directory of the
+/// checkpoint.
+fn resolve_index_path(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join(SAFETENSORS_INDEX_FILE)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+impl Array {
+    /// Load dictionary of ``MLXArray`` from a sharded `safetensors` checkpoint.
+    ///
+    /// Returns an [`IoError::InvalidIndex`] if an array is in more than one shard, or if the index
+    /// assigns an array to a shard that does not contain it.
+    ///
+    /// # Params
+    ///
+    /// - path: path of the index file, or of the directory that contains
+    ///   [`SAFETENSORS_INDEX_FILE`]
+    /// - stream: stream or device to evaluate on
+    #[default_device]
+    pub fn load_safetensors_sharded_device(
+        path: impl AsRef<Path>,
+        stream: impl AsRef<Stream>,
+    ) -> Result<HashMap<String, Array>, IoError> {
+        let index_path = resolve_index_path(path.as_ref());
+        let index = SafeTensorsIndex::from_file(&index_path)?;
+        let dir = index_path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut arrays = HashMap::new();
+        let mut sources: HashMap<String, &str> = HashMap::new();
+        for shard in index.shard_files() {
+            for (key, array) in Array::load_safetensors_device(dir.join(shard), &stream)? {
+                if let Some(first) = sources.insert(key.clone(), shard) {
+                    return Err(IoError::InvalidIndex(format!(
+                        "{:?} is in both {} and {}",
+                        key, first, shard
+                    )));
+                }
+                arrays.insert(key, array);
+            }
+        }
+
+        for (key, shard) in &index.weight_map {
+            if sources.get(key) != Some(&shard.as_str()) {
+                return Err(IoError::InvalidIndex(format!(
+                    "{:?} is not in {}",
+                    key, shard
+                )));
+            }
+        }
+
+        Ok(arrays)
+    }
+
+    /// Save dictionary of arrays as a sharded `safetensors` checkpoint.
+    ///
+    /// The arrays are sorted by key and split into shards of at most `max_shard_size` bytes,
+    /// except for arrays that are larger than `max_shard_size` which get a shard of their own. The
+    /// shards are named `model-0000X-of-0000N.safetensors` and are written to `dir` together with
+    /// [`SAFETENSORS_INDEX_FILE`].
+    ///
+    /// # Params
+    ///
+    /// - arrays: arrays to save
+    /// - metadata: metadata to save in every shard
+    /// - dir: directory to save the checkpoint to, which is created if it does not exist
+    /// - max_shard_size: maximum size in bytes of a shard, eg. [`DEFAULT_MAX_SHARD_SIZE`]
+    pub fn save_safetensors_sharded<'a, I, S, V>(
+        arrays: I,
+        metadata: impl Into<Option<&'a HashMap<String, String>>>,
+        dir: impl AsRef<Path>,
+        max_shard_size: u64,
+    ) -> Result<SafeTensorsIndex, IoError>
+    where
+        I: IntoIterator<Item = (S, V)>,
+        S: AsRef<str>,
+        V: AsRef<Array>,
+    {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        let mut arrays: Vec<_> = arrays.into_iter().collect();
+        arrays.sort_by(|a, b| a.0.as_ref().cmp(b.0.as_ref()));
+
+        let mut shards: Vec<Vec<(S, V)>> = Vec::new();
+        let mut shard_size = 0;
+        let mut total_size = 0;
+        for (key, array) in arrays {
+            let size = array.as_ref().nbytes() as u64;
+            if shards.is_empty() || (shard_size > 0 && shard_size + size > max_shard_size) {
+                shards.push(Vec::new());
+                shard_size = 0;
+            }
+
+            shard_size += size;
+            total_size += size;
+            if let Some(shard) = shards.last_mut() {
+                shard.push((key, array));
+            }
+        }
+
+        let mut index = SafeTensorsIndex {
+            metadata: SafeTensorsIndexMetadata { total_size },
+            weight_map: BTreeMap::new(),
+        };
+
+        let metadata = metadata.into();
+        let num_shards = shards.len();
+        for (i, shard) in shards.into_iter().enumerate() {
+            let file_name = format!("model-{:05}-of-{:05}.safetensors", i + 1, num_shards);
+            for (key, _) in &shard {
+                index
+                    .weight_map
+                    .insert(key.as_ref().to_string(), file_name.clone());
+            }
+
+            Array::save_safetensors(shard, metadata, dir.join(&file_name))?;
+        }
+
+        index.save(dir.join(SAFETENSORS_INDEX_FILE))?;
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_sharded() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+
+        // Each array is 16 bytes, so two of them fit in a shard
+        let mut arrays = std::collections::HashMap::new();
+        for key in ["a", "b", "c"] {
+            arrays.insert(key.to_string(), Array::ones::<f32>(&[4]).unwrap());
+        }
+
+        let index = Array::save_safetensors_sharded(&arrays, None, tmp_dir.path(), 32).unwrap();
+        assert_eq!(index.metadata.total_size, 48);
+        assert_eq!(index.weight_map["a"], "model-00001-of-00002.safetensors");
+        assert_eq!(index.weight_map["b"], "model-00001-of-00002.safetensors");
+        assert_eq!(index.weight_map["c"], "model-00002-of-00002.safetensors");
+
+        let loaded_index =
+            SafeTensorsIndex::from_file(tmp_dir.path().join(SAFETENSORS_INDEX_FILE)).unwrap();
+        assert_eq!(loaded_index, index);
+
+        let loaded_arrays = Array::load_safetensors_sharded(tmp_dir.path()).unwrap();
+        assert_eq!(loaded_arrays.len(), 3);
+        for (key, array) in arrays {
+            assert_eq!(loaded_arrays[&key], array);
+        }
+    }
+
+    #[test]
+    fn test_load_sharded_inconsistent_index() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+
+        let mut arrays = std::collections::HashMap::new();
+        for key in ["a", "b"] {
+            arrays.insert(key.to_string(), Array::ones::<f32>(&[4]).unwrap());
+        }
+        let mut index = Array::save_safetensors_sharded(&arrays, None, tmp_dir.path(), 16).unwrap();
+        let index_path = tmp_dir.path().join(SAFETENSORS_INDEX_FILE);
+
+        // `b` is assigned to the shard of `a`
+        let first_shard = index.weight_map["a"].clone();
+        let second_shard = index.weight_map["b"].clone();
+        index
+            .weight_map
+            .insert("b".to_string(), first_shard.clone());
+        index.save(&index_path).unwrap();
+        assert!(matches!(
+            Array::load_safetensors_sharded(tmp_dir.path()),
+            Err(IoError::InvalidIndex(_))
+        ));
+
+        // `a` is in both shards
+        index
+            .weight_map
+            .insert("b".to_string(), second_shard.clone());
+        index.save(&index_path).unwrap();
+        let duplicated = [
+            ("a", Array::ones::<f32>(&[4]).unwrap()),
+            ("b", Array::ones::<f32>(&[4]).unwrap()),
+        ];
+        Array::save_safetensors(duplicated, None, tmp_dir.path().join(&second_shard)).unwrap();
+        assert!(matches!(
+            Array::load_safetensors_sharded(tmp_dir.path()),
+            Err(IoError::InvalidIndex(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_missing_index() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+
+        let result = Array::load_safetensors_sharded(tmp_dir.path());
+        assert!(
+            matches!(result, Err(IoError::Io(ref err)) if err.kind() == std::io::ErrorKind::NotFound)
+        );
+    }
+}
@@ -19,6 +19,7 @@ pub use arithmetic::*;
 pub use convolution::*;
 pub use cumulative::*;
 pub use factory::*;
+pub use io::*;
 pub use logical::*;
 pub use other::*;
 pub use quantization::*;
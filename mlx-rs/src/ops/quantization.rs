@@ -78,6 +78,67 @@ pub fn quantized_matmul_device(
     })
 }
 
+/// Perform the matrix multiplication with the quantized matrix `w` gathered with matrix-level
+/// indices, ie. the quantized equivalent of [`gather_mm`](crate::ops::gather_mm).
+///
+/// # Params
+///
+/// - `x`: input array
+/// - `w`: quantized matrix packed in unsigned integers
+/// - `scales`: scales to use per `group_size` elements of `w`
+/// - `biases`: biases to use per `group_size` elements of `w`
+/// - `lhs_indices`: integer indices for `x`. Default to the batch indices of `x`
+/// - `rhs_indices`: integer indices for `w`. Default to the batch indices of `w`
+/// - `transpose`: whether to multiply with the transposed `w` or not (default: `true`)
+/// - `group_size`: the size of the group in `w` that shares a scale and bias (default: `64`)
+/// - `bits`: the number of bits occupied by each element in `w` (default: `4`)
+#[allow(clippy::too_many_arguments)]
+#[generate_macro]
+#[default_device]
+pub fn gather_qmm_device<'lhs, 'rhs>(
+    x: impl AsRef<Array>,
+    w: impl AsRef<Array>,
+    scales: impl AsRef<Array>,
+    biases: impl AsRef<Array>,
+    #[optional] lhs_indices: impl Into<Option<&'lhs Array>>,
+    #[optional] rhs_indices: impl Into<Option<&'rhs Array>>,
+    #[optional] transpose: impl Into<Option<bool>>,
+    #[optional] group_size: impl Into<Option<i32>>,
+    #[optional] bits: impl Into<Option<i32>>,
+    #[optional] stream: impl AsRef<Stream>,
+) -> Result<Array> {
+    let transpose = transpose.into().unwrap_or(true);
+    let group_size = group_size.into().unwrap_or(64);
+    let bits = bits.into().unwrap_or(4);
+
+    unsafe {
+        let lhs_indices_ptr = lhs_indices
+            .into()
+            .map(|i| i.as_ptr())
+            .unwrap_or(mlx_sys::mlx_array_new());
+        let rhs_indices_ptr = rhs_indices
+            .into()
+            .map(|i| i.as_ptr())
+            .unwrap_or(mlx_sys::mlx_array_new());
+
+        <Array as Guarded>::try_from_op(|res| {
+            mlx_sys::mlx_gather_qmm(
+                res,
+                x.as_ref().as_ptr(),
+                w.as_ref().as_ptr(),
+                scales.as_ref().as_ptr(),
+                biases.as_ref().as_ptr(),
+                lhs_indices_ptr,
+                rhs_indices_ptr,
+                transpose,
+                group_size,
+                bits,
+                stream.as_ref().as_ptr(),
+            )
+        })
+    }
+}
+
 /// Dequantize the matrix `w` using the provided `scales` and `biases` and the `group_size` and
 /// `bits` configuration.
 ///
@@ -112,7 +173,8 @@ pub fn dequantize_device(
 #[cfg(test)]
 mod tests {
     use crate::{
-        ops::{dequantize, expand_dims, quantize},
+        array,
+        ops::{dequantize, expand_dims, gather_mm, gather_qmm, quantize},
         Array,
     };
 
@@ -134,4 +196,22 @@ mod tests {
             assert!(max_diff <= 127.0 / (1 << i) as f32);
         }
     }
+
+    #[test]
+    fn test_gather_qmm() {
+        let x = crate::random::normal::<f32>(&[4, 1, 64], None, None, None).unwrap();
+        let w = crate::random::normal::<f32>(&[3, 32, 64], None, None, None).unwrap();
+        let indices = array!([2u32, 0, 1, 2]);
+
+        let (w_q, scales, biases) = quantize(&w, 64, 4).unwrap();
+        let w_hat = dequantize(&w_q, &scales, &biases, 64, 4).unwrap();
+
+        let out = gather_qmm(&x, &w_q, &scales, &biases, None, &indices, true, 64, 4).unwrap();
+        let expected = gather_mm(&x, w_hat.swap_axes(-1, -2).unwrap(), None, &indices).unwrap();
+        assert_eq!(out.shape(), &[4, 1, 32]);
+        assert!(out
+            .all_close(&expected, 1e-4, 1e-4, None)
+            .unwrap()
+            .item::<bool>());
+    }
 }
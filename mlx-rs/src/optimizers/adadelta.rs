@@ -26,10 +26,14 @@ generate_builder! {
         root = crate
     )]
     pub struct AdaDelta {
-        /// The learning rate
-        #[builder(ty_override = f32)]
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
         pub lr: Array,
 
+        /// Schedule used to update [`AdaDelta::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
         /// The coefficient used for computing a running average of squared gradients. Default to
         /// [`AdaDelta::DEFAULT_RHO`].
         #[builder(optional, ty_override = f32, default = AdaDelta::DEFAULT_RHO)]
@@ -42,12 +46,13 @@ generate_builder! {
 
         /// Inner state
         #[builder(ignore)]
-        pub state: State<(Array, Array)>,
+        pub state: StepState<State<(Array, Array)>>,
     }
 }
 
 /// Builds a new [`AdaDelta`] optimizer
 fn build_adadelta(builder: AdaDeltaBuilder) -> Result<AdaDelta, AdaDeltaBuildError> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
     let rho = builder.rho;
     let eps = builder.eps;
 
@@ -60,10 +65,11 @@ fn build_adadelta(builder: AdaDeltaBuilder) -> Result<AdaDelta, AdaDeltaBuildErr
     }
 
     Ok(AdaDelta {
-        lr: array!(builder.lr),
+        lr,
+        lr_schedule,
         rho: array!(rho),
         eps: array!(eps),
-        state: State::new(),
+        state: StepState::default(),
     })
 }
 
@@ -76,7 +82,7 @@ impl AdaDelta {
 }
 
 impl Optimizer for AdaDelta {
-    type State = State<(Array, Array)>;
+    type State = StepState<State<(Array, Array)>>;
 
     fn state(&self) -> &Self::State {
         &self.state
@@ -86,13 +92,22 @@ impl Optimizer for AdaDelta {
         &mut self.state
     }
 
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
     fn update_single(
         &mut self,
         key: &Rc<str>,
         gradient: &Array,
         parameter: &mut Array,
     ) -> crate::error::Result<()> {
-        let (v, u) = get_mut_or_insert_with(&mut self.state, key, || (array!(0.0), array!(0.0)));
+        let (v, u) =
+            get_mut_or_insert_with(&mut self.state.params, key, || (array!(0.0), array!(0.0)));
 
         let one_minus_rho = array!(1.0).subtract(&self.rho)?;
         let first_term = self.rho.multiply(&v)?;
@@ -121,19 +136,25 @@ impl Updatable for AdaDelta {
     fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .flat_map(|(_, (v, u))| vec![v, u])
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
     }
 
     fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter_mut()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .flat_map(|(_, (v, u))| vec![v, u])
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
     }
 }
 
@@ -168,9 +168,39 @@ impl AdafactorState {
     }
 }
 
-/// `Option<Array>`. Type alias for the learning rate used in Adafactor builder due to limitation in
-/// the `generate_builder` macro
-pub type AdafactorBuilderLr = Option<f32>;
+/// Optional [`LearningRate`] accepted by the Adafactor builder.
+///
+/// This is a new type rather than `Option<LearningRate>` due to limitation in the
+/// `generate_builder` macro, so that the builder accepts a constant or a [`Scheduler`] directly.
+#[derive(Debug, Clone)]
+pub struct AdafactorBuilderLr(pub Option<LearningRate>);
+
+impl From<f32> for AdafactorBuilderLr {
+    fn from(value: f32) -> Self {
+        Self(Some(value.into()))
+    }
+}
+
+impl From<Option<f32>> for AdafactorBuilderLr {
+    fn from(value: Option<f32>) -> Self {
+        Self(value.map(LearningRate::from))
+    }
+}
+
+impl From<LearningRate> for AdafactorBuilderLr {
+    fn from(value: LearningRate) -> Self {
+        Self(Some(value))
+    }
+}
+
+impl<S> From<S> for AdafactorBuilderLr
+where
+    S: Scheduler + 'static,
+{
+    fn from(schedule: S) -> Self {
+        Self(Some(schedule.into()))
+    }
+}
 
 /// Type alias for the learning rate used in Adafactor
 pub type AdafactorLr = Option<Array>;
@@ -196,9 +226,14 @@ generate_builder! {
         root = crate
     )]
     pub struct Adafactor {
-        /// The learning rate.
-        #[builder(optional, default = Adafactor::DEFAULT_LR)]
-        pub lr: Option<f32>,
+        /// The current learning rate, `None` if only the relative step size is used. The builder
+        /// accepts either a constant or a [`Scheduler`].
+        #[builder(optional, ty_override = AdafactorBuilderLr, default = Adafactor::DEFAULT_LR)]
+        pub lr: AdafactorLr,
+
+        /// Schedule used to update [`Adafactor::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
 
         /// The first term is added to the square of the gradients to improve numerical stability.
         /// Default to [`Adafactor::DEFAULT_EPS`].
@@ -239,7 +274,7 @@ generate_builder! {
 
         /// Inner state.
         #[builder(ignore)]
-        pub state: State<AdafactorState>,
+        pub state: StepState<State<AdafactorState>>,
     }
 }
 
@@ -253,12 +288,21 @@ fn build_adafactor(builder: AdafactorBuilder) -> Result<Adafactor, AdafactorBuil
     let relative_step = builder.relative_step;
     let warmup_init = builder.warmup_init;
 
-    if builder.lr.is_none() && !relative_step {
+    let (lr, lr_schedule) = match builder.lr.0 {
+        Some(lr) => {
+            let (lr, lr_schedule) = lr.into_parts()?;
+            (Some(lr), lr_schedule)
+        }
+        None => (None, None),
+    };
+
+    if lr.is_none() && !relative_step {
         return Err(AdafactorBuildError::LrIsNoneAndRelativeStepIsFalse);
     }
 
     Ok(Adafactor {
-        lr: builder.lr,
+        lr,
+        lr_schedule,
         eps: (array!(eps.0), array!(eps.1)),
         clip_threshold: array!(clip_threshold),
         decay_rate: array!(decay_rate),
@@ -267,13 +311,13 @@ fn build_adafactor(builder: AdafactorBuilder) -> Result<Adafactor, AdafactorBuil
         scale_parameter,
         relative_step,
         warmup_init,
-        state: State::new(),
+        state: StepState::default(),
     })
 }
 
 impl Adafactor {
     /// Default value for `lr`
-    pub const DEFAULT_LR: Option<f32> = None;
+    pub const DEFAULT_LR: AdafactorBuilderLr = AdafactorBuilderLr(None);
 
     /// Default values for `eps`
     pub const DEFAULT_EPS: (f32, f32) = (1e-30, 1e-3);
@@ -315,7 +359,7 @@ fn get_mut_or_insert_with<'a, T, E>(
 fn compute_lr(
     relative_step: bool,
     warmup_init: bool,
-    lr: Option<f32>,
+    lr: Option<&Array>,
     scale_parameter: bool,
     eps: &(Array, Array),
     step: &Array,
@@ -332,7 +376,8 @@ fn compute_lr(
         minimum(min_step, array!(1.0) / sqrt(step)?)?
     } else {
         // SAFETY: This is already checked in the `build` stage.
-        array!(lr.expect("The learning rate should be set if the relative step is not enabled"))
+        lr.expect("The learning rate should be set if the relative step is not enabled")
+            .clone()
     };
 
     let mut parameter_scale = array!(1.0);
@@ -344,7 +389,7 @@ fn compute_lr(
 }
 
 impl Optimizer for Adafactor {
-    type State = State<AdafactorState>;
+    type State = StepState<State<AdafactorState>>;
 
     fn state(&self) -> &Self::State {
         &self.state
@@ -354,6 +399,14 @@ impl Optimizer for Adafactor {
         &mut self.state
     }
 
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        match &mut self.lr {
+            Some(lr) => advance_step(&mut self.state.step, lr, self.lr_schedule.as_ref()),
+            // The relative step size does not depend on a schedule
+            None => increment_step(&mut self.state.step),
+        }
+    }
+
     fn update_single(
         &mut self,
         key: &std::rc::Rc<str>,
@@ -361,7 +414,7 @@ impl Optimizer for Adafactor {
         parameter: &mut Array,
     ) -> crate::error::Result<()> {
         let beta1_is_some = self.beta1.is_some();
-        let state = get_mut_or_insert_with(&mut self.state, key, || {
+        let state = get_mut_or_insert_with(&mut self.state.params, key, || {
             AdafactorState::new(parameter, beta1_is_some)
         })?;
 
@@ -375,7 +428,7 @@ impl Optimizer for Adafactor {
         let lr = compute_lr(
             self.relative_step,
             self.warmup_init,
-            self.lr,
+            self.lr.as_ref(),
             self.scale_parameter,
             &self.eps,
             step,
@@ -443,7 +496,9 @@ impl Updatable for Adafactor {
     fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
         use itertools::Itertools;
 
-        self.state
+        let states = self
+            .state
+            .params
             .iter()
             .sorted_by(|a, b| a.0.cmp(b.0))
             .flat_map(|(_, v)| {
@@ -457,13 +512,18 @@ impl Updatable for Adafactor {
                 .into_iter()
                 .filter_map(|v| v.as_ref())
                 .collect::<Vec<_>>()
-            })
+            });
+
+        std::iter::once(&self.state.step)
+            .chain(self.lr.as_ref())
+            .chain(states)
     }
 
     fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
         use itertools::Itertools;
 
-        self.state
+        let StepState { step, params } = &mut self.state;
+        let states = params
             .iter_mut()
             .sorted_by(|a, b| a.0.cmp(b.0))
             .flat_map(|(_, v)| {
@@ -477,7 +537,9 @@ impl Updatable for Adafactor {
                 .into_iter()
                 .filter_map(|v| v.as_mut())
                 .collect::<Vec<_>>()
-            })
+            });
+
+        std::iter::once(step).chain(self.lr.as_mut()).chain(states)
     }
 }
 
@@ -1,6 +1,6 @@
-use std::{convert::Infallible, rc::Rc};
+use std::rc::Rc;
 
-use crate::{array, ops::square, utils::Updatable, Array};
+use crate::{array, error::Exception, ops::square, utils::Updatable, Array};
 use mlx_internal_macros::{generate_builder, Buildable};
 
 use crate::utils::get_mut_or_insert_with;
@@ -18,13 +18,19 @@ generate_builder! {
     #[buildable(root = crate)]
     #[builder(
         build_with = build_adagrad,
+        default_infallible,
+        err = Exception,
         root = crate
     )]
     pub struct AdaGrad {
-        /// Learning rate
-        #[builder(ty_override = f32)]
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
         pub lr: Array,
 
+        /// Schedule used to update [`AdaGrad::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
         /// The epsilon added to the denominator to improve numerical stability. Default to
         /// [`AdaGrad::DEFAULT_EPS`].
         #[builder(optional, ty_override = f32, default = AdaGrad::DEFAULT_EPS)]
@@ -32,18 +38,20 @@ generate_builder! {
 
         /// Inner state
         #[builder(ignore)]
-        pub state: State,
+        pub state: StepState,
     }
 }
 
 /// Builds a new [`AdaGrad`].
-fn build_adagrad(builder: AdaGradBuilder) -> Result<AdaGrad, Infallible> {
+fn build_adagrad(builder: AdaGradBuilder) -> Result<AdaGrad, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
     let eps = array!(builder.eps);
 
     Ok(AdaGrad {
-        lr: array!(builder.lr),
+        lr,
+        lr_schedule,
         eps,
-        state: State::new(),
+        state: StepState::default(),
     })
 }
 
@@ -53,7 +61,7 @@ impl AdaGrad {
 }
 
 impl Optimizer for AdaGrad {
-    type State = State;
+    type State = StepState;
 
     fn state(&self) -> &Self::State {
         &self.state
@@ -63,13 +71,21 @@ impl Optimizer for AdaGrad {
         &mut self.state
     }
 
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
     fn update_single(
         &mut self,
         key: &Rc<str>,
         gradient: &Array,
         parameter: &mut Array,
     ) -> crate::error::Result<()> {
-        let state = get_mut_or_insert_with(&mut self.state, key, || array!(0.0));
+        let state = get_mut_or_insert_with(&mut self.state.params, key, || array!(0.0));
 
         let v = state.add(square(gradient)?)?;
 
@@ -88,19 +104,25 @@ impl Updatable for AdaGrad {
     fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .map(|(_, v)| v)
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
     }
 
     fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter_mut()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .map(|(_, v)| v)
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
     }
 }
 
@@ -1,8 +1,6 @@
-use std::convert::Infallible;
-
 use mlx_internal_macros::{generate_builder, Buildable};
 
-use crate::{array, utils::get_mut_or_insert_with};
+use crate::{array, error::Exception, utils::get_mut_or_insert_with};
 
 use super::*;
 
@@ -20,13 +18,19 @@ generate_builder! {
     #[buildable(root = crate)]
     #[builder(
         build_with = build_adam,
+        default_infallible,
+        err = Exception,
         root = crate
     )]
     pub struct Adam {
-        /// The learning rate
-        #[builder(ty_override = f32)]
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
         pub lr: Array,
 
+        /// Schedule used to update [`Adam::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
         /// The coefficients used for computing running averages of the gradient and its square
         ///
         /// Default to [`Adam::DEFAULT_BETAS`]
@@ -41,21 +45,22 @@ generate_builder! {
 
         /// Inner state
         #[builder(ignore)]
-        pub state: State<(Array, Array)>,
+        pub state: StepState<State<(Array, Array)>>,
     }
 }
 
 /// Builds a new [`Adam`].
-fn build_adam(builder: AdamBuilder) -> Result<Adam, Infallible> {
-    let lr = array!(builder.lr);
+fn build_adam(builder: AdamBuilder) -> Result<Adam, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
     let betas = builder.betas;
     let eps = array!(builder.eps);
 
     Ok(Adam {
         lr,
+        lr_schedule,
         betas: (array!(betas.0), array!(betas.1)),
         eps,
-        state: State::new(),
+        state: StepState::default(),
     })
 }
 
@@ -68,7 +73,7 @@ impl Adam {
 }
 
 impl Optimizer for Adam {
-    type State = State<(Array, Array)>;
+    type State = StepState<State<(Array, Array)>>;
 
     fn state(&self) -> &Self::State {
         &self.state
@@ -78,6 +83,14 @@ impl Optimizer for Adam {
         &mut self.state
     }
 
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
     fn update_single(
         &mut self,
         key: &Rc<str>,
@@ -85,7 +98,8 @@ impl Optimizer for Adam {
         parameter: &mut Array,
     ) -> crate::error::Result<()> {
         let betas = &self.betas;
-        let state = get_mut_or_insert_with(&mut self.state, key, || (array!(0.0), array!(0.0)));
+        let state =
+            get_mut_or_insert_with(&mut self.state.params, key, || (array!(0.0), array!(0.0)));
 
         let (new_parameter, new_state) =
             adam_apply_single(&self.lr, betas, &self.eps, gradient, parameter, state)?;
@@ -127,19 +141,25 @@ impl Updatable for Adam {
     fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .flat_map(|(_, (v, u))| vec![v, u])
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
     }
 
     fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter_mut()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .flat_map(|(_, (v, u))| vec![v, u])
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
     }
 }
 
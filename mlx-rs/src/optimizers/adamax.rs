@@ -1,9 +1,10 @@
-use std::{convert::Infallible, rc::Rc};
+use std::rc::Rc;
 
 use mlx_internal_macros::{generate_builder, Buildable};
 
 use crate::{
     array,
+    error::Exception,
     ops::{abs, maximum},
     utils::{get_mut_or_insert_with, Updatable},
     Array,
@@ -22,13 +23,19 @@ generate_builder! {
     #[buildable(root = crate)]
     #[builder(
         build_with = build_adamax,
+        default_infallible,
+        err = Exception,
         root = crate
     )]
     pub struct Adamax {
-        /// The learning rate.
-        #[builder(ty_override = f32)]
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
         pub lr: Array,
 
+        /// Schedule used to update [`Adamax::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
         /// The beta coefficients
         #[builder(optional, ty_override = Betas, default = Adamax::DEFAULT_BETAS)]
         pub betas: (Array, Array),
@@ -39,20 +46,21 @@ generate_builder! {
 
         /// Inner state.
         #[builder(ignore)]
-        pub state: State<(Array, Array)>,
+        pub state: StepState<State<(Array, Array)>>,
     }
 }
 
-fn build_adamax(builder: AdamaxBuilder) -> Result<Adamax, Infallible> {
-    let lr = builder.lr;
+fn build_adamax(builder: AdamaxBuilder) -> Result<Adamax, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
     let betas = builder.betas;
     let eps = builder.eps;
 
     Ok(Adamax {
-        lr: array!(lr),
+        lr,
+        lr_schedule,
         betas: (array!(betas.0), array!(betas.1)),
         eps: array!(eps),
-        state: State::new(),
+        state: StepState::default(),
     })
 }
 
@@ -65,7 +73,7 @@ impl Adamax {
 }
 
 impl Optimizer for Adamax {
-    type State = State<(Array, Array)>;
+    type State = StepState<State<(Array, Array)>>;
 
     fn state(&self) -> &Self::State {
         &self.state
@@ -75,6 +83,14 @@ impl Optimizer for Adamax {
         &mut self.state
     }
 
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
     fn update_single(
         &mut self,
         key: &Rc<str>,
@@ -82,7 +98,8 @@ impl Optimizer for Adamax {
         parameter: &mut Array,
     ) -> crate::error::Result<()> {
         let (b1, b2) = &self.betas;
-        let (m, v) = get_mut_or_insert_with(&mut self.state, key, || (array!(0.0), array!(0.0)));
+        let (m, v) =
+            get_mut_or_insert_with(&mut self.state.params, key, || (array!(0.0), array!(0.0)));
 
         let one_minus_b1 = array!(1.0).subtract(b1)?;
         let new_m = b1.multiply(&*m)?.add(&one_minus_b1.multiply(gradient)?)?;
@@ -103,19 +120,25 @@ impl Updatable for Adamax {
     fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .flat_map(|(_, (v, u))| vec![v, u])
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
     }
 
     fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter_mut()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .flat_map(|(_, (v, u))| vec![v, u])
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
     }
 }
 
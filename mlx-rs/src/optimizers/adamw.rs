@@ -1,9 +1,8 @@
-use std::convert::Infallible;
-
 use mlx_internal_macros::{generate_builder, Buildable};
 
 use crate::{
     array,
+    error::Exception,
     utils::{get_mut_or_insert_with, Updatable},
     Array,
 };
@@ -22,13 +21,19 @@ generate_builder! {
     #[buildable(root = crate)]
     #[builder(
         build_with = build_adamw,
+        default_infallible,
+        err = Exception,
         root = crate
     )]
     pub struct AdamW {
-        /// The learning rate.
-        #[builder(ty_override = f32)]
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
         pub lr: Array,
 
+        /// Schedule used to update [`AdamW::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
         /// The coefficients used for computing running averages of the gradient and its square.
         ///
         /// Default to [`AdamW::DEFAULT_BETAS`].
@@ -49,23 +54,24 @@ generate_builder! {
 
         /// Inner state.
         #[builder(ignore)]
-        pub state: State<(Array, Array)>,
+        pub state: StepState<State<(Array, Array)>>,
     }
 }
 
 /// Builds a new [`AdamW`] optimizer.
-fn build_adamw(builder: AdamWBuilder) -> Result<AdamW, Infallible> {
-    let lr = builder.lr;
+fn build_adamw(builder: AdamWBuilder) -> Result<AdamW, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
     let betas = builder.betas;
     let eps = builder.eps;
     let weight_decay = builder.weight_decay;
 
     Ok(AdamW {
-        lr: array!(lr),
+        lr,
+        lr_schedule,
         betas: (array!(betas.0), array!(betas.1)),
         eps: array!(eps),
         weight_decay: array!(weight_decay),
-        state: State::new(),
+        state: StepState::default(),
     })
 }
 
@@ -81,7 +87,7 @@ impl AdamW {
 }
 
 impl Optimizer for AdamW {
-    type State = State<(Array, Array)>;
+    type State = StepState<State<(Array, Array)>>;
 
     fn state(&self) -> &Self::State {
         &self.state
@@ -91,6 +97,14 @@ impl Optimizer for AdamW {
         &mut self.state
     }
 
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
     fn update_single(
         &mut self,
         key: &std::rc::Rc<str>,
@@ -98,7 +112,8 @@ impl Optimizer for AdamW {
         parameter: &mut Array,
     ) -> Result<(), crate::error::Exception> {
         let betas = &self.betas;
-        let state = get_mut_or_insert_with(&mut self.state, key, || (array!(0.0), array!(0.0)));
+        let state =
+            get_mut_or_insert_with(&mut self.state.params, key, || (array!(0.0), array!(0.0)));
 
         // SAFETY: These are all single-element arrays and won't panic.
         let one_minus_lr_wd = array!(1.0) - (&self.lr * &self.weight_decay);
@@ -124,19 +139,25 @@ impl Updatable for AdamW {
     fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .flat_map(|(_, (v, u))| vec![v, u])
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
     }
 
     fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter_mut()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .flat_map(|(_, (v, u))| vec![v, u])
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
     }
 }
 
@@ -0,0 +1,277 @@
+use mlx_internal_macros::{generate_builder, Buildable};
+
+use crate::{
+    array,
+    error::Exception,
+    utils::{get_mut_or_insert_with, Updatable},
+    Array,
+};
+
+use super::*;
+
+/// `(f32, f32, f32)`. Type alias for betas in the Adan optimizer builder due to limitation in the
+/// `generate_builder` macro
+pub type AdanBetas = (f32, f32, f32);
+
+/// State of the Adan optimizer for a single parameter.
+#[derive(Debug, Clone)]
+pub struct AdanState {
+    /// Running average of the gradient
+    pub exp_avg: Array,
+
+    /// Running average of the gradient difference
+    pub exp_avg_diff: Array,
+
+    /// Running average of the squared Nesterov gradient
+    pub exp_avg_sq: Array,
+
+    /// Gradient of the previous step
+    pub prev_grad: Array,
+}
+
+impl AdanState {
+    fn new(gradient: &Array) -> Self {
+        Self {
+            exp_avg: array!(0.0),
+            exp_avg_diff: array!(0.0),
+            exp_avg_sq: array!(0.0),
+            prev_grad: gradient.clone(),
+        }
+    }
+}
+
+impl OptimizerState for State<AdanState> {
+    type UnflattenError = UnflattenError;
+
+    fn flatten(&self) -> impl Iterator<Item = (Rc<str>, &Array)> {
+        self.iter().flat_map(|(k, v)| {
+            [
+                (Rc::from(format!("{}.exp_avg", k)), &v.exp_avg),
+                (Rc::from(format!("{}.exp_avg_diff", k)), &v.exp_avg_diff),
+                (Rc::from(format!("{}.exp_avg_sq", k)), &v.exp_avg_sq),
+                (Rc::from(format!("{}.prev_grad", k)), &v.prev_grad),
+            ]
+        })
+    }
+
+    fn flatten_mut(&mut self) -> impl Iterator<Item = (Rc<str>, &mut Array)> {
+        self.iter_mut().flat_map(|(k, v)| {
+            [
+                (Rc::from(format!("{}.exp_avg", k)), &mut v.exp_avg),
+                (Rc::from(format!("{}.exp_avg_diff", k)), &mut v.exp_avg_diff),
+                (Rc::from(format!("{}.exp_avg_sq", k)), &mut v.exp_avg_sq),
+                (Rc::from(format!("{}.prev_grad", k)), &mut v.prev_grad),
+            ]
+        })
+    }
+
+    fn unflatten<I, K>(input: I) -> Result<Self, Self::UnflattenError>
+    where
+        Self: Sized,
+        I: IntoIterator<Item = (K, Array)>,
+        K: Ord + AsRef<str> + Into<Rc<str>>,
+    {
+        let mut parts: HashMap<Rc<str>, [Option<Array>; 4]> = HashMap::new();
+        for (k, v) in input {
+            let (prefix, suffix) = k
+                .as_ref()
+                .rsplit_once('.')
+                .ok_or(UnflattenError::InvalidKey)?;
+
+            let index = match suffix {
+                "exp_avg" => 0,
+                "exp_avg_diff" => 1,
+                "exp_avg_sq" => 2,
+                "prev_grad" => 3,
+                _ => return Err(UnflattenError::InvalidKey),
+            };
+            parts.entry(Rc::from(prefix)).or_default()[index] = Some(v);
+        }
+
+        parts
+            .into_iter()
+            .map(|(k, [exp_avg, exp_avg_diff, exp_avg_sq, prev_grad])| {
+                let state = AdanState {
+                    exp_avg: exp_avg.ok_or(UnflattenError::ExpectingNextPair)?,
+                    exp_avg_diff: exp_avg_diff.ok_or(UnflattenError::ExpectingNextPair)?,
+                    exp_avg_sq: exp_avg_sq.ok_or(UnflattenError::ExpectingNextPair)?,
+                    prev_grad: prev_grad.ok_or(UnflattenError::ExpectingNextPair)?,
+                };
+                Ok((k, state))
+            })
+            .collect()
+    }
+}
+
+generate_builder! {
+    /// The Adan optimizer [1].
+    ///
+    /// Adan estimates the first and second moments of the Nesterov momentum by also keeping a
+    /// running average of the difference between consecutive gradients. The weight decay is
+    /// applied as a proximal step.
+    ///
+    /// [1]: Xie, X. et al., 2024. Adan: Adaptive Nesterov momentum algorithm for faster optimizing
+    ///     deep models. IEEE TPAMI.
+    #[derive(Debug, Clone, Buildable)]
+    #[buildable(root = crate)]
+    #[builder(
+        build_with = build_adan,
+        default_infallible,
+        err = Exception,
+        root = crate
+    )]
+    pub struct Adan {
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
+        pub lr: Array,
+
+        /// Schedule used to update [`Adan::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
+        /// The coefficients used for computing running averages of the gradient, the gradient
+        /// difference and the squared Nesterov gradient.
+        ///
+        /// Default to [`Adan::DEFAULT_BETAS`].
+        #[builder(optional, ty_override = AdanBetas, default = Adan::DEFAULT_BETAS)]
+        pub betas: (Array, Array, Array),
+
+        /// The epsilon added to the denominator to improve numerical stability.
+        ///
+        /// Default to [`Adan::DEFAULT_EPS`].
+        #[builder(optional, ty_override = f32, default = Adan::DEFAULT_EPS)]
+        pub eps: Array,
+
+        /// The weight decay.
+        ///
+        /// Default to [`Adan::DEFAULT_WEIGHT_DECAY`].
+        #[builder(optional, ty_override = f32, default = Adan::DEFAULT_WEIGHT_DECAY)]
+        pub weight_decay: Array,
+
+        /// Inner state.
+        #[builder(ignore)]
+        pub state: StepState<State<AdanState>>,
+    }
+}
+
+/// Builds a new [`Adan`] optimizer.
+fn build_adan(builder: AdanBuilder) -> Result<Adan, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
+    let betas = builder.betas;
+
+    Ok(Adan {
+        lr,
+        lr_schedule,
+        betas: (array!(betas.0), array!(betas.1), array!(betas.2)),
+        eps: array!(builder.eps),
+        weight_decay: array!(builder.weight_decay),
+        state: StepState::default(),
+    })
+}
+
+impl Adan {
+    /// Default value for `betas`.
+    pub const DEFAULT_BETAS: (f32, f32, f32) = (0.98, 0.92, 0.99);
+
+    /// Default value for `eps`.
+    pub const DEFAULT_EPS: f32 = 1e-8;
+
+    /// Default value for `weight_decay`.
+    pub const DEFAULT_WEIGHT_DECAY: f32 = 0.0;
+}
+
+impl Optimizer for Adan {
+    type State = StepState<State<AdanState>>;
+
+    fn state(&self) -> &Self::State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut Self::State {
+        &mut self.state
+    }
+
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
+    fn update_single(
+        &mut self,
+        key: &Rc<str>,
+        gradient: &Array,
+        parameter: &mut Array,
+    ) -> crate::error::Result<()> {
+        let (b1, b2, b3) = &self.betas;
+        let step = &self.state.step;
+        let state =
+            get_mut_or_insert_with(&mut self.state.params, key, || AdanState::new(gradient));
+
+        let one = array!(1.0);
+        let diff = gradient.subtract(&state.prev_grad)?;
+        let nesterov_grad = gradient.add(b2.multiply(&diff)?)?;
+
+        state.exp_avg = b1
+            .multiply(&state.exp_avg)?
+            .add(one.subtract(b1)?.multiply(gradient)?)?;
+        state.exp_avg_diff = b2
+            .multiply(&state.exp_avg_diff)?
+            .add(one.subtract(b2)?.multiply(&diff)?)?;
+        state.exp_avg_sq = b3
+            .multiply(&state.exp_avg_sq)?
+            .add(one.subtract(b3)?.multiply(nesterov_grad.square()?)?)?;
+        state.prev_grad = gradient.clone();
+
+        let m_hat = state.exp_avg.divide(bias_correction(b1, step)?)?;
+        let v_hat = state.exp_avg_diff.divide(bias_correction(b2, step)?)?;
+        let n_hat = state.exp_avg_sq.divide(bias_correction(b3, step)?)?;
+
+        let update = m_hat
+            .add(b2.multiply(&v_hat)?)?
+            .divide(n_hat.sqrt()?.add(&self.eps)?)?;
+
+        *parameter = parameter
+            .subtract(self.lr.multiply(&update)?)?
+            .divide(one.add(self.lr.multiply(&self.weight_decay)?)?)?;
+
+        Ok(())
+    }
+}
+
+impl Updatable for Adan {
+    fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+        use itertools::Itertools;
+
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, v)| [&v.exp_avg, &v.exp_avg_diff, &v.exp_avg_sq, &v.prev_grad]),
+        )
+    }
+
+    fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+        use itertools::Itertools;
+
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, v)| {
+                    [
+                        &mut v.exp_avg,
+                        &mut v.exp_avg_diff,
+                        &mut v.exp_avg_sq,
+                        &mut v.prev_grad,
+                    ]
+                }),
+        )
+    }
+}
+
+impl_updatable_for_mut_optimizer!(Adan);
@@ -0,0 +1,90 @@
+use std::rc::Rc;
+
+use crate::{error::EmaBuildError, module::ModuleParameters, utils::Updatable, Array};
+
+use super::*;
+
+/// Keeps an exponential moving average of the parameters updated by the wrapped optimizer.
+///
+/// After every update, the average is updated as `ema = decay * ema + (1 - decay) * parameter`.
+/// The averaged parameters are usually better for evaluation and can be swapped into the model
+/// with [`Ema::swap_parameters`]:
+///
+/// ```rust,ignore
+/// let mut optimizer = Ema::new(AdamW::new(1e-3), 0.999)?;
+///
+/// // training loop
+/// optimizer.update(&mut model, gradients)?;
+///
+/// // evaluate with the averaged parameters and swap the training parameters back
+/// optimizer.swap_parameters(&mut model);
+/// evaluate(&mut model)?;
+/// optimizer.swap_parameters(&mut model);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Ema<O: Optimizer> {
+    /// The wrapped optimizer
+    pub inner: O,
+
+    /// The decay of the moving average
+    pub decay: Array,
+
+    /// Inner state
+    pub state: ShadowState<O::State>,
+}
+
+impl<O> Ema<O>
+where
+    O: Optimizer,
+    O::State: Default,
+{
+    /// Default value for `decay`.
+    pub const DEFAULT_DECAY: f32 = 0.999;
+
+    /// Wraps `inner` and keeps a moving average of the parameters with the given `decay`.
+    pub fn new(mut inner: O, decay: f32) -> Result<Self, EmaBuildError> {
+        if !(0.0..=1.0).contains(&decay) {
+            return Err(EmaBuildError::InvalidDecay(decay));
+        }
+
+        let state = ShadowState {
+            inner: std::mem::take(inner.state_mut()),
+            ..Default::default()
+        };
+
+        Ok(Self {
+            inner,
+            decay: array!(decay),
+            state,
+        })
+    }
+}
+
+impl<O> Ema<O>
+where
+    O: Optimizer,
+{
+    /// Swaps the averaged parameters with the parameters of the model.
+    ///
+    /// Calling this a second time restores the training parameters, which must be done before the
+    /// next update.
+    pub fn swap_parameters<M>(&mut self, model: &mut M)
+    where
+        M: ModuleParameters,
+    {
+        self.state.swap_parameters(model)
+    }
+
+    fn update_shadow(&mut self, key: &Rc<str>, parameter: &mut Array) -> crate::error::Result<()> {
+        if let Some(average) = self.state.shadow.get_mut(key) {
+            let one_minus_decay = array!(1.0).subtract(&self.decay)?;
+            *average = self
+                .decay
+                .multiply(&*average)?
+                .add(one_minus_decay.multiply(&*parameter)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl_shadow_optimizer!(Ema);
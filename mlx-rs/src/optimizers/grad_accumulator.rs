@@ -0,0 +1,250 @@
+use std::{borrow::Borrow, collections::HashMap, rc::Rc};
+
+use mlx_internal_macros::{generate_builder, Buildable};
+
+use crate::{
+    array, error::GradientAccumulatorBuildError, module::FlattenedModuleParam, transforms::eval,
+    Array,
+};
+
+/// How the accumulated gradients are reduced across micro-batches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientReduction {
+    /// The gradients are summed.
+    Sum,
+
+    /// The gradients are averaged over the number of accumulated micro-batches.
+    Mean,
+}
+
+generate_builder! {
+    /// Accumulates gradients over several micro-batches before they are applied by an
+    /// [`Optimizer`](super::Optimizer).
+    ///
+    /// The gradients are accumulated lazily and the running sum is evaluated every
+    /// [`GradientAccumulator::eval_every`] micro-batches so that the graph doesn't grow without
+    /// bound. A key that is missing from a micro-batch is treated as a zero gradient, so with
+    /// [`GradientReduction::Mean`] it is still divided by the total number of micro-batches.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let mut accumulator = GradientAccumulator::new(4)?;
+    ///
+    /// for (x, y) in micro_batches {
+    ///     let (_, grads) = loss_and_grad(&mut model, (&x, &y))?;
+    ///     accumulator.accumulate(&grads)?;
+    ///
+    ///     if accumulator.is_ready() {
+    ///         let grads = accumulator.take()?;
+    ///         let (grads, _) = clip_grad_norm(&grads, 1.0)?;
+    ///         let grads: FlattenedModuleParam = grads
+    ///             .into_iter()
+    ///             .map(|(k, v)| (k, v.into_owned()))
+    ///             .collect();
+    ///         optimizer.update(&mut model, grads)?;
+    ///     }
+    /// }
+    /// ```
+    #[derive(Debug, Clone, Buildable)]
+    #[buildable(root = crate)]
+    #[builder(
+        build_with = build_gradient_accumulator,
+        err = GradientAccumulatorBuildError,
+        root = crate
+    )]
+    pub struct GradientAccumulator {
+        /// Number of micro-batches to accumulate before [`GradientAccumulator::is_ready`] returns
+        /// `true`.
+        pub accumulation_steps: i32,
+
+        /// How the gradients are reduced. Default to [`GradientAccumulator::DEFAULT_REDUCTION`].
+        #[builder(optional, default = GradientAccumulator::DEFAULT_REDUCTION)]
+        pub reduction: GradientReduction,
+
+        /// Evaluate the accumulated gradients every `eval_every` micro-batches. Default to
+        /// [`GradientAccumulator::DEFAULT_EVAL_EVERY`].
+        #[builder(optional, default = GradientAccumulator::DEFAULT_EVAL_EVERY)]
+        pub eval_every: i32,
+
+        /// Sum of the gradients accumulated so far.
+        #[builder(ignore)]
+        pub accumulated: FlattenedModuleParam,
+
+        /// Number of micro-batches accumulated so far.
+        #[builder(ignore)]
+        pub count: i32,
+    }
+}
+
+fn build_gradient_accumulator(
+    builder: GradientAccumulatorBuilder,
+) -> Result<GradientAccumulator, GradientAccumulatorBuildError> {
+    let accumulation_steps = builder.accumulation_steps;
+    let eval_every = builder.eval_every;
+
+    if accumulation_steps <= 0 {
+        return Err(GradientAccumulatorBuildError::NonPositiveAccumulationSteps(
+            accumulation_steps,
+        ));
+    }
+
+    if eval_every <= 0 {
+        return Err(GradientAccumulatorBuildError::NonPositiveEvalEvery(
+            eval_every,
+        ));
+    }
+
+    Ok(GradientAccumulator {
+        accumulation_steps,
+        reduction: builder.reduction,
+        eval_every,
+        accumulated: HashMap::new(),
+        count: 0,
+    })
+}
+
+impl GradientAccumulator {
+    /// Default value for `reduction`.
+    pub const DEFAULT_REDUCTION: GradientReduction = GradientReduction::Mean;
+
+    /// Default value for `eval_every`.
+    pub const DEFAULT_EVAL_EVERY: i32 = 1;
+
+    /// Adds the gradients of a micro-batch.
+    ///
+    /// This accepts both a [`FlattenedModuleParam`] and the
+    /// [`MaybeClippedGrads`](super::MaybeClippedGrads) returned by
+    /// [`clip_grad_norm`](super::clip_grad_norm).
+    pub fn accumulate<V>(&mut self, gradients: &HashMap<Rc<str>, V>) -> crate::error::Result<()>
+    where
+        V: Borrow<Array>,
+    {
+        for (key, gradient) in gradients {
+            let gradient = gradient.borrow();
+            match self.accumulated.get_mut(key) {
+                Some(sum) => *sum = sum.add(gradient)?,
+                None => {
+                    self.accumulated.insert(key.clone(), gradient.clone());
+                }
+            }
+        }
+
+        self.count += 1;
+        if self.count % self.eval_every == 0 {
+            eval(self.accumulated.values())?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` once [`GradientAccumulator::accumulation_steps`] micro-batches have been
+    /// accumulated.
+    pub fn is_ready(&self) -> bool {
+        self.count >= self.accumulation_steps
+    }
+
+    /// Returns `true` if no gradient has been accumulated.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the reduced gradients without resetting the accumulator.
+    pub fn gradients(&self) -> crate::error::Result<FlattenedModuleParam> {
+        match self.reduction {
+            GradientReduction::Sum => Ok(self.accumulated.clone()),
+            GradientReduction::Mean => {
+                let count = array!(self.count.max(1) as f32);
+                self.accumulated
+                    .iter()
+                    .map(|(key, sum)| Ok((key.clone(), sum.divide(&count)?)))
+                    .collect()
+            }
+        }
+    }
+
+    /// Returns the reduced gradients and resets the accumulator.
+    pub fn take(&mut self) -> crate::error::Result<FlattenedModuleParam> {
+        let gradients = self.gradients()?;
+        self.reset();
+        Ok(gradients)
+    }
+
+    /// Discards the accumulated gradients.
+    pub fn reset(&mut self) {
+        self.accumulated.clear();
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap};
+
+    use crate::{
+        array,
+        builder::Builder,
+        module::FlattenedModuleParam,
+        optimizers::{clip_grad_norm, MaybeClippedGrads},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_accumulate_mean_with_missing_keys() {
+        let mut accumulator = GradientAccumulator::new(2).unwrap();
+
+        let mut first: FlattenedModuleParam = HashMap::new();
+        first.insert("a".into(), array!([1.0, 2.0]));
+        first.insert("b".into(), array!(4.0));
+        accumulator.accumulate(&first).unwrap();
+        assert!(!accumulator.is_ready());
+
+        let mut second: FlattenedModuleParam = HashMap::new();
+        second.insert("a".into(), array!([3.0, 4.0]));
+        accumulator.accumulate(&second).unwrap();
+        assert!(accumulator.is_ready());
+
+        let gradients = accumulator.take().unwrap();
+        assert_eq!(gradients["a"], array!([2.0, 3.0]));
+        assert_eq!(gradients["b"], array!(2.0));
+        assert!(accumulator.is_empty());
+        assert!(!accumulator.is_ready());
+    }
+
+    #[test]
+    fn test_accumulate_sum_clipped() {
+        let mut accumulator = GradientAccumulatorBuilder::new(2)
+            .reduction(GradientReduction::Sum)
+            .eval_every(2)
+            .build()
+            .unwrap();
+
+        let mut gradients: FlattenedModuleParam = HashMap::new();
+        gradients.insert("a".into(), array!([3.0, 4.0]));
+
+        let (clipped, _) = clip_grad_norm(&gradients, 1.0).unwrap();
+        accumulator.accumulate(&clipped).unwrap();
+
+        let unclipped: MaybeClippedGrads = gradients
+            .iter()
+            .map(|(k, v)| (k.clone(), Cow::Borrowed(v)))
+            .collect();
+        accumulator.accumulate(&unclipped).unwrap();
+
+        let summed = accumulator.gradients().unwrap();
+        let expected = array!([3.6, 4.8]);
+        assert!(summed["a"]
+            .all_close(&expected, 1e-5, 1e-5, None)
+            .unwrap()
+            .item::<bool>());
+    }
+
+    #[test]
+    fn test_build_zero_steps() {
+        assert_eq!(
+            GradientAccumulator::new(0).unwrap_err(),
+            GradientAccumulatorBuildError::NonPositiveAccumulationSteps(0)
+        );
+    }
+}
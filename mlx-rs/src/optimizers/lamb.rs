@@ -0,0 +1,178 @@
+use mlx_internal_macros::{generate_builder, Buildable};
+
+use crate::{
+    array,
+    error::Exception,
+    linalg::norm,
+    ops::r#where,
+    utils::{get_mut_or_insert_with, Updatable},
+    Array,
+};
+
+use super::*;
+
+generate_builder! {
+    /// The LAMB optimizer [1].
+    ///
+    /// LAMB computes a bias corrected Adam update with decoupled weight decay and scales it
+    /// layer-wise by the trust ratio `||w|| / ||update||`, which allows training with very large
+    /// batch sizes.
+    ///
+    /// [1]: You, Y. et al., 2020. Large batch optimization for deep learning: Training BERT in 76
+    ///     minutes. ICLR 2020.
+    #[derive(Debug, Clone, Buildable)]
+    #[buildable(root = crate)]
+    #[builder(
+        build_with = build_lamb,
+        default_infallible,
+        err = Exception,
+        root = crate
+    )]
+    pub struct Lamb {
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
+        pub lr: Array,
+
+        /// Schedule used to update [`Lamb::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
+        /// The coefficients used for computing running averages of the gradient and its square.
+        ///
+        /// Default to [`Lamb::DEFAULT_BETAS`].
+        #[builder(optional, ty_override = Betas, default = Lamb::DEFAULT_BETAS)]
+        pub betas: (Array, Array),
+
+        /// The epsilon added to the denominator to improve numerical stability.
+        ///
+        /// Default to [`Lamb::DEFAULT_EPS`].
+        #[builder(optional, ty_override = f32, default = Lamb::DEFAULT_EPS)]
+        pub eps: Array,
+
+        /// The weight decay.
+        ///
+        /// Default to [`Lamb::DEFAULT_WEIGHT_DECAY`].
+        #[builder(optional, ty_override = f32, default = Lamb::DEFAULT_WEIGHT_DECAY)]
+        pub weight_decay: Array,
+
+        /// Inner state.
+        #[builder(ignore)]
+        pub state: StepState<State<(Array, Array)>>,
+    }
+}
+
+/// Builds a new [`Lamb`] optimizer.
+fn build_lamb(builder: LambBuilder) -> Result<Lamb, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
+    let betas = builder.betas;
+
+    Ok(Lamb {
+        lr,
+        lr_schedule,
+        betas: (array!(betas.0), array!(betas.1)),
+        eps: array!(builder.eps),
+        weight_decay: array!(builder.weight_decay),
+        state: StepState::default(),
+    })
+}
+
+impl Lamb {
+    /// Default value for `betas`.
+    pub const DEFAULT_BETAS: (f32, f32) = (0.9, 0.999);
+
+    /// Default value for `eps`.
+    pub const DEFAULT_EPS: f32 = 1e-6;
+
+    /// Default value for `weight_decay`.
+    pub const DEFAULT_WEIGHT_DECAY: f32 = 0.0;
+}
+
+impl Optimizer for Lamb {
+    type State = StepState<State<(Array, Array)>>;
+
+    fn state(&self) -> &Self::State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut Self::State {
+        &mut self.state
+    }
+
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
+    fn update_single(
+        &mut self,
+        key: &Rc<str>,
+        gradient: &Array,
+        parameter: &mut Array,
+    ) -> crate::error::Result<()> {
+        let (b1, b2) = &self.betas;
+        let step = &self.state.step;
+        let (m, v) =
+            get_mut_or_insert_with(&mut self.state.params, key, || (array!(0.0), array!(0.0)));
+
+        let one_minus_b1 = array!(1.0).subtract(b1)?;
+        let one_minus_b2 = array!(1.0).subtract(b2)?;
+
+        *m = b1.multiply(&*m)?.add(&one_minus_b1.multiply(gradient)?)?;
+        *v = b2
+            .multiply(&*v)?
+            .add(&one_minus_b2.multiply(gradient.square()?)?)?;
+
+        let m_hat = m.divide(bias_correction(b1, step)?)?;
+        let v_hat = v.divide(bias_correction(b2, step)?)?;
+
+        let update = m_hat
+            .divide(v_hat.sqrt()?.add(&self.eps)?)?
+            .add(self.weight_decay.multiply(&*parameter)?)?;
+
+        let ratio = trust_ratio(
+            &norm(&*parameter, None, None, None)?,
+            &norm(&update, None, None, None)?,
+        )?;
+        *parameter = parameter.subtract(self.lr.multiply(&ratio)?.multiply(&update)?)?;
+
+        Ok(())
+    }
+}
+
+/// Returns `||w|| / ||update||` if both norms are positive and `1` otherwise.
+fn trust_ratio(weight_norm: &Array, update_norm: &Array) -> crate::error::Result<Array> {
+    let zero = array!(0.0);
+    let positive = weight_norm.gt(&zero)?.logical_and(update_norm.gt(&zero)?)?;
+    r#where(&positive, weight_norm.divide(update_norm)?, array!(1.0))
+}
+
+impl Updatable for Lamb {
+    fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+        use itertools::Itertools;
+
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
+    }
+
+    fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+        use itertools::Itertools;
+
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
+    }
+}
+
+impl_updatable_for_mut_optimizer!(Lamb);
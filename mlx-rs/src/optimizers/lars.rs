@@ -0,0 +1,171 @@
+use mlx_internal_macros::{generate_builder, Buildable};
+
+use crate::{
+    array,
+    error::Exception,
+    linalg::norm,
+    ops::r#where,
+    utils::{get_mut_or_insert_with, Updatable},
+    Array,
+};
+
+use super::*;
+
+generate_builder! {
+    /// The LARS optimizer [1].
+    ///
+    /// LARS is SGD with momentum where the learning rate of every layer is scaled by the trust
+    /// ratio `trust_coefficient * ||w|| / (||g|| + weight_decay * ||w|| + eps)`. The ratio falls
+    /// back to 1 if either norm is zero.
+    ///
+    /// [1]: You, Y., Gitman, I. and Ginsburg, B., 2017. Large batch training of convolutional
+    ///     networks. arXiv preprint arXiv:1708.03888.
+    #[derive(Debug, Clone, Buildable)]
+    #[buildable(root = crate)]
+    #[builder(
+        build_with = build_lars,
+        default_infallible,
+        err = Exception,
+        root = crate
+    )]
+    pub struct Lars {
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
+        pub lr: Array,
+
+        /// Schedule used to update [`Lars::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
+        /// Momentum strength. Default to [`Lars::DEFAULT_MOMENTUM`].
+        #[builder(optional, ty_override = f32, default = Lars::DEFAULT_MOMENTUM)]
+        pub momentum: Array,
+
+        /// Weight decay (L2 penalty). Default to [`Lars::DEFAULT_WEIGHT_DECAY`].
+        #[builder(optional, ty_override = f32, default = Lars::DEFAULT_WEIGHT_DECAY)]
+        pub weight_decay: Array,
+
+        /// The trust coefficient. Default to [`Lars::DEFAULT_TRUST_COEFFICIENT`].
+        #[builder(optional, ty_override = f32, default = Lars::DEFAULT_TRUST_COEFFICIENT)]
+        pub trust_coefficient: Array,
+
+        /// The epsilon added to the denominator of the trust ratio. Default to
+        /// [`Lars::DEFAULT_EPS`].
+        #[builder(optional, ty_override = f32, default = Lars::DEFAULT_EPS)]
+        pub eps: Array,
+
+        /// Inner state.
+        #[builder(ignore)]
+        pub state: StepState,
+    }
+}
+
+/// Builds a new [`Lars`] optimizer.
+fn build_lars(builder: LarsBuilder) -> Result<Lars, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
+
+    Ok(Lars {
+        lr,
+        lr_schedule,
+        momentum: array!(builder.momentum),
+        weight_decay: array!(builder.weight_decay),
+        trust_coefficient: array!(builder.trust_coefficient),
+        eps: array!(builder.eps),
+        state: StepState::default(),
+    })
+}
+
+impl Lars {
+    /// Default value for `momentum`.
+    pub const DEFAULT_MOMENTUM: f32 = 0.9;
+
+    /// Default value for `weight_decay`.
+    pub const DEFAULT_WEIGHT_DECAY: f32 = 0.0;
+
+    /// Default value for `trust_coefficient`.
+    pub const DEFAULT_TRUST_COEFFICIENT: f32 = 0.001;
+
+    /// Default value for `eps`.
+    pub const DEFAULT_EPS: f32 = 1e-8;
+}
+
+impl Optimizer for Lars {
+    type State = StepState;
+
+    fn state(&self) -> &Self::State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut Self::State {
+        &mut self.state
+    }
+
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
+    fn update_single(
+        &mut self,
+        key: &Rc<str>,
+        gradient: &Array,
+        parameter: &mut Array,
+    ) -> crate::error::Result<()> {
+        let v = get_mut_or_insert_with(&mut self.state.params, key, || array!(0.0));
+
+        let weight_norm = norm(&*parameter, None, None, None)?;
+        let grad_norm = norm(gradient, None, None, None)?;
+
+        // The trust ratio falls back to 1 if either norm is zero
+        let zero = array!(0.0);
+        let positive = weight_norm.gt(&zero)?.logical_and(grad_norm.gt(&zero)?)?;
+        let denominator = grad_norm
+            .add(self.weight_decay.multiply(&weight_norm)?)?
+            .add(&self.eps)?;
+        let ratio = self
+            .trust_coefficient
+            .multiply(&weight_norm)?
+            .divide(&denominator)?;
+        let local_lr = r#where(&positive, &ratio, array!(1.0))?;
+
+        let gradient = gradient.add(self.weight_decay.multiply(&*parameter)?)?;
+        *v = self
+            .momentum
+            .multiply(&*v)?
+            .add(self.lr.multiply(&local_lr)?.multiply(&gradient)?)?;
+        *parameter = parameter.subtract(&*v)?;
+
+        Ok(())
+    }
+}
+
+impl Updatable for Lars {
+    fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+        use itertools::Itertools;
+
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
+    }
+
+    fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+        use itertools::Itertools;
+
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
+    }
+}
+
+impl_updatable_for_mut_optimizer!(Lars);
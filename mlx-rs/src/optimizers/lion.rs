@@ -2,6 +2,7 @@ use mlx_internal_macros::{generate_builder, Buildable};
 
 use crate::{
     array,
+    error::Exception,
     utils::{get_mut_or_insert_with, Updatable},
     Array,
 };
@@ -22,11 +23,18 @@ generate_builder! {
     #[buildable(root = crate)]
     #[builder(
         build_with = build_lion,
+        default_infallible,
+        err = Exception,
         root = crate
     )]
     pub struct Lion {
-        /// The learning rate.
-        pub lr: f32,
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
+        pub lr: Array,
+
+        /// Schedule used to update [`Lion::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
 
         /// The coefficients used for computing running averages of the gradient and its square.
         /// Default to [`Lion::DEFAULT_BETAS`].
@@ -39,20 +47,21 @@ generate_builder! {
 
         /// Inner state.
         #[builder(ignore)]
-        pub state: State,
+        pub state: StepState,
     }
 }
 
-fn build_lion(builder: LionBuilder) -> Result<Lion, std::convert::Infallible> {
-    let lr = builder.lr;
+fn build_lion(builder: LionBuilder) -> Result<Lion, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
     let betas = builder.betas;
     let weight_decay = builder.weight_decay;
 
     Ok(Lion {
         lr,
+        lr_schedule,
         betas: (array!(betas.0), array!(betas.1)),
         weight_decay,
-        state: State::new(),
+        state: StepState::default(),
     })
 }
 
@@ -65,7 +74,7 @@ impl Lion {
 }
 
 impl Optimizer for Lion {
-    type State = State;
+    type State = StepState;
 
     fn state(&self) -> &Self::State {
         &self.state
@@ -75,6 +84,14 @@ impl Optimizer for Lion {
         &mut self.state
     }
 
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
     fn update_single(
         &mut self,
         key: &std::rc::Rc<str>,
@@ -84,7 +101,7 @@ impl Optimizer for Lion {
         use crate::ops::sign;
 
         let (b1, b2) = &self.betas;
-        let m = get_mut_or_insert_with(&mut self.state, key, || array!(0.0));
+        let m = get_mut_or_insert_with(&mut self.state.params, key, || array!(0.0));
 
         let one_minus_b1 = array!(1.0).subtract(b1)?;
         let one_minus_b2 = array!(1.0).subtract(b2)?;
@@ -94,11 +111,10 @@ impl Optimizer for Lion {
 
         if self.weight_decay > 0.0 {
             // SAFETY: These coeffs are all single-element arrays and won't panic.
-            *parameter = array!(1.0 - self.lr * self.weight_decay) * &*parameter;
+            *parameter = (array!(1.0) - &self.lr * self.weight_decay) * &*parameter;
         }
 
-        let lr = array!(self.lr);
-        *parameter = parameter.subtract(lr.multiply(sign(&c)?)?)?;
+        *parameter = parameter.subtract(self.lr.multiply(sign(&c)?)?)?;
 
         Ok(())
     }
@@ -108,19 +124,25 @@ impl Updatable for Lion {
     fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .map(|(_, v)| v)
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
     }
 
     fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter_mut()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .map(|(_, v)| v)
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
     }
 }
 
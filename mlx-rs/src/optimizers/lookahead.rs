@@ -0,0 +1,98 @@
+use std::rc::Rc;
+
+use crate::{
+    error::LookaheadBuildError, module::ModuleParameters, ops::r#where, utils::Updatable, Array,
+};
+
+use super::*;
+
+/// The Lookahead optimizer [1].
+///
+/// The wrapped optimizer updates the "fast" parameters, and every `sync_period` steps the "slow"
+/// parameters are moved towards them with `slow = slow + alpha * (fast - slow)` before the fast
+/// parameters are reset to the slow ones. The slow parameters can be swapped into the model with
+/// [`Lookahead::swap_parameters`].
+///
+/// [1]: Zhang, M. et al., 2019. Lookahead optimizer: k steps forward, 1 step back. NeurIPS 2019.
+#[derive(Debug, Clone)]
+pub struct Lookahead<O: Optimizer> {
+    /// The wrapped optimizer
+    pub inner: O,
+
+    /// Number of steps between two synchronizations of the slow parameters
+    pub sync_period: i32,
+
+    /// Step size of the slow parameters
+    pub alpha: Array,
+
+    /// Inner state
+    pub state: ShadowState<O::State>,
+}
+
+impl<O> Lookahead<O>
+where
+    O: Optimizer,
+    O::State: Default,
+{
+    /// Default value for `sync_period`.
+    pub const DEFAULT_SYNC_PERIOD: i32 = 5;
+
+    /// Default value for `alpha`.
+    pub const DEFAULT_ALPHA: f32 = 0.5;
+
+    /// Wraps `inner` and synchronizes the slow parameters every `sync_period` steps.
+    pub fn new(mut inner: O, sync_period: i32, alpha: f32) -> Result<Self, LookaheadBuildError> {
+        if sync_period <= 0 {
+            return Err(LookaheadBuildError::NonPositiveSyncPeriod(sync_period));
+        }
+
+        if !(0.0..=1.0).contains(&alpha) {
+            return Err(LookaheadBuildError::InvalidAlpha(alpha));
+        }
+
+        let state = ShadowState {
+            inner: std::mem::take(inner.state_mut()),
+            ..Default::default()
+        };
+
+        Ok(Self {
+            inner,
+            sync_period,
+            alpha: array!(alpha),
+            state,
+        })
+    }
+}
+
+impl<O> Lookahead<O>
+where
+    O: Optimizer,
+{
+    /// Swaps the slow parameters with the parameters of the model.
+    ///
+    /// Calling this a second time restores the fast parameters, which must be done before the
+    /// next update.
+    pub fn swap_parameters<M>(&mut self, model: &mut M)
+    where
+        M: ModuleParameters,
+    {
+        self.state.swap_parameters(model)
+    }
+
+    fn update_shadow(&mut self, key: &Rc<str>, parameter: &mut Array) -> crate::error::Result<()> {
+        if let Some(slow) = self.state.shadow.get_mut(key) {
+            let sync = self
+                .state
+                .step
+                .remainder(array!(self.sync_period))?
+                .eq(array!(0))?;
+
+            let new_slow = slow.add(self.alpha.multiply(parameter.subtract(&*slow)?)?)?;
+            *slow = r#where(&sync, &new_slow, &*slow)?;
+            *parameter = r#where(&sync, &new_slow, &*parameter)?;
+        }
+        Ok(())
+    }
+}
+
+impl_shadow_optimizer!(Lookahead);
@@ -0,0 +1,399 @@
+use std::{borrow::Borrow, collections::HashMap, rc::Rc};
+
+use mlx_internal_macros::{generate_builder, Buildable};
+
+use crate::{
+    array,
+    error::{Exception, LossScalerBuildError},
+    module::{FlattenedModuleParam, ModuleParameters},
+    utils::Updatable,
+    Array, Dtype,
+};
+
+use super::*;
+
+generate_builder! {
+    /// Dynamic loss scaling for training in half precision.
+    ///
+    /// The loss is multiplied by [`LossScaler::scale`] before the gradients are computed so that
+    /// small gradients do not underflow in `Float16`, and the gradients are divided by the same
+    /// factor before they are applied. If any of the gradients is not finite, the optimizer step is
+    /// skipped and the scale is multiplied by [`LossScaler::backoff_factor`]. After
+    /// [`LossScaler::growth_interval`] consecutive finite steps, the scale is multiplied by
+    /// [`LossScaler::growth_factor`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let mut scaler = LossScaler::new();
+    /// let mut loss_and_grad = scaled_value_and_grad(loss_fn);
+    ///
+    /// for (x, y) in batches {
+    ///     let (loss, scaled_grads) = loss_and_grad(&mut model, &scaler, (&x, &y))?;
+    ///     let applied = scaler.step(&mut optimizer, &mut model, &scaled_grads)?;
+    /// }
+    /// ```
+    #[derive(Debug, Clone, Buildable)]
+    #[buildable(root = crate)]
+    #[builder(
+        build_with = build_loss_scaler,
+        default_infallible,
+        err = LossScalerBuildError,
+        root = crate
+    )]
+    pub struct LossScaler {
+        /// The current loss scale. The builder sets the initial value, which default to
+        /// [`LossScaler::DEFAULT_INIT_SCALE`].
+        #[builder(optional, default = LossScaler::DEFAULT_INIT_SCALE)]
+        pub scale: f32,
+
+        /// Factor applied to the scale after [`LossScaler::growth_interval`] consecutive finite
+        /// steps. Default to [`LossScaler::DEFAULT_GROWTH_FACTOR`].
+        #[builder(optional, default = LossScaler::DEFAULT_GROWTH_FACTOR)]
+        pub growth_factor: f32,
+
+        /// Factor applied to the scale when the gradients overflow. Default to
+        /// [`LossScaler::DEFAULT_BACKOFF_FACTOR`].
+        #[builder(optional, default = LossScaler::DEFAULT_BACKOFF_FACTOR)]
+        pub backoff_factor: f32,
+
+        /// Number of consecutive finite steps before the scale grows. Default to
+        /// [`LossScaler::DEFAULT_GROWTH_INTERVAL`].
+        #[builder(optional, default = LossScaler::DEFAULT_GROWTH_INTERVAL)]
+        pub growth_interval: i32,
+
+        /// Number of consecutive finite steps since the scale was last changed.
+        #[builder(ignore)]
+        pub finite_steps: i32,
+    }
+}
+
+fn build_loss_scaler(builder: LossScalerBuilder) -> Result<LossScaler, LossScalerBuildError> {
+    let scale = builder.scale;
+    let growth_factor = builder.growth_factor;
+    let backoff_factor = builder.backoff_factor;
+    let growth_interval = builder.growth_interval;
+
+    if scale <= 0.0 || !scale.is_finite() {
+        return Err(LossScalerBuildError::InvalidScale(scale));
+    }
+
+    if growth_factor < 1.0 {
+        return Err(LossScalerBuildError::InvalidGrowthFactor(growth_factor));
+    }
+
+    if backoff_factor <= 0.0 || backoff_factor >= 1.0 {
+        return Err(LossScalerBuildError::InvalidBackoffFactor(backoff_factor));
+    }
+
+    if growth_interval <= 0 {
+        return Err(LossScalerBuildError::NonPositiveGrowthInterval(
+            growth_interval,
+        ));
+    }
+
+    Ok(LossScaler {
+        scale,
+        growth_factor,
+        backoff_factor,
+        growth_interval,
+        finite_steps: 0,
+    })
+}
+
+impl LossScaler {
+    /// Default value for the initial `scale`, ie. `2^15`. A larger scale would overflow when the
+    /// loss is computed in `Float16`.
+    pub const DEFAULT_INIT_SCALE: f32 = 32768.0;
+
+    /// Default value for `growth_factor`.
+    pub const DEFAULT_GROWTH_FACTOR: f32 = 2.0;
+
+    /// Default value for `backoff_factor`.
+    pub const DEFAULT_BACKOFF_FACTOR: f32 = 0.5;
+
+    /// Default value for `growth_interval`.
+    pub const DEFAULT_GROWTH_INTERVAL: i32 = 2000;
+
+    /// Multiplies the loss by the current scale.
+    pub fn scale_loss(&self, loss: &Array) -> crate::error::Result<Array> {
+        loss.multiply(array!(self.scale))
+    }
+
+    /// Divides the gradients by the current scale.
+    ///
+    /// The gradients keep their data type, so that the parameters updated by a plain optimizer are
+    /// not promoted. [`MasterWeights`] casts the gradients to `Float32` itself. Small gradients
+    /// may underflow in half precision once unscaled, so cast them to `Float32` beforehand if they
+    /// should be kept.
+    pub fn unscale<V>(
+        &self,
+        gradients: &HashMap<Rc<str>, V>,
+    ) -> crate::error::Result<FlattenedModuleParam>
+    where
+        V: Borrow<Array>,
+    {
+        let inv_scale = array!(1.0 / self.scale);
+        gradients
+            .iter()
+            .map(|(key, gradient)| {
+                let gradient = gradient.borrow();
+                let unscaled = gradient.multiply(inv_scale.as_dtype(gradient.dtype())?)?;
+                Ok((key.clone(), unscaled))
+            })
+            .collect()
+    }
+
+    /// Updates the scale after a step, depending on whether the gradients were finite.
+    pub fn update_scale(&mut self, finite: bool) {
+        if finite {
+            self.finite_steps += 1;
+            if self.finite_steps >= self.growth_interval {
+                self.scale *= self.growth_factor;
+                self.finite_steps = 0;
+            }
+        } else {
+            self.scale *= self.backoff_factor;
+            self.finite_steps = 0;
+        }
+    }
+
+    /// Unscales the gradients and applies them with the optimizer if they are all finite, then
+    /// updates the scale.
+    ///
+    /// Returns `true` if the optimizer step was applied and `false` if it was skipped because of
+    /// an overflow.
+    pub fn step<O, M, V>(
+        &mut self,
+        optimizer: &mut O,
+        model: &mut M,
+        scaled_gradients: &HashMap<Rc<str>, V>,
+    ) -> crate::error::Result<bool>
+    where
+        O: Optimizer,
+        M: ModuleParameters,
+        V: Borrow<Array>,
+    {
+        let gradients = self.unscale(scaled_gradients)?;
+        let finite = all_finite(&gradients)?;
+
+        if finite {
+            optimizer.update(model, gradients)?;
+        }
+        self.update_scale(finite);
+
+        Ok(finite)
+    }
+}
+
+/// Returns `true` if none of the gradients contains `inf` or `nan`.
+pub fn all_finite<V>(gradients: &HashMap<Rc<str>, V>) -> crate::error::Result<bool>
+where
+    V: Borrow<Array>,
+{
+    gradients
+        .values()
+        .try_fold(array!(true), |acc, gradient| {
+            acc.logical_and(gradient.borrow().is_finite()?.all(None, None)?)
+        })?
+        .try_item()
+}
+
+/// Similar to [`nn::value_and_grad`](crate::nn::value_and_grad) but the loss is scaled by the
+/// [`LossScaler`] passed to the returned function before it is differentiated.
+///
+/// The returned function returns the unscaled loss and the scaled gradients, which are meant to be
+/// passed to [`LossScaler::step`].
+pub fn scaled_value_and_grad<'a, F, M, Args>(
+    mut f: F,
+) -> impl FnMut(&mut M, &LossScaler, Args) -> Result<(Array, FlattenedModuleParam), Exception> + 'a
+where
+    M: ModuleParameters + 'a,
+    F: FnMut(&mut M, Args) -> Result<Array, Exception> + 'a,
+    Args: Clone,
+{
+    let scaled_loss = move |model: &mut M, (scale, args): (Array, Args)| {
+        f(model, args).and_then(|loss| loss.multiply(scale))
+    };
+    let mut vg = crate::nn::value_and_grad(scaled_loss);
+
+    move |model, scaler, args| {
+        let scale = array!(scaler.scale);
+        let (loss, gradients) = vg(model, (scale.clone(), args))?;
+        Ok((loss.divide(scale)?, gradients))
+    }
+}
+
+/// Keeps `Float32` master weights for a model that runs in half precision.
+///
+/// The wrapped optimizer updates a `Float32` copy of every parameter, using the gradients cast to
+/// `Float32`, and the parameters of the model are then set to the master weights cast back to
+/// their own data type. This avoids losing small updates to the limited precision of `Float16`
+/// and `Bfloat16`. The master weights are stored in [`ShadowState::shadow`] and are saved and
+/// loaded together with the state of the wrapped optimizer.
+#[derive(Debug, Clone)]
+pub struct MasterWeights<O: Optimizer> {
+    /// The wrapped optimizer
+    pub inner: O,
+
+    /// Inner state
+    pub state: ShadowState<O::State>,
+}
+
+impl<O> MasterWeights<O>
+where
+    O: Optimizer,
+    O::State: Default,
+{
+    /// Wraps `inner` so that it updates `Float32` master weights.
+    pub fn new(mut inner: O) -> Self {
+        let state = ShadowState {
+            inner: std::mem::take(inner.state_mut()),
+            ..Default::default()
+        };
+
+        Self { inner, state }
+    }
+}
+
+impl<O> Optimizer for MasterWeights<O>
+where
+    O: Optimizer,
+{
+    type State = ShadowState<O::State>;
+
+    fn state(&self) -> &Self::State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut Self::State {
+        &mut self.state
+    }
+
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        increment_step(&mut self.state.step)?;
+        with_inner_state(&mut self.inner, &mut self.state.inner, |inner| {
+            inner.advance_step()
+        })
+    }
+
+    fn update_single(
+        &mut self,
+        key: &Rc<str>,
+        gradient: &Array,
+        parameter: &mut Array,
+    ) -> crate::error::Result<()> {
+        if !self.state.shadow.contains_key(key) {
+            let master = parameter.as_dtype(Dtype::Float32)?;
+            self.state.shadow.insert(key.clone(), master);
+        }
+
+        let ShadowState {
+            inner: inner_state,
+            shadow,
+            ..
+        } = &mut self.state;
+        let master = shadow
+            .get_mut(key)
+            .expect("The master weight is inserted above");
+        let gradient = gradient.as_dtype(Dtype::Float32)?;
+
+        with_inner_state(&mut self.inner, inner_state, |optimizer| {
+            optimizer.update_single(key, &gradient, master)
+        })?;
+
+        *parameter = master.as_dtype(parameter.dtype())?;
+        Ok(())
+    }
+}
+
+impl<O> Updatable for MasterWeights<O>
+where
+    O: Optimizer,
+{
+    fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+        shadow_updatable_states(&self.inner, &self.state)
+    }
+
+    fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+        shadow_updatable_states_mut(&mut self.inner, &mut self.state)
+    }
+}
+
+impl<O> Updatable for &'_ mut MasterWeights<O>
+where
+    O: Optimizer,
+{
+    fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+        <MasterWeights<O> as Updatable>::updatable_states(&**self)
+    }
+
+    fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+        <MasterWeights<O> as Updatable>::updatable_states_mut(&mut **self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{array, builder::Builder, module::FlattenedModuleParam, Dtype};
+
+    use super::{all_finite, LossScaler, LossScalerBuilder};
+
+    #[test]
+    fn test_loss_scaler_unscale() {
+        let scaler = LossScaler::new();
+
+        let mut gradients: FlattenedModuleParam = HashMap::new();
+        gradients.insert("a".into(), array!([32768.0, 65536.0]));
+
+        let unscaled = scaler.unscale(&gradients).unwrap();
+        assert_eq!(unscaled["a"], array!([1.0, 2.0]));
+        assert!(all_finite(&unscaled).unwrap());
+
+        gradients.insert("b".into(), array!([f32::NAN]));
+        assert!(!all_finite(&gradients).unwrap());
+    }
+
+    #[test]
+    fn test_loss_scaler_unscale_keeps_dtype() {
+        let scaler = LossScaler::new();
+
+        let mut gradients: FlattenedModuleParam = HashMap::new();
+        let scaled = array!([0.5 * LossScaler::DEFAULT_INIT_SCALE]);
+        gradients.insert("a".into(), scaled.as_dtype(Dtype::Float16).unwrap());
+        gradients.insert("b".into(), scaled.as_dtype(Dtype::Bfloat16).unwrap());
+        gradients.insert("c".into(), scaled);
+
+        let unscaled = scaler.unscale(&gradients).unwrap();
+        assert_eq!(unscaled["a"].dtype(), Dtype::Float16);
+        assert_eq!(unscaled["b"].dtype(), Dtype::Bfloat16);
+        assert_eq!(unscaled["c"].dtype(), Dtype::Float32);
+        for key in ["a", "b", "c"] {
+            let value = unscaled[key]
+                .as_dtype(Dtype::Float32)
+                .unwrap()
+                .item::<f32>();
+            assert_eq!(value, 0.5);
+        }
+    }
+
+    #[test]
+    fn test_loss_scaler_update_scale() {
+        let mut scaler = LossScalerBuilder::new()
+            .scale(8.0)
+            .growth_interval(2)
+            .build()
+            .unwrap();
+
+        scaler.update_scale(false);
+        assert_eq!(scaler.scale, 4.0);
+
+        scaler.update_scale(true);
+        assert_eq!(scaler.scale, 4.0);
+        scaler.update_scale(true);
+        assert_eq!(scaler.scale, 8.0);
+        assert_eq!(scaler.finite_steps, 0);
+    }
+}
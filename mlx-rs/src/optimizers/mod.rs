@@ -5,6 +5,7 @@
 use std::{
     borrow::{Borrow, Cow},
     collections::HashMap,
+    ops::{Deref, DerefMut},
     path::Path,
     rc::Rc,
 };
@@ -23,9 +24,23 @@ mod adagrad;
 mod adam;
 mod adamax;
 mod adamw;
+mod adan;
+mod ema;
+mod grad_accumulator;
+mod lamb;
+mod lars;
 mod lion;
+mod lookahead;
+mod mixed_precision;
+mod multi_optimizer;
+mod muon;
+mod nadam;
+mod radam;
 mod rmsprop;
+pub mod schedulers;
 mod sgd;
+mod shadow;
+mod swa;
 
 pub use adadelta::*;
 pub use adafactor::*;
@@ -33,10 +48,25 @@ pub use adagrad::*;
 pub use adam::*;
 pub use adamax::*;
 pub use adamw::*;
+pub use adan::*;
+pub use ema::*;
+pub use grad_accumulator::*;
 use itertools::Itertools;
+pub use lamb::*;
+pub use lars::*;
 pub use lion::*;
+pub use lookahead::*;
+pub use mixed_precision::*;
+pub use multi_optimizer::*;
+pub use muon::*;
+pub use nadam::*;
+pub use radam::*;
 pub use rmsprop::*;
 pub use sgd::*;
+pub use shadow::*;
+pub use swa::*;
+
+use schedulers::{advance_step, increment_step, LearningRate, Scheduler};
 
 // Unfortunate workaround to implement Updatable for mutable references of
 // optimizers This is needed because of the orphan rule and lack of negative
@@ -170,6 +200,96 @@ impl OptimizerState for State<(Array, Array)> {
     }
 }
 
+/// Key of the step in the flattened [`StepState`].
+const STEP_KEY: &str = "step";
+
+/// Optimizer state that also keeps track of the step, ie. the number of updates applied so far.
+///
+/// The step is used to evaluate the learning rate schedule, if any, and is saved with the key
+/// `"step"` alongside the per-parameter state. The per-parameter state can be accessed directly
+/// through `Deref`.
+#[derive(Debug, Clone)]
+pub struct StepState<S = State> {
+    /// Number of updates applied so far
+    pub step: Array,
+
+    /// Per-parameter state
+    pub params: S,
+}
+
+impl<S> StepState<S> {
+    /// Creates a new [`StepState`] at step 0.
+    pub fn new(params: S) -> Self {
+        Self {
+            step: array!(0),
+            params,
+        }
+    }
+}
+
+impl<S: Default> Default for StepState<S> {
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+impl<S> Deref for StepState<S> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        &self.params
+    }
+}
+
+impl<S> DerefMut for StepState<S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.params
+    }
+}
+
+impl<S> OptimizerState for StepState<S>
+where
+    S: OptimizerState,
+{
+    type UnflattenError = S::UnflattenError;
+
+    fn flatten(&self) -> impl Iterator<Item = (Rc<str>, &Array)> {
+        std::iter::once((Rc::from(STEP_KEY), &self.step)).chain(self.params.flatten())
+    }
+
+    fn flatten_mut(&mut self) -> impl Iterator<Item = (Rc<str>, &mut Array)> {
+        let Self { step, params } = self;
+        std::iter::once((Rc::from(STEP_KEY), step)).chain(params.flatten_mut())
+    }
+
+    fn unflatten<I, K>(input: I) -> Result<Self, Self::UnflattenError>
+    where
+        I: IntoIterator<Item = (K, Array)>,
+        K: Ord + AsRef<str> + Into<Rc<str>>,
+    {
+        let mut step = None;
+        let params = S::unflatten(input.into_iter().filter_map(|(k, v)| {
+            if k.as_ref() == STEP_KEY {
+                step = Some(v);
+                None
+            } else {
+                Some((k, v))
+            }
+        }))?;
+
+        Ok(Self {
+            // The step defaults to 0 for states saved without it
+            step: step.unwrap_or_else(|| array!(0)),
+            params,
+        })
+    }
+}
+
+/// Returns `1 - beta^step`, ie. the bias correction of a running average after `step` updates.
+fn bias_correction(beta: &Array, step: &Array) -> crate::error::Result<Array> {
+    array!(1.0).subtract(beta.power(step.as_dtype(crate::Dtype::Float32)?)?)
+}
+
 /// Trait for optimizers.
 pub trait Optimizer: Updatable {
     /// State of the optimizer.
@@ -193,6 +313,14 @@ pub trait Optimizer: Updatable {
         parameter: &mut Array,
     ) -> crate::error::Result<()>;
 
+    /// Advance the step of the optimizer and update the learning rate if it is scheduled.
+    ///
+    /// This is called once at the beginning of [`Optimizer::update`]. The default implementation
+    /// does nothing.
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        Ok(())
+    }
+
     /// Apply the gradients to the parameters of the model and update the model with the new
     /// parameters.
     fn update<M>(
@@ -203,6 +331,8 @@ pub trait Optimizer: Updatable {
     where
         M: ModuleParameters,
     {
+        self.advance_step()?;
+
         let mut parameters = model.parameters_mut().flatten();
 
         for (key, gradient) in gradients.borrow().iter() {
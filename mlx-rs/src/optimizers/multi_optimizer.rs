@@ -0,0 +1,377 @@
+use std::{fmt::Debug, rc::Rc};
+
+use crate::{
+    error::{IoError, UnflattenError},
+    utils::Updatable,
+    Array,
+};
+
+use super::*;
+
+/// Type alias for the predicate used to select the parameters of a group.
+///
+/// The predicate is called with the flattened key of the parameter and its gradient.
+pub type ParamPredicate = dyn Fn(&str, &Array) -> bool;
+
+/// Selects the parameters that belong to a group of a [`MultiOptimizer`].
+#[derive(Clone)]
+pub enum ParamFilter {
+    /// Matches the parameters whose key is the prefix or starts with the prefix followed by a
+    /// `.`, eg. `"embedding"` matches `"embedding.weight"` but not `"embeddings.weight"`.
+    Prefix(Rc<str>),
+
+    /// Matches the parameters for which the predicate returns `true`.
+    Predicate(Rc<ParamPredicate>),
+}
+
+impl ParamFilter {
+    /// Creates a filter that matches the parameters for which the predicate returns `true`.
+    pub fn predicate(f: impl Fn(&str, &Array) -> bool + 'static) -> Self {
+        ParamFilter::Predicate(Rc::new(f))
+    }
+
+    /// Returns `true` if the parameter belongs to the group.
+    pub fn matches(&self, key: &str, gradient: &Array) -> bool {
+        match self {
+            ParamFilter::Prefix(prefix) => key
+                .strip_prefix(prefix.as_ref())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.')),
+            ParamFilter::Predicate(f) => f(key, gradient),
+        }
+    }
+}
+
+impl Debug for ParamFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamFilter::Prefix(prefix) => f.debug_tuple("Prefix").field(prefix).finish(),
+            ParamFilter::Predicate(_) => f.debug_tuple("Predicate").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl From<&str> for ParamFilter {
+    fn from(prefix: &str) -> Self {
+        ParamFilter::Prefix(Rc::from(prefix))
+    }
+}
+
+impl From<String> for ParamFilter {
+    fn from(prefix: String) -> Self {
+        ParamFilter::Prefix(Rc::from(prefix))
+    }
+}
+
+impl From<Rc<str>> for ParamFilter {
+    fn from(prefix: Rc<str>) -> Self {
+        ParamFilter::Prefix(prefix)
+    }
+}
+
+/// State of a [`MultiOptimizer`], which holds the state of every group and of the default
+/// optimizer.
+///
+/// The state of the `i`-th group is flattened with the keys prefixed by `"{i}."`, and the state of
+/// the default optimizer with the keys prefixed by `"default."`.
+#[derive(Debug, Clone, Default)]
+pub struct MultiOptimizerState<S, T = S> {
+    /// State of each group
+    pub groups: Vec<S>,
+
+    /// State of the default optimizer
+    pub default: T,
+}
+
+impl<S, T> OptimizerState for MultiOptimizerState<S, T>
+where
+    S: OptimizerState,
+    T: OptimizerState,
+{
+    type UnflattenError = IoError;
+
+    fn flatten(&self) -> impl Iterator<Item = (Rc<str>, &Array)> {
+        let groups = self.groups.iter().enumerate().flat_map(|(i, state)| {
+            state
+                .flatten()
+                .map(move |(k, v)| (Rc::from(format!("{}.{}", i, k)), v))
+        });
+        let default = self
+            .default
+            .flatten()
+            .map(|(k, v)| (Rc::from(format!("default.{}", k)), v));
+        groups.chain(default)
+    }
+
+    fn flatten_mut(&mut self) -> impl Iterator<Item = (Rc<str>, &mut Array)> {
+        let groups = self.groups.iter_mut().enumerate().flat_map(|(i, state)| {
+            state
+                .flatten_mut()
+                .map(move |(k, v)| (Rc::from(format!("{}.{}", i, k)), v))
+        });
+        let default = self
+            .default
+            .flatten_mut()
+            .map(|(k, v)| (Rc::from(format!("default.{}", k)), v));
+        groups.chain(default)
+    }
+
+    fn unflatten<I, K>(input: I) -> Result<Self, Self::UnflattenError>
+    where
+        I: IntoIterator<Item = (K, Array)>,
+        K: Ord + AsRef<str> + Into<Rc<str>>,
+    {
+        let mut grouped: Vec<Vec<(Rc<str>, Array)>> = Vec::new();
+        let mut default: Vec<(Rc<str>, Array)> = Vec::new();
+        for (key, value) in input {
+            let (prefix, key) = key
+                .as_ref()
+                .split_once('.')
+                .ok_or(UnflattenError::InvalidKey)?;
+            if prefix == "default" {
+                default.push((Rc::from(key), value));
+                continue;
+            }
+
+            let index = prefix
+                .parse::<usize>()
+                .map_err(|_| UnflattenError::InvalidKey)?;
+            if grouped.len() <= index {
+                grouped.resize_with(index + 1, Vec::new);
+            }
+            grouped[index].push((Rc::from(key), value));
+        }
+
+        let groups = grouped
+            .into_iter()
+            .map(|entries| S::unflatten(entries).map_err(Into::into))
+            .collect::<Result<_, IoError>>()?;
+        let default = T::unflatten(default).map_err(Into::into)?;
+        Ok(Self { groups, default })
+    }
+}
+
+/// Applies a different optimizer to each group of parameters.
+///
+/// Every parameter is routed to the first group whose [`ParamFilter`] matches, and the parameters
+/// that don't match any group are updated by the default optimizer. This allows, eg., to disable
+/// weight decay on the biases and norms, or to use a lower learning rate for the embedding:
+///
+/// ```rust
+/// use mlx_rs::{
+///     builder::Builder,
+///     optimizers::{AdamW, AdamWBuilder, MultiOptimizer, ParamFilter},
+/// };
+///
+/// let no_decay = AdamWBuilder::new(1e-3).weight_decay(0.0).build().unwrap();
+/// let optimizer = MultiOptimizer::new(AdamW::new(1e-3))
+///     .with_group("embedding", AdamW::new(1e-4))
+///     .with_group(
+///         ParamFilter::predicate(|key, _| key.ends_with(".bias") || key.contains("norm")),
+///         no_decay,
+///     );
+/// ```
+///
+/// The groups share one optimizer type `O`, but the default optimizer can be of another type `D`,
+/// eg. [`Muon`] for the matrices and [`AdamW`] for everything else. As a [`MultiOptimizer`] is an
+/// optimizer itself, it can be used as the default optimizer of another one to combine more types:
+///
+/// ```rust
+/// use mlx_rs::optimizers::{AdamW, MultiOptimizer, Muon, ParamFilter};
+///
+/// let adamw = MultiOptimizer::new(AdamW::new(1e-3)).with_group("embedding", AdamW::new(1e-4));
+/// let optimizer = MultiOptimizer::with_default(adamw).with_group(
+///     ParamFilter::predicate(|key, gradient| gradient.ndim() == 2 && !key.contains("embedding")),
+///     Muon::new(0.02),
+/// );
+/// ```
+///
+/// The states of the groups are held by the [`MultiOptimizer`] so that they can be saved and loaded
+/// together with [`OptimizerState::save_safetensors`] and [`OptimizerState::load_safetensors`].
+#[derive(Debug, Clone)]
+pub struct MultiOptimizer<O: Optimizer, D: Optimizer = O> {
+    /// Parameter groups in order of precedence
+    pub groups: Vec<(ParamFilter, O)>,
+
+    /// Optimizer for the parameters that don't belong to any group
+    pub default: D,
+
+    /// Inner state
+    pub state: MultiOptimizerState<O::State, D::State>,
+}
+
+impl<O> MultiOptimizer<O>
+where
+    O: Optimizer,
+    O::State: Default,
+{
+    /// Creates a new [`MultiOptimizer`] with no groups, ie. every parameter is updated by the
+    /// `default` optimizer, and the groups use the same optimizer type as `default`.
+    pub fn new(default: O) -> Self {
+        Self::with_default(default)
+    }
+}
+
+impl<O, D> MultiOptimizer<O, D>
+where
+    O: Optimizer,
+    O::State: Default,
+    D: Optimizer,
+    D::State: Default,
+{
+    /// Creates a new [`MultiOptimizer`] with no groups, ie. every parameter is updated by the
+    /// `default` optimizer, and the groups use the optimizer type `O`.
+    pub fn with_default(default: D) -> Self {
+        Self {
+            groups: Vec::new(),
+            default,
+            state: MultiOptimizerState::default(),
+        }
+    }
+
+    /// Adds a group of parameters selected by `filter` and updated by `optimizer`.
+    ///
+    /// Groups added first take precedence over the ones added later.
+    pub fn with_group(mut self, filter: impl Into<ParamFilter>, optimizer: O) -> Self {
+        self.groups.push((filter.into(), optimizer));
+        self
+    }
+
+    /// Returns the index of the group the parameter belongs to, or `None` if it is updated by the
+    /// default optimizer.
+    pub fn group_index(&self, key: &str, gradient: &Array) -> Option<usize> {
+        self.groups
+            .iter()
+            .position(|(filter, _)| filter.matches(key, gradient))
+    }
+
+    /// Swaps the state of the group in and out of its optimizer around `f`.
+    fn with_group_optimizer<T>(&mut self, index: usize, f: impl FnOnce(&mut O) -> T) -> T {
+        if self.state.groups.len() <= index {
+            self.state
+                .groups
+                .resize_with(self.groups.len(), Default::default);
+        }
+
+        let optimizer = &mut self.groups[index].1;
+        let state = &mut self.state.groups[index];
+
+        std::mem::swap(state, optimizer.state_mut());
+        let output = f(optimizer);
+        std::mem::swap(state, optimizer.state_mut());
+
+        output
+    }
+
+    /// Swaps the state of the default optimizer in and out of it around `f`.
+    fn with_default_optimizer<T>(&mut self, f: impl FnOnce(&mut D) -> T) -> T {
+        std::mem::swap(&mut self.state.default, self.default.state_mut());
+        let output = f(&mut self.default);
+        std::mem::swap(&mut self.state.default, self.default.state_mut());
+
+        output
+    }
+}
+
+impl<O, D> Optimizer for MultiOptimizer<O, D>
+where
+    O: Optimizer,
+    O::State: Default,
+    D: Optimizer,
+    D::State: Default,
+{
+    type State = MultiOptimizerState<O::State, D::State>;
+
+    fn state(&self) -> &Self::State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut Self::State {
+        &mut self.state
+    }
+
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        for index in 0..self.groups.len() {
+            self.with_group_optimizer(index, |optimizer| optimizer.advance_step())?;
+        }
+        self.with_default_optimizer(|optimizer| optimizer.advance_step())
+    }
+
+    fn update_single(
+        &mut self,
+        key: &Rc<str>,
+        gradient: &Array,
+        parameter: &mut Array,
+    ) -> crate::error::Result<()> {
+        match self.group_index(key, gradient) {
+            Some(index) => self.with_group_optimizer(index, |optimizer| {
+                optimizer.update_single(key, gradient, parameter)
+            }),
+            None => self.with_default_optimizer(|optimizer| {
+                optimizer.update_single(key, gradient, parameter)
+            }),
+        }
+    }
+}
+
+impl<O, D> Updatable for MultiOptimizer<O, D>
+where
+    O: Optimizer,
+    D: Optimizer,
+{
+    fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+        use itertools::Itertools;
+
+        let groups = self.groups.iter().flat_map(|(_, optimizer)| {
+            optimizer.updatable_states().into_iter().collect::<Vec<_>>()
+        });
+        let default = self
+            .default
+            .updatable_states()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let state = self
+            .state
+            .flatten()
+            .sorted_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, v)| v);
+
+        groups.chain(default).chain(state).collect::<Vec<_>>()
+    }
+
+    fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+        use itertools::Itertools;
+
+        let groups = self.groups.iter_mut().flat_map(|(_, optimizer)| {
+            optimizer
+                .updatable_states_mut()
+                .into_iter()
+                .collect::<Vec<_>>()
+        });
+        let default = self
+            .default
+            .updatable_states_mut()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let state = self
+            .state
+            .flatten_mut()
+            .sorted_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, v)| v);
+
+        groups.chain(default).chain(state).collect::<Vec<_>>()
+    }
+}
+
+impl<O, D> Updatable for &'_ mut MultiOptimizer<O, D>
+where
+    O: Optimizer,
+    D: Optimizer,
+{
+    fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+        <MultiOptimizer<O, D> as Updatable>::updatable_states(&**self)
+    }
+
+    fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+        <MultiOptimizer<O, D> as Updatable>::updatable_states_mut(&mut **self)
+    }
+}
@@ -0,0 +1,215 @@
+use mlx_internal_macros::{generate_builder, Buildable};
+
+use crate::{
+    array,
+    error::Exception,
+    linalg::norm,
+    utils::{get_mut_or_insert_with, Updatable},
+    Array,
+};
+
+use super::*;
+
+generate_builder! {
+    /// The Muon optimizer [1].
+    ///
+    /// Muon runs SGD with (Nesterov) momentum and orthogonalizes the update of every matrix
+    /// parameter with a quintic Newton–Schulz iteration, see [`newton_schulz`]. Parameters with
+    /// more than two dimensions are flattened to `[shape[0], -1]` for the orthogonalization, and
+    /// parameters with fewer than two dimensions receive the plain momentum update. Embeddings,
+    /// biases and output layers are usually better optimized with AdamW, eg. through a
+    /// [`MultiOptimizer`] with a default [`AdamW`], see [`MultiOptimizer::with_default`].
+    ///
+    /// [1]: Jordan, K. et al., 2024. Muon: An optimizer for hidden layers in neural networks.
+    ///     <https://kellerjordan.github.io/posts/muon/>
+    #[derive(Debug, Clone, Buildable)]
+    #[buildable(root = crate)]
+    #[builder(
+        build_with = build_muon,
+        default_infallible,
+        err = Exception,
+        root = crate
+    )]
+    pub struct Muon {
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
+        pub lr: Array,
+
+        /// Schedule used to update [`Muon::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
+        /// Momentum strength. Default to [`Muon::DEFAULT_MOMENTUM`].
+        #[builder(optional, ty_override = f32, default = Muon::DEFAULT_MOMENTUM)]
+        pub momentum: Array,
+
+        /// Decoupled weight decay. Default to [`Muon::DEFAULT_WEIGHT_DECAY`].
+        #[builder(optional, ty_override = f32, default = Muon::DEFAULT_WEIGHT_DECAY)]
+        pub weight_decay: Array,
+
+        /// Enables nesterov momentum. Default to [`Muon::DEFAULT_NESTEROV`].
+        #[builder(optional, ty_override = bool, default = Muon::DEFAULT_NESTEROV)]
+        pub nesterov: bool,
+
+        /// Number of Newton–Schulz iterations. Default to [`Muon::DEFAULT_NS_STEPS`].
+        #[builder(optional, default = Muon::DEFAULT_NS_STEPS)]
+        pub ns_steps: i32,
+
+        /// Inner state.
+        #[builder(ignore)]
+        pub state: StepState,
+    }
+}
+
+/// Builds a new [`Muon`] optimizer.
+fn build_muon(builder: MuonBuilder) -> Result<Muon, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
+
+    Ok(Muon {
+        lr,
+        lr_schedule,
+        momentum: array!(builder.momentum),
+        weight_decay: array!(builder.weight_decay),
+        nesterov: builder.nesterov,
+        ns_steps: builder.ns_steps,
+        state: StepState::default(),
+    })
+}
+
+impl Muon {
+    /// Default value for `momentum`.
+    pub const DEFAULT_MOMENTUM: f32 = 0.95;
+
+    /// Default value for `weight_decay`.
+    pub const DEFAULT_WEIGHT_DECAY: f32 = 0.0;
+
+    /// Default value for `nesterov`.
+    pub const DEFAULT_NESTEROV: bool = true;
+
+    /// Default value for `ns_steps`.
+    pub const DEFAULT_NS_STEPS: i32 = 5;
+}
+
+/// Coefficients of the quintic Newton–Schulz iteration used by [`Muon`].
+const NEWTON_SCHULZ_COEFFS: (f32, f32, f32) = (3.4445, -4.7750, 2.0315);
+
+/// Approximately orthogonalizes a matrix with `steps` iterations of a quintic Newton–Schulz
+/// iteration.
+///
+/// The coefficients are chosen to maximize the slope at zero rather than to converge, so the
+/// singular values of the output are only pushed close to 1, which is enough for [`Muon`].
+///
+/// Returns an error if `matrix` is not 2-D.
+pub fn newton_schulz(matrix: &Array, steps: i32) -> crate::error::Result<Array> {
+    if matrix.ndim() != 2 {
+        return Err(Exception::custom(format!(
+            "newton_schulz expects a 2-D matrix, got shape {:?}",
+            matrix.shape()
+        )));
+    }
+
+    let (a, b, c) = NEWTON_SCHULZ_COEFFS;
+    let shape = matrix.shape();
+    let transposed = shape[0] > shape[1];
+
+    let mut x = matrix.divide(norm(matrix, None, None, None)?.add(array!(1e-7))?)?;
+    if transposed {
+        x = x.t();
+    }
+
+    for _ in 0..steps {
+        let gram = x.matmul(x.t())?;
+        let poly = gram
+            .multiply(array!(b))?
+            .add(gram.matmul(&gram)?.multiply(array!(c))?)?;
+        x = x.multiply(array!(a))?.add(poly.matmul(&x)?)?;
+    }
+
+    if transposed {
+        x = x.t();
+    }
+    Ok(x)
+}
+
+impl Optimizer for Muon {
+    type State = StepState;
+
+    fn state(&self) -> &Self::State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut Self::State {
+        &mut self.state
+    }
+
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
+    fn update_single(
+        &mut self,
+        key: &Rc<str>,
+        gradient: &Array,
+        parameter: &mut Array,
+    ) -> crate::error::Result<()> {
+        let buf = get_mut_or_insert_with(&mut self.state.params, key, || array!(0.0));
+
+        *buf = self.momentum.multiply(&*buf)?.add(gradient)?;
+        let mut update = match self.nesterov {
+            true => gradient.add(self.momentum.multiply(&*buf)?)?,
+            false => buf.clone(),
+        };
+
+        let shape = update.shape().to_vec();
+        if shape.len() >= 2 {
+            let rows = shape[0];
+            let matrix = update.reshape(&[rows, -1])?;
+            let cols = matrix.shape()[1];
+
+            // Scale the update so that its RMS doesn't depend on the aspect ratio
+            let scale = (rows as f32 / cols as f32).max(1.0).sqrt();
+            update = newton_schulz(&matrix, self.ns_steps)?
+                .multiply(array!(scale))?
+                .reshape(&shape)?;
+        }
+
+        let decay = array!(1.0).subtract(self.lr.multiply(&self.weight_decay)?)?;
+        *parameter = parameter
+            .multiply(&decay)?
+            .subtract(self.lr.multiply(&update)?)?;
+
+        Ok(())
+    }
+}
+
+impl Updatable for Muon {
+    fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+        use itertools::Itertools;
+
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
+    }
+
+    fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+        use itertools::Itertools;
+
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
+    }
+}
+
+impl_updatable_for_mut_optimizer!(Muon);
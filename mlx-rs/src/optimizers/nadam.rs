@@ -0,0 +1,163 @@
+use mlx_internal_macros::{generate_builder, Buildable};
+
+use crate::{
+    array,
+    error::Exception,
+    utils::{get_mut_or_insert_with, Updatable},
+    Array,
+};
+
+use super::*;
+
+generate_builder! {
+    /// The NAdam optimizer [1], ie. Adam with Nesterov momentum.
+    ///
+    /// The first moment is bias corrected with a look-ahead of one step:
+    ///
+    /// ```text
+    /// m_hat = b1 * m / (1 - b1^(t + 1)) + (1 - b1) * g / (1 - b1^t)
+    /// ```
+    ///
+    /// [1]: Dozat, T., 2016. Incorporating Nesterov momentum into Adam. ICLR 2016 workshop.
+    #[derive(Debug, Clone, Buildable)]
+    #[buildable(root = crate)]
+    #[builder(
+        build_with = build_nadam,
+        default_infallible,
+        err = Exception,
+        root = crate
+    )]
+    pub struct NAdam {
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
+        pub lr: Array,
+
+        /// Schedule used to update [`NAdam::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
+        /// The coefficients used for computing running averages of the gradient and its square.
+        ///
+        /// Default to [`NAdam::DEFAULT_BETAS`].
+        #[builder(optional, ty_override = Betas, default = NAdam::DEFAULT_BETAS)]
+        pub betas: (Array, Array),
+
+        /// The epsilon added to the denominator to improve numerical stability.
+        ///
+        /// Default to [`NAdam::DEFAULT_EPS`].
+        #[builder(optional, ty_override = f32, default = NAdam::DEFAULT_EPS)]
+        pub eps: Array,
+
+        /// Inner state.
+        #[builder(ignore)]
+        pub state: StepState<State<(Array, Array)>>,
+    }
+}
+
+/// Builds a new [`NAdam`] optimizer.
+fn build_nadam(builder: NAdamBuilder) -> Result<NAdam, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
+    let betas = builder.betas;
+
+    Ok(NAdam {
+        lr,
+        lr_schedule,
+        betas: (array!(betas.0), array!(betas.1)),
+        eps: array!(builder.eps),
+        state: StepState::default(),
+    })
+}
+
+impl NAdam {
+    /// Default value for `betas`.
+    pub const DEFAULT_BETAS: (f32, f32) = super::Adam::DEFAULT_BETAS;
+
+    /// Default value for `eps`.
+    pub const DEFAULT_EPS: f32 = super::Adam::DEFAULT_EPS;
+}
+
+impl Optimizer for NAdam {
+    type State = StepState<State<(Array, Array)>>;
+
+    fn state(&self) -> &Self::State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut Self::State {
+        &mut self.state
+    }
+
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
+    fn update_single(
+        &mut self,
+        key: &Rc<str>,
+        gradient: &Array,
+        parameter: &mut Array,
+    ) -> crate::error::Result<()> {
+        let (b1, b2) = &self.betas;
+        let step = &self.state.step;
+        let (m, v) =
+            get_mut_or_insert_with(&mut self.state.params, key, || (array!(0.0), array!(0.0)));
+
+        let one_minus_b1 = array!(1.0).subtract(b1)?;
+        let one_minus_b2 = array!(1.0).subtract(b2)?;
+
+        *m = b1.multiply(&*m)?.add(&one_minus_b1.multiply(gradient)?)?;
+        *v = b2
+            .multiply(&*v)?
+            .add(&one_minus_b2.multiply(gradient.square()?)?)?;
+
+        let next_step = step.add(array!(1))?;
+        let m_hat = b1
+            .multiply(&*m)?
+            .divide(bias_correction(b1, &next_step)?)?
+            .add(
+                one_minus_b1
+                    .multiply(gradient)?
+                    .divide(bias_correction(b1, step)?)?,
+            )?;
+        let v_hat = v.divide(bias_correction(b2, step)?)?;
+
+        *parameter = parameter.subtract(
+            self.lr
+                .multiply(m_hat.divide(v_hat.sqrt()?.add(&self.eps)?)?)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl Updatable for NAdam {
+    fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+        use itertools::Itertools;
+
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
+    }
+
+    fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+        use itertools::Itertools;
+
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
+    }
+}
+
+impl_updatable_for_mut_optimizer!(NAdam);
@@ -0,0 +1,187 @@
+use mlx_internal_macros::{generate_builder, Buildable};
+
+use crate::{
+    array,
+    error::Exception,
+    ops::r#where,
+    utils::{get_mut_or_insert_with, Updatable},
+    Array,
+};
+
+use super::*;
+
+generate_builder! {
+    /// The RAdam optimizer [1].
+    ///
+    /// RAdam rectifies the variance of the adaptive learning rate. While the variance is
+    /// intractable, ie. for the first few steps, the update falls back to SGD with bias corrected
+    /// momentum.
+    ///
+    /// [1]: Liu, L. et al., 2020. On the variance of the adaptive learning rate and beyond. ICLR
+    ///     2020.
+    #[derive(Debug, Clone, Buildable)]
+    #[buildable(root = crate)]
+    #[builder(
+        build_with = build_radam,
+        default_infallible,
+        err = Exception,
+        root = crate
+    )]
+    pub struct RAdam {
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
+        pub lr: Array,
+
+        /// Schedule used to update [`RAdam::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
+        /// The coefficients used for computing running averages of the gradient and its square.
+        ///
+        /// Default to [`RAdam::DEFAULT_BETAS`].
+        #[builder(optional, ty_override = Betas, default = RAdam::DEFAULT_BETAS)]
+        pub betas: (Array, Array),
+
+        /// The epsilon added to the denominator to improve numerical stability.
+        ///
+        /// Default to [`RAdam::DEFAULT_EPS`].
+        #[builder(optional, ty_override = f32, default = RAdam::DEFAULT_EPS)]
+        pub eps: Array,
+
+        /// Inner state.
+        #[builder(ignore)]
+        pub state: StepState<State<(Array, Array)>>,
+    }
+}
+
+/// Builds a new [`RAdam`] optimizer.
+fn build_radam(builder: RAdamBuilder) -> Result<RAdam, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
+    let betas = builder.betas;
+
+    Ok(RAdam {
+        lr,
+        lr_schedule,
+        betas: (array!(betas.0), array!(betas.1)),
+        eps: array!(builder.eps),
+        state: StepState::default(),
+    })
+}
+
+impl RAdam {
+    /// Default value for `betas`.
+    pub const DEFAULT_BETAS: (f32, f32) = super::Adam::DEFAULT_BETAS;
+
+    /// Default value for `eps`.
+    pub const DEFAULT_EPS: f32 = super::Adam::DEFAULT_EPS;
+
+    /// The variance is rectified once the length of the approximated SMA exceeds this threshold.
+    pub const RHO_THRESHOLD: f32 = 5.0;
+}
+
+impl Optimizer for RAdam {
+    type State = StepState<State<(Array, Array)>>;
+
+    fn state(&self) -> &Self::State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut Self::State {
+        &mut self.state
+    }
+
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
+    fn update_single(
+        &mut self,
+        key: &Rc<str>,
+        gradient: &Array,
+        parameter: &mut Array,
+    ) -> crate::error::Result<()> {
+        let (b1, b2) = &self.betas;
+        let step = &self.state.step;
+        let (m, v) =
+            get_mut_or_insert_with(&mut self.state.params, key, || (array!(0.0), array!(0.0)));
+
+        let one = array!(1.0);
+        let two = array!(2.0);
+        let one_minus_b1 = one.subtract(b1)?;
+        let one_minus_b2 = one.subtract(b2)?;
+
+        *m = b1.multiply(&*m)?.add(&one_minus_b1.multiply(gradient)?)?;
+        *v = b2
+            .multiply(&*v)?
+            .add(&one_minus_b2.multiply(gradient.square()?)?)?;
+
+        let m_hat = m.divide(bias_correction(b1, step)?)?;
+
+        // Length of the approximated simple moving average
+        let t = step.as_dtype(crate::Dtype::Float32)?;
+        let b2_t = b2.power(&t)?;
+        let rho_inf = two.divide(&one_minus_b2)?.subtract(&one)?;
+        let rho_t = rho_inf.subtract(
+            two.multiply(&t)?
+                .multiply(&b2_t)?
+                .divide(one.subtract(&b2_t)?)?,
+        )?;
+
+        // The rectification is NaN when the variance is intractable, but it is masked out below
+        let rect = rho_t
+            .subtract(array!(4.0))?
+            .multiply(rho_t.subtract(&two)?)?
+            .multiply(&rho_inf)?
+            .divide(
+                rho_inf
+                    .subtract(array!(4.0))?
+                    .multiply(rho_inf.subtract(&two)?)?
+                    .multiply(&rho_t)?,
+            )?
+            .sqrt()?;
+        let adaptive_lr = one
+            .subtract(&b2_t)?
+            .sqrt()?
+            .divide(v.sqrt()?.add(&self.eps)?)?;
+
+        let rectified = m_hat.multiply(&rect)?.multiply(&adaptive_lr)?;
+        let tractable = rho_t.gt(array!(Self::RHO_THRESHOLD))?;
+        let update = r#where(&tractable, &rectified, &m_hat)?;
+
+        *parameter = parameter.subtract(self.lr.multiply(&update)?)?;
+
+        Ok(())
+    }
+}
+
+impl Updatable for RAdam {
+    fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+        use itertools::Itertools;
+
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
+    }
+
+    fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+        use itertools::Itertools;
+
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .flat_map(|(_, (v, u))| vec![v, u]),
+        )
+    }
+}
+
+impl_updatable_for_mut_optimizer!(RAdam);
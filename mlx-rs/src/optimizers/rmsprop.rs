@@ -24,10 +24,14 @@ generate_builder! {
         root = crate
     )]
     pub struct RmsProp {
-        /// Learning rate
-        #[builder(ty_override = f32)]
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
         pub lr: Array,
 
+        /// Schedule used to update [`RmsProp::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
+
         /// The smoothing constant. Default to [`RmsProp::DEFAULT_ALPHA`] if not specified.
         #[builder(optional, ty_override = f32, default = RmsProp::DEFAULT_ALPHA)]
         pub alpha: Array,
@@ -39,12 +43,12 @@ generate_builder! {
 
         /// Inner state
         #[builder(ignore)]
-        pub state: State,
+        pub state: StepState,
     }
 }
 
 fn build_rmdprop(builder: RmsPropBuilder) -> Result<RmsProp, RmsPropBuildError> {
-    let lr = builder.lr;
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
     let alpha = builder.alpha;
     let epsilon = builder.epsilon;
 
@@ -57,10 +61,11 @@ fn build_rmdprop(builder: RmsPropBuilder) -> Result<RmsProp, RmsPropBuildError>
     }
 
     Ok(RmsProp {
-        lr: array!(lr),
+        lr,
+        lr_schedule,
         alpha: array!(alpha),
         epsilon: array!(epsilon),
-        state: State::new(),
+        state: StepState::default(),
     })
 }
 
@@ -73,7 +78,7 @@ impl RmsProp {
 }
 
 impl Optimizer for RmsProp {
-    type State = State;
+    type State = StepState;
 
     fn state(&self) -> &Self::State {
         &self.state
@@ -83,13 +88,21 @@ impl Optimizer for RmsProp {
         &mut self.state
     }
 
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
     fn update_single(
         &mut self,
         key: &Rc<str>,
         gradient: &Array,
         parameter: &mut Array,
     ) -> crate::error::Result<()> {
-        let state = get_mut_or_insert_with(&mut self.state, key, || array!(0.0));
+        let state = get_mut_or_insert_with(&mut self.state.params, key, || array!(0.0));
 
         let lr = &self.lr;
         let alpha = &self.alpha;
@@ -115,19 +128,25 @@ impl Updatable for RmsProp {
     fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .map(|(_, v)| v)
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
     }
 
     fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter_mut()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .map(|(_, v)| v)
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
     }
 }
 
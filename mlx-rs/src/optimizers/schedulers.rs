@@ -0,0 +1,464 @@
+//! Learning rate schedulers.
+//!
+//! A scheduler computes the learning rate from the optimizer step, ie. the
+//! number of updates applied so far. The optimizers that take a learning rate
+//! accept either a constant or a scheduler, eg.
+//!
+//! ```rust
+//! use mlx_rs::optimizers::{schedulers::CosineDecay, Sgd};
+//!
+//! let constant = Sgd::new(0.1);
+//! let scheduled = Sgd::new(CosineDecay::new(0.1, 1000));
+//! ```
+//!
+//! The schedule is evaluated at the beginning of every
+//! [`Optimizer::update`](super::Optimizer::update) and the step is saved with
+//! the optimizer state.
+
+use std::{fmt::Debug, rc::Rc};
+
+use crate::{
+    array,
+    error::{Result, SchedulerBuildError},
+    ops::{minimum, r#where},
+    transforms::compile::is_tracing,
+    Array, Dtype,
+};
+
+/// Trait for learning rate schedules.
+pub trait Scheduler: Debug {
+    /// Returns the learning rate at the given step.
+    ///
+    /// The step is a scalar integer array so that the schedule can be traced,
+    /// eg. when the update is compiled.
+    fn value_at(&self, step: &Array) -> Result<Array>;
+}
+
+impl<S> Scheduler for Rc<S>
+where
+    S: Scheduler + ?Sized,
+{
+    fn value_at(&self, step: &Array) -> Result<Array> {
+        (**self).value_at(step)
+    }
+}
+
+impl<S> Scheduler for Box<S>
+where
+    S: Scheduler + ?Sized,
+{
+    fn value_at(&self, step: &Array) -> Result<Array> {
+        (**self).value_at(step)
+    }
+}
+
+/// Learning rate of an optimizer, either a constant or a [`Scheduler`].
+#[derive(Debug, Clone)]
+pub enum LearningRate {
+    /// Constant learning rate
+    Constant(f32),
+
+    /// Learning rate that changes with the optimizer step
+    Scheduled(Rc<dyn Scheduler>),
+}
+
+impl From<f32> for LearningRate {
+    fn from(value: f32) -> Self {
+        LearningRate::Constant(value)
+    }
+}
+
+impl<S> From<S> for LearningRate
+where
+    S: Scheduler + 'static,
+{
+    fn from(schedule: S) -> Self {
+        LearningRate::Scheduled(Rc::new(schedule))
+    }
+}
+
+impl LearningRate {
+    /// Splits the learning rate into its initial value and the schedule, if any.
+    ///
+    /// Returns an error if the schedule cannot be evaluated at step 0.
+    pub(crate) fn into_parts(self) -> Result<(Array, Option<Rc<dyn Scheduler>>)> {
+        match self {
+            LearningRate::Constant(value) => Ok((array!(value), None)),
+            LearningRate::Scheduled(schedule) => {
+                let initial = schedule.value_at(&array!(0))?;
+                Ok((initial, Some(schedule)))
+            }
+        }
+    }
+}
+
+/// Sets `lr` to the value of `schedule` at `step`, if any, and increments `step`.
+pub(crate) fn advance_step(
+    step: &mut Array,
+    lr: &mut Array,
+    schedule: Option<&Rc<dyn Scheduler>>,
+) -> Result<()> {
+    if let Some(schedule) = schedule {
+        *lr = schedule.value_at(step)?;
+    }
+    increment_step(step)
+}
+
+/// Increments `step`.
+pub(crate) fn increment_step(step: &mut Array) -> Result<()> {
+    *step = step.add(array!(1))?;
+
+    // Nothing else may depend on the step, eg. without a schedule, so it is
+    // evaluated to keep it from growing a graph with one node per update. The
+    // step cannot be evaluated while the update is traced, eg. compiled, in
+    // which case it is an output of the traced function and evaluated with it.
+    if !is_tracing() {
+        step.eval()?;
+    }
+    Ok(())
+}
+
+fn to_float(step: &Array) -> Result<Array> {
+    step.as_dtype(Dtype::Float32)
+}
+
+/// Multiplies the learning rate by `decay_rate` every `step_size` steps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepDecay {
+    /// Initial learning rate
+    pub init: f32,
+
+    /// Multiplicative factor to decay by
+    pub decay_rate: f32,
+
+    /// Decay every `step_size` steps
+    pub step_size: i32,
+}
+
+impl StepDecay {
+    /// Creates a new [`StepDecay`] schedule.
+    pub fn new(init: f32, decay_rate: f32, step_size: i32) -> Result<Self, SchedulerBuildError> {
+        if step_size < 1 {
+            return Err(SchedulerBuildError::NonPositiveSteps(step_size));
+        }
+
+        Ok(Self {
+            init,
+            decay_rate,
+            step_size,
+        })
+    }
+}
+
+impl Scheduler for StepDecay {
+    fn value_at(&self, step: &Array) -> Result<Array> {
+        let num_decays = to_float(&step.floor_divide(array!(self.step_size))?)?;
+        array!(self.decay_rate)
+            .power(&num_decays)?
+            .multiply(array!(self.init))
+    }
+}
+
+/// Multiplies the learning rate by `decay_rate` every step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExponentialDecay {
+    /// Initial learning rate
+    pub init: f32,
+
+    /// Multiplicative factor to decay by
+    pub decay_rate: f32,
+}
+
+impl ExponentialDecay {
+    /// Creates a new [`ExponentialDecay`] schedule.
+    pub fn new(init: f32, decay_rate: f32) -> Self {
+        Self { init, decay_rate }
+    }
+}
+
+impl Scheduler for ExponentialDecay {
+    fn value_at(&self, step: &Array) -> Result<Array> {
+        array!(self.decay_rate)
+            .power(&to_float(step)?)?
+            .multiply(array!(self.init))
+    }
+}
+
+/// Cosine decay from `init` to `end` over `decay_steps` steps. The learning
+/// rate stays at `end` afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CosineDecay {
+    /// Initial learning rate
+    pub init: f32,
+
+    /// Number of steps to decay over
+    pub decay_steps: i32,
+
+    /// Final learning rate. Default to [`CosineDecay::DEFAULT_END`].
+    pub end: f32,
+}
+
+impl CosineDecay {
+    /// Default value for `end`
+    pub const DEFAULT_END: f32 = 0.0;
+
+    /// Creates a new [`CosineDecay`] schedule that decays to
+    /// [`CosineDecay::DEFAULT_END`].
+    pub fn new(init: f32, decay_steps: i32) -> Self {
+        Self {
+            init,
+            decay_steps,
+            end: Self::DEFAULT_END,
+        }
+    }
+
+    /// Sets the final learning rate.
+    pub fn end(mut self, end: f32) -> Self {
+        self.end = end;
+        self
+    }
+}
+
+impl Scheduler for CosineDecay {
+    fn value_at(&self, step: &Array) -> Result<Array> {
+        let decay_steps = self.decay_steps.max(1) as f32;
+        let step = minimum(to_float(step)?, array!(decay_steps))?;
+        let cosine = step
+            .multiply(array!(std::f32::consts::PI / decay_steps))?
+            .cos()?;
+        let decay = cosine.add(array!(1.0))?.multiply(array!(0.5))?;
+        decay
+            .multiply(array!(self.init - self.end))?
+            .add(array!(self.end))
+    }
+}
+
+/// Linear schedule from `init` to `end` over `steps` steps, eg. for a warmup.
+/// The learning rate stays at `end` afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearSchedule {
+    /// Initial learning rate
+    pub init: f32,
+
+    /// Final learning rate
+    pub end: f32,
+
+    /// Number of steps to go from `init` to `end`
+    pub steps: i32,
+}
+
+impl LinearSchedule {
+    /// Creates a new [`LinearSchedule`].
+    pub fn new(init: f32, end: f32, steps: i32) -> Result<Self, SchedulerBuildError> {
+        if steps < 1 {
+            return Err(SchedulerBuildError::NonPositiveSteps(steps));
+        }
+
+        Ok(Self { init, end, steps })
+    }
+}
+
+impl Scheduler for LinearSchedule {
+    fn value_at(&self, step: &Array) -> Result<Array> {
+        let step = minimum(to_float(step)?, array!(self.steps as f32))?;
+        let slope = (self.end - self.init) / self.steps as f32;
+        step.multiply(array!(slope))?.add(array!(self.init))
+    }
+}
+
+fn check_boundaries(boundaries: &[i32], expected: usize) -> Result<(), SchedulerBuildError> {
+    if boundaries.len() != expected {
+        return Err(SchedulerBuildError::BoundariesMismatch {
+            expected,
+            found: boundaries.len(),
+        });
+    }
+    if boundaries.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(SchedulerBuildError::UnsortedBoundaries);
+    }
+    Ok(())
+}
+
+/// Joins multiple schedules, eg. a linear warmup followed by a cosine decay.
+///
+/// The `i + 1`-th schedule starts at `boundaries[i]` and is evaluated with the
+/// step relative to its boundary.
+#[derive(Debug, Clone)]
+pub struct JoinSchedules {
+    schedules: Vec<Rc<dyn Scheduler>>,
+    boundaries: Vec<i32>,
+}
+
+impl JoinSchedules {
+    /// Creates a new [`JoinSchedules`]. There must be one boundary less than
+    /// schedules.
+    pub fn new(
+        schedules: Vec<Rc<dyn Scheduler>>,
+        boundaries: Vec<i32>,
+    ) -> Result<Self, SchedulerBuildError> {
+        check_boundaries(&boundaries, schedules.len().saturating_sub(1))?;
+        Ok(Self {
+            schedules,
+            boundaries,
+        })
+    }
+}
+
+impl Scheduler for JoinSchedules {
+    fn value_at(&self, step: &Array) -> Result<Array> {
+        let mut schedules = self.schedules.iter();
+        let mut output = match schedules.next() {
+            Some(first) => first.value_at(step)?,
+            None => return Err("JoinSchedules requires at least one schedule".into()),
+        };
+
+        for (schedule, &boundary) in schedules.zip(&self.boundaries) {
+            let boundary = array!(boundary);
+            let value = schedule.value_at(&step.subtract(&boundary)?)?;
+            output = r#where(step.lt(&boundary)?, &output, &value)?;
+        }
+
+        Ok(output)
+    }
+}
+
+/// Piecewise constant learning rate, ie. `values[i]` is used from
+/// `boundaries[i - 1]` (inclusive) to `boundaries[i]` (exclusive).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PiecewiseConstant {
+    boundaries: Vec<i32>,
+    values: Vec<f32>,
+}
+
+impl PiecewiseConstant {
+    /// Creates a new [`PiecewiseConstant`] schedule. There must be one
+    /// boundary less than values.
+    pub fn new(boundaries: Vec<i32>, values: Vec<f32>) -> Result<Self, SchedulerBuildError> {
+        check_boundaries(&boundaries, values.len().saturating_sub(1))?;
+        Ok(Self { boundaries, values })
+    }
+}
+
+impl Scheduler for PiecewiseConstant {
+    fn value_at(&self, step: &Array) -> Result<Array> {
+        let mut values = self.values.iter();
+        let mut output = match values.next() {
+            Some(&first) => array!(first),
+            None => return Err("PiecewiseConstant requires at least one value".into()),
+        };
+
+        for (&value, &boundary) in values.zip(&self.boundaries) {
+            output = r#where(step.ge(array!(boundary))?, array!(value), &output)?;
+        }
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use super::*;
+
+    fn values(schedule: &impl Scheduler, steps: impl IntoIterator<Item = i32>) -> Vec<f32> {
+        steps
+            .into_iter()
+            .map(|step| schedule.value_at(&array!(step)).unwrap().item::<f32>())
+            .collect()
+    }
+
+    // The unit tests below are adapted from the mlx python codebase
+    #[test]
+    fn test_step_decay() {
+        let schedule = StepDecay::new(1e-1, 0.9, 1000).unwrap();
+        let lr = values(&schedule, [0, 999, 2500]);
+        assert_float_eq!(lr[0], 1e-1, abs <= 1e-6);
+        assert_float_eq!(lr[1], 1e-1, abs <= 1e-6);
+        assert_float_eq!(lr[2], 0.081, abs <= 1e-6);
+    }
+
+    #[test]
+    fn test_exponential_decay() {
+        let schedule = ExponentialDecay::new(1e-1, 0.99);
+        let lr = values(&schedule, [0, 10]);
+        assert_float_eq!(lr[0], 1e-1, abs <= 1e-6);
+        assert_float_eq!(lr[1], 0.1 * 0.99f32.powi(10), abs <= 1e-6);
+    }
+
+    #[test]
+    fn test_cosine_decay() {
+        let schedule = CosineDecay::new(0.1, 10);
+        let lr = values(&schedule, [0, 4, 10, 20]);
+        let expected = 0.1 * 0.5 * (1.0 + (std::f32::consts::PI * 4.0 / 10.0).cos());
+        assert_float_eq!(lr[0], 0.1, abs <= 1e-6);
+        assert_float_eq!(lr[1], expected, abs <= 1e-6);
+        assert_float_eq!(lr[2], 0.0, abs <= 1e-6);
+        assert_float_eq!(lr[3], 0.0, abs <= 1e-6);
+
+        let schedule = CosineDecay::new(0.1, 10).end(0.05);
+        let lr = values(&schedule, [20]);
+        assert_float_eq!(lr[0], 0.05, abs <= 1e-6);
+    }
+
+    #[test]
+    fn test_linear_schedule() {
+        let schedule = LinearSchedule::new(0.0, 1.0, 100).unwrap();
+        let lr = values(&schedule, [0, 50, 100, 200]);
+        assert_float_eq!(lr[0], 0.0, abs <= 1e-6);
+        assert_float_eq!(lr[1], 0.5, abs <= 1e-6);
+        assert_float_eq!(lr[2], 1.0, abs <= 1e-6);
+        assert_float_eq!(lr[3], 1.0, abs <= 1e-6);
+
+        assert_eq!(
+            LinearSchedule::new(0.0, 1.0, 0).unwrap_err(),
+            SchedulerBuildError::NonPositiveSteps(0)
+        );
+    }
+
+    #[test]
+    fn test_join_schedules() {
+        let warmup = LinearSchedule::new(0.0, 0.1, 10).unwrap();
+        let decay = CosineDecay::new(0.1, 10);
+        let schedules: Vec<Rc<dyn Scheduler>> = vec![Rc::new(warmup), Rc::new(decay)];
+        let schedule = JoinSchedules::new(schedules, vec![10]).unwrap();
+
+        let lr = values(&schedule, [0, 5, 10, 15, 20]);
+        assert_float_eq!(lr[0], 0.0, abs <= 1e-6);
+        assert_float_eq!(lr[1], 0.05, abs <= 1e-6);
+        assert_float_eq!(lr[2], 0.1, abs <= 1e-6);
+        assert_float_eq!(lr[3], 0.05, abs <= 1e-6);
+        assert_float_eq!(lr[4], 0.0, abs <= 1e-6);
+
+        assert!(JoinSchedules::new(vec![Rc::new(CosineDecay::new(0.1, 10))], vec![10]).is_err());
+    }
+
+    #[test]
+    fn test_piecewise_constant() {
+        let schedule = PiecewiseConstant::new(vec![10, 20], vec![1.0, 0.1, 0.01]).unwrap();
+        let lr = values(&schedule, [0, 9, 10, 19, 20, 100]);
+        assert_eq!(lr, vec![1.0, 1.0, 0.1, 0.1, 0.01, 0.01]);
+
+        assert_eq!(
+            PiecewiseConstant::new(vec![20, 10], vec![1.0, 0.1, 0.01]).unwrap_err(),
+            SchedulerBuildError::UnsortedBoundaries
+        );
+    }
+
+    #[derive(Debug)]
+    struct InvalidSchedule;
+
+    impl Scheduler for InvalidSchedule {
+        fn value_at(&self, step: &Array) -> Result<Array> {
+            step.reshape(&[2])
+        }
+    }
+
+    #[test]
+    fn test_invalid_schedule_fails_to_build() {
+        use crate::{builder::Builder, optimizers::SgdBuilder};
+
+        assert!(LearningRate::from(InvalidSchedule).into_parts().is_err());
+        assert!(SgdBuilder::new(InvalidSchedule).build().is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use std::{borrow::Cow, rc::Rc};
 
-use crate::{array, utils::get_mut_or_insert_with, Array};
+use crate::{array, error::Exception, utils::get_mut_or_insert_with, Array};
 use mlx_internal_macros::{generate_builder, Buildable};
 
 use super::*;
@@ -11,11 +11,18 @@ generate_builder! {
     #[buildable(root = crate)]
     #[builder(
         build_with = build_sgd,
+        default_infallible,
+        err = Exception,
         root = crate
     )]
     pub struct Sgd {
-        /// Learning rate
-        pub lr: f32,
+        /// The current learning rate. The builder accepts either a constant or a [`Scheduler`].
+        #[builder(ty_override = LearningRate)]
+        pub lr: Array,
+
+        /// Schedule used to update [`Sgd::lr`] at every step, if any.
+        #[builder(ignore)]
+        pub lr_schedule: Option<Rc<dyn Scheduler>>,
 
         /// Momentum strength. Default to [`Sgd::DEFAULT_MOMENTUM`] if not specified.
         #[builder(optional, default = Sgd::DEFAULT_MOMENTUM)]
@@ -35,12 +42,12 @@ generate_builder! {
 
         /// Inner state
         #[builder(ignore)]
-        pub state: State,
+        pub state: StepState,
     }
 }
 
-fn build_sgd(builder: SgdBuilder) -> Result<Sgd, std::convert::Infallible> {
-    let lr = builder.lr;
+fn build_sgd(builder: SgdBuilder) -> Result<Sgd, Exception> {
+    let (lr, lr_schedule) = builder.lr.into_parts()?;
     let momentum = builder.momentum;
     let weight_decay = builder.weight_decay;
     let dampening = builder.dampening;
@@ -48,11 +55,12 @@ fn build_sgd(builder: SgdBuilder) -> Result<Sgd, std::convert::Infallible> {
 
     Ok(Sgd {
         lr,
+        lr_schedule,
         momentum,
         weight_decay,
         dampening,
         nesterov,
-        state: State::new(),
+        state: StepState::default(),
     })
 }
 
@@ -71,7 +79,7 @@ impl Sgd {
 }
 
 impl Optimizer for Sgd {
-    type State = State;
+    type State = StepState;
 
     fn state(&self) -> &Self::State {
         &self.state
@@ -81,6 +89,14 @@ impl Optimizer for Sgd {
         &mut self.state
     }
 
+    fn advance_step(&mut self) -> crate::error::Result<()> {
+        advance_step(
+            &mut self.state.step,
+            &mut self.lr,
+            self.lr_schedule.as_ref(),
+        )
+    }
+
     /// Apply SGD to a single parameter. Returns the updated parameter and the updated state.
     #[inline]
     fn update_single(
@@ -89,7 +105,7 @@ impl Optimizer for Sgd {
         gradient: &Array,
         parameter: &mut Array,
     ) -> crate::error::Result<()> {
-        let state = get_mut_or_insert_with(&mut self.state, key, || array!(0.0));
+        let state = get_mut_or_insert_with(&mut self.state.params, key, || array!(0.0));
         let mut gradient = Cow::Borrowed(gradient);
 
         if self.weight_decay != 0.0 {
@@ -98,8 +114,7 @@ impl Optimizer for Sgd {
         }
 
         if self.momentum <= 0.0 {
-            let lr = array!(self.lr);
-            *parameter = parameter.subtract(lr.multiply(gradient)?)?;
+            *parameter = parameter.subtract(self.lr.multiply(gradient)?)?;
             return Ok(());
         }
 
@@ -116,15 +131,13 @@ impl Optimizer for Sgd {
         match self.nesterov {
             true => {
                 let momentum = array!(self.momentum);
-                let lr = array!(self.lr);
                 let update = gradient.add(momentum.multiply(&v)?)?;
-                *parameter = parameter.subtract(lr.multiply(&update)?)?;
+                *parameter = parameter.subtract(self.lr.multiply(&update)?)?;
                 *state = v;
             }
             false => {
                 let update = &v;
-                let lr = array!(self.lr);
-                *parameter = parameter.subtract(lr.multiply(update)?)?;
+                *parameter = parameter.subtract(self.lr.multiply(update)?)?;
                 *state = v;
             }
         }
@@ -137,19 +150,25 @@ impl Updatable for Sgd {
     fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .map(|(_, v)| v)
+        [&self.state.step, &self.lr].into_iter().chain(
+            self.state
+                .params
+                .iter()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
     }
 
     fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
         use itertools::Itertools;
 
-        self.state
-            .iter_mut()
-            .sorted_by(|a, b| a.0.cmp(b.0))
-            .map(|(_, v)| v)
+        let StepState { step, params } = &mut self.state;
+        [step, &mut self.lr].into_iter().chain(
+            params
+                .iter_mut()
+                .sorted_by(|a, b| a.0.cmp(b.0))
+                .map(|(_, v)| v),
+        )
     }
 }
 
@@ -0,0 +1,278 @@
+use std::rc::Rc;
+
+use crate::{
+    error::{IoError, UnflattenError},
+    module::ModuleParameters,
+    Array,
+};
+
+use super::*;
+
+const INNER_PREFIX: &str = "inner";
+const SHADOW_PREFIX: &str = "shadow";
+
+/// State of the optimizer wrappers that keep a shadow copy of the parameters, ie. [`Ema`],
+/// [`Lookahead`], [`MasterWeights`] and [`Swa`].
+///
+/// The state of the wrapped optimizer is held here rather than by the wrapped optimizer itself so
+/// that everything is saved and loaded together. It is flattened with the keys `"step"`,
+/// `"inner.{key}"` and `"shadow.{key}"`.
+#[derive(Debug, Clone)]
+pub struct ShadowState<S> {
+    /// Number of updates applied so far
+    pub step: Array,
+
+    /// State of the wrapped optimizer
+    pub inner: S,
+
+    /// Shadow copy of the parameters, keyed by the flattened parameter keys
+    pub shadow: State,
+}
+
+impl<S: Default> Default for ShadowState<S> {
+    fn default() -> Self {
+        Self {
+            step: array!(0),
+            inner: S::default(),
+            shadow: State::new(),
+        }
+    }
+}
+
+impl<S> OptimizerState for ShadowState<S>
+where
+    S: OptimizerState,
+{
+    type UnflattenError = IoError;
+
+    fn flatten(&self) -> impl Iterator<Item = (Rc<str>, &Array)> {
+        let inner = self
+            .inner
+            .flatten()
+            .map(|(k, v)| (Rc::from(format!("{}.{}", INNER_PREFIX, k)), v));
+        let shadow = self
+            .shadow
+            .iter()
+            .map(|(k, v)| (Rc::from(format!("{}.{}", SHADOW_PREFIX, k)), v));
+
+        std::iter::once((Rc::from(STEP_KEY), &self.step))
+            .chain(inner)
+            .chain(shadow)
+    }
+
+    fn flatten_mut(&mut self) -> impl Iterator<Item = (Rc<str>, &mut Array)> {
+        let Self {
+            step,
+            inner,
+            shadow,
+        } = self;
+        let inner = inner
+            .flatten_mut()
+            .map(|(k, v)| (Rc::from(format!("{}.{}", INNER_PREFIX, k)), v));
+        let shadow = shadow
+            .iter_mut()
+            .map(|(k, v)| (Rc::from(format!("{}.{}", SHADOW_PREFIX, k)), v));
+
+        std::iter::once((Rc::from(STEP_KEY), step))
+            .chain(inner)
+            .chain(shadow)
+    }
+
+    fn unflatten<I, K>(input: I) -> Result<Self, Self::UnflattenError>
+    where
+        I: IntoIterator<Item = (K, Array)>,
+        K: Ord + AsRef<str> + Into<Rc<str>>,
+    {
+        let mut step = None;
+        let mut inner = Vec::new();
+        let mut shadow = State::new();
+
+        for (key, value) in input {
+            let key = key.as_ref();
+            if key == STEP_KEY {
+                step = Some(value);
+                continue;
+            }
+
+            match key.split_once('.') {
+                Some((INNER_PREFIX, rest)) => inner.push((Rc::<str>::from(rest), value)),
+                Some((SHADOW_PREFIX, rest)) => {
+                    shadow.insert(Rc::from(rest), value);
+                }
+                _ => return Err(UnflattenError::InvalidKey.into()),
+            }
+        }
+
+        Ok(Self {
+            step: step.unwrap_or_else(|| array!(0)),
+            inner: S::unflatten(inner).map_err(Into::into)?,
+            shadow,
+        })
+    }
+}
+
+impl<S> ShadowState<S> {
+    /// Swaps the shadow copy with the parameters of the model.
+    ///
+    /// Calling this twice restores the original parameters. Only the parameters that have been
+    /// updated at least once have a shadow copy.
+    pub fn swap_parameters<M>(&mut self, model: &mut M)
+    where
+        M: ModuleParameters,
+    {
+        let mut parameters = model.parameters_mut().flatten();
+        for (key, shadow) in self.shadow.iter_mut() {
+            if let Some(parameter) = parameters.get_mut(key) {
+                std::mem::swap(&mut **parameter, shadow);
+            }
+        }
+    }
+}
+
+/// Runs `f` with the state swapped into the wrapped optimizer.
+pub(super) fn with_inner_state<O, T>(
+    inner: &mut O,
+    state: &mut O::State,
+    f: impl FnOnce(&mut O) -> T,
+) -> T
+where
+    O: Optimizer,
+{
+    std::mem::swap(state, inner.state_mut());
+    let output = f(inner);
+    std::mem::swap(state, inner.state_mut());
+    output
+}
+
+/// Collects the updatable states of a wrapper, ie. the step, the wrapped optimizer, its state and
+/// the shadow copy, in a consistent order.
+pub(super) fn shadow_updatable_states<'a, O>(
+    inner: &'a O,
+    state: &'a ShadowState<O::State>,
+) -> Vec<&'a Array>
+where
+    O: Optimizer,
+{
+    use itertools::Itertools;
+
+    let inner_state = state
+        .inner
+        .flatten()
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, v)| v);
+    let shadow = state
+        .shadow
+        .iter()
+        .sorted_by(|a, b| a.0.cmp(b.0))
+        .map(|(_, v)| v);
+
+    std::iter::once(&state.step)
+        .chain(inner.updatable_states())
+        .chain(inner_state)
+        .chain(shadow)
+        .collect()
+}
+
+/// Mutable version of [`shadow_updatable_states`].
+pub(super) fn shadow_updatable_states_mut<'a, O>(
+    inner: &'a mut O,
+    state: &'a mut ShadowState<O::State>,
+) -> Vec<&'a mut Array>
+where
+    O: Optimizer,
+{
+    use itertools::Itertools;
+
+    let ShadowState {
+        step,
+        inner: inner_state,
+        shadow,
+    } = state;
+    let inner_state = inner_state
+        .flatten_mut()
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, v)| v);
+    let shadow = shadow
+        .iter_mut()
+        .sorted_by(|a, b| a.0.cmp(b.0))
+        .map(|(_, v)| v);
+
+    std::iter::once(step)
+        .chain(inner.updatable_states_mut())
+        .chain(inner_state)
+        .chain(shadow)
+        .collect()
+}
+
+/// Implements [`Optimizer`] and [`Updatable`] for a wrapper that has the fields `inner` and
+/// `state`, and a method `update_shadow` that is called after the wrapped optimizer has updated a
+/// parameter.
+macro_rules! impl_shadow_optimizer {
+    ($wrapper:ident) => {
+        impl<O> Optimizer for $wrapper<O>
+        where
+            O: Optimizer,
+        {
+            type State = ShadowState<O::State>;
+
+            fn state(&self) -> &Self::State {
+                &self.state
+            }
+
+            fn state_mut(&mut self) -> &mut Self::State {
+                &mut self.state
+            }
+
+            fn advance_step(&mut self) -> crate::error::Result<()> {
+                increment_step(&mut self.state.step)?;
+                with_inner_state(&mut self.inner, &mut self.state.inner, |inner| {
+                    inner.advance_step()
+                })
+            }
+
+            fn update_single(
+                &mut self,
+                key: &Rc<str>,
+                gradient: &Array,
+                parameter: &mut Array,
+            ) -> crate::error::Result<()> {
+                if !self.state.shadow.contains_key(key) {
+                    self.state.shadow.insert(key.clone(), parameter.clone());
+                }
+
+                with_inner_state(&mut self.inner, &mut self.state.inner, |inner| {
+                    inner.update_single(key, gradient, parameter)
+                })?;
+
+                self.update_shadow(key, parameter)
+            }
+        }
+
+        impl<O> Updatable for $wrapper<O>
+        where
+            O: Optimizer,
+        {
+            fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+                shadow_updatable_states(&self.inner, &self.state)
+            }
+
+            fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+                shadow_updatable_states_mut(&mut self.inner, &mut self.state)
+            }
+        }
+
+        impl<O> Updatable for &'_ mut $wrapper<O>
+        where
+            O: Optimizer,
+        {
+            fn updatable_states(&self) -> impl IntoIterator<Item = &Array> {
+                <$wrapper<O> as Updatable>::updatable_states(&**self)
+            }
+
+            fn updatable_states_mut(&mut self) -> impl IntoIterator<Item = &mut Array> {
+                <$wrapper<O> as Updatable>::updatable_states_mut(&mut **self)
+            }
+        }
+    };
+}
+pub(super) use impl_shadow_optimizer;
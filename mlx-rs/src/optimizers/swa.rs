@@ -0,0 +1,105 @@
+use std::rc::Rc;
+
+use crate::{
+    error::SwaBuildError,
+    module::ModuleParameters,
+    ops::{floor_divide, r#where},
+    utils::Updatable,
+    Array, Dtype,
+};
+
+use super::*;
+
+/// Stochastic weight averaging [1].
+///
+/// Starting at step `start`, the parameters updated by the wrapped optimizer are averaged with
+/// equal weights every `frequency` steps. The averaged parameters can be swapped into the model
+/// with [`Swa::swap_parameters`]. Note that the statistics of batch norm layers should be
+/// recomputed with the averaged parameters before evaluation.
+///
+/// [1]: Izmailov, P. et al., 2018. Averaging weights leads to wider optima and better
+///     generalization. UAI 2018.
+#[derive(Debug, Clone)]
+pub struct Swa<O: Optimizer> {
+    /// The wrapped optimizer
+    pub inner: O,
+
+    /// First step, counting from 1, whose parameters are averaged
+    pub start: i32,
+
+    /// Number of steps between two averaged parameters
+    pub frequency: i32,
+
+    /// Inner state
+    pub state: ShadowState<O::State>,
+}
+
+impl<O> Swa<O>
+where
+    O: Optimizer,
+    O::State: Default,
+{
+    /// Default value for `frequency`.
+    pub const DEFAULT_FREQUENCY: i32 = 1;
+
+    /// Wraps `inner` and averages the parameters every `frequency` steps starting at step `start`.
+    pub fn new(mut inner: O, start: i32, frequency: i32) -> Result<Self, SwaBuildError> {
+        if start <= 0 {
+            return Err(SwaBuildError::NonPositiveStart(start));
+        }
+
+        if frequency <= 0 {
+            return Err(SwaBuildError::NonPositiveFrequency(frequency));
+        }
+
+        let state = ShadowState {
+            inner: std::mem::take(inner.state_mut()),
+            ..Default::default()
+        };
+
+        Ok(Self {
+            inner,
+            start,
+            frequency,
+            state,
+        })
+    }
+}
+
+impl<O> Swa<O>
+where
+    O: Optimizer,
+{
+    /// Swaps the averaged parameters with the parameters of the model.
+    ///
+    /// Calling this a second time restores the training parameters, which must be done before the
+    /// next update.
+    pub fn swap_parameters<M>(&mut self, model: &mut M)
+    where
+        M: ModuleParameters,
+    {
+        self.state.swap_parameters(model)
+    }
+
+    fn update_shadow(&mut self, key: &Rc<str>, parameter: &mut Array) -> crate::error::Result<()> {
+        if let Some(average) = self.state.shadow.get_mut(key) {
+            let frequency = array!(self.frequency);
+            let since_start = self.state.step.subtract(array!(self.start))?;
+            let active = since_start
+                .ge(array!(0))?
+                .logical_and(since_start.remainder(&frequency)?.eq(array!(0))?)?;
+
+            // Number of parameters averaged before this step
+            let count = floor_divide(&since_start, &frequency)?.as_dtype(Dtype::Float32)?;
+            let new_average = average.add(
+                parameter
+                    .subtract(&*average)?
+                    .divide(count.add(array!(1.0))?)?,
+            )?;
+            *average = r#where(&active, &new_average, &*average)?;
+        }
+        Ok(())
+    }
+}
+
+impl_shadow_optimizer!(Swa);
@@ -2,6 +2,10 @@
 
 use crate::module::{Module, ModuleParameters};
 
+/// Chooses the group size and number of bits of the layer at a path, or `None` to keep the layer
+/// unquantized. See [`Quantizable::try_into_quantized_with`].
+pub type QuantizationPredicate<'a> = dyn FnMut(&str) -> Option<(i32, i32)> + 'a;
+
 /// Trait for quantization of modules.
 pub trait Quantizable {
     /// The default group size for quantization.
@@ -22,6 +26,35 @@ pub trait Quantizable {
         group_size: i32,
         bits: i32,
     ) -> Result<Self::Quantized, Self::QuantizationError>;
+
+    /// Quantize the module with the group size and number of bits that `predicate` returns for
+    /// each layer.
+    ///
+    /// `path` is the key of the module in the parameters of its parent modules, eg.
+    /// `"layers.0.mlp"`, and the layers are passed the key of their own parameters, eg.
+    /// `"layers.0.mlp.up_proj"`. Only the layers wrapped in [`MaybeQuantized`] can be kept
+    /// unquantized, the other modules use the default group size and number of bits if `predicate`
+    /// returns `None`.
+    fn try_into_quantized_with(
+        self,
+        path: &str,
+        predicate: &mut QuantizationPredicate<'_>,
+    ) -> Result<Self::Quantized, Self::QuantizationError>
+    where
+        Self: Sized,
+    {
+        let (group_size, bits) =
+            predicate(path).unwrap_or((Self::DEFAULT_GROUP_SIZE, Self::DEFAULT_BITS));
+        self.try_into_quantized(group_size, bits)
+    }
+}
+
+fn child_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
 }
 
 impl<M> Quantizable for Vec<M>
@@ -41,6 +74,17 @@ where
             .map(|m| m.try_into_quantized(group_size, bits))
             .collect()
     }
+
+    fn try_into_quantized_with(
+        self,
+        path: &str,
+        predicate: &mut QuantizationPredicate<'_>,
+    ) -> Result<Self::Quantized, Self::QuantizationError> {
+        self.into_iter()
+            .enumerate()
+            .map(|(i, m)| m.try_into_quantized_with(&child_path(path, &i.to_string()), predicate))
+            .collect()
+    }
 }
 
 impl<M> Quantizable for Box<M>
@@ -58,6 +102,43 @@ where
     ) -> Result<Self::Quantized, Self::QuantizationError> {
         (*self).try_into_quantized(group_size, bits).map(Box::new)
     }
+
+    fn try_into_quantized_with(
+        self,
+        path: &str,
+        predicate: &mut QuantizationPredicate<'_>,
+    ) -> Result<Self::Quantized, Self::QuantizationError> {
+        (*self)
+            .try_into_quantized_with(path, predicate)
+            .map(Box::new)
+    }
+}
+
+impl<M> Quantizable for Option<M>
+where
+    M: Quantizable,
+{
+    type Quantized = Option<M::Quantized>;
+
+    type QuantizationError = M::QuantizationError;
+
+    fn try_into_quantized(
+        self,
+        group_size: i32,
+        bits: i32,
+    ) -> Result<Self::Quantized, Self::QuantizationError> {
+        self.map(|m| m.try_into_quantized(group_size, bits))
+            .transpose()
+    }
+
+    fn try_into_quantized_with(
+        self,
+        path: &str,
+        predicate: &mut QuantizationPredicate<'_>,
+    ) -> Result<Self::Quantized, Self::QuantizationError> {
+        self.map(|m| m.try_into_quantized_with(path, predicate))
+            .transpose()
+    }
 }
 
 /// A wrapper for a quantizable module.
@@ -93,6 +174,23 @@ where
             MaybeQuantized::Quantized(q) => Ok(MaybeQuantized::Quantized(q)),
         }
     }
+
+    fn try_into_quantized_with(
+        self,
+        path: &str,
+        predicate: &mut QuantizationPredicate<'_>,
+    ) -> Result<Self, Self::QuantizationError> {
+        match self {
+            MaybeQuantized::Original(m) => match predicate(path) {
+                Some((group_size, bits)) => {
+                    let quantized = m.try_into_quantized(group_size, bits)?;
+                    Ok(MaybeQuantized::Quantized(quantized))
+                }
+                None => Ok(MaybeQuantized::Original(m)),
+            },
+            MaybeQuantized::Quantized(q) => Ok(MaybeQuantized::Quantized(q)),
+        }
+    }
 }
 
 impl<M> MaybeQuantized<M>
@@ -231,4 +329,58 @@ mod tests {
         qembedding = nn::quantize(qembedding, None, None).unwrap();
         assert!(qembedding.is_quantized());
     }
+
+    #[test]
+    fn test_quantize_with_predicate() {
+        let layers = vec![
+            MaybeQuantized::new(Linear::new(64, 64).unwrap()),
+            MaybeQuantized::new(Linear::new(64, 64).unwrap()),
+        ];
+
+        let mut paths = Vec::new();
+        let layers = layers
+            .try_into_quantized_with("layers", &mut |path| {
+                paths.push(path.to_string());
+                (path == "layers.1").then_some((32, 8))
+            })
+            .unwrap();
+        assert_eq!(paths, ["layers.0", "layers.1"]);
+        assert!(!layers[0].is_quantized());
+        match &layers[1] {
+            MaybeQuantized::Quantized(q) => {
+                assert_eq!(q.group_size, 32);
+                assert_eq!(q.bits, 8);
+            }
+            MaybeQuantized::Original(_) => panic!("expected a quantized layer"),
+        }
+    }
+
+    #[test]
+    fn test_quantized_embedding_parameters() {
+        let embedding = Embedding::new(64, 64).unwrap();
+        let qembedding = nn::QuantizedEmbedding::try_from(embedding).unwrap();
+
+        let params = qembedding.parameters().flatten();
+        assert_eq!(params.len(), 3);
+        assert!(params.contains_key("inner.weight"));
+        assert!(params.contains_key("scales"));
+        assert!(params.contains_key("biases"));
+
+        // The parameters are frozen when the layer is built
+        assert_eq!(qembedding.all_frozen(), Some(true));
+        assert!(qembedding.trainable_parameters().flatten().is_empty());
+    }
+
+    #[test]
+    fn test_quantizable_option() {
+        let linear = Some(MaybeQuantized::new(Linear::new(64, 64).unwrap()));
+        let qlinear = nn::quantize(linear, None, None).unwrap();
+        assert!(qlinear.as_ref().unwrap().is_quantized());
+        assert!(qlinear.parameters().flatten().contains_key("scales"));
+
+        let none: Option<MaybeQuantized<Linear>> = None;
+        let none = nn::quantize(none, None, None).unwrap();
+        assert!(none.parameters().flatten().is_empty());
+        assert_eq!(none.all_frozen(), None);
+    }
 }
@@ -4,7 +4,6 @@ use crate::ops::indexing::TryIndexOp;
 use crate::utils::guard::Guarded;
 use crate::utils::IntoOption;
 use crate::{error::Result, Array, ArrayElement, Stream, StreamOrDevice};
-use mach_sys::mach_time;
 use mlx_internal_macros::{default_device, generate_macro};
 use parking_lot::Mutex;
 use std::borrow::Cow;
@@ -16,8 +15,9 @@ struct RandomState {
 
 impl RandomState {
     fn new() -> Result<Self> {
-        let now = unsafe { mach_time::mach_approximate_time() };
-        Ok(Self { state: key(now)? })
+        Ok(Self {
+            state: key(initial_seed())?,
+        })
     }
 
     fn next(&mut self) -> Result<Array> {
@@ -32,6 +32,21 @@ impl RandomState {
     }
 }
 
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn initial_seed() -> u64 {
+    unsafe { mach_sys::mach_time::mach_approximate_time() }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+fn initial_seed() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
 fn state() -> &'static Mutex<RandomState> {
     static STATE: OnceLock<Mutex<RandomState>> = OnceLock::new();
     STATE.get_or_init(|| Mutex::new(RandomState::new().unwrap()))
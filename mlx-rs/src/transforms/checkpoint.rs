@@ -0,0 +1,153 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    error::{get_and_clear_closure_error, Exception, Result},
+    utils::{guard::Guarded, Closure, Shared, SharedFn, VectorArray},
+    Array,
+};
+
+fn build_checkpoint(
+    f: Shared<SharedFn<'static>>,
+) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'static {
+    move |args: &[Array]| -> Result<Vec<Array>> {
+        // The closure is called again in the backward pass so it must outlive
+        // this call
+        let closure = Closure::new_shared(&f);
+        let checkpointed =
+            Closure::try_from_op(|res| unsafe { mlx_sys::mlx_checkpoint(res, closure.as_ptr()) })?;
+
+        let inputs = VectorArray::try_from_iter(args.iter())?;
+        <Vec<Array> as Guarded>::try_from_op(|res| unsafe {
+            mlx_sys::mlx_closure_apply(res, checkpointed.as_ptr(), inputs.as_ptr())
+        })
+        .map_err(|e| match get_and_clear_closure_error() {
+            Some(err) => err,
+            None => e,
+        })
+    }
+}
+
+/// Trait for functions/closures that can be converted into a checkpointed closure.
+pub trait IntoCheckpoint<Err> {
+    /// Convert the function/closure into a checkpointed closure.
+    fn into_checkpoint(self) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'static;
+}
+
+impl<F> IntoCheckpoint<()> for F
+where
+    F: FnMut(&[Array]) -> Vec<Array> + 'static,
+{
+    fn into_checkpoint(mut self) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'static {
+        let f = move |args: &[Array]| -> Result<Vec<Array>> { Ok(self(args)) };
+        build_checkpoint(Rc::new(RefCell::new(Box::new(f))))
+    }
+}
+
+impl<F> IntoCheckpoint<Exception> for F
+where
+    F: FnMut(&[Array]) -> Result<Vec<Array>> + 'static,
+{
+    fn into_checkpoint(self) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'static {
+        build_checkpoint(Rc::new(RefCell::new(Box::new(self))))
+    }
+}
+
+/// Returns a gradient checkpointed version of `f`.
+///
+/// The intermediate results of `f` are not kept for the backward pass.
+/// Instead, `f` is called again to recompute them when the gradient is
+/// computed, which trades compute for memory.
+///
+/// Only the `Array`s passed as inputs are saved for the backward pass. See
+/// [`crate::nn::Checkpointed`] to checkpoint a module.
+///
+/// `f` must be `'static` because mlx keeps it alive with the outputs and calls
+/// it again in the backward pass, which may happen after the checkpointed
+/// closure is dropped.
+///
+/// # Example
+///
+/// ```rust
+/// use mlx_rs::{array, error::Result, transforms::{checkpoint, grad}, Array};
+///
+/// let layer = |args: &[Array]| -> Result<Vec<Array>> {
+///     Ok(vec![args[0].exp()?.sin()?])
+/// };
+///
+/// let loss = move |x: &Array| -> Result<Array> {
+///     let y = checkpoint(layer)(std::slice::from_ref(x))?;
+///     y[0].sum(None, None)
+/// };
+///
+/// let dx = grad(loss)(&array!([1.0f32, 2.0])).unwrap();
+/// ```
+pub fn checkpoint<F, Err>(f: F) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'static
+where
+    F: IntoCheckpoint<Err> + 'static,
+{
+    f.into_checkpoint()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        array,
+        error::{Exception, Result},
+        transforms::{grad, value_and_grad},
+        Array,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_checkpoint() {
+        let f = |args: &[Array]| -> Result<Vec<Array>> { Ok(vec![args[0].exp()?]) };
+
+        let loss = move |args: &[Array]| -> Result<Vec<Array>> {
+            let y = checkpoint(f)(args)?;
+            Ok(vec![y[0].sum(None, None)?])
+        };
+
+        let x = array!([0.0f32, 1.0]);
+        let (value, grads) = value_and_grad(loss)(&[x.clone()]).unwrap();
+
+        let expected = x.exp().unwrap();
+        assert_eq!(value[0], expected.sum(None, None).unwrap());
+        assert_eq!(grads[0], expected);
+    }
+
+    #[test]
+    fn test_checkpoint_is_called_again_in_backward_pass() {
+        let num_calls = Rc::new(std::cell::Cell::new(0));
+        let counter = num_calls.clone();
+        let f = move |args: &[Array]| -> Vec<Array> {
+            counter.set(counter.get() + 1);
+            vec![args[0].square().unwrap()]
+        };
+        let mut checkpointed = checkpoint(f);
+        let loss = |x: &Array| -> Result<Array> {
+            checkpointed(std::slice::from_ref(x))?[0].sum(None, None)
+        };
+
+        let dfdx = grad(loss)(&array!(3.0f32)).unwrap();
+        assert_eq!(dfdx.item::<f32>(), 6.0);
+
+        // Once in the forward pass and once more in the backward pass
+        assert!(num_calls.get() >= 2);
+    }
+
+    #[test]
+    fn test_checkpoint_with_error() {
+        let f =
+            |args: &[Array]| -> Result<Vec<Array>> { args[0].add(&args[1]).map(|res| vec![res]) };
+
+        let a = array!([1.0f32, 2.0, 3.0]);
+        let b = array!([4.0f32, 5.0]);
+        let result = checkpoint(f)(&[a, b]);
+        assert!(result.is_err());
+
+        // Check that the error is not just "mlx_closure returned a non-zero value"
+        let err: Exception = result.unwrap_err();
+        assert!(!err.what().contains("non-zero value"))
+    }
+}
@@ -15,7 +15,9 @@ use crate::{
     Array,
 };
 
-use super::{update_by_replace_with_ref_to_new_array, Closure, Compiled, Guarded, VectorArray};
+use super::{
+    update_by_replace_with_ref_to_new_array, Closure, Compiled, Guarded, TracingGuard, VectorArray,
+};
 
 /// Similar to [`crate::transforms::compile`] but allows for functions that take
 /// a mutable reference to a state `U`.
@@ -508,7 +510,10 @@ impl<F> CompiledState<F> {
             }
 
             // call the function with the tracer arguments and the state holding tracers
-            let mut result = (f)(*state_clone.borrow_mut(), tracer_args);
+            let mut result = {
+                let _tracing = TracingGuard::new();
+                (f)(*state_clone.borrow_mut(), tracer_args)
+            };
 
             // recapture the state as it may have changed
             let mut state_output_tracers = state_clone
@@ -578,7 +583,10 @@ impl<F> CompiledState<F> {
             }
 
             // call the function with the tracer arguments and the state holding tracers
-            let mut result = (f)(*state_clone.borrow_mut(), tracer_args)?;
+            let mut result = {
+                let _tracing = TracingGuard::new();
+                (f)(*state_clone.borrow_mut(), tracer_args)?
+            };
 
             // recapture the state as it may have changed
             let mut state_output_tracers = state_clone
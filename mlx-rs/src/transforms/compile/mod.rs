@@ -142,6 +142,7 @@
 //! See mlx-rs/mlx-tests/tests/test_compile_with_state.rs for more examples.
 //!
 
+use std::cell::Cell;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
@@ -203,6 +204,32 @@ impl<F> Drop for CompiledState<F> {
     }
 }
 
+thread_local! {
+    static TRACE_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Returns `true` while a function compiled with [`compile_with_state`] is traced on this thread,
+/// in which case the arrays are placeholders that cannot be evaluated.
+pub(crate) fn is_tracing() -> bool {
+    TRACE_DEPTH.with(|depth| depth.get() > 0)
+}
+
+/// Marks the current thread as tracing until dropped.
+struct TracingGuard;
+
+impl TracingGuard {
+    fn new() -> Self {
+        TRACE_DEPTH.with(|depth| depth.set(depth.get() + 1));
+        TracingGuard
+    }
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        TRACE_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
 fn type_id_to_usize<T>(_val: &T) -> usize
 where
     T: 'static,
@@ -0,0 +1,443 @@
+use std::{cell::RefCell, ffi::c_void, rc::Rc};
+
+use mlx_sys::{mlx_vector_array, mlx_vector_int};
+
+use crate::{
+    error::{get_and_clear_closure_error, Result},
+    utils::{
+        call_shared_payload, drop_shared_payload, guard::Guarded, into_shared_payload,
+        mlx_vector_array_values, new_mlx_vector_array, Closure, Shared, SharedFn, VectorArray,
+        SUCCESS,
+    },
+    Array,
+};
+
+// The closures are kept alive by the outputs of the custom function, eg. the
+// vjp is called when the gradient is computed, so they cannot borrow anything
+type VjpFn = dyn FnMut(&[Array], &[Array], &[Array]) -> Result<Vec<Array>> + 'static;
+type JvpFn = dyn FnMut(&[Array], &[Array], &[i32]) -> Result<Vec<Array>> + 'static;
+type VmapFn = dyn FnMut(&[Array], &[i32]) -> Result<(Vec<Array>, Vec<i32>)> + 'static;
+
+/// A function with user-defined transforms.
+///
+/// See [`custom_function`] for more details.
+pub struct CustomFunction {
+    forward: Shared<SharedFn<'static>>,
+    vjp: Option<Shared<VjpFn>>,
+    jvp: Option<Shared<JvpFn>>,
+    vmap: Option<Shared<VmapFn>>,
+}
+
+impl std::fmt::Debug for CustomFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomFunction")
+            .field("vjp", &self.vjp.is_some())
+            .field("jvp", &self.jvp.is_some())
+            .field("vmap", &self.vmap.is_some())
+            .finish()
+    }
+}
+
+impl CustomFunction {
+    /// Creates a new custom function with the given forward function and no
+    /// custom transforms.
+    pub fn new<F>(forward: F) -> Self
+    where
+        F: FnMut(&[Array]) -> Result<Vec<Array>> + 'static,
+    {
+        Self {
+            forward: Rc::new(RefCell::new(Box::new(forward))),
+            vjp: None,
+            jvp: None,
+            vmap: None,
+        }
+    }
+
+    /// Sets the custom vector-Jacobian product.
+    ///
+    /// The closure is called with `(primals, cotangents, outputs)` and should
+    /// return one cotangent for each of the primals.
+    pub fn vjp<F>(mut self, vjp: F) -> Self
+    where
+        F: FnMut(&[Array], &[Array], &[Array]) -> Result<Vec<Array>> + 'static,
+    {
+        self.vjp = Some(Rc::new(RefCell::new(Box::new(vjp))));
+        self
+    }
+
+    /// Sets the custom Jacobian-vector product.
+    ///
+    /// The closure is called with `(primals, tangents, argnums)` where
+    /// `tangents` are the tangents of the primals in `argnums`, and should
+    /// return one tangent for each of the outputs.
+    pub fn jvp<F>(mut self, jvp: F) -> Self
+    where
+        F: FnMut(&[Array], &[Array], &[i32]) -> Result<Vec<Array>> + 'static,
+    {
+        self.jvp = Some(Rc::new(RefCell::new(Box::new(jvp))));
+        self
+    }
+
+    /// Sets the custom vectorization rule.
+    ///
+    /// The closure is called with `(inputs, axes)` where `axes` are the
+    /// vectorized axes of the inputs (`-1` if an input is not vectorized), and
+    /// should return the batched outputs and the vectorized axis of each
+    /// output.
+    pub fn vmap<F>(mut self, vmap: F) -> Self
+    where
+        F: FnMut(&[Array], &[i32]) -> Result<(Vec<Array>, Vec<i32>)> + 'static,
+    {
+        self.vmap = Some(Rc::new(RefCell::new(Box::new(vmap))));
+        self
+    }
+
+    /// Applies the custom function to `args`.
+    pub fn call(&self, args: &[Array]) -> Result<Vec<Array>> {
+        let forward = Closure::new_shared(&self.forward);
+        let vjp = CustomVjpClosure::new(self.vjp.as_ref());
+        let jvp = CustomJvpClosure::new(self.jvp.as_ref());
+        let vmap = CustomVmapClosure::new(self.vmap.as_ref());
+
+        let custom = Closure::try_from_op(|res| unsafe {
+            mlx_sys::mlx_custom_function(res, forward.as_ptr(), vjp.0, jvp.0, vmap.0)
+        })?;
+
+        let inputs = VectorArray::try_from_iter(args.iter())?;
+        <Vec<Array> as Guarded>::try_from_op(|res| unsafe {
+            mlx_sys::mlx_closure_apply(res, custom.as_ptr(), inputs.as_ptr())
+        })
+        .map_err(|e| match get_and_clear_closure_error() {
+            Some(err) => err,
+            None => e,
+        })
+    }
+}
+
+/// Returns a function whose transforms can be customized.
+///
+/// This is useful to define operations whose backward pass is written by hand,
+/// eg. straight-through estimators, numerically stable gradients or gradient
+/// reversal layers. Transforms that are not customized are derived from the
+/// forward function as usual.
+///
+/// The returned [`CustomFunction`] can be called inside the closures passed to
+/// [`grad()`](super::grad()), [`value_and_grad()`](super::value_and_grad()),
+/// [`crate::nn::value_and_grad()`] and the other function transforms. Errors
+/// returned by any of the closures are propagated to the caller of the
+/// transform.
+///
+/// The closures must be `'static` because mlx keeps them alive with the
+/// outputs of the function and calls them again when the outputs are
+/// transformed, which may happen after the [`CustomFunction`] is dropped. Move
+/// or clone the captured values into the closures instead of borrowing them.
+///
+/// # Example
+///
+/// ```rust
+/// use mlx_rs::{array, error::Result, transforms::{custom_function, grad}, Array};
+///
+/// // Straight-through estimator: round in the forward pass and pass the
+/// // cotangents through unchanged in the backward pass
+/// let round_ste = custom_function(|args: &[Array]| Ok(vec![args[0].round(None)?]))
+///     .vjp(|_primals, cotangents, _outputs| Ok(cotangents.to_vec()));
+///
+/// let loss = |x: &Array| -> Result<Array> {
+///     let rounded = round_ste.call(std::slice::from_ref(x))?;
+///     (&rounded[0] * x).sum(None, None)
+/// };
+///
+/// let x = array!([0.4f32, 1.6]);
+/// let dx = grad(loss)(&x).unwrap();
+/// ```
+pub fn custom_function<F>(forward: F) -> CustomFunction
+where
+    F: FnMut(&[Array]) -> Result<Vec<Array>> + 'static,
+{
+    CustomFunction::new(forward)
+}
+
+struct CustomVjpClosure(mlx_sys::mlx_closure_custom);
+
+impl CustomVjpClosure {
+    fn new(vjp: Option<&Shared<VjpFn>>) -> Self {
+        let c_closure = unsafe {
+            match vjp {
+                Some(vjp) => mlx_sys::mlx_closure_custom_new_func_payload(
+                    Some(vjp_trampoline),
+                    into_shared_payload(vjp),
+                    Some(drop_shared_payload::<VjpFn>),
+                ),
+                // An empty closure tells mlx to use the default transform
+                None => mlx_sys::mlx_closure_custom_new(),
+            }
+        };
+        Self(c_closure)
+    }
+}
+
+impl Drop for CustomVjpClosure {
+    fn drop(&mut self) {
+        let status = unsafe { mlx_sys::mlx_closure_custom_free(self.0) };
+        debug_assert_eq!(status, SUCCESS);
+    }
+}
+
+extern "C" fn vjp_trampoline(
+    ret: *mut mlx_vector_array,
+    primals: mlx_vector_array,
+    cotangents: mlx_vector_array,
+    outputs: mlx_vector_array,
+    payload: *mut c_void,
+) -> i32 {
+    unsafe {
+        call_shared_payload::<VjpFn, _>(
+            payload,
+            |vjp| {
+                vjp(
+                    &mlx_vector_array_values(primals)?,
+                    &mlx_vector_array_values(cotangents)?,
+                    &mlx_vector_array_values(outputs)?,
+                )
+            },
+            |grads| *ret = new_mlx_vector_array(grads),
+        )
+    }
+}
+
+struct CustomJvpClosure(mlx_sys::mlx_closure_custom_jvp);
+
+impl CustomJvpClosure {
+    fn new(jvp: Option<&Shared<JvpFn>>) -> Self {
+        let c_closure = unsafe {
+            match jvp {
+                Some(jvp) => mlx_sys::mlx_closure_custom_jvp_new_func_payload(
+                    Some(jvp_trampoline),
+                    into_shared_payload(jvp),
+                    Some(drop_shared_payload::<JvpFn>),
+                ),
+                None => mlx_sys::mlx_closure_custom_jvp_new(),
+            }
+        };
+        Self(c_closure)
+    }
+}
+
+impl Drop for CustomJvpClosure {
+    fn drop(&mut self) {
+        let status = unsafe { mlx_sys::mlx_closure_custom_jvp_free(self.0) };
+        debug_assert_eq!(status, SUCCESS);
+    }
+}
+
+extern "C" fn jvp_trampoline(
+    ret: *mut mlx_vector_array,
+    primals: mlx_vector_array,
+    tangents: mlx_vector_array,
+    argnums: *const i32,
+    argnums_num: usize,
+    payload: *mut c_void,
+) -> i32 {
+    unsafe {
+        let argnums = slice_or_empty(argnums, argnums_num);
+        call_shared_payload::<JvpFn, _>(
+            payload,
+            |jvp| {
+                jvp(
+                    &mlx_vector_array_values(primals)?,
+                    &mlx_vector_array_values(tangents)?,
+                    argnums,
+                )
+            },
+            |tangents| *ret = new_mlx_vector_array(tangents),
+        )
+    }
+}
+
+struct CustomVmapClosure(mlx_sys::mlx_closure_custom_vmap);
+
+impl CustomVmapClosure {
+    fn new(vmap: Option<&Shared<VmapFn>>) -> Self {
+        let c_closure = unsafe {
+            match vmap {
+                Some(vmap) => mlx_sys::mlx_closure_custom_vmap_new_func_payload(
+                    Some(vmap_trampoline),
+                    into_shared_payload(vmap),
+                    Some(drop_shared_payload::<VmapFn>),
+                ),
+                None => mlx_sys::mlx_closure_custom_vmap_new(),
+            }
+        };
+        Self(c_closure)
+    }
+}
+
+impl Drop for CustomVmapClosure {
+    fn drop(&mut self) {
+        let status = unsafe { mlx_sys::mlx_closure_custom_vmap_free(self.0) };
+        debug_assert_eq!(status, SUCCESS);
+    }
+}
+
+extern "C" fn vmap_trampoline(
+    ret_0: *mut mlx_vector_array,
+    ret_1: *mut mlx_vector_int,
+    inputs: mlx_vector_array,
+    axes: *const i32,
+    axes_num: usize,
+    payload: *mut c_void,
+) -> i32 {
+    unsafe {
+        let axes = slice_or_empty(axes, axes_num);
+        call_shared_payload::<VmapFn, _>(
+            payload,
+            |vmap| vmap(&mlx_vector_array_values(inputs)?, axes),
+            |(outputs, out_axes)| {
+                *ret_0 = new_mlx_vector_array(outputs);
+                *ret_1 = mlx_sys::mlx_vector_int_new_data(out_axes.as_ptr(), out_axes.len());
+            },
+        )
+    }
+}
+
+unsafe fn slice_or_empty<'a>(ptr: *const i32, len: usize) -> &'a [i32] {
+    if ptr.is_null() || len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        array,
+        error::{Exception, Result},
+        transforms::{grad, jvp, value_and_grad, vmap},
+        Array,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_custom_vjp() {
+        // Straight-through estimator
+        let round_ste = custom_function(|args: &[Array]| Ok(vec![args[0].round(None)?]))
+            .vjp(|_primals, cotangents, _outputs| Ok(cotangents.to_vec()));
+
+        let f = |x: &Array| -> Result<Array> {
+            let y = round_ste.call(std::slice::from_ref(x))?;
+            y[0].sum(None, None)
+        };
+
+        let x = array!([0.4f32, 1.6, -2.3]);
+        let dfdx = grad(f)(&x).unwrap();
+        assert_eq!(dfdx, array!([1.0f32, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn test_custom_vjp_with_value_and_grad() {
+        // Gradient reversal
+        let reverse = custom_function(|args: &[Array]| Ok(vec![args[0].clone()]))
+            .vjp(|_primals, cotangents, _outputs| Ok(vec![cotangents[0].negative()?]));
+
+        let f = |args: &[Array]| -> Result<Vec<Array>> {
+            let y = reverse.call(args)?;
+            Ok(vec![y[0].square()?.sum(None, None)?])
+        };
+
+        let x = array!([1.0f32, 2.0]);
+        let (value, grads) = value_and_grad(f)(&[x]).unwrap();
+        assert_eq!(value[0].item::<f32>(), 5.0);
+        assert_eq!(grads[0], array!([-2.0f32, -4.0]));
+    }
+
+    #[test]
+    fn test_custom_function_can_be_called_repeatedly() {
+        let double = custom_function(|args: &[Array]| Ok(vec![args[0].multiply(array!(2.0f32))?]))
+            .vjp(|_primals, cotangents, _outputs| {
+                Ok(vec![cotangents[0].multiply(array!(3.0f32))?])
+            });
+
+        let f = |x: &Array| -> Result<Array> {
+            double.call(std::slice::from_ref(x))?[0].sum(None, None)
+        };
+        let mut df = grad(f);
+
+        for _ in 0..3 {
+            let dfdx = df(&array!(1.0f32)).unwrap();
+            assert_eq!(dfdx.item::<f32>(), 3.0);
+        }
+    }
+
+    #[test]
+    fn test_custom_function_dropped_before_vjp() {
+        let scale = array!(3.0f32);
+        let f = move |x: &Array| -> Result<Array> {
+            let scale = scale.clone();
+            let double =
+                custom_function(|args: &[Array]| Ok(vec![args[0].multiply(array!(2.0f32))?])).vjp(
+                    move |_primals, cotangents, _outputs| Ok(vec![cotangents[0].multiply(&scale)?]),
+                );
+            let y = double.call(std::slice::from_ref(x))?;
+
+            // The vjp is only called in the backward pass
+            drop(double);
+            y[0].sum(None, None)
+        };
+
+        let dfdx = grad(f)(&array!(1.0f32)).unwrap();
+        assert_eq!(dfdx.item::<f32>(), 3.0);
+    }
+
+    #[test]
+    fn test_custom_jvp() {
+        let square = custom_function(|args: &[Array]| Ok(vec![args[0].square()?]))
+            .jvp(|primals, tangents, _argnums| Ok(vec![&primals[0] * &tangents[0] * 10.0]));
+
+        let f = |args: &[Array]| -> Result<Vec<Array>> { square.call(args) };
+        let (out, dout) =
+            crate::transforms::fallible_jvp(f, &[array!(2.0f32)], &[array!(1.0f32)]).unwrap();
+        assert_eq!(out[0].item::<f32>(), 4.0);
+        assert_eq!(dout[0].item::<f32>(), 20.0);
+
+        // The non-customized transforms are derived from the forward function
+        let g = |args: &[Array]| -> Vec<Array> { square.call(args).unwrap() };
+        let (_, dout) = jvp(g, &[array!(2.0f32)], &[array!(1.0f32)]).unwrap();
+        assert_eq!(dout[0].item::<f32>(), 20.0);
+    }
+
+    #[test]
+    fn test_custom_vmap() {
+        let sum = custom_function(|args: &[Array]| Ok(vec![args[0].sum(None, None)?])).vmap(
+            |inputs, axes| {
+                // Reduce over every axis except the vectorized one
+                let x = &inputs[0];
+                let reduce_axes: Vec<i32> =
+                    (0..x.ndim() as i32).filter(|a| *a != axes[0]).collect();
+                Ok((vec![x.sum(&reduce_axes[..], None)?], vec![0]))
+            },
+        );
+
+        let f = |args: &[Array]| -> Result<Vec<Array>> { sum.call(args) };
+        let x = array!([[1.0f32, 2.0], [3.0, 4.0]]);
+        let out = vmap(f, None, None)(&[x]).unwrap();
+        assert_eq!(out[0], array!([3.0f32, 7.0]));
+    }
+
+    #[test]
+    fn test_custom_vjp_with_error() {
+        let identity = custom_function(|args: &[Array]| Ok(vec![args[0].clone()]))
+            .vjp(|_primals, _cotangents, _outputs| Err(Exception::custom("custom vjp failed")));
+
+        let f = |x: &Array| -> Result<Array> {
+            identity.call(std::slice::from_ref(x))?[0].sum(None, None)
+        };
+
+        let result = grad(f)(&array!([1.0f32, 2.0]));
+        assert!(result.is_err());
+
+        let err = result.unwrap_err();
+        assert_eq!(err.what(), "custom vjp failed");
+    }
+}
@@ -0,0 +1,834 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    error::{Exception, Result},
+    ops::{ones_like, stack, zeros_dtype, zeros_like},
+    utils::{Closure, IntoOption, Shared, SharedFn},
+    Array, Dtype,
+};
+
+use super::{jvp_inner, vjp_inner};
+
+/// Computes the Jacobian blocks of a function, indexed by `[output][argnum]`.
+type JacobianFn = for<'f> fn(&Shared<SharedFn<'f>>, &[Array], &[usize]) -> Result<Vec<Vec<Array>>>;
+
+fn shared<'a>(f: impl FnMut(&[Array]) -> Result<Vec<Array>> + 'a) -> Shared<SharedFn<'a>> {
+    Rc::new(RefCell::new(Box::new(f)))
+}
+
+fn call_shared(f: &Shared<SharedFn<'_>>, args: &[Array]) -> Result<Vec<Array>> {
+    let mut f = f
+        .try_borrow_mut()
+        .map_err(|_| Exception::custom("closure is already in use"))?;
+    f(args)
+}
+
+fn resolve_argnums(argnums: &[i32], num_args: usize) -> Result<Vec<usize>> {
+    argnums
+        .iter()
+        .map(|&argnum| {
+            usize::try_from(argnum)
+                .ok()
+                .filter(|&argnum| argnum < num_args)
+                .ok_or_else(|| {
+                    Exception::custom(format!(
+                        "Invalid argument number {} for a function with {} arguments",
+                        argnum, num_args
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Returns the one-hot arrays of the given shape and dtype, ie. the rows of the
+/// identity matrix reshaped to `shape`.
+fn standard_basis(shape: &[i32], dtype: Dtype) -> Result<Vec<Array>> {
+    let size: i32 = shape.iter().product();
+    if size == 0 {
+        return Ok(Vec::new());
+    }
+
+    Array::eye::<f32>(size, None, None)?
+        .as_dtype(dtype)?
+        .split_equal(size, 0)?
+        .into_iter()
+        .map(|e| e.reshape(shape))
+        .collect()
+}
+
+/// Stacks the rows (or columns) of a Jacobian block along `axis` and reshapes
+/// the result to `shape`.
+fn assemble(parts: Vec<Array>, axis: i32, shape: &[i32], dtype: Dtype) -> Result<Array> {
+    if parts.is_empty() {
+        return zeros_dtype(shape, dtype);
+    }
+    stack(&parts, axis)?.reshape(shape)
+}
+
+/// Reverse mode Jacobian, ie. one vector-Jacobian product per output element.
+fn jacrev_inner(
+    f: &Shared<SharedFn<'_>>,
+    args: &[Array],
+    argnums: &[usize],
+) -> Result<Vec<Vec<Array>>> {
+    let outputs = call_shared(f, args)?;
+    let zeros = outputs.iter().map(zeros_like).collect::<Result<Vec<_>>>()?;
+
+    outputs
+        .iter()
+        .enumerate()
+        .map(|(i, output)| {
+            // rows[k][a] is the gradient of the k-th element of the output
+            // with regard to the a-th argument in `argnums`
+            let rows = standard_basis(output.shape(), output.dtype())?
+                .into_iter()
+                .map(|e| {
+                    let mut cotangents = zeros.clone();
+                    cotangents[i] = e;
+                    let (_, vjps) = vjp_inner(Closure::new_shared(f), args, &cotangents)?;
+                    Ok(argnums.iter().map(|&j| vjps[j].clone()).collect())
+                })
+                .collect::<Result<Vec<Vec<Array>>>>()?;
+
+            argnums
+                .iter()
+                .enumerate()
+                .map(|(a, &j)| {
+                    let shape = [output.shape(), args[j].shape()].concat();
+                    let rows = rows.iter().map(|row| row[a].clone()).collect();
+                    assemble(rows, 0, &shape, args[j].dtype())
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Forward mode Jacobian, ie. one Jacobian-vector product per input element.
+fn jacfwd_inner(
+    f: &Shared<SharedFn<'_>>,
+    args: &[Array],
+    argnums: &[usize],
+) -> Result<Vec<Vec<Array>>> {
+    let outputs = call_shared(f, args)?;
+    let zeros = args.iter().map(zeros_like).collect::<Result<Vec<_>>>()?;
+
+    let mut jacobians = vec![Vec::with_capacity(argnums.len()); outputs.len()];
+    for &j in argnums {
+        // columns[k][i] is the derivative of the i-th output with regard to
+        // the k-th element of the j-th argument
+        let columns = standard_basis(args[j].shape(), args[j].dtype())?
+            .into_iter()
+            .map(|e| {
+                let mut tangents = zeros.clone();
+                tangents[j] = e;
+                jvp_inner(Closure::new_shared(f), args, &tangents).map(|(_, jvps)| jvps)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (i, output) in outputs.iter().enumerate() {
+            let shape = [output.shape(), args[j].shape()].concat();
+            let columns = columns.iter().map(|column| column[i].clone()).collect();
+            jacobians[i].push(assemble(columns, -1, &shape, output.dtype())?);
+        }
+    }
+
+    Ok(jacobians)
+}
+
+/// Returns a closure that computes the gradient of a scalar valued `f` with
+/// regard to the arguments in `argnums`.
+fn scalar_grad<'a>(f: Shared<SharedFn<'a>>, argnums: Vec<usize>) -> Shared<SharedFn<'a>> {
+    shared(move |args: &[Array]| -> Result<Vec<Array>> {
+        let outputs = call_shared(&f, args)?;
+        if outputs.len() != 1 || outputs[0].size() != 1 {
+            return Err(Exception::custom(
+                "The function must return a single scalar to compute the Hessian",
+            ));
+        }
+
+        let cotangents = [ones_like(&outputs[0])?];
+        let (_, vjps) = vjp_inner(Closure::new_shared(&f), args, &cotangents)?;
+        Ok(argnums.iter().map(|&j| vjps[j].clone()).collect())
+    })
+}
+
+/// Forward-over-reverse Hessian of a scalar valued function.
+fn hessian_inner(
+    f: &Shared<SharedFn<'_>>,
+    args: &[Array],
+    argnums: &[usize],
+) -> Result<Vec<Vec<Array>>> {
+    let grad = scalar_grad(f.clone(), argnums.to_vec());
+    jacfwd_inner(&grad, args, argnums)
+}
+
+/// Forward-over-reverse Hessian-vector product of a scalar valued function.
+fn hvp_inner(
+    f: &Shared<SharedFn<'_>>,
+    primals: &[Array],
+    tangents: &[Array],
+    argnums: &[usize],
+) -> Result<Vec<Array>> {
+    if tangents.len() != argnums.len() {
+        return Err(Exception::custom(format!(
+            "Expected {} tangents, one for each argument number, but got {}",
+            argnums.len(),
+            tangents.len()
+        )));
+    }
+
+    let mut full_tangents = primals.iter().map(zeros_like).collect::<Result<Vec<_>>>()?;
+    for (&j, tangent) in argnums.iter().zip(tangents) {
+        full_tangents[j] = tangent.clone();
+    }
+
+    let grad = scalar_grad(f.clone(), argnums.to_vec());
+    let (_, hvps) = jvp_inner(Closure::new_shared(&grad), primals, &full_tangents)?;
+    Ok(hvps)
+}
+
+fn build_jacobian<'a>(
+    f: Shared<SharedFn<'a>>,
+    argnums: Option<&[i32]>,
+    jacobian: JacobianFn,
+) -> impl FnMut(&[Array]) -> Result<Vec<Vec<Array>>> + 'a {
+    let argnums = argnums.unwrap_or(&[0]).to_vec();
+    move |args: &[Array]| -> Result<Vec<Vec<Array>>> {
+        let argnums = resolve_argnums(&argnums, args.len())?;
+        jacobian(&f, args, &argnums)
+    }
+}
+
+fn build_array_to_array<'a>(
+    f: Shared<SharedFn<'a>>,
+    argnums: Option<&[i32]>,
+    jacobian: JacobianFn,
+) -> impl FnMut(&Array) -> Result<Array> + 'a {
+    let mut jac = build_jacobian(f, argnums, jacobian);
+    move |x: &Array| -> Result<Array> {
+        let mut blocks = jac(std::slice::from_ref(x))?;
+        Ok(blocks.swap_remove(0).swap_remove(0))
+    }
+}
+
+fn build_array_to_vec<'a>(
+    f: Shared<SharedFn<'a>>,
+    argnums: Option<&[i32]>,
+    jacobian: JacobianFn,
+) -> impl FnMut(&Array) -> Result<Vec<Array>> + 'a {
+    let mut jac = build_jacobian(f, argnums, jacobian);
+    move |x: &Array| -> Result<Vec<Array>> {
+        let blocks = jac(std::slice::from_ref(x))?;
+        Ok(blocks.into_iter().map(|mut b| b.swap_remove(0)).collect())
+    }
+}
+
+fn build_slice_to_array<'a>(
+    f: Shared<SharedFn<'a>>,
+    argnums: Option<&[i32]>,
+    jacobian: JacobianFn,
+) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'a {
+    let mut jac = build_jacobian(f, argnums, jacobian);
+    move |args: &[Array]| -> Result<Vec<Array>> { Ok(jac(args)?.swap_remove(0)) }
+}
+
+/// Trait for functions/closures that can be converted into a closure that computes the Jacobian.
+///
+/// The shape of the returned Jacobian depends on the closure:
+///
+/// - `&Array -> Array`: an `Array` of shape `[output.shape, input.shape]`
+/// - `&Array -> Vec<Array>`: one `Array` per output
+/// - `&[Array] -> Array`: one `Array` per argument in `argnums`
+/// - `&[Array] -> Vec<Array>`: `Vec<Vec<Array>>` indexed by `[output][argnum]`
+pub trait IntoJacobian<'a, Args, Output, Err> {
+    /// Convert the function/closure into a closure that computes the Jacobian in reverse mode.
+    fn into_jacrev(
+        self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(Args) -> Result<Output> + 'a;
+
+    /// Convert the function/closure into a closure that computes the Jacobian in forward mode.
+    fn into_jacfwd(
+        self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(Args) -> Result<Output> + 'a;
+}
+
+impl<'a, F> IntoJacobian<'a, &Array, Array, ()> for F
+where
+    F: FnMut(&Array) -> Array + 'a,
+{
+    // refining_impl_trait is fine here because we have restricted the Args and Output types
+    // in the generics.
+    #[allow(refining_impl_trait)]
+    fn into_jacrev(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Array> + 'a {
+        let f = shared(move |args: &[Array]| Ok(vec![self(&args[0])]));
+        build_array_to_array(f, argnums.into_option(), jacrev_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_jacfwd(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Array> + 'a {
+        let f = shared(move |args: &[Array]| Ok(vec![self(&args[0])]));
+        build_array_to_array(f, argnums.into_option(), jacfwd_inner)
+    }
+}
+
+impl<'a, F> IntoJacobian<'a, &Array, Array, Exception> for F
+where
+    F: FnMut(&Array) -> Result<Array> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_jacrev(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Array> + 'a {
+        let f = shared(move |args: &[Array]| self(&args[0]).map(|y| vec![y]));
+        build_array_to_array(f, argnums.into_option(), jacrev_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_jacfwd(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Array> + 'a {
+        let f = shared(move |args: &[Array]| self(&args[0]).map(|y| vec![y]));
+        build_array_to_array(f, argnums.into_option(), jacfwd_inner)
+    }
+}
+
+impl<'a, F> IntoJacobian<'a, &Array, Vec<Array>, ()> for F
+where
+    F: FnMut(&Array) -> Vec<Array> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_jacrev(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Vec<Array>> + 'a {
+        let f = shared(move |args: &[Array]| Ok(self(&args[0])));
+        build_array_to_vec(f, argnums.into_option(), jacrev_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_jacfwd(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Vec<Array>> + 'a {
+        let f = shared(move |args: &[Array]| Ok(self(&args[0])));
+        build_array_to_vec(f, argnums.into_option(), jacfwd_inner)
+    }
+}
+
+impl<'a, F> IntoJacobian<'a, &Array, Vec<Array>, Exception> for F
+where
+    F: FnMut(&Array) -> Result<Vec<Array>> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_jacrev(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Vec<Array>> + 'a {
+        let f = shared(move |args: &[Array]| self(&args[0]));
+        build_array_to_vec(f, argnums.into_option(), jacrev_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_jacfwd(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Vec<Array>> + 'a {
+        let f = shared(move |args: &[Array]| self(&args[0]));
+        build_array_to_vec(f, argnums.into_option(), jacfwd_inner)
+    }
+}
+
+impl<'a, F> IntoJacobian<'a, &[Array], Vec<Array>, ()> for F
+where
+    F: FnMut(&[Array]) -> Array + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_jacrev(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'a {
+        let f = shared(move |args: &[Array]| Ok(vec![self(args)]));
+        build_slice_to_array(f, argnums.into_option(), jacrev_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_jacfwd(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'a {
+        let f = shared(move |args: &[Array]| Ok(vec![self(args)]));
+        build_slice_to_array(f, argnums.into_option(), jacfwd_inner)
+    }
+}
+
+impl<'a, F> IntoJacobian<'a, &[Array], Vec<Array>, Exception> for F
+where
+    F: FnMut(&[Array]) -> Result<Array> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_jacrev(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'a {
+        let f = shared(move |args: &[Array]| self(args).map(|y| vec![y]));
+        build_slice_to_array(f, argnums.into_option(), jacrev_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_jacfwd(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'a {
+        let f = shared(move |args: &[Array]| self(args).map(|y| vec![y]));
+        build_slice_to_array(f, argnums.into_option(), jacfwd_inner)
+    }
+}
+
+impl<'a, F> IntoJacobian<'a, &[Array], Vec<Vec<Array>>, ()> for F
+where
+    F: FnMut(&[Array]) -> Vec<Array> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_jacrev(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Vec<Array>>> + 'a {
+        let f = shared(move |args: &[Array]| Ok(self(args)));
+        build_jacobian(f, argnums.into_option(), jacrev_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_jacfwd(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Vec<Array>>> + 'a {
+        let f = shared(move |args: &[Array]| Ok(self(args)));
+        build_jacobian(f, argnums.into_option(), jacfwd_inner)
+    }
+}
+
+impl<'a, F> IntoJacobian<'a, &[Array], Vec<Vec<Array>>, Exception> for F
+where
+    F: FnMut(&[Array]) -> Result<Vec<Array>> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_jacrev(
+        self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Vec<Array>>> + 'a {
+        build_jacobian(shared(self), argnums.into_option(), jacrev_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_jacfwd(
+        self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Vec<Array>>> + 'a {
+        build_jacobian(shared(self), argnums.into_option(), jacfwd_inner)
+    }
+}
+
+/// Trait for scalar valued functions/closures that can be converted into a closure that computes
+/// the Hessian or Hessian-vector products.
+///
+/// The shape of the returned Hessian depends on the closure:
+///
+/// - `&Array -> Array`: an `Array` of shape `[input.shape, input.shape]`
+/// - `&[Array] -> Array`: `Vec<Vec<Array>>` indexed by `[argnum][argnum]`
+pub trait IntoHessian<'a, Args, Output, HvpOutput, Err> {
+    /// Convert the function/closure into a closure that computes the Hessian.
+    fn into_hessian(
+        self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(Args) -> Result<Output> + 'a;
+
+    /// Convert the function/closure into a closure that computes the Hessian-vector product
+    /// given the primals and the tangents.
+    fn into_hvp(
+        self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(Args, Args) -> Result<HvpOutput> + 'a;
+}
+
+fn build_array_hvp<'a>(
+    f: Shared<SharedFn<'a>>,
+    argnums: Option<&[i32]>,
+) -> impl FnMut(&Array, &Array) -> Result<Array> + 'a {
+    let mut hvp = build_slice_hvp(f, argnums);
+    move |primal: &Array, tangent: &Array| -> Result<Array> {
+        let hvps = hvp(std::slice::from_ref(primal), std::slice::from_ref(tangent))?;
+        Ok(hvps.into_iter().next().unwrap())
+    }
+}
+
+fn build_slice_hvp<'a>(
+    f: Shared<SharedFn<'a>>,
+    argnums: Option<&[i32]>,
+) -> impl FnMut(&[Array], &[Array]) -> Result<Vec<Array>> + 'a {
+    let argnums = argnums.unwrap_or(&[0]).to_vec();
+    move |primals: &[Array], tangents: &[Array]| -> Result<Vec<Array>> {
+        let argnums = resolve_argnums(&argnums, primals.len())?;
+        hvp_inner(&f, primals, tangents, &argnums)
+    }
+}
+
+impl<'a, F> IntoHessian<'a, &Array, Array, Array, ()> for F
+where
+    F: FnMut(&Array) -> Array + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_hessian(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Array> + 'a {
+        let f = shared(move |args: &[Array]| Ok(vec![self(&args[0])]));
+        build_array_to_array(f, argnums.into_option(), hessian_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_hvp(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array, &Array) -> Result<Array> + 'a {
+        let f = shared(move |args: &[Array]| Ok(vec![self(&args[0])]));
+        build_array_hvp(f, argnums.into_option())
+    }
+}
+
+impl<'a, F> IntoHessian<'a, &Array, Array, Array, Exception> for F
+where
+    F: FnMut(&Array) -> Result<Array> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_hessian(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Array> + 'a {
+        let f = shared(move |args: &[Array]| self(&args[0]).map(|y| vec![y]));
+        build_array_to_array(f, argnums.into_option(), hessian_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_hvp(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array, &Array) -> Result<Array> + 'a {
+        let f = shared(move |args: &[Array]| self(&args[0]).map(|y| vec![y]));
+        build_array_hvp(f, argnums.into_option())
+    }
+}
+
+impl<'a, F> IntoHessian<'a, &[Array], Vec<Vec<Array>>, Vec<Array>, ()> for F
+where
+    F: FnMut(&[Array]) -> Array + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_hessian(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Vec<Array>>> + 'a {
+        let f = shared(move |args: &[Array]| Ok(vec![self(args)]));
+        build_jacobian(f, argnums.into_option(), hessian_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_hvp(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array], &[Array]) -> Result<Vec<Array>> + 'a {
+        let f = shared(move |args: &[Array]| Ok(vec![self(args)]));
+        build_slice_hvp(f, argnums.into_option())
+    }
+}
+
+impl<'a, F> IntoHessian<'a, &[Array], Vec<Vec<Array>>, Vec<Array>, Exception> for F
+where
+    F: FnMut(&[Array]) -> Result<Array> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_hessian(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Vec<Array>>> + 'a {
+        let f = shared(move |args: &[Array]| self(args).map(|y| vec![y]));
+        build_jacobian(f, argnums.into_option(), hessian_inner)
+    }
+
+    #[allow(refining_impl_trait)]
+    fn into_hvp(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array], &[Array]) -> Result<Vec<Array>> + 'a {
+        let f = shared(move |args: &[Array]| self(args).map(|y| vec![y]));
+        build_slice_hvp(f, argnums.into_option())
+    }
+}
+
+/// Returns a function which computes the Jacobian of `f` in reverse mode with the default
+/// argument numbers `&[0]`.
+///
+/// The Jacobian is computed with one vector-Jacobian product per output element, which is
+/// efficient for functions with fewer outputs than inputs. See [`IntoJacobian`] for the shape of
+/// the result.
+///
+/// See also [`jacrev_with_argnums`] for a version that allows specifying the argument numbers and
+/// [`jacfwd`] for the forward mode version.
+///
+/// # Example
+///
+/// ```rust
+/// use mlx_rs::{array, transforms::jacrev, Array};
+///
+/// let f = |x: &Array| x.square().unwrap();
+///
+/// // Jacobian of an element-wise function is diagonal
+/// let jac = jacrev(f)(&array!([1.0f32, 2.0])).unwrap();
+/// assert_eq!(jac, array!([[2.0f32, 0.0], [0.0, 4.0]]));
+/// ```
+pub fn jacrev<'a, F, Args, Output, Err>(f: F) -> impl FnMut(Args) -> Result<Output> + 'a
+where
+    F: IntoJacobian<'a, Args, Output, Err>,
+{
+    f.into_jacrev(None)
+}
+
+/// Returns a function which computes the Jacobian of `f` in reverse mode.
+///
+/// See also [`jacrev`] for a version that uses the default argument numbers `&[0]`.
+pub fn jacrev_with_argnums<'a, F, Args, Output, Err>(
+    f: F,
+    argnums: impl IntoOption<&'a [i32]>,
+) -> impl FnMut(Args) -> Result<Output> + 'a
+where
+    F: IntoJacobian<'a, Args, Output, Err>,
+{
+    f.into_jacrev(argnums)
+}
+
+/// Returns a function which computes the Jacobian of `f` in forward mode with the default
+/// argument numbers `&[0]`.
+///
+/// The Jacobian is computed with one Jacobian-vector product per input element, which is
+/// efficient for functions with fewer inputs than outputs. See [`IntoJacobian`] for the shape of
+/// the result.
+///
+/// See also [`jacfwd_with_argnums`] for a version that allows specifying the argument numbers and
+/// [`jacrev`] for the reverse mode version.
+pub fn jacfwd<'a, F, Args, Output, Err>(f: F) -> impl FnMut(Args) -> Result<Output> + 'a
+where
+    F: IntoJacobian<'a, Args, Output, Err>,
+{
+    f.into_jacfwd(None)
+}
+
+/// Returns a function which computes the Jacobian of `f` in forward mode.
+///
+/// See also [`jacfwd`] for a version that uses the default argument numbers `&[0]`.
+pub fn jacfwd_with_argnums<'a, F, Args, Output, Err>(
+    f: F,
+    argnums: impl IntoOption<&'a [i32]>,
+) -> impl FnMut(Args) -> Result<Output> + 'a
+where
+    F: IntoJacobian<'a, Args, Output, Err>,
+{
+    f.into_jacfwd(argnums)
+}
+
+/// Returns a function which computes the Hessian of the scalar valued `f` with the default
+/// argument numbers `&[0]`.
+///
+/// The Hessian is computed as the forward mode Jacobian of the gradient. See [`IntoHessian`] for
+/// the shape of the result.
+///
+/// See also [`hessian_with_argnums`] for a version that allows specifying the argument numbers.
+///
+/// # Example
+///
+/// ```rust
+/// use mlx_rs::{array, error::Result, transforms::hessian, Array};
+///
+/// let f = |x: &Array| -> Result<Array> { x.power(&array!(3.0f32))?.sum(None, None) };
+///
+/// let h = hessian(f)(&array!([1.0f32, 2.0])).unwrap();
+/// assert_eq!(h, array!([[6.0f32, 0.0], [0.0, 12.0]]));
+/// ```
+pub fn hessian<'a, F, Args, Output, HvpOutput, Err>(f: F) -> impl FnMut(Args) -> Result<Output> + 'a
+where
+    F: IntoHessian<'a, Args, Output, HvpOutput, Err>,
+{
+    f.into_hessian(None)
+}
+
+/// Returns a function which computes the Hessian of the scalar valued `f`.
+///
+/// See also [`hessian`] for a version that uses the default argument numbers `&[0]`.
+pub fn hessian_with_argnums<'a, F, Args, Output, HvpOutput, Err>(
+    f: F,
+    argnums: impl IntoOption<&'a [i32]>,
+) -> impl FnMut(Args) -> Result<Output> + 'a
+where
+    F: IntoHessian<'a, Args, Output, HvpOutput, Err>,
+{
+    f.into_hessian(argnums)
+}
+
+/// Returns a function which computes the Hessian-vector product of the scalar valued `f` with
+/// the default argument numbers `&[0]`.
+///
+/// The returned function takes the primals and the tangents, ie. the "vector" in the
+/// Hessian-vector product. There should be one tangent for each argument in the argument
+/// numbers. The Hessian itself is never materialized.
+///
+/// See also [`hvp_with_argnums`] for a version that allows specifying the argument numbers.
+///
+/// # Example
+///
+/// ```rust
+/// use mlx_rs::{array, error::Result, transforms::hvp, Array};
+///
+/// let f = |x: &Array| -> Result<Array> { x.power(&array!(3.0f32))?.sum(None, None) };
+///
+/// let hv = hvp(f)(&array!([1.0f32, 2.0]), &array!([1.0f32, 1.0])).unwrap();
+/// assert_eq!(hv, array!([6.0f32, 12.0]));
+/// ```
+pub fn hvp<'a, F, Args, Output, HvpOutput, Err>(
+    f: F,
+) -> impl FnMut(Args, Args) -> Result<HvpOutput> + 'a
+where
+    F: IntoHessian<'a, Args, Output, HvpOutput, Err>,
+{
+    f.into_hvp(None)
+}
+
+/// Returns a function which computes the Hessian-vector product of the scalar valued `f`.
+///
+/// See also [`hvp`] for a version that uses the default argument numbers `&[0]`.
+pub fn hvp_with_argnums<'a, F, Args, Output, HvpOutput, Err>(
+    f: F,
+    argnums: impl IntoOption<&'a [i32]>,
+) -> impl FnMut(Args, Args) -> Result<HvpOutput> + 'a
+where
+    F: IntoHessian<'a, Args, Output, HvpOutput, Err>,
+{
+    f.into_hvp(argnums)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{array, error::Result, ops::matmul, Array};
+
+    use super::*;
+
+    fn assert_all_close(a: &Array, b: &Array) {
+        assert_eq!(a.shape(), b.shape());
+        assert!(a.all_close(b, None, None, None).unwrap().item::<bool>());
+    }
+
+    #[test]
+    fn test_jacrev_and_jacfwd_element_wise() {
+        let f = |x: &Array| -> Result<Array> { x.exp() };
+        let x = array!([0.0f32, 1.0, 2.0]);
+
+        let expected = x.exp().unwrap().diag(None).unwrap();
+        assert_all_close(&jacrev(f)(&x).unwrap(), &expected);
+        assert_all_close(&jacfwd(f)(&x).unwrap(), &expected);
+    }
+
+    #[test]
+    fn test_jacobian_shape() {
+        // f: [2, 3] -> [4]
+        let w = Array::from_iter(0..24, &[4, 6])
+            .as_dtype(crate::Dtype::Float32)
+            .unwrap();
+        let f = move |x: &Array| -> Result<Array> { matmul(&w, &x.reshape(&[6])?) };
+        let x = Array::ones::<f32>(&[2, 3]).unwrap();
+
+        let jac_rev = jacrev(f.clone())(&x).unwrap();
+        let jac_fwd = jacfwd(f)(&x).unwrap();
+        assert_eq!(jac_rev.shape(), &[4, 2, 3]);
+        assert_all_close(&jac_rev, &jac_fwd);
+    }
+
+    #[test]
+    fn test_jacobian_with_argnums() {
+        let f = |args: &[Array]| -> Result<Vec<Array>> {
+            Ok(vec![(&args[0] * &args[1]), args[0].sum(None, None)?])
+        };
+        let x = array!([1.0f32, 2.0]);
+        let y = array!([3.0f32, 4.0]);
+
+        let jac = jacrev_with_argnums(f, &[0, 1])(&[x.clone(), y.clone()]).unwrap();
+        let jac_fwd = jacfwd_with_argnums(f, &[0, 1])(&[x.clone(), y.clone()]).unwrap();
+
+        assert_eq!(jac.len(), 2);
+        assert_eq!(jac[0].len(), 2);
+        assert_all_close(&jac[0][0], &y.diag(None).unwrap());
+        assert_all_close(&jac[0][1], &x.diag(None).unwrap());
+        assert_all_close(&jac[1][0], &array!([1.0f32, 1.0]));
+        assert_all_close(&jac[1][1], &array!([0.0f32, 0.0]));
+
+        for (rev, fwd) in jac.iter().flatten().zip(jac_fwd.iter().flatten()) {
+            assert_all_close(rev, fwd);
+        }
+    }
+
+    #[test]
+    fn test_hessian() {
+        let f = |x: &Array| -> Result<Array> { x.power(&array!(3.0f32))?.sum(None, None) };
+        let x = array!([1.0f32, 2.0, 3.0]);
+
+        let h = hessian(f)(&x).unwrap();
+        let expected = (&x * 6.0f32).diag(None).unwrap();
+        assert_all_close(&h, &expected);
+    }
+
+    #[test]
+    fn test_hessian_with_argnums() {
+        // f(x, y) = sum(x^2 * y)
+        let f =
+            |args: &[Array]| -> Result<Array> { (args[0].square()? * &args[1]).sum(None, None) };
+        let x = array!([1.0f32, 2.0]);
+        let y = array!([3.0f32, 4.0]);
+
+        let h = hessian_with_argnums(f, &[0, 1])(&[x.clone(), y.clone()]).unwrap();
+        assert_all_close(&h[0][0], &(&y * 2.0f32).diag(None).unwrap());
+        assert_all_close(&h[0][1], &(&x * 2.0f32).diag(None).unwrap());
+        assert_all_close(&h[1][0], &(&x * 2.0f32).diag(None).unwrap());
+        assert_all_close(&h[1][1], &Array::zeros::<f32>(&[2, 2]).unwrap());
+    }
+
+    #[test]
+    fn test_hvp() {
+        let f = |x: &Array| -> Result<Array> { x.power(&array!(3.0f32))?.sum(None, None) };
+        let x = array!([1.0f32, 2.0, 3.0]);
+        let v = array!([1.0f32, -1.0, 0.5]);
+
+        let hv = hvp(f)(&x, &v).unwrap();
+        let expected = matmul(&hessian(f)(&x).unwrap(), &v).unwrap();
+        assert_all_close(&hv, &expected);
+    }
+
+    #[test]
+    fn test_hessian_of_non_scalar_function() {
+        let f = |x: &Array| -> Result<Array> { x.square() };
+        let result = hessian(f)(&array!([1.0f32, 2.0]));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_invalid_argnums() {
+        let f = |args: &[Array]| -> Result<Array> { args[0].sum(None, None) };
+        let result = jacrev_with_argnums(f, &[1])(&[array!([1.0f32])]);
+        assert!(result.is_err());
+    }
+}
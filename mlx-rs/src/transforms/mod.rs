@@ -22,6 +22,8 @@
 //! with respect to the first argument, in order to manually specify the the
 //! argument to compute the gradient with respect to, use
 //! [`grad_with_argnums()`] or [`value_and_grad_with_argnums()`].
+//! [`value_and_grad_with_aux()`] can be used when the function also returns
+//! auxiliary outputs that should not be differentiated.
 //!
 //! TODO: update the example once https://github.com/oxideai/mlx-rs/pull/218 is merged
 //!
@@ -44,6 +46,31 @@
 //! let dfdx2 = calculate_grad(|args| calculate_grad(f, args), &x).unwrap();
 //! assert_eq!(dfdx2.item::<f32>(), 2.0);
 //! ```
+//!
+//! # Jacobians and Hessians
+//!
+//! [`jacrev()`] and [`jacfwd()`] compute the full Jacobian of a function in
+//! reverse and forward mode respectively. [`hessian()`] and [`hvp()`] compute
+//! the Hessian and Hessian-vector products of scalar valued functions. They
+//! take the same closures as [`grad()`].
+//!
+//! # Custom Transforms
+//!
+//! [`custom_function()`] defines a function whose vector-Jacobian product,
+//! Jacobian-vector product and/or vectorization rule are written by hand, eg.
+//! for straight-through estimators or numerically stable gradients.
+//!
+//! # Gradient Checkpointing
+//!
+//! [`checkpoint()`] recomputes the intermediate results of a function in the
+//! backward pass instead of keeping them in memory. See also
+//! [`crate::nn::Checkpointed`] for modules.
+//!
+//! # Vectorization
+//!
+//! [`vmap()`] maps a function over an axis of its inputs so that per-example
+//! code does not need to be batched by hand. It can be composed with the other
+//! function transforms.
 
 use mlx_sys::mlx_closure_value_and_grad;
 
@@ -54,14 +81,22 @@ use crate::{
     Array,
 };
 
+mod checkpoint;
 pub mod compile;
+mod custom_function;
 mod grad;
+mod jacobian;
 mod keyed_value_and_grad;
 mod value_and_grad;
+mod vmap;
 
+pub use checkpoint::*;
+pub use custom_function::*;
 pub use grad::*;
+pub use jacobian::*;
 pub use keyed_value_and_grad::*;
 pub use value_and_grad::*;
+pub use vmap::*;
 
 /// Evaluate an iterator of [`Array`]s.
 pub fn eval<'a>(outputs: impl IntoIterator<Item = &'a Array>) -> Result<()> {
@@ -108,6 +108,119 @@ where
     f.into_value_and_grad(argnums)
 }
 
+/// Splits the outputs of a function with auxiliary outputs into the value and
+/// the auxiliary outputs.
+pub(crate) fn split_aux(mut outputs: Vec<Array>) -> (Array, Vec<Array>) {
+    let aux = outputs.split_off(1);
+    let value = outputs.pop().expect("Expected a value");
+    (value, aux)
+}
+
+/// Trait for functions/closures that return a value and auxiliary outputs and can be converted
+/// into a closure that computes the value, the auxiliary outputs and the gradient.
+pub trait IntoValueAndGradWithAux<'a, Err> {
+    /// Convert the function/closure into a closure that computes the value, the auxiliary outputs
+    /// and the gradient.
+    fn into_value_and_grad_with_aux(
+        self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<((Array, Vec<Array>), Vec<Array>)> + 'a;
+}
+
+impl<'a, F> IntoValueAndGradWithAux<'a, ()> for F
+where
+    F: FnMut(&[Array]) -> (Array, Vec<Array>) + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_value_and_grad_with_aux(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<((Array, Vec<Array>), Vec<Array>)> + 'a {
+        let argnums = argnums.into_option().unwrap_or(&[0]);
+        let f = move |args: &[Array]| -> Vec<Array> {
+            let (value, aux) = self(args);
+            std::iter::once(value).chain(aux).collect()
+        };
+        let mut vg = build_value_and_gradient(f, argnums);
+        move |args: &[Array]| -> Result<((Array, Vec<Array>), Vec<Array>)> {
+            let (outputs, grads) = vg(args)?;
+            Ok((split_aux(outputs), grads))
+        }
+    }
+}
+
+impl<'a, F> IntoValueAndGradWithAux<'a, Exception> for F
+where
+    F: FnMut(&[Array]) -> Result<(Array, Vec<Array>)> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_value_and_grad_with_aux(
+        mut self,
+        argnums: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<((Array, Vec<Array>), Vec<Array>)> + 'a {
+        let argnums = argnums.into_option().unwrap_or(&[0]);
+        let f = move |args: &[Array]| -> Result<Vec<Array>> {
+            let (value, aux) = self(args)?;
+            Ok(std::iter::once(value).chain(aux).collect())
+        };
+        let mut vg = build_fallible_value_and_gradient(f, argnums);
+        move |args: &[Array]| -> Result<((Array, Vec<Array>), Vec<Array>)> {
+            let (outputs, grads) = vg(args)?;
+            Ok((split_aux(outputs), grads))
+        }
+    }
+}
+
+/// Returns a function which computes the value and gradient of `f` with a default argument
+/// number `&[0]`, where `f` also returns auxiliary outputs.
+///
+/// `f` returns a tuple of the value to differentiate, which must be a scalar, and the auxiliary
+/// outputs, eg. the accuracy or updated hidden states. The auxiliary outputs are not
+/// differentiated. The returned function returns `((value, aux), grads)`.
+///
+/// See also [`value_and_grad_with_aux_and_argnums`] for a version that allows specifying the
+/// argument numbers
+///
+/// # Example
+///
+/// ```rust
+/// use mlx_rs::{array, error::Result, transforms::value_and_grad_with_aux, Array};
+///
+/// let f = |args: &[Array]| -> Result<(Array, Vec<Array>)> {
+///     let y = args[0].square()?;
+///     Ok((y.sum(None, None)?, vec![y]))
+/// };
+///
+/// let x = array!([1.0f32, 2.0]);
+/// let ((loss, aux), grads) = value_and_grad_with_aux(f)(&[x]).unwrap();
+/// assert_eq!(loss, array!(5.0f32));
+/// assert_eq!(aux[0], array!([1.0f32, 4.0]));
+/// assert_eq!(grads[0], array!([2.0f32, 4.0]));
+/// ```
+pub fn value_and_grad_with_aux<'a, F, Err>(
+    f: F,
+) -> impl FnMut(&[Array]) -> Result<((Array, Vec<Array>), Vec<Array>)> + 'a
+where
+    F: IntoValueAndGradWithAux<'a, Err> + 'a,
+{
+    f.into_value_and_grad_with_aux(None)
+}
+
+/// Returns a function which computes the value and gradient of `f`, where `f` also returns
+/// auxiliary outputs.
+///
+/// See also [`value_and_grad_with_aux`] for a version that uses the default argument numbers
+/// `&[0]`.
+pub fn value_and_grad_with_aux_and_argnums<'a, F, Err>(
+    f: F,
+    argnums: impl IntoOption<&'a [i32]>,
+) -> impl FnMut(&[Array]) -> Result<((Array, Vec<Array>), Vec<Array>)> + 'a
+where
+    F: IntoValueAndGradWithAux<'a, Err> + 'a,
+{
+    f.into_value_and_grad_with_aux(argnums)
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -160,4 +273,51 @@ mod tests {
         let err = result.unwrap_err();
         assert!(!err.what().contains("non-zero value"))
     }
+
+    #[test]
+    fn test_value_and_grad_with_aux() {
+        let fun = |args: &[Array]| -> (Array, Vec<Array>) {
+            let y = &args[0] * &args[1];
+            (y.sum(None, None).unwrap(), vec![y.clone(), &y * 2.0])
+        };
+
+        let x = array!([1.0f32, 2.0]);
+        let y = array!([3.0f32, 4.0]);
+        let ((value, aux), grads) =
+            value_and_grad_with_aux_and_argnums(fun, &[0, 1])(&[x.clone(), y.clone()]).unwrap();
+
+        assert_eq!(value.item::<f32>(), 11.0);
+        assert_eq!(aux.len(), 2);
+        assert_eq!(aux[0], array!([3.0f32, 8.0]));
+        assert_eq!(aux[1], array!([6.0f32, 16.0]));
+
+        // The auxiliary outputs do not contribute to the gradient
+        assert_eq!(grads[0], y);
+        assert_eq!(grads[1], x);
+    }
+
+    #[test]
+    fn test_value_and_grad_with_aux_with_error() {
+        let fun = |args: &[Array]| -> Result<(Array, Vec<Array>)> {
+            let y = args[0].add(&args[1])?;
+            Ok((y.sum(None, None)?, vec![y]))
+        };
+
+        // Success case
+        let args = &[array!([1.0f32, 2.0]), array!([3.0f32, 4.0])];
+        let ((value, aux), grads) = value_and_grad_with_aux(fun)(args).unwrap();
+        assert_eq!(value.item::<f32>(), 10.0);
+        assert_eq!(aux[0], array!([4.0f32, 6.0]));
+        assert_eq!(grads[0], array!([1.0f32, 1.0]));
+
+        // Error case
+        // Use non-broadcastable shapes
+        let args = &[array!([1.0, 2.0, 3.0]), array!([4.0, 5.0])];
+        let result = value_and_grad_with_aux(fun)(args);
+        assert!(result.is_err());
+
+        // Check that the error is not just "mlx_closure returned a non-zero value"
+        let err = result.unwrap_err();
+        assert!(!err.what().contains("non-zero value"))
+    }
 }
@@ -0,0 +1,336 @@
+use crate::{
+    error::{get_and_clear_closure_error, Exception, Result},
+    utils::{guard::Guarded, Closure, IntoOption, VectorArray},
+    Array,
+};
+
+/// Axis value used by mlx to indicate that an input is not vectorized.
+const UNMAPPED_AXIS: i32 = -1;
+
+fn vmap_inner(
+    closure: &Closure<'_>,
+    arrays: &[Array],
+    in_axes: &[i32],
+    out_axes: Option<&[i32]>,
+) -> Result<Vec<Array>> {
+    let inputs = VectorArray::try_from_iter(arrays.iter())?;
+
+    let (s_inputs, s_outputs) =
+        <(Vec<Array>, Vec<Array>) as Guarded>::try_from_op(|(res_0, res_1)| unsafe {
+            mlx_sys::mlx_detail_vmap_trace(
+                res_0,
+                res_1,
+                closure.as_ptr(),
+                inputs.as_ptr(),
+                in_axes.as_ptr(),
+                in_axes.len(),
+            )
+        })
+        .map_err(|e| match get_and_clear_closure_error() {
+            Some(err) => err,
+            None => e,
+        })?;
+
+    // All outputs are stacked along the first axis by default
+    let default_out_axes;
+    let out_axes = match out_axes {
+        Some(out_axes) => out_axes,
+        None => {
+            default_out_axes = vec![0; s_outputs.len()];
+            &default_out_axes
+        }
+    };
+
+    let c_s_inputs = VectorArray::try_from_iter(s_inputs.iter())?;
+    let c_s_outputs = VectorArray::try_from_iter(s_outputs.iter())?;
+    <Vec<Array> as Guarded>::try_from_op(|res| unsafe {
+        mlx_sys::mlx_detail_vmap_replace(
+            res,
+            inputs.as_ptr(),
+            c_s_inputs.as_ptr(),
+            c_s_outputs.as_ptr(),
+            in_axes.as_ptr(),
+            in_axes.len(),
+            out_axes.as_ptr(),
+            out_axes.len(),
+        )
+    })
+}
+
+fn resolve_in_axes(in_axes: Option<&[Option<i32>]>, num_args: usize) -> Vec<i32> {
+    match in_axes {
+        Some(in_axes) => in_axes
+            .iter()
+            .map(|axis| axis.unwrap_or(UNMAPPED_AXIS))
+            .collect(),
+        None => vec![0; num_args],
+    }
+}
+
+fn build_vmap<'a, F>(
+    mut f: F,
+    in_axes: Option<&'a [Option<i32>]>,
+    out_axes: Option<&'a [i32]>,
+) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'a
+where
+    F: FnMut(&[Array]) -> Vec<Array> + 'a,
+{
+    move |arrays: &[Array]| -> Result<Vec<Array>> {
+        let in_axes = resolve_in_axes(in_axes, arrays.len());
+        let closure = Closure::new(&mut f);
+        vmap_inner(&closure, arrays, &in_axes, out_axes)
+    }
+}
+
+fn build_fallible_vmap<'a, F>(
+    mut f: F,
+    in_axes: Option<&'a [Option<i32>]>,
+    out_axes: Option<&'a [i32]>,
+) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'a
+where
+    F: FnMut(&[Array]) -> Result<Vec<Array>> + 'a,
+{
+    move |arrays: &[Array]| -> Result<Vec<Array>> {
+        let in_axes = resolve_in_axes(in_axes, arrays.len());
+        let closure = Closure::new_fallible(&mut f);
+        vmap_inner(&closure, arrays, &in_axes, out_axes)
+    }
+}
+
+/// Trait for functions/closures that can be converted into a vectorized closure.
+pub trait IntoVmap<'a, Args, Output, Err> {
+    /// Convert the function/closure into a closure that maps over the given axes of the inputs.
+    fn into_vmap(
+        self,
+        in_axes: impl IntoOption<&'a [Option<i32>]>,
+        out_axes: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(Args) -> Result<Output> + 'a;
+}
+
+impl<'a, F> IntoVmap<'a, &[Array], Vec<Array>, ()> for F
+where
+    F: FnMut(&[Array]) -> Vec<Array> + 'a,
+{
+    // refining_impl_trait is fine here because we have restricted the Args and Output types
+    // in the generics.
+    #[allow(refining_impl_trait)]
+    fn into_vmap(
+        self,
+        in_axes: impl IntoOption<&'a [Option<i32>]>,
+        out_axes: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'a {
+        build_vmap(self, in_axes.into_option(), out_axes.into_option())
+    }
+}
+
+impl<'a, F> IntoVmap<'a, &[Array], Vec<Array>, Exception> for F
+where
+    F: FnMut(&[Array]) -> Result<Vec<Array>> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_vmap(
+        self,
+        in_axes: impl IntoOption<&'a [Option<i32>]>,
+        out_axes: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Vec<Array>> + 'a {
+        build_fallible_vmap(self, in_axes.into_option(), out_axes.into_option())
+    }
+}
+
+impl<'a, F> IntoVmap<'a, &Array, Array, ()> for F
+where
+    F: FnMut(&Array) -> Array + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_vmap(
+        mut self,
+        in_axes: impl IntoOption<&'a [Option<i32>]>,
+        out_axes: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Array> + 'a {
+        let f = move |args: &[Array]| -> Vec<Array> { vec![self(&args[0])] };
+        let mut g = build_vmap(f, in_axes.into_option(), out_axes.into_option());
+        move |args: &Array| -> Result<Array> {
+            let args_clone = &[args.clone()];
+            let result = g(args_clone)?;
+            Ok(result.into_iter().next().unwrap())
+        }
+    }
+}
+
+impl<'a, F> IntoVmap<'a, &Array, Array, Exception> for F
+where
+    F: FnMut(&Array) -> Result<Array> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_vmap(
+        mut self,
+        in_axes: impl IntoOption<&'a [Option<i32>]>,
+        out_axes: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&Array) -> Result<Array> + 'a {
+        let f = move |args: &[Array]| -> Result<Vec<Array>> { self(&args[0]).map(|res| vec![res]) };
+        let mut g = build_fallible_vmap(f, in_axes.into_option(), out_axes.into_option());
+        move |args: &Array| -> Result<Array> {
+            let args_clone = &[args.clone()];
+            let result = g(args_clone)?;
+            Ok(result.into_iter().next().unwrap())
+        }
+    }
+}
+
+impl<'a, F> IntoVmap<'a, &[Array], Array, ()> for F
+where
+    F: FnMut(&[Array]) -> Array + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_vmap(
+        mut self,
+        in_axes: impl IntoOption<&'a [Option<i32>]>,
+        out_axes: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Array> + 'a {
+        let f = move |args: &[Array]| -> Vec<Array> { vec![self(args)] };
+        let mut g = build_vmap(f, in_axes.into_option(), out_axes.into_option());
+        move |args: &[Array]| -> Result<Array> {
+            let result = g(args)?;
+            Ok(result.into_iter().next().unwrap())
+        }
+    }
+}
+
+impl<'a, F> IntoVmap<'a, &[Array], Array, Exception> for F
+where
+    F: FnMut(&[Array]) -> Result<Array> + 'a,
+{
+    #[allow(refining_impl_trait)]
+    fn into_vmap(
+        mut self,
+        in_axes: impl IntoOption<&'a [Option<i32>]>,
+        out_axes: impl IntoOption<&'a [i32]>,
+    ) -> impl FnMut(&[Array]) -> Result<Array> + 'a {
+        let f = move |args: &[Array]| -> Result<Vec<Array>> { self(args).map(|res| vec![res]) };
+        let mut g = build_fallible_vmap(f, in_axes.into_option(), out_axes.into_option());
+        move |args: &[Array]| -> Result<Array> {
+            let result = g(args)?;
+            Ok(result.into_iter().next().unwrap())
+        }
+    }
+}
+
+/// Returns a vectorized version of `f`.
+///
+/// The returned function maps `f` over the given axes of its inputs and stacks
+/// the results along the given axes of the outputs.
+///
+/// # Params
+///
+/// - `f`: the function to vectorize
+/// - `in_axes`: the axis to map over for each input. `None` for an input means
+///   that the input is not mapped over and is passed to `f` as a whole.
+///   Defaults to the first axis of every input.
+/// - `out_axes`: the axis along which each output is stacked. Defaults to the
+///   first axis of every output.
+///
+/// # Example
+///
+/// ```rust
+/// use mlx_rs::{array, transforms::vmap, Array};
+///
+/// let f = |args: &[Array]| -> Vec<Array> { vec![&args[0] * &args[1]] };
+///
+/// let xs = array!([[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]]);
+/// let y = array!([10.0, 100.0]);
+///
+/// // Map over the rows of `xs` while passing `y` as is
+/// let out = vmap(f, &[Some(0), None], None)(&[xs, y]).unwrap();
+/// assert_eq!(out[0].shape(), &[3, 2]);
+/// ```
+///
+/// The returned function can be passed to the other function transforms, eg.
+/// [`grad()`](super::grad()), and functions that are compiled with
+/// [`compile`](super::compile::compile) can be vectorized.
+pub fn vmap<'a, F, Args, Output, Err>(
+    f: F,
+    in_axes: impl IntoOption<&'a [Option<i32>]>,
+    out_axes: impl IntoOption<&'a [i32]>,
+) -> impl FnMut(Args) -> Result<Output> + 'a
+where
+    F: IntoVmap<'a, Args, Output, Err>,
+{
+    f.into_vmap(in_axes, out_axes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        array,
+        error::Result,
+        transforms::{compile::compile, grad, vmap},
+        Array,
+    };
+
+    // The unit tests below are adapted from the mlx c++ codebase
+
+    #[test]
+    fn test_vmap_unary() {
+        let f = |x: &Array| -> Array { x.sum(None, None).unwrap() };
+        let x = array!([[1.0f32, 2.0], [3.0, 4.0]]);
+
+        let out = vmap(f, None, None)(&x).unwrap();
+        assert_eq!(out, array!([3.0f32, 7.0]));
+
+        let out = vmap(f, &[Some(1)], None)(&x).unwrap();
+        assert_eq!(out, array!([4.0f32, 6.0]));
+    }
+
+    #[test]
+    fn test_vmap_in_and_out_axes() {
+        let f = |args: &[Array]| -> Vec<Array> { vec![&args[0] + &args[1]] };
+        let x = array!([[1.0f32, 2.0], [3.0, 4.0]]);
+        let y = array!([10.0f32, 20.0]);
+
+        // `y` is not mapped over
+        let out = vmap(f, &[Some(0), None], None)(&[x.clone(), y.clone()]).unwrap();
+        assert_eq!(out[0], array!([[11.0f32, 22.0], [13.0, 24.0]]));
+
+        // Map over the columns of `x` and the elements of `y`, and stack the
+        // results along the last axis
+        let out = vmap(f, &[Some(1), Some(0)], &[1])(&[x, y]).unwrap();
+        assert_eq!(out[0], array!([[11.0f32, 22.0], [13.0, 24.0]]));
+    }
+
+    #[test]
+    fn test_vmap_with_error() {
+        let f = |args: &[Array]| -> Result<Vec<Array>> { args[0].add(&args[1]).map(|r| vec![r]) };
+
+        // Non-broadcastable shapes after removing the mapped axis
+        let x = array!([[1.0f32, 2.0, 3.0], [4.0, 5.0, 6.0]]);
+        let y = array!([[1.0f32, 2.0], [3.0, 4.0]]);
+        let result = vmap(f, None, None)(&[x, y]);
+        assert!(result.is_err());
+
+        // Check that the error is not just "mlx_closure returned a non-zero value"
+        let err = result.unwrap_err();
+        assert!(!err.what().contains("non-zero value"))
+    }
+
+    #[test]
+    fn test_grad_of_vmap() {
+        let f = |x: &Array| -> Result<Array> {
+            let mut per_example = vmap(|x: &Array| x.square(), None, None);
+            per_example(x)?.sum(None, None)
+        };
+
+        let x = array!([1.0f32, 2.0, 3.0]);
+        let dfdx = grad(f)(&x).unwrap();
+        assert_eq!(dfdx, array!([2.0f32, 4.0, 6.0]));
+    }
+
+    #[test]
+    fn test_vmap_compiled() {
+        let f = |x: &Array| -> Result<Array> { x.sum(None, None) };
+        let vmapped = move |x: &Array| -> Result<Array> { vmap(f, None, None)(x) };
+
+        let x = array!([[1.0f32, 2.0], [3.0, 4.0]]);
+        let out = compile(vmapped, None)(&x).unwrap();
+        assert_eq!(out, array!([3.0f32, 7.0]));
+    }
+}
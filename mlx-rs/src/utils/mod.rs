@@ -7,7 +7,7 @@ use crate::error::set_closure_error;
 use crate::module::ModuleParameters;
 use crate::{complex64, error::Exception, Array, FromNested};
 use std::collections::HashMap;
-use std::{marker::PhantomData, rc::Rc};
+use std::{cell::RefCell, marker::PhantomData, rc::Rc};
 
 /// Success status code from the c binding
 pub(crate) const SUCCESS: i32 = 0;
@@ -230,6 +230,24 @@ impl<'a> Closure<'a> {
             lt_marker: PhantomData,
         }
     }
+
+    /// Creates a closure that can be called by mlx any number of times.
+    ///
+    /// The mlx closure holds its own reference to `closure`, which is released
+    /// when mlx frees the closure.
+    pub(crate) fn new_shared(closure: &Shared<SharedFn<'a>>) -> Self {
+        let c_closure = unsafe {
+            mlx_sys::mlx_closure_new_func_payload(
+                Some(trampoline_shared),
+                into_shared_payload(closure),
+                Some(drop_shared_payload::<SharedFn<'a>>),
+            )
+        };
+        Self {
+            c_closure,
+            lt_marker: PhantomData,
+        }
+    }
 }
 
 impl Drop for Closure<'_> {
@@ -274,7 +292,7 @@ where
 }
 
 /// Function to create a new (+1 reference) mlx_vector_array from a vector of Array
-fn new_mlx_vector_array(arrays: Vec<Array>) -> mlx_sys::mlx_vector_array {
+pub(crate) fn new_mlx_vector_array(arrays: Vec<Array>) -> mlx_sys::mlx_vector_array {
     unsafe {
         let result = mlx_sys::mlx_vector_array_new();
         let ctx_ptrs: Vec<mlx_sys::mlx_array> = arrays.iter().map(|array| array.as_ptr()).collect();
@@ -283,7 +301,7 @@ fn new_mlx_vector_array(arrays: Vec<Array>) -> mlx_sys::mlx_vector_array {
     }
 }
 
-fn mlx_vector_array_values(
+pub(crate) fn mlx_vector_array_values(
     vector_array: mlx_sys::mlx_vector_array,
 ) -> Result<Vec<Array>, Exception> {
     unsafe {
@@ -357,6 +375,68 @@ where
 
 extern "C" fn noop_dtor(_data: *mut std::ffi::c_void) {}
 
+/// Shared handle to a closure that is passed to mlx as a payload.
+///
+/// Unlike the closures created by [`Closure::new`], these may be called by mlx
+/// any number of times and after the Rust side has returned (eg. in the
+/// backward pass).
+pub(crate) type Shared<T> = Rc<RefCell<Box<T>>>;
+
+/// Fallible closure that can be shared with mlx.
+pub(crate) type SharedFn<'a> = dyn FnMut(&[Array]) -> Result<Vec<Array>, Exception> + 'a;
+
+/// Creates a new payload holding a reference to `shared`.
+pub(crate) fn into_shared_payload<T: ?Sized>(shared: &Shared<T>) -> *mut std::ffi::c_void {
+    Box::into_raw(Box::new(shared.clone())) as *mut std::ffi::c_void
+}
+
+/// Releases the reference held by a payload created by [`into_shared_payload`].
+pub(crate) extern "C" fn drop_shared_payload<T: ?Sized>(payload: *mut std::ffi::c_void) {
+    unsafe { drop(Box::from_raw(payload as *mut Shared<T>)) };
+}
+
+/// Calls `f` with the closure in `payload`, storing the error, if any, so that
+/// it can be propagated to the caller of the mlx function.
+///
+/// # Safety
+///
+/// `payload` must have been created by [`into_shared_payload`] with the same `T`.
+pub(crate) unsafe fn call_shared_payload<T: ?Sized, R>(
+    payload: *mut std::ffi::c_void,
+    f: impl FnOnce(&mut T) -> Result<R, Exception>,
+    write: impl FnOnce(R),
+) -> i32 {
+    let shared = &*(payload as *const Shared<T>);
+    let result = shared
+        .try_borrow_mut()
+        .map_err(|_| Exception::custom("closure is already in use"))
+        .and_then(|mut closure| f(&mut **closure));
+    match result {
+        Ok(value) => {
+            write(value);
+            SUCCESS
+        }
+        Err(err) => {
+            set_closure_error(err);
+            FAILURE
+        }
+    }
+}
+
+extern "C" fn trampoline_shared(
+    ret: *mut mlx_vector_array,
+    vector_array: mlx_vector_array,
+    payload: *mut std::ffi::c_void,
+) -> i32 {
+    unsafe {
+        call_shared_payload::<SharedFn<'_>, _>(
+            payload,
+            |closure| closure(&mlx_vector_array_values(vector_array)?),
+            |result| *ret = new_mlx_vector_array(result),
+        )
+    }
+}
+
 pub(crate) fn get_mut_or_insert_with<'a, T>(
     map: &'a mut HashMap<Rc<str>, T>,
     key: &Rc<str>,
@@ -2,9 +2,69 @@ extern crate cmake;
 
 use bindgen::RustTarget;
 use cmake::Config;
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Default BLAS/LAPACK libraries linked on non-Apple targets. This can be
+/// overridden with a comma separated list in the `MLX_SYS_BLAS_LIBS`
+/// environment variable, eg. `MLX_SYS_BLAS_LIBS=openblas`.
+const DEFAULT_BLAS_LIBS: &[&str] = &["lapack", "blas"];
+
+/// Prefixes searched for the mlx-c CMake config files in addition to those in
+/// `CMAKE_PREFIX_PATH`.
+const DEFAULT_PREFIXES: &[&str] = &["/opt/homebrew", "/usr/local", "/usr"];
+
+fn is_apple_target() -> bool {
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    matches!(target_os.as_str(), "macos" | "ios")
+}
+
+fn link_apple_frameworks() {
+    println!("cargo:rustc-link-lib=c++");
+    println!("cargo:rustc-link-lib=dylib=objc");
+    println!("cargo:rustc-link-lib=framework=Foundation");
+
+    #[cfg(feature = "metal")]
+    {
+        println!("cargo:rustc-link-lib=framework=Metal");
+    }
+
+    #[cfg(feature = "accelerate")]
+    {
+        println!("cargo:rustc-link-lib=framework=Accelerate");
+    }
+}
+
+fn link_system_blas() {
+    println!("cargo:rerun-if-env-changed=MLX_SYS_BLAS_LIBS");
+    println!("cargo:rerun-if-env-changed=MLX_SYS_BLAS_LIB_DIR");
+
+    if let Ok(dir) = env::var("MLX_SYS_BLAS_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={}", dir);
+    }
+
+    let libs = match env::var("MLX_SYS_BLAS_LIBS") {
+        Ok(libs) => libs
+            .split(',')
+            .map(str::trim)
+            .filter(|lib| !lib.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>(),
+        Err(_) => DEFAULT_BLAS_LIBS.iter().map(|s| s.to_string()).collect(),
+    };
+
+    for lib in libs {
+        println!("cargo:rustc-link-lib=dylib={}", lib);
+    }
+
+    println!("cargo:rustc-link-lib=dylib=stdc++");
+}
+
+fn build_and_link_mlx_c() -> Vec<PathBuf> {
+    let is_apple = is_apple_target();
 
-fn build_and_link_mlx_c() {
     let mut config = Config::new("src/mlx-c");
     config.very_verbose(true);
     config.define("CMAKE_INSTALL_PREFIX", ".");
@@ -22,14 +82,23 @@ fn build_and_link_mlx_c() {
     config.define("MLX_BUILD_METAL", "OFF");
     config.define("MLX_BUILD_ACCELERATE", "OFF");
 
-    #[cfg(feature = "metal")]
-    {
-        config.define("MLX_BUILD_METAL", "ON");
-    }
+    // Metal and Accelerate are only available on Apple platforms. On other
+    // targets these features are ignored and mlx is built for the CPU only.
+    if is_apple {
+        #[cfg(feature = "metal")]
+        {
+            config.define("MLX_BUILD_METAL", "ON");
+        }
 
-    #[cfg(feature = "accelerate")]
-    {
-        config.define("MLX_BUILD_ACCELERATE", "ON");
+        #[cfg(feature = "accelerate")]
+        {
+            config.define("MLX_BUILD_ACCELERATE", "ON");
+        }
+    } else if cfg!(any(feature = "metal", feature = "accelerate")) {
+        println!(
+            "cargo:warning=`metal` and `accelerate` are only supported on Apple targets, \
+             building mlx for the CPU only"
+        );
     }
 
     // build the mlx-c project
@@ -39,39 +108,180 @@ fn build_and_link_mlx_c() {
     println!("cargo:rustc-link-lib=static=mlx");
     println!("cargo:rustc-link-lib=static=mlxc");
 
-    println!("cargo:rustc-link-lib=c++");
-    println!("cargo:rustc-link-lib=dylib=objc");
-    println!("cargo:rustc-link-lib=framework=Foundation");
+    link_platform_libs(is_apple);
 
-    #[cfg(feature = "metal")]
-    {
-        println!("cargo:rustc-link-lib=framework=Metal");
+    vec![PathBuf::from("src/mlx-c")]
+}
+
+fn link_platform_libs(is_apple: bool) {
+    if is_apple {
+        link_apple_frameworks();
+    } else {
+        link_system_blas();
     }
+}
 
-    #[cfg(feature = "accelerate")]
-    {
-        println!("cargo:rustc-link-lib=framework=Accelerate");
+fn use_system_mlx_c() -> bool {
+    println!("cargo:rerun-if-env-changed=MLX_SYS_USE_SYSTEM");
+    cfg!(feature = "system") || env::var("MLX_SYS_USE_SYSTEM").is_ok_and(|v| v != "0")
+}
+
+/// Link against the mlx and mlx-c libraries installed under `lib_dir`.
+///
+/// Static libraries are preferred if present, in which case the platform
+/// libraries mlx depends on are linked as well. Otherwise the shared libraries
+/// are linked.
+fn link_installed_mlx_c(lib_dir: &Path) {
+    println!("cargo:rustc-link-search=native={}", lib_dir.display());
+
+    if lib_dir.join("libmlxc.a").exists() {
+        println!("cargo:rustc-link-lib=static=mlxc");
+        println!("cargo:rustc-link-lib=static=mlx");
+        link_platform_libs(is_apple_target());
+    } else {
+        println!("cargo:rustc-link-lib=dylib=mlxc");
+        println!("cargo:rustc-link-lib=dylib=mlx");
     }
 }
 
-fn main() {
-    build_and_link_mlx_c();
+/// Use the mlx-c installation in `prefix`, which is expected to contain
+/// `include/mlx/c/mlx.h` and the libraries in `lib`.
+fn link_mlx_c_prefix(prefix: &Path) -> Option<Vec<PathBuf>> {
+    let include_dir = prefix.join("include");
+    if !include_dir.join("mlx/c/mlx.h").exists() {
+        return None;
+    }
 
-    // generate bindings
-    let bindings = bindgen::Builder::default()
+    link_installed_mlx_c(&prefix.join("lib"));
+    Some(vec![include_dir])
+}
+
+/// Find mlx-c through pkg-config.
+///
+/// As with the other installations, the static libraries are preferred if
+/// present. The `Libs.private` of mlx-c do not list the platform libraries mlx
+/// depends on, so they are linked as in the vendored build.
+fn probe_pkg_config() -> Option<Vec<PathBuf>> {
+    let lib = pkg_config::Config::new()
+        .cargo_metadata(false)
+        .probe("mlxc")
+        .ok()?;
+    let is_static = lib
+        .link_paths
+        .iter()
+        .any(|dir| dir.join("libmlxc.a").exists());
+
+    // `probe` emits the link flags itself
+    let lib = pkg_config::Config::new()
+        .statik(is_static)
+        .probe("mlxc")
+        .ok()?;
+    if is_static {
+        link_platform_libs(is_apple_target());
+    }
+    Some(lib.include_paths)
+}
+
+fn probe_cmake_config() -> Option<Vec<PathBuf>> {
+    println!("cargo:rerun-if-env-changed=CMAKE_PREFIX_PATH");
+
+    let prefixes = env::var_os("CMAKE_PREFIX_PATH")
+        .map(|paths| env::split_paths(&paths).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    prefixes
+        .into_iter()
+        .chain(DEFAULT_PREFIXES.iter().map(PathBuf::from))
+        .filter(|prefix| {
+            [
+                "lib/cmake/mlxc",
+                "lib/cmake/MLXC",
+                "share/cmake/mlxc",
+                "share/cmake/MLXC",
+            ]
+            .iter()
+            .any(|dir| prefix.join(dir).is_dir())
+        })
+        .find_map(|prefix| link_mlx_c_prefix(&prefix))
+}
+
+/// Find and link a prebuilt mlx-c. Returns the include directories if a
+/// prebuilt mlx-c should be used, or `None` if the vendored copy should be
+/// built instead.
+fn find_and_link_system_mlx_c() -> Option<Vec<PathBuf>> {
+    println!("cargo:rerun-if-env-changed=MLX_C_DIR");
+
+    if let Some(dir) = env::var_os("MLX_C_DIR") {
+        let dir = PathBuf::from(dir);
+        let include_dirs = link_mlx_c_prefix(&dir).unwrap_or_else(|| {
+            panic!(
+                "MLX_C_DIR is set to {} but it does not contain include/mlx/c/mlx.h",
+                dir.display()
+            )
+        });
+        return Some(include_dirs);
+    }
+
+    if !use_system_mlx_c() {
+        return None;
+    }
+
+    let include_dirs = probe_pkg_config().or_else(probe_cmake_config).expect(
+        "Unable to find a system mlx-c through pkg-config or CMake config files. \
+             Set MLX_C_DIR to the installation prefix of mlx-c.",
+    );
+    Some(include_dirs)
+}
+
+fn generate_bindings(include_dirs: &[PathBuf]) {
+    // Write the bindings to the $OUT_DIR/bindings.rs file.
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("bindings.rs");
+
+    // Reuse pregenerated bindings if provided
+    println!("cargo:rerun-if-env-changed=MLX_SYS_BINDINGS");
+    if let Some(bindings) = env::var_os("MLX_SYS_BINDINGS") {
+        let bindings = PathBuf::from(bindings);
+        println!("cargo:rerun-if-changed={}", bindings.display());
+        std::fs::copy(&bindings, &out_path).unwrap_or_else(|err| {
+            panic!(
+                "Couldn't copy pregenerated bindings from {}: {}",
+                bindings.display(),
+                err
+            )
+        });
+        return;
+    }
+
+    let include_dir = include_dirs
+        .iter()
+        .find(|dir| dir.join("mlx/c/mlx.h").exists())
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from("src/mlx-c"));
+    let header = |name: &str| include_dir.join("mlx/c").join(name).display().to_string();
+
+    let mut builder = bindgen::Builder::default()
         .rust_target(RustTarget::Stable_1_73)
-        .header("src/mlx-c/mlx/c/mlx.h")
-        .header("src/mlx-c/mlx/c/linalg.h")
-        .header("src/mlx-c/mlx/c/error.h")
-        .header("src/mlx-c/mlx/c/transforms_impl.h")
-        .clang_arg("-Isrc/mlx-c")
+        .header(header("mlx.h"))
+        .header(header("linalg.h"))
+        .header(header("error.h"))
+        .header(header("transforms_impl.h"));
+    for dir in include_dirs {
+        builder = builder.clang_arg(format!("-I{}", dir.display()));
+    }
+
+    let bindings = builder
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
         .generate()
         .expect("Unable to generate bindings");
 
-    // Write the bindings to the $OUT_DIR/bindings.rs file.
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     bindings
-        .write_to_file(out_path.join("bindings.rs"))
+        .write_to_file(out_path)
         .expect("Couldn't write bindings!");
 }
+
+fn main() {
+    let include_dirs = find_and_link_system_mlx_c().unwrap_or_else(build_and_link_mlx_c);
+
+    // generate bindings
+    generate_bindings(&include_dirs);
+}
@@ -1,8 +1,12 @@
+use std::{collections::HashMap, rc::Rc};
+
 use mlx_rs::{
     array,
+    error::IoError,
     macros::ModuleParameters,
-    module::{ModuleParameters, Param, Parameter},
-    Array,
+    module::{load_parameters, ModuleParameters, ModuleParametersExt, Param, Parameter},
+    nn::{Linear, QuantizedLinear},
+    Array, Dtype,
 };
 
 #[derive(ModuleParameters)]
@@ -327,3 +331,252 @@ fn test_nested_module_recursive_unfreeze() {
     assert_eq!(flattened["nested.a"], &array!(2.0));
     assert_eq!(flattened["nested.b"], &array!(3.0));
 }
+
+#[test]
+fn test_module_to_dtype() {
+    let mut m = NestedStructModule {
+        a: Param::new(array!(1.0)),
+        nested: StructModule {
+            a: Param::new(array!(2.0)),
+            b: Param::new(array!(3)),
+            c: Param::new(None),
+        },
+        neste_no_param: UnitStructModule,
+    };
+
+    m.to_dtype(Dtype::Bfloat16).unwrap();
+    assert_eq!(m.a.dtype(), Dtype::Bfloat16);
+    assert_eq!(m.nested.a.dtype(), Dtype::Bfloat16);
+
+    // Integer parameters are not cast
+    assert_eq!(m.nested.b.dtype(), Dtype::Int32);
+
+    m.to_dtype_where(Dtype::Float32, |key, _| key.starts_with("nested."))
+        .unwrap();
+    assert_eq!(m.a.dtype(), Dtype::Bfloat16);
+    assert_eq!(m.nested.a.dtype(), Dtype::Float32);
+}
+
+#[test]
+fn test_module_trainable_to_dtype() {
+    let mut m = NestedStructModule {
+        a: Param::new(array!(1.0)),
+        nested: StructModule {
+            a: Param::new(array!(2.0)),
+            b: Param::new(array!(3.0)),
+            c: Param::new(None),
+        },
+        neste_no_param: UnitStructModule,
+    };
+
+    m.nested.freeze_parameters(true);
+    m.trainable_to_dtype(Dtype::Float16).unwrap();
+
+    assert_eq!(m.a.dtype(), Dtype::Float16);
+    assert_eq!(m.nested.a.dtype(), Dtype::Float32);
+    assert_eq!(m.nested.b.dtype(), Dtype::Float32);
+}
+
+fn saved_nested_module(dir: &tempfile::TempDir) -> std::path::PathBuf {
+    let m = NestedStructModule {
+        a: Param::new(array!([1.0, 2.0])),
+        nested: StructModule {
+            a: Param::new(array!(2.0)),
+            b: Param::new(array!(3)),
+            c: Param::new(None),
+        },
+        neste_no_param: UnitStructModule,
+    };
+
+    let path = dir.path().join("model.safetensors");
+    m.save_safetensors(&path).unwrap();
+    path
+}
+
+fn zeros_nested_module() -> NestedStructModule {
+    NestedStructModule {
+        a: Param::new(array!(0.0)),
+        nested: StructModule {
+            a: Param::new(array!(0.0)),
+            b: Param::new(array!(0.0)),
+            c: Param::new(Some(array!(0.0))),
+        },
+        neste_no_param: UnitStructModule,
+    }
+}
+
+#[test]
+fn test_load_safetensors_with_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = saved_nested_module(&dir);
+
+    let mut m = zeros_nested_module();
+    let report = m.load_safetensors_with_report(&path).unwrap();
+
+    assert!(!report.is_clean());
+    assert_eq!(report.missing_keys, vec![Rc::<str>::from("nested.c")]);
+    assert!(report.unexpected_keys.is_empty());
+
+    assert_eq!(report.shape_mismatches.len(), 1);
+    assert_eq!(&*report.shape_mismatches[0].key, "a");
+    assert_eq!(report.shape_mismatches[0].expected, Vec::<i32>::new());
+    assert_eq!(report.shape_mismatches[0].found, vec![2]);
+
+    assert_eq!(report.dtype_mismatches.len(), 1);
+    assert_eq!(&*report.dtype_mismatches[0].key, "nested.b");
+    assert_eq!(report.dtype_mismatches[0].expected, Dtype::Float32);
+    assert_eq!(report.dtype_mismatches[0].found, Dtype::Int32);
+
+    // Only the matching parameter is loaded
+    assert_eq!(m.a.as_ref(), &array!(0.0));
+    assert_eq!(m.nested.a.as_ref(), &array!(2.0));
+    assert_eq!(m.nested.b.as_ref(), &array!(0.0));
+}
+
+#[test]
+fn test_load_safetensors_strict() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = saved_nested_module(&dir);
+
+    let mut m = zeros_nested_module();
+    let result = m.load_safetensors_strict(&path);
+    assert!(matches!(result, Err(IoError::ParameterMismatch(_))));
+
+    // The module is left untouched
+    assert_eq!(m.nested.a.as_ref(), &array!(0.0));
+}
+
+#[test]
+fn test_load_safetensors_remapped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("model.safetensors");
+
+    let source = StructModule {
+        a: Param::new(array!(1.0)),
+        b: Param::new(array!(2.0)),
+        c: Param::new(Some(array!(3.0))),
+    };
+    source.save_safetensors(&path).unwrap();
+
+    let mut m = zeros_nested_module();
+    let report = m
+        .load_safetensors_remapped(
+            &path,
+            |key| (key != "c").then(|| format!("nested.{}", key)),
+            false,
+        )
+        .unwrap();
+
+    assert_eq!(
+        report.missing_keys,
+        vec![Rc::<str>::from("a"), Rc::from("nested.c")]
+    );
+    assert!(report.unexpected_keys.is_empty());
+    assert_eq!(m.nested.a.as_ref(), &array!(1.0));
+    assert_eq!(m.nested.b.as_ref(), &array!(2.0));
+    assert_eq!(m.nested.c.as_ref(), &Some(array!(0.0)));
+}
+
+#[test]
+fn test_load_safetensors_quantized_keys() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("quantized.safetensors");
+
+    let source = QuantizedLinear::try_from_linear(Linear::new(64, 8).unwrap(), None, None).unwrap();
+
+    // The Python `mlx` saves the weight and bias of a quantized layer without `inner`
+    let arrays: HashMap<String, Array> = source
+        .parameters()
+        .flatten()
+        .into_iter()
+        .map(|(key, value)| (key.replace("inner.", ""), value.clone()))
+        .collect();
+    assert!(arrays.contains_key("weight"));
+    Array::save_safetensors(&arrays, None, &path).unwrap();
+
+    let mut m = QuantizedLinear::try_from_linear(Linear::new(64, 8).unwrap(), None, None).unwrap();
+    m.load_safetensors_strict(&path).unwrap();
+    assert_eq!(m.inner.weight.as_ref(), source.inner.weight.as_ref());
+    assert_eq!(m.inner.bias.as_ref(), source.inner.bias.as_ref());
+    assert_eq!(m.scales.as_ref(), source.scales.as_ref());
+}
+
+#[test]
+fn test_load_parameters_duplicate_key() {
+    let arrays = vec![("a", array!(1.0)), ("b", array!(2.0))];
+
+    let mut m = zeros_nested_module();
+    let result = load_parameters(&mut m, arrays, |_| Some("nested.a".to_string()), false);
+    match result {
+        Err(IoError::DuplicateParameter { key, first, second }) => {
+            assert_eq!(key, "nested.a");
+            assert_eq!(first, "a");
+            assert_eq!(second, "b");
+        }
+        other => panic!("expected a duplicate parameter, got {:?}", other),
+    }
+    assert_eq!(m.nested.a.as_ref(), &array!(0.0));
+}
+
+#[test]
+fn test_save_and_load_safetensors_sharded() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let m = NestedStructModule {
+        a: Param::new(array!([1.0, 2.0])),
+        nested: StructModule {
+            a: Param::new(array!(3.0)),
+            b: Param::new(array!(4.0)),
+            c: Param::new(None),
+        },
+        neste_no_param: UnitStructModule,
+    };
+
+    // One array per shard
+    let index = m.save_safetensors_sharded(dir.path(), 4).unwrap();
+    assert_eq!(index.shard_files().len(), 3);
+    assert_eq!(index.metadata.total_size, 16);
+
+    let mut loaded = zeros_nested_module();
+    loaded.a = Param::new(array!([0.0, 0.0]));
+    let report = loaded.load_safetensors_sharded(dir.path()).unwrap();
+    assert_eq!(report.missing_keys, vec![Rc::<str>::from("nested.c")]);
+
+    assert_eq!(loaded.a.as_ref(), &array!([1.0, 2.0]));
+    assert_eq!(loaded.nested.a.as_ref(), &array!(3.0));
+    assert_eq!(loaded.nested.b.as_ref(), &array!(4.0));
+
+    // The shape of `a` does not match
+    let mut m = zeros_nested_module();
+    let result = m.load_safetensors_sharded_remapped(dir.path(), |key| Some(key.to_string()), true);
+    assert!(matches!(result, Err(IoError::ParameterMismatch(_))));
+    assert_eq!(m.nested.a.as_ref(), &array!(0.0));
+}
+
+#[test]
+fn test_module_parameters_npz_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("model.npz");
+
+    let m = NestedStructModule {
+        a: Param::new(array!([1.0, 2.0])),
+        nested: StructModule {
+            a: Param::new(array!(3.0)),
+            b: Param::new(array!(4.0)),
+            c: Param::new(Some(array!(5.0))),
+        },
+        neste_no_param: UnitStructModule,
+    };
+    Array::save_npz_compressed(m.parameters().flatten(), &path).unwrap();
+
+    let mut loaded = zeros_nested_module();
+    loaded.a = Param::new(array!([0.0, 0.0]));
+    let arrays = Array::load_npz(&path).unwrap();
+    let report = load_parameters(&mut loaded, arrays, |key| Some(key.to_string()), true).unwrap();
+
+    assert!(report.is_clean());
+    assert_eq!(loaded.a.as_ref(), &array!([1.0, 2.0]));
+    assert_eq!(loaded.nested.a.as_ref(), &array!(3.0));
+    assert_eq!(loaded.nested.b.as_ref(), &array!(4.0));
+    assert_eq!(loaded.nested.c.as_ref(), &Some(array!(5.0)));
+}
@@ -10,10 +10,12 @@ use mlx_rs::{
     macros::ModuleParameters,
     module::{FlattenedModuleParam, Module, ModuleParameters, Param},
     nn,
-    ops::{ones, zeros},
+    ops::{eye, ones, zeros},
     optimizers::{
-        AdaDelta, AdaGrad, AdafactorBuilder, Adam, AdamW, Adamax, Lion, LionBuilder, Optimizer,
-        RmsProp, RmsPropBuilder, Sgd, SgdBuilder,
+        newton_schulz, schedulers::ExponentialDecay, AdaDelta, AdaGrad, AdafactorBuilder, Adam,
+        AdamW, Adamax, Adan, AdanBuilder, Ema, Lamb, LambBuilder, Lars, LarsBuilder, Lion,
+        LionBuilder, Lookahead, MasterWeights, MultiOptimizer, Muon, NAdam, Optimizer, ParamFilter,
+        RAdam, RmsProp, RmsPropBuilder, Sgd, SgdBuilder, Swa,
     },
     random::uniform,
     transforms::{eval, eval_params},
@@ -535,6 +537,96 @@ fn test_sgd() {
     assert_array_eq!(optim.state["second"].as_ref(), expected_state_second, ATOL);
 }
 
+#[test]
+fn test_sgd_with_schedule() {
+    let mut model = SimpleModel {
+        a: Param::new(ones::<f32>(&[1]).unwrap()),
+    };
+    let mut gradients = GradsMap::new();
+    gradients.insert("a".into(), ones::<f32>(&[1]).unwrap());
+
+    let mut optim = Sgd::new(ExponentialDecay::new(0.1, 0.5));
+
+    // The learning rate at step 0 is used for the first update
+    optim.update(&mut model, &gradients).unwrap();
+    assert_array_eq!(model.a.as_ref(), array!([0.9]), ATOL);
+    assert_array_eq!(optim.lr, array!(0.1), ATOL);
+
+    optim.update(&mut model, &gradients).unwrap();
+    assert_array_eq!(model.a.as_ref(), array!([0.85]), ATOL);
+    assert_array_eq!(optim.lr, array!(0.05), ATOL);
+    assert_eq!(optim.state.step.item::<i32>(), 2);
+
+    assert_save_and_load(optim, Sgd::new(ExponentialDecay::new(0.1, 0.5))).unwrap();
+}
+
+#[test]
+fn test_multi_optimizer() {
+    let (mut model, gradients) = create_default_test_model_and_grads();
+
+    let new_optim = || {
+        MultiOptimizer::new(Sgd::new(1e-2))
+            .with_group(
+                "first",
+                SgdBuilder::new(1e-1).momentum(0.9).build().unwrap(),
+            )
+            .with_group(
+                ParamFilter::predicate(|key, _| key == "first.b"),
+                Sgd::new(1.0),
+            )
+    };
+    let mut optim = new_optim();
+    optim.update(&mut model, gradients).unwrap();
+
+    // `first.b` matches the prefix group first
+    let expected_first_a = ones::<f32>(&[10]).unwrap() * -0.1;
+    let expected_first_b = ones::<f32>(&[1]).unwrap() * -0.1;
+    let expected_second = ones::<f32>(&[1]).unwrap() * -0.01;
+
+    assert_array_eq!(model.first.a.as_ref(), expected_first_a, ATOL);
+    assert_array_eq!(model.first.b.as_ref(), expected_first_b, ATOL);
+    assert_array_eq!(model.second.as_ref(), expected_second, ATOL);
+
+    assert!(optim.state.groups[0].contains_key("first.a"));
+    assert!(optim.state.groups[0].contains_key("first.b"));
+    assert!(optim.state.groups[1].is_empty());
+    assert!(optim.state.default.contains_key("second"));
+
+    assert_save_and_load(optim, new_optim()).unwrap();
+}
+
+#[test]
+fn test_multi_optimizer_mixed_types() {
+    let (mut model, gradients) = create_default_test_model_and_grads();
+
+    let new_optim =
+        || MultiOptimizer::with_default(Sgd::new(1e-2)).with_group("first", Muon::new(0.1));
+    let mut optim = new_optim();
+    optim.update(&mut model, gradients).unwrap();
+
+    let expected_second = ones::<f32>(&[1]).unwrap() * -0.01;
+    assert_array_eq!(model.second.as_ref(), expected_second, ATOL);
+    assert!(
+        model
+            .first
+            .a
+            .as_ref()
+            .abs()
+            .unwrap()
+            .sum(None, None)
+            .unwrap()
+            .item::<f32>()
+            > 0.0
+    );
+
+    assert!(optim.state.groups[0].contains_key("first.a"));
+    assert!(optim.state.groups[0].contains_key("first.b"));
+    assert!(optim.state.default.contains_key("second"));
+    assert_eq!(optim.state.default.step.item::<i32>(), 1);
+
+    assert_save_and_load(optim, new_optim()).unwrap();
+}
+
 // This unit test is adapted from the swift binding unit test `testLion` in
 // `mlx-swift/Tests/MLXTests/IntegrationTests.swift`
 #[test]
@@ -715,6 +807,40 @@ fn test_adafactor() {
     assert_save_and_load(optimizer, AdafactorBuilder::new().lr(0.1).build().unwrap()).unwrap();
 }
 
+#[test]
+fn test_adafactor_with_schedule() {
+    let mut model = SimpleModel {
+        a: Param::new(ones::<f32>(&[1]).unwrap()),
+    };
+    let mut gradients = GradsMap::new();
+    gradients.insert("a".into(), ones::<f32>(&[1]).unwrap());
+
+    let mut optim = AdafactorBuilder::new()
+        .lr(ExponentialDecay::new(0.1, 0.5))
+        .relative_step(false)
+        .scale_parameter(false)
+        .build()
+        .unwrap();
+
+    // The update of a parameter of a single element is the learning rate
+    optim.update(&mut model, &gradients).unwrap();
+    assert_array_eq!(model.a.as_ref(), array!([0.9]), ATOL);
+    assert_array_eq!(optim.lr.as_ref().unwrap(), array!(0.1), ATOL);
+
+    optim.update(&mut model, &gradients).unwrap();
+    assert_array_eq!(model.a.as_ref(), array!([0.85]), ATOL);
+    assert_array_eq!(optim.lr.as_ref().unwrap(), array!(0.05), ATOL);
+    assert_eq!(optim.state.step.item::<i32>(), 2);
+
+    let new_optim = AdafactorBuilder::new()
+        .lr(ExponentialDecay::new(0.1, 0.5))
+        .relative_step(false)
+        .scale_parameter(false)
+        .build()
+        .unwrap();
+    assert_save_and_load(optim, new_optim).unwrap();
+}
+
 #[test]
 fn test_adafactor1() {
     mlx_rs::random::seed(193).unwrap();
@@ -822,3 +948,236 @@ fn test_adafactor2() {
         0.09670660972595214
     );
 }
+
+/// Applies a single update of `optim` to the default test model and checks that every parameter
+/// equals `expected`, then checks that the state can be saved and loaded.
+fn assert_single_update<O>(mut optim: O, new_optim: O, expected: f32)
+where
+    O: Optimizer,
+{
+    let (mut model, gradients) = create_default_test_model_and_grads();
+    optim.update(&mut model, gradients).unwrap();
+
+    let expected_first_a = ones::<f32>(&[10]).unwrap() * expected;
+    let expected_first_b = ones::<f32>(&[1]).unwrap() * expected;
+    let expected_second = ones::<f32>(&[1]).unwrap() * expected;
+
+    assert_array_eq!(model.first.a.as_ref(), expected_first_a, ATOL);
+    assert_array_eq!(model.first.b.as_ref(), expected_first_b, ATOL);
+    assert_array_eq!(model.second.as_ref(), expected_second, ATOL);
+
+    assert_save_and_load(optim, new_optim).unwrap();
+}
+
+#[test]
+fn test_lamb() {
+    // The weights are zero so the trust ratio falls back to 1
+    assert_single_update(Lamb::new(0.1), Lamb::new(0.1), -0.1);
+}
+
+#[test]
+fn test_lars() {
+    // The weights are zero so the trust ratio falls back to 1
+    assert_single_update(Lars::new(0.1), Lars::new(0.1), -0.1);
+}
+
+#[test]
+fn test_nadam() {
+    // m_hat = 0.9 * 0.1 / (1 - 0.9^2) + 0.1 / (1 - 0.9)
+    assert_single_update(NAdam::new(0.1), NAdam::new(0.1), -0.147_368_42);
+}
+
+#[test]
+fn test_radam() {
+    // The variance is intractable at the first step so the update is the bias corrected momentum
+    assert_single_update(RAdam::new(0.1), RAdam::new(0.1), -0.1);
+}
+
+#[test]
+fn test_adan() {
+    assert_single_update(Adan::new(0.1), Adan::new(0.1), -0.1);
+}
+
+/// Gradients of the parameter of [`SimpleModel`] at each step of [`multi_step_update`].
+const GRADIENTS: [[f32; 3]; 8] = [
+    [0.5, -1.0, 0.25],
+    [0.3, 0.2, -0.6],
+    [-0.4, 0.8, 0.1],
+    [0.6, -0.3, 0.2],
+    [0.1, 0.5, -0.2],
+    [-0.2, 0.1, 0.7],
+    [0.4, -0.6, 0.3],
+    [0.2, 0.3, -0.1],
+];
+
+/// Applies `steps` updates of `optim` to a model with non-zero weights and returns the weights.
+fn multi_step_update<O>(optim: &mut O, steps: usize) -> Array
+where
+    O: Optimizer,
+{
+    let mut model = SimpleModel {
+        a: Param::new(array!([1.0f32, -2.0, 0.5])),
+    };
+
+    for gradient in &GRADIENTS[..steps] {
+        let mut gradients = GradsMap::new();
+        gradients.insert("a".into(), Array::from_slice(gradient, &[3]));
+        optim.update(&mut model, &gradients).unwrap();
+    }
+
+    model.a.as_ref().clone()
+}
+
+// The expected values of the multi-step tests below are computed with a float64 reference
+// implementation of the update rules
+#[test]
+fn test_lamb_multi_step() {
+    let mut optim = LambBuilder::new(0.1).weight_decay(0.01).build().unwrap();
+    let a = multi_step_update(&mut optim, 3);
+    assert_array_eq!(a, array!([0.553_137_3, -1.810_027_5, 0.571_281]), ATOL);
+}
+
+#[test]
+fn test_lars_multi_step() {
+    let mut optim = LarsBuilder::new(0.1).weight_decay(0.01).build().unwrap();
+    let a = multi_step_update(&mut optim, 3);
+    assert_array_eq!(a, array!([0.999_639_1, -1.999_76, 0.500_196_7]), ATOL);
+}
+
+#[test]
+fn test_radam_multi_step() {
+    // The variance becomes tractable at the sixth step, so the last three updates are rectified
+    let mut optim = RAdam::new(0.1);
+    let a = multi_step_update(&mut optim, 8);
+    assert_array_eq!(a, array!([0.850_499_9, -1.874_449_3, 0.506_130_2]), ATOL);
+}
+
+#[test]
+fn test_adan_multi_step() {
+    let mut optim = AdanBuilder::new(0.1).weight_decay(0.02).build().unwrap();
+    let a = multi_step_update(&mut optim, 3);
+    assert_array_eq!(a, array!([0.835_935, -1.951_128_4, 0.468_427]), ATOL);
+}
+
+#[test]
+fn test_muon() {
+    // The parameters are vectors so the update is the nesterov momentum `g + 0.95 * g`
+    assert_single_update(Muon::new(0.1), Muon::new(0.1), -0.195);
+
+    let mut model = SimpleModel {
+        a: Param::new(zeros::<f32>(&[4, 4]).unwrap()),
+    };
+    let mut gradients = GradsMap::new();
+    gradients.insert("a".into(), eye::<f32>(4, None, None).unwrap() * 3.0);
+
+    let mut optim = Muon::new(0.1);
+    optim.update(&mut model, gradients).unwrap();
+
+    let orthogonalized = newton_schulz(&(eye::<f32>(4, None, None).unwrap() * 5.85), 5).unwrap();
+    assert_array_eq!(model.a.as_ref(), orthogonalized * -0.1, ATOL);
+}
+
+#[test]
+fn test_newton_schulz() {
+    let x = eye::<f32>(4, None, None).unwrap() * 3.0;
+    let y = newton_schulz(&x, 5).unwrap();
+
+    // The singular values of `x / ||x||` are 0.5 and are pushed towards 1
+    let expected = eye::<f32>(4, None, None).unwrap() * 0.765_438_5;
+    assert_array_eq!(y, expected, 1e-4);
+}
+
+#[test]
+fn test_newton_schulz_invalid_shape() {
+    assert!(newton_schulz(&array!(1.0), 5).is_err());
+    assert!(newton_schulz(&ones::<f32>(&[4]).unwrap(), 5).is_err());
+    assert!(newton_schulz(&ones::<f32>(&[2, 3, 4]).unwrap(), 5).is_err());
+}
+
+fn create_simple_model_and_grads() -> (SimpleModel, GradsMap) {
+    let model = SimpleModel {
+        a: Param::new(zeros::<f32>(&[1]).unwrap()),
+    };
+    let mut gradients = GradsMap::new();
+    gradients.insert("a".into(), ones::<f32>(&[1]).unwrap());
+
+    (model, gradients)
+}
+
+#[test]
+fn test_ema() {
+    let (mut model, gradients) = create_simple_model_and_grads();
+    let mut optim = Ema::new(Sgd::new(0.1), 0.5).unwrap();
+
+    optim.update(&mut model, &gradients).unwrap();
+    optim.update(&mut model, &gradients).unwrap();
+    assert_array_eq!(model.a.as_ref(), array!([-0.2]), ATOL);
+
+    // 0.5 * (0.5 * 0.0 + 0.5 * -0.1) + 0.5 * -0.2
+    optim.swap_parameters(&mut model);
+    assert_array_eq!(model.a.as_ref(), array!([-0.125]), ATOL);
+
+    optim.swap_parameters(&mut model);
+    assert_array_eq!(model.a.as_ref(), array!([-0.2]), ATOL);
+
+    assert_save_and_load(optim, Ema::new(Sgd::new(0.1), 0.5).unwrap()).unwrap();
+}
+
+#[test]
+fn test_lookahead() {
+    let (mut model, gradients) = create_simple_model_and_grads();
+    let mut optim = Lookahead::new(Sgd::new(0.1), 2, 0.5).unwrap();
+
+    // The slow parameters are only updated every other step
+    optim.update(&mut model, &gradients).unwrap();
+    assert_array_eq!(model.a.as_ref(), array!([-0.1]), ATOL);
+    assert_array_eq!(optim.state.shadow["a"], array!([0.0]), ATOL);
+
+    // slow = 0.0 + 0.5 * (-0.2 - 0.0) and the fast parameters are reset to it
+    optim.update(&mut model, &gradients).unwrap();
+    assert_array_eq!(model.a.as_ref(), array!([-0.1]), ATOL);
+    assert_array_eq!(optim.state.shadow["a"], array!([-0.1]), ATOL);
+
+    assert_save_and_load(optim, Lookahead::new(Sgd::new(0.1), 2, 0.5).unwrap()).unwrap();
+}
+
+#[test]
+fn test_swa() {
+    let (mut model, gradients) = create_simple_model_and_grads();
+    let mut optim = Swa::new(Sgd::new(0.1), 2, 1).unwrap();
+
+    for _ in 0..3 {
+        optim.update(&mut model, &gradients).unwrap();
+    }
+    assert_array_eq!(model.a.as_ref(), array!([-0.3]), ATOL);
+
+    // Average of the parameters after the second and third steps
+    optim.swap_parameters(&mut model);
+    assert_array_eq!(model.a.as_ref(), array!([-0.25]), ATOL);
+    optim.swap_parameters(&mut model);
+
+    assert_save_and_load(optim, Swa::new(Sgd::new(0.1), 2, 1).unwrap()).unwrap();
+}
+
+#[test]
+fn test_master_weights() {
+    let mut model = SimpleModel {
+        a: Param::new(ones::<f32>(&[1]).unwrap().as_dtype(Dtype::Float16).unwrap()),
+    };
+    let mut gradients = GradsMap::new();
+    gradients.insert(
+        "a".into(),
+        ones::<f32>(&[1]).unwrap().as_dtype(Dtype::Float16).unwrap(),
+    );
+
+    // The updates are too small to change a `Float16` value close to 1
+    let mut optim = MasterWeights::new(Sgd::new(1e-4));
+    optim.update(&mut model, &gradients).unwrap();
+    optim.update(&mut model, &gradients).unwrap();
+
+    assert_eq!(model.a.dtype(), Dtype::Float16);
+    assert_eq!(optim.state.shadow["a"].dtype(), Dtype::Float32);
+    assert_array_eq!(optim.state.shadow["a"], array!([0.9998]), ATOL);
+
+    assert_save_and_load(optim, MasterWeights::new(Sgd::new(1e-4))).unwrap();
+}
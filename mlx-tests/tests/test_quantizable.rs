@@ -3,7 +3,7 @@ use mlx_rs::{
     macros::{ModuleParameters, Quantizable},
     module::Module,
     nn::Linear,
-    quantization::MaybeQuantized,
+    quantization::{MaybeQuantized, Quantizable},
     Array,
 };
 
@@ -26,3 +26,20 @@ impl Module<&Array> for QuantizableExample {
         self.ql.training_mode(mode)
     }
 }
+
+#[test]
+fn test_quantize_with_predicate() {
+    let example = QuantizableExample {
+        ql: MaybeQuantized::new(Linear::new(64, 64).unwrap()),
+    };
+
+    let mut paths = Vec::new();
+    let example = example
+        .try_into_quantized_with("model", &mut |path| {
+            paths.push(path.to_string());
+            None
+        })
+        .unwrap();
+    assert_eq!(paths, ["model.ql"]);
+    assert!(!example.ql.is_quantized());
+}